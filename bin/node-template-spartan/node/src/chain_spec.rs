@@ -1,16 +1,31 @@
 use sp_core::{Pair, Public, sr25519};
 use node_template_spartan_runtime::{
-	AccountId, BalancesConfig, GenesisConfig, PoCConfig,
+	AccountId, BalancesConfig, GenesisConfig, OffenceRewardPot, PoCConfig,
 	SudoConfig, SystemConfig, WASM_BINARY, Signature
 };
+use frame_support::traits::Get;
 use sp_runtime::traits::{Verify, IdentifyAccount};
 use sc_service::ChainType;
+use sc_chain_spec::ChainSpecExtension;
+use sc_consensus_poc_finality::KDeepFinalityConfig;
+use serde::{Deserialize, Serialize};
 
 // The URL for the telemetry server.
 // const STAGING_TELEMETRY_URL: &str = "wss://telemetry.polkadot.io/submit/";
 
+/// Node `ChainSpec` extensions.
+///
+/// Additional parameters for some Substrate core modules,
+/// customizable from the chain spec.
+#[derive(Default, Clone, Serialize, Deserialize, ChainSpecExtension)]
+#[serde(rename_all = "camelCase")]
+pub struct Extensions {
+	/// Parameters of k-deep confirmation finality, nothing is finalized if not set.
+	pub k_deep_finality: Option<KDeepFinalityConfig>,
+}
+
 /// Specialized `ChainSpec`. This is a specialization of the general Substrate ChainSpec type.
-pub type ChainSpec = sc_service::GenericChainSpec<GenesisConfig>;
+pub type ChainSpec = sc_service::GenericChainSpec<GenesisConfig, Extensions>;
 
 /// Generate a crypto pair from seed.
 pub fn get_from_seed<TPublic: Public>(seed: &str) -> <TPublic::Pair as Pair>::Public {
@@ -59,7 +74,7 @@ pub fn development_config() -> Result<ChainSpec, String> {
 		// Properties
 		None,
 		// Extensions
-		None,
+		development_extensions(),
 	))
 }
 
@@ -101,10 +116,17 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
 		// Properties
 		None,
 		// Extensions
-		None,
+		development_extensions(),
 	))
 }
 
+/// Finalize blocks that are 100 blocks deep and at least 10 minutes old.
+fn development_extensions() -> Extensions {
+	Extensions {
+		k_deep_finality: Some(KDeepFinalityConfig { depth: 100, min_slot_age: 600 }),
+	}
+}
+
 /// Configure initial storage state for FRAME modules.
 fn testnet_genesis(
 	wasm_binary: &[u8],
@@ -119,10 +141,17 @@ fn testnet_genesis(
 		},
 		balances: BalancesConfig {
 			// Configure endowed accounts with initial balance of 1 << 60.
-			balances: endowed_accounts.iter().cloned().map(|k|(k, 1 << 60)).collect(),
+			// The offence reward pot is funded too, so that reporters can be rewarded.
+			balances: endowed_accounts
+				.iter()
+				.cloned()
+				.chain(std::iter::once(OffenceRewardPot::get()))
+				.map(|k|(k, 1 << 60))
+				.collect(),
 		},
 		po_c: PoCConfig {
 			epoch_config: Some(node_template_spartan_runtime::POC_GENESIS_EPOCH_CONFIG),
+			spartan_parameters: Default::default(),
 		},
 		sudo: SudoConfig {
 			// Assign network admin rights.
@@ -1,5 +1,6 @@
 use structopt::StructOpt;
 use sc_cli::RunCmd;
+use std::path::PathBuf;
 
 #[derive(Debug, StructOpt)]
 pub struct Cli {
@@ -8,6 +9,22 @@ pub struct Cli {
 
 	#[structopt(flatten)]
 	pub run: RunCmd,
+
+	/// Run built-in farmer with the plot stored in the given directory.
+	///
+	/// The farmer uses `poc0` key from the keystore, a new key is generated if there is none.
+	/// Requires running the node as an authority (`--validator`).
+	#[structopt(long, value_name = "PATH", parse(from_os_str))]
+	pub farm: Option<PathBuf>,
+
+	/// Number of pieces to plot for the built-in farmer.
+	#[structopt(long, value_name = "PIECES", default_value = "256")]
+	pub plot_size: u64,
+
+	/// Time in milliseconds farmers connected over RPC have to propose proof of space after being
+	/// notified about a slot.
+	#[structopt(long, value_name = "MILLISECONDS", default_value = "5000")]
+	pub poc_solution_timeout: u64,
 }
 
 #[derive(Debug, StructOpt)]
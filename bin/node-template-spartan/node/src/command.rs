@@ -19,7 +19,9 @@ use crate::{chain_spec, service};
 use crate::cli::{Cli, Subcommand};
 use sc_cli::{SubstrateCli, RuntimeVersion, Role, ChainSpec};
 use sc_service::PartialComponents;
+use sc_consensus_poc_farmer::FarmerConfig;
 use node_template_spartan_runtime::Block;
+use std::time::Duration;
 
 impl SubstrateCli for Cli {
 	fn impl_name() -> String {
@@ -112,7 +114,11 @@ pub fn run() -> sc_cli::Result<()> {
 			runner.async_run(|config| {
 				let PartialComponents { client, task_manager, backend, ..}
 					= service::new_partial(&config)?;
-				Ok((cmd.run(client, backend), task_manager))
+				let aux_revert = Box::new(|client, backend, blocks| {
+					sc_consensus_poc::revert(client, backend, blocks)?;
+					Ok(())
+				});
+				Ok((cmd.run(client, backend, Some(aux_revert)), task_manager))
 			})
 		},
 		Some(Subcommand::Benchmark(cmd)) => {
@@ -127,10 +133,23 @@ pub fn run() -> sc_cli::Result<()> {
 		},
 		None => {
 			let runner = cli.create_runner(&cli.run)?;
+			let farmer_config = cli.farm.clone().map(|path| FarmerConfig {
+				path,
+				plot_size: cli.plot_size,
+			});
+			let solution_timeout = Duration::from_millis(cli.poc_solution_timeout);
 			runner.run_node_until_exit(|config| async move {
+				// only authorities author blocks, solutions of the built-in farmer would be unused
+				if farmer_config.is_some() && !config.role.is_authority() {
+					return Err(sc_cli::Error::Input(
+						"Built-in farmer (`--farm`) requires running the node as an authority \
+						(`--validator`)".into()
+					));
+				}
+
 				match config.role {
 					Role::Light => service::new_light(config),
-					_ => service::new_full(config),
+					_ => service::new_full(config, farmer_config, solution_timeout),
 				}.map_err(sc_cli::Error::Service)
 			})
 		}
@@ -6,6 +6,7 @@
 #![warn(missing_docs)]
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use node_template_spartan_runtime::{opaque::Block, AccountId, Balance, Index};
 use sp_api::ProvideRuntimeApi;
@@ -13,7 +14,8 @@ use sp_blockchain::{Error as BlockChainError, HeaderMetadata, HeaderBackend};
 use sp_block_builder::BlockBuilder;
 pub use sc_rpc_api::DenyUnsafe;
 use sc_transaction_pool_api::TransactionPool;
-use sc_consensus_poc::NewSlotNotifier;
+use sc_consensus_epochs::SharedEpochChanges;
+use sc_consensus_poc::{BlockSigningNotifier, Epoch, NewSlotNotifier};
 use sc_rpc::SubscriptionTaskExecutor;
 
 
@@ -28,7 +30,16 @@ pub struct FullDeps<C, P> {
 	/// Executor to drive the subscription manager in the Grandpa RPC handler.
 	pub subscription_executor: SubscriptionTaskExecutor,
 	/// A function that can be called whenever it is necessary to create a subscription for new slots
-	pub new_slot_notifier: Option<NewSlotNotifier>
+	pub new_slot_notifier: Option<NewSlotNotifier>,
+	/// A function that can be called whenever it is necessary to create a subscription for block
+	/// signing requests
+	pub block_signing_notifier: Option<BlockSigningNotifier>,
+	/// PoC epoch changes, shared with the import queue
+	pub shared_epoch_changes: SharedEpochChanges<Block, Epoch>,
+	/// PoC configuration
+	pub poc_config: sc_consensus_poc::Config,
+	/// Time farmers have to propose proof of space after being notified about slot
+	pub solution_timeout: Duration,
 }
 
 /// Instantiate all full RPC extensions.
@@ -41,6 +52,7 @@ pub fn create_full<C, P>(
 	C::Api: substrate_frame_rpc_system::AccountNonceApi<Block, AccountId, Index>,
 	C::Api: pallet_transaction_payment_rpc::TransactionPaymentRuntimeApi<Block, Balance>,
 	C::Api: BlockBuilder<Block>,
+	C::Api: sp_consensus_poc::PoCApi<Block>,
 	P: TransactionPool + 'static,
 {
 	use substrate_frame_rpc_system::{FullSystem, SystemApi};
@@ -53,6 +65,10 @@ pub fn create_full<C, P>(
 		deny_unsafe,
 		subscription_executor,
 		new_slot_notifier,
+		block_signing_notifier,
+		shared_epoch_changes,
+		poc_config,
+		solution_timeout,
 	} = deps;
 
 	io.extend_with(
@@ -63,12 +79,29 @@ pub fn create_full<C, P>(
 		TransactionPaymentApi::to_delegate(TransactionPayment::new(client.clone()))
 	);
 
-	if let Some(new_slot_notifier) = new_slot_notifier {
+	let signing_context = poc_config.spartan_parameters.signing_context.clone();
+
+	io.extend_with(
+		sc_consensus_poc_rpc::PoCStateApi::to_delegate(
+			sc_consensus_poc_rpc::PoCStateRpcHandler::new(
+				client.clone(),
+				shared_epoch_changes,
+				poc_config,
+			),
+		)
+	);
+
+	if let (Some(new_slot_notifier), Some(block_signing_notifier)) =
+		(new_slot_notifier, block_signing_notifier)
+	{
 		io.extend_with(
 			sc_consensus_poc_rpc::PoCApi::to_delegate(
 				sc_consensus_poc_rpc::PoCRpcHandler::new(
 					subscription_executor,
 					new_slot_notifier,
+					block_signing_notifier,
+					solution_timeout,
+					signing_context,
 				),
 			)
 		);
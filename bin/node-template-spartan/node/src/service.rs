@@ -1,6 +1,7 @@
 //! Service and ServiceFactory implementation. Specialized wrapper over substrate service.
 
 use std::sync::Arc;
+use std::time::Duration;
 use sc_client_api::{ExecutorProvider, RemoteBackend};
 use node_template_spartan_runtime::{self, opaque::Block, RuntimeApi};
 use sc_service::{error::Error as ServiceError, Configuration, TaskManager};
@@ -8,6 +9,9 @@ use sc_executor::native_executor_instance;
 pub use sc_executor::NativeExecutor;
 use sc_telemetry::{Telemetry, TelemetryWorker};
 use sc_consensus_poc::SlotProportion;
+use sc_consensus_poc_archiver::ArchiverLink;
+use sc_consensus_poc_farmer::{Farmer, FarmerConfig};
+use sc_consensus_poc_finality::{KDeepFinalityConfig, KDeepFinalityParams};
 use sp_runtime::traits::Block as BlockT;
 
 // Our native executor instance.
@@ -33,6 +37,7 @@ pub fn new_partial(config: &Configuration) -> Result<sc_service::PartialComponen
 			Arc<FullClient>
 		>,
 		sc_consensus_poc::PoCLink<Block>,
+		ArchiverLink,
 		Option<Telemetry>,
 	)
 >, ServiceError> {
@@ -69,11 +74,13 @@ pub fn new_partial(config: &Configuration) -> Result<sc_service::PartialComponen
 	);
 
 	let (block_import, poc_link) = sc_consensus_poc::block_import(
-		sc_consensus_poc::Config::get_or_compute(&*client)?,
+		sc_consensus_poc::Config::get_or_compute(&*client)?.with_fork_choice_tie_breaking(true),
 		client.clone(),
 		client.clone(),
 	)?;
 
+	let archiver_link = ArchiverLink::default();
+
 	let slot_duration = poc_link.config().slot_duration();
 	let import_queue = sc_consensus_poc::import_queue(
 		poc_link.clone(),
@@ -81,26 +88,39 @@ pub fn new_partial(config: &Configuration) -> Result<sc_service::PartialComponen
 		None,
 		client.clone(),
 		select_chain.clone(),
-		move |_, ()| {
-			async move {
-				let timestamp = sp_timestamp::InherentDataProvider::from_system_time();
+		{
+			let client = client.clone();
+			let archiver_link = archiver_link.clone();
+			move |parent, ()| {
+				let client = client.clone();
+				let archiver_link = archiver_link.clone();
+				async move {
+					let timestamp = sp_timestamp::InherentDataProvider::from_system_time();
 
-				let slot =
-					sp_consensus_poc::inherents::InherentDataProvider::from_timestamp_and_duration(
-						*timestamp,
-						slot_duration,
-					);
+					let slot =
+						sp_consensus_poc::inherents::InherentDataProvider::from_timestamp_and_duration(
+							*timestamp,
+							slot_duration,
+						);
 
-				let uncles =
-					sp_authorship::InherentDataProvider::<<Block as BlockT>::Header>::check_inherents();
+					let uncles =
+						sp_authorship::InherentDataProvider::<<Block as BlockT>::Header>::check_inherents();
 
-				Ok((timestamp, slot, uncles))
+					let root_blocks = sc_consensus_poc_archiver::root_blocks_inherent_data_provider(
+						&*client,
+						parent,
+						&archiver_link,
+					)?;
+
+					Ok((timestamp, slot, uncles, root_blocks))
+				}
 			}
 		},
 		&task_manager.spawn_essential_handle(),
 		config.prometheus_registry(),
 		sp_consensus::CanAuthorWithNativeVersion::new(client.executor().clone()),
 		telemetry.as_ref().map(|x| x.handle()),
+		None,
 	)?;
 
 	Ok(sc_service::PartialComponents {
@@ -111,12 +131,16 @@ pub fn new_partial(config: &Configuration) -> Result<sc_service::PartialComponen
 		keystore_container,
 		select_chain,
 		transaction_pool,
-		other: (block_import, poc_link, telemetry),
+		other: (block_import, poc_link, archiver_link, telemetry),
 	})
 }
 
 /// Builds a new service for a full client.
-pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
+pub fn new_full(
+	mut config: Configuration,
+	farmer_config: Option<FarmerConfig>,
+	solution_timeout: Duration,
+) -> Result<TaskManager, ServiceError> {
 	let sc_service::PartialComponents {
 		client,
 		backend,
@@ -125,9 +149,17 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 		keystore_container,
 		select_chain,
 		transaction_pool,
-		other: (block_import, poc_link, mut telemetry),
+		other: (block_import, poc_link, archiver_link, mut telemetry),
 	} = new_partial(&config)?;
 
+	config.network.request_response_protocols.push(
+		sc_consensus_poc_warp_sync::request_response_config_for_chain::<Block, _>(
+			&config,
+			task_manager.spawn_handle(),
+			client.clone(),
+		),
+	);
+
 	let (network, system_rpc_tx, network_starter) =
 		sc_service::build_network(sc_service::BuildNetworkParams {
 			config: &config,
@@ -145,12 +177,55 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 		);
 	}
 
+	// Nodes that download state of a recent block instead of importing all blocks need epochs of
+	// that block to verify blocks that follow it
+	if matches!(config.network.sync_mode, sc_network::config::SyncMode::Fast { .. }) {
+		task_manager.spawn_handle().spawn(
+			"poc-warp-sync",
+			sc_consensus_poc_warp_sync::run_warp_sync(
+				client.clone(),
+				network.clone(),
+				config.protocol_id(),
+				poc_link.clone(),
+			),
+		);
+	}
+
+	let k_deep_finality = sc_chain_spec::get_extension::<Option<KDeepFinalityConfig>>(
+		config.chain_spec.extensions(),
+	).cloned().flatten();
+	if let Some(k_deep_finality) = k_deep_finality {
+		task_manager.spawn_essential_handle().spawn(
+			"poc-k-deep-finality",
+			sc_consensus_poc_finality::run_k_deep_finality::<Block, FullBackend, _, _>(
+				KDeepFinalityParams {
+					client: client.clone(),
+					select_chain: select_chain.clone(),
+					config: k_deep_finality,
+				},
+			),
+		);
+	}
+
+	// Subscribed before archiver starts, such that farmer doesn't miss any segments
+	let archived_segment_notification_stream = farmer_config
+		.as_ref()
+		.map(|_| archiver_link.archived_segment_notification_stream());
+
+	task_manager.spawn_handle().spawn_blocking(
+		"poc-archiver",
+		sc_consensus_poc_archiver::run_archiver(client.clone(), archiver_link.clone()),
+	);
+
 	let role = config.role.clone();
 	let force_authoring = config.force_authoring;
 	let backoff_authoring_blocks: Option<()> = None;
 	let prometheus_registry = config.prometheus_registry().cloned();
 
 	let mut new_slot_notifier = None;
+	let mut block_signing_notifier = None;
+	let shared_epoch_changes = poc_link.epoch_changes().clone();
+	let poc_config = poc_link.config().clone();
 
 	if role.is_authority() {
 		let proposer_factory = sc_basic_authorship::ProposerFactory::new(
@@ -166,6 +241,7 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 
 		let client_clone = client.clone();
 		let slot_duration = poc_link.config().slot_duration();
+		let spartan_parameters = poc_link.config().spartan_parameters.clone();
 		let poc_config = sc_consensus_poc::PoCParams {
 			client: client.clone(),
 			select_chain: select_chain.clone(),
@@ -175,6 +251,7 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 			justification_sync_link: network.clone(),
 			create_inherent_data_providers: move |parent, ()| {
 				let client_clone = client_clone.clone();
+				let archiver_link = archiver_link.clone();
 				async move {
 					let uncles = sc_consensus_uncles::create_uncles_inherent_data_provider(
 						&*client_clone,
@@ -189,7 +266,13 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 							slot_duration,
 						);
 
-					Ok((timestamp, slot, uncles))
+					let root_blocks = sc_consensus_poc_archiver::root_blocks_inherent_data_provider(
+						&*client_clone,
+						parent,
+						&archiver_link,
+					)?;
+
+					Ok((timestamp, slot, uncles, root_blocks))
 				}
 			},
 			force_authoring,
@@ -198,11 +281,39 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 			can_author_with,
 			block_proposal_slot_portion: SlotProportion::new(2f32 / 3f32),
 			max_block_proposal_slot_portion: None,
+			registry: prometheus_registry.clone(),
 			telemetry: None
 		};
 
 		let poc = sc_consensus_poc::start_poc(poc_config)?;
 		new_slot_notifier.replace(poc.get_new_slot_notifier());
+		block_signing_notifier.replace(poc.get_block_signing_notifier());
+
+		if let Some(farmer_config) = farmer_config {
+			let keystore = keystore_container.local_keystore()
+				.ok_or_else(|| ServiceError::Other("Built-in farmer requires local keystore".into()))?;
+			let farmer = Arc::new(
+				Farmer::new(&farmer_config, &spartan_parameters, &keystore)
+					.map_err(|error| ServiceError::Other(error.to_string()))?
+			);
+
+			task_manager.spawn_essential_handle().spawn_blocking("poc-farmer-slots", {
+				let farmer = farmer.clone();
+				let new_slot_notifier = poc.get_new_slot_notifier();
+				async move { farmer.run_slots(new_slot_notifier) }
+			});
+			task_manager.spawn_essential_handle().spawn_blocking("poc-farmer-block-signing", {
+				let farmer = farmer.clone();
+				let block_signing_notifier = poc.get_block_signing_notifier();
+				async move { farmer.run_block_signing(block_signing_notifier) }
+			});
+			if let Some(archived_segments) = archived_segment_notification_stream {
+				task_manager.spawn_handle().spawn_blocking(
+					"poc-farmer-archiving",
+					async move { farmer.run_archiving(archived_segments) },
+				);
+			}
+		}
 
 		// the PoC authoring task is considered essential, i.e. if it
 		// fails we take down the service with it.
@@ -220,6 +331,10 @@ pub fn new_full(config: Configuration) -> Result<TaskManager, ServiceError> {
 				deny_unsafe,
 				subscription_executor,
 				new_slot_notifier: new_slot_notifier.clone(),
+				block_signing_notifier: block_signing_notifier.clone(),
+				shared_epoch_changes: shared_epoch_changes.clone(),
+				poc_config: poc_config.clone(),
+				solution_timeout,
 			};
 
 			crate::rpc::create_full(deps)
@@ -281,7 +396,7 @@ pub fn new_light(config: Configuration) -> Result<TaskManager, ServiceError> {
 	));
 
 	let (poc_block_import, poc_link) = sc_consensus_poc::block_import(
-		sc_consensus_poc::Config::get_or_compute(&*client)?,
+		sc_consensus_poc::Config::get_or_compute(&*client)?.with_fork_choice_tie_breaking(true),
 		client.clone(),
 		client.clone(),
 	)?;
@@ -311,6 +426,7 @@ pub fn new_light(config: Configuration) -> Result<TaskManager, ServiceError> {
 		config.prometheus_registry(),
 		sp_consensus::NeverCanAuthor,
 		telemetry.as_ref().map(|x| x.handle()),
+		Some(sc_consensus_poc::block_body_fetcher(on_demand.clone())),
 	)?;
 
 	let (network, system_rpc_tx, network_starter) =
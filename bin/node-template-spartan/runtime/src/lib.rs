@@ -8,7 +8,9 @@ include!(concat!(env!("OUT_DIR"), "/wasm_binary.rs"));
 
 use sp_api::impl_runtime_apis;
 use sp_core::{crypto::KeyTypeId, OpaqueMetadata};
-use sp_runtime::traits::{AccountIdLookup, BlakeTwo256, Block as BlockT, IdentifyAccount, Verify};
+use sp_runtime::traits::{
+    AccountIdConversion, AccountIdLookup, BlakeTwo256, Block as BlockT, IdentifyAccount, Verify,
+};
 use sp_runtime::{
     create_runtime_str, generic, impl_opaque_keys,
     transaction_validity::{TransactionSource, TransactionValidity},
@@ -27,14 +29,14 @@ pub use frame_support::{
         constants::{BlockExecutionWeight, ExtrinsicBaseWeight, RocksDbWeight, WEIGHT_PER_SECOND},
         IdentityFee, Weight,
     },
-    StorageValue,
+    PalletId, StorageValue,
 };
 pub use pallet_balances::Call as BalancesCall;
 pub use pallet_timestamp::Call as TimestampCall;
 use pallet_transaction_payment::CurrencyAdapter;
 #[cfg(any(feature = "std", test))]
 pub use sp_runtime::BuildStorage;
-pub use sp_runtime::{Perbill, Permill};
+pub use sp_runtime::{Perbill, Percent, Permill};
 
 /// Import the template pallet.
 pub use pallet_template_spartan;
@@ -138,11 +140,23 @@ const ERA_DURATION_IN_BLOCKS: u32 = 2016;
 const INITIAL_SOLUTION_RANGE: u64 =
     u64::MAX / (1024 * 1024 * 1024 / 4096) * SLOT_PROBABILITY.0 / SLOT_PROBABILITY.1;
 
+/// Solution range can increase by at most 4x on every era change.
+const MAX_SOLUTION_RANGE_INCREASE: (u64, u64) = (4, 1);
+
+/// Solution range can decrease by at most 4x on every era change.
+const MAX_SOLUTION_RANGE_DECREASE: (u64, u64) = (4, 1);
+
 const EPOCH_DURATION_IN_BLOCKS: BlockNumber = 256;
 const EPOCH_DURATION_IN_SLOTS: u64 =
     EPOCH_DURATION_IN_BLOCKS as u64 * SLOT_PROBABILITY.1 / SLOT_PROBABILITY.0;
 
 const EON_DURATION_IN_SLOTS: u64 = 2u64.pow(14);
+/// Salt for the next eon is revealed this many slots into the current eon, leaving the rest of the
+/// eon for farmers to prepare.
+const EON_NEXT_SALT_REVEAL: u64 = EON_DURATION_IN_SLOTS - EON_DURATION_IN_SLOTS / 16;
+
+/// Farmers reported for equivocation can't produce blocks for this many slots.
+const BAN_DURATION_IN_SLOTS: u64 = EON_DURATION_IN_SLOTS;
 
 /// The PoC epoch configuration at genesis.
 pub const POC_GENESIS_EPOCH_CONFIG: sp_consensus_poc::PoCEpochConfiguration =
@@ -231,24 +245,33 @@ parameter_types! {
     pub const EpochDuration: u64 = EPOCH_DURATION_IN_SLOTS;
     pub const EraDuration: u32 = ERA_DURATION_IN_BLOCKS;
     pub const EonDuration: u64 = EON_DURATION_IN_SLOTS;
+    pub const EonNextSaltReveal: u64 = EON_NEXT_SALT_REVEAL;
     pub const InitialSolutionRange: u64 = INITIAL_SOLUTION_RANGE;
     pub const SlotProbability: (u64, u64) = SLOT_PROBABILITY;
+    pub const MaxSolutionRangeIncrease: (u64, u64) = MAX_SOLUTION_RANGE_INCREASE;
+    pub const MaxSolutionRangeDecrease: (u64, u64) = MAX_SOLUTION_RANGE_DECREASE;
     pub const ExpectedBlockTime: Moment = MILLISECS_PER_BLOCK;
     pub const ReportLongevity: u64 = EPOCH_DURATION_IN_BLOCKS as u64;
+    pub const BanDuration: u64 = BAN_DURATION_IN_SLOTS;
 }
 
 impl pallet_spartan::Config for Runtime {
     type EpochDuration = EpochDuration;
     type EraDuration = EraDuration;
     type EonDuration = EonDuration;
+    type EonNextSaltReveal = EonNextSaltReveal;
     type InitialSolutionRange = InitialSolutionRange;
     type SlotProbability = SlotProbability;
+    type MaxSolutionRangeIncrease = MaxSolutionRangeIncrease;
+    type MaxSolutionRangeDecrease = MaxSolutionRangeDecrease;
     type ExpectedBlockTime = ExpectedBlockTime;
     type EpochChangeTrigger = pallet_spartan::NormalEpochChange;
     type EraChangeTrigger = pallet_spartan::NormalEraChange;
     type EonChangeTrigger = pallet_spartan::NormalEonChange;
 
-    type HandleEquivocation = pallet_spartan::EquivocationHandler<OffencesPoC, ReportLongevity>;
+    type HandleEquivocation =
+        pallet_spartan::EquivocationHandler<OffencesPoC, ReportLongevity, Rewards>;
+    type BanDuration = BanDuration;
 
     type WeightInfo = ();
 }
@@ -289,12 +312,26 @@ parameter_types! {
 }
 
 impl pallet_transaction_payment::Config for Runtime {
-    type OnChargeTransaction = CurrencyAdapter<Balances, ()>;
+    type OnChargeTransaction = CurrencyAdapter<Balances, Rewards>;
     type TransactionByteFee = TransactionByteFee;
     type WeightToFee = IdentityFee<Balance>;
     type FeeMultiplierUpdate = ();
 }
 
+parameter_types! {
+    pub const BlockReward: Balance = 1_000_000;
+    pub const AuthorFeeShare: Percent = Percent::from_percent(50);
+}
+
+impl pallet_rewards::Config for Runtime {
+    type Event = Event;
+    type Currency = Balances;
+    type FindAuthor = PoC;
+    type BlockReward = BlockReward;
+    type AuthorFeeShare = AuthorFeeShare;
+    type WeightInfo = ();
+}
+
 impl pallet_sudo::Config for Runtime {
     type Event = Event;
     type Call = Call;
@@ -313,9 +350,22 @@ parameter_types! {
         BlockWeights::get().max_block;
 }
 
+parameter_types! {
+    pub const ReporterReward: Balance = 100_000;
+    pub const OffenceRewardPotId: PalletId = PalletId(*b"poc/offr");
+    pub OffenceRewardPot: AccountId = OffenceRewardPotId::get().into_account();
+}
+
 impl pallet_offences_poc::Config for Runtime {
     type Event = Event;
     type OnOffenceHandler = PoC;
+    type Currency = Balances;
+    type ReporterReward = ReporterReward;
+    type RewardPot = OffenceRewardPot;
+}
+
+impl pallet_poc_solution_data::Config for Runtime {
+    type WeightInfo = ();
 }
 
 /// Configure the pallet-template in pallets/template.
@@ -333,11 +383,13 @@ construct_runtime!(
         System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
         RandomnessCollectiveFlip: pallet_randomness_collective_flip::{Pallet, Storage},
         Timestamp: pallet_timestamp::{Pallet, Call, Storage, Inherent},
-        PoC: pallet_spartan::{Pallet, Call, Storage, Config, ValidateUnsigned},
+        PoC: pallet_spartan::{Pallet, Call, Storage, Config, Inherent, ValidateUnsigned},
+        PoCSolutionData: pallet_poc_solution_data::{Pallet, Call, Storage, Inherent},
         Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
         TransactionPayment: pallet_transaction_payment::{Pallet, Storage},
         Sudo: pallet_sudo::{Pallet, Call, Config<T>, Storage, Event<T>},
-        OffencesPoC: pallet_offences_poc::{Pallet, Storage, Event},
+        OffencesPoC: pallet_offences_poc::{Pallet, Storage, Event<T>},
+        Rewards: pallet_rewards::{Pallet, Call, Storage, Event<T>},
         // Include the custom logic from the pallet-template in the runtime.
         TemplateModule: pallet_template_spartan::{Pallet, Call, Storage, Event<T>},
     }
@@ -440,6 +492,8 @@ impl_runtime_apis! {
                 epoch_length: EpochDuration::get(),
                 c: SlotProbability::get(),
                 randomness: PoC::randomness(),
+                spartan_parameters: PoC::spartan_parameters(),
+                compact_pre_digest: true,
             }
         }
 
@@ -472,10 +526,28 @@ impl_runtime_apis! {
         }
 
         fn is_in_block_list(farmer_id: &sp_consensus_poc::FarmerId) -> bool {
-            // TODO: Either check tx pool too for pending equivocations or replace equivocation
-            //  mechanism with an alternative one, so that blocking happens faster
+            // Farmers with equivocation reports that are not yet included in a block are tracked by
+            // the client separately
             PoC::is_in_block_list(farmer_id)
         }
+
+        fn block_list() -> Vec<(sp_consensus_poc::FarmerId, sp_consensus_poc::Slot)> {
+            PoC::block_list()
+        }
+
+        fn farmer_offences(
+            farmer_id: &sp_consensus_poc::FarmerId,
+        ) -> Vec<sp_consensus_poc::offence::OffenceRecord> {
+            OffencesPoC::farmer_offences(farmer_id)
+        }
+
+        fn next_archived_segment_index() -> u64 {
+            PoC::next_archived_segment_index()
+        }
+
+        fn records_root(segment_index: u64) -> Option<[u8; 32]> {
+            PoC::records_root(segment_index)
+        }
     }
 
     impl sp_session::SessionKeys<Block> for Runtime {
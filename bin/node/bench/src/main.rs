@@ -22,6 +22,7 @@ mod construct;
 mod core;
 mod generator;
 mod import;
+mod poc;
 mod simple_trie;
 mod state_sizes;
 mod tempdb;
@@ -37,6 +38,7 @@ use crate::{
 	construct::ConstructionBenchmarkDescription,
 	core::{run_benchmark, Mode as BenchmarkMode},
 	import::ImportBenchmarkDescription,
+	poc::PoCImportBenchmarkDescription,
 	tempdb::DatabaseType,
 	trie::{DatabaseSize, TrieReadBenchmarkDescription, TrieWriteBenchmarkDescription},
 	txpool::PoolBenchmarkDescription,
@@ -163,6 +165,7 @@ fn main() {
 			database_type: BenchDataBaseType::RocksDb,
 		},
 		PoolBenchmarkDescription { database_type: BenchDataBaseType::RocksDb },
+		PoCImportBenchmarkDescription { blocks: 100 },
 	);
 
 	if opt.list {
@@ -0,0 +1,262 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! PoC import benchmarks.
+//!
+//! The goal of this benchmark is to figure out the time needed to import a batch of PoC blocks
+//! received during initial sync through the PoC import queue. Solutions and seal signatures of the
+//! whole batch are verified in parallel before blocks are verified in context of the chain and
+//! imported one by one, which is what dominates import of PoC blocks during initial sync.
+
+use std::{borrow::Cow, convert::TryInto, sync::Arc};
+
+use futures::{executor::block_on, future::poll_fn, task::Poll};
+use sc_block_builder::BlockBuilderProvider;
+use sc_consensus::{
+	BlockImportError, BlockImportStatus, DefaultImportQueue, ImportQueue, IncomingBlock, Link,
+};
+use sc_consensus_poc::Config;
+use sp_api::ProvideRuntimeApi;
+use sp_consensus::{AlwaysCanAuthor, BlockOrigin};
+use sp_consensus_poc::{
+	digests::{CompatibleDigestItem, PreDigest, Solution},
+	FarmerId, PoCApi,
+};
+use sp_consensus_spartan::spartan::{create_tag, Spartan, SIGNING_CONTEXT};
+use sp_core::Public;
+use sp_runtime::{
+	generic::{BlockId, Digest, DigestItem},
+	traits::{Block as BlockT, Header as _, NumberFor},
+};
+use substrate_test_runtime_client::{
+	runtime::Block, DefaultTestClientBuilderExt, TestClient, TestClientBuilder,
+	TestClientBuilderExt,
+};
+
+use crate::core::{self, Mode, Path};
+
+pub struct PoCImportBenchmarkDescription {
+	/// Number of blocks in a batch.
+	pub blocks: usize,
+}
+
+pub struct PoCImportBenchmark {
+	blocks: Vec<Block>,
+}
+
+impl core::BenchmarkDescription for PoCImportBenchmarkDescription {
+	fn path(&self) -> Path {
+		let mut path = Path::new(&["poc", "import"]);
+		path.push(&format!("{}", self.blocks));
+		path
+	}
+
+	fn setup(self: Box<Self>) -> Box<dyn core::Benchmark> {
+		Box::new(PoCImportBenchmark { blocks: sealed_blocks(self.blocks) })
+	}
+
+	fn name(&self) -> Cow<'static, str> {
+		format!("PoC import of {} blocks through the import queue", self.blocks).into()
+	}
+}
+
+impl core::Benchmark for PoCImportBenchmark {
+	fn run(&mut self, mode: Mode) -> std::time::Duration {
+		// Every run imports blocks into a fresh client, otherwise they would be known already
+		let (_client, mut import_queue) = new_client_and_import_queue();
+		let blocks = self.blocks.iter().cloned().map(incoming_block).collect::<Vec<_>>();
+
+		if mode == Mode::Profile {
+			std::thread::park_timeout(std::time::Duration::from_secs(3));
+		}
+
+		let start = std::time::Instant::now();
+		import_blocks(&mut import_queue, blocks);
+		let elapsed = start.elapsed();
+
+		if mode == Mode::Profile {
+			std::thread::park_timeout(std::time::Duration::from_secs(1));
+		}
+		elapsed
+	}
+}
+
+/// Link that keeps track of blocks processed by the import queue.
+#[derive(Default)]
+struct ImportLink {
+	processed: usize,
+	imported: usize,
+	errors: Vec<BlockImportError>,
+}
+
+impl Link<Block> for ImportLink {
+	fn blocks_processed(
+		&mut self,
+		imported: usize,
+		count: usize,
+		results: Vec<(
+			Result<BlockImportStatus<NumberFor<Block>>, BlockImportError>,
+			<Block as BlockT>::Hash,
+		)>,
+	) {
+		self.processed += count;
+		self.imported += imported;
+		self.errors
+			.extend(results.into_iter().filter_map(|(result, _hash)| result.err()));
+	}
+}
+
+fn new_client_and_import_queue() -> (Arc<TestClient>, DefaultImportQueue<Block, TestClient>) {
+	let (client, select_chain) = TestClientBuilder::new().build_with_longest_chain();
+	let client = Arc::new(client);
+	let config = Config::get_or_compute(&*client).expect("PoC config is available; qed");
+	let slot_duration = config.slot_duration();
+	let (block_import, poc_link) =
+		sc_consensus_poc::block_import(config, client.clone(), client.clone())
+			.expect("PoC block import is created; qed");
+
+	let import_queue = sc_consensus_poc::import_queue(
+		poc_link,
+		block_import,
+		None,
+		client.clone(),
+		select_chain,
+		move |_, ()| async move {
+			let timestamp = sp_timestamp::InherentDataProvider::from_system_time();
+
+			let slot =
+				sp_consensus_poc::inherents::InherentDataProvider::from_timestamp_and_duration(
+					*timestamp,
+					slot_duration,
+				);
+
+			Ok((timestamp, slot))
+		},
+		&sp_core::testing::TaskExecutor::new(),
+		None,
+		AlwaysCanAuthor,
+		None,
+		None,
+	)
+	.expect("PoC import queue is created; qed");
+
+	(client, import_queue)
+}
+
+fn incoming_block(block: Block) -> IncomingBlock<Block> {
+	let (header, body) = block.deconstruct();
+	IncomingBlock {
+		hash: header.hash(),
+		header: Some(header),
+		body: Some(body),
+		indexed_body: None,
+		justifications: None,
+		origin: None,
+		allow_missing_state: false,
+		skip_execution: false,
+		import_existing: false,
+		state: None,
+	}
+}
+
+/// Import `blocks` through `import_queue` and wait until all of them are processed.
+fn import_blocks(
+	import_queue: &mut DefaultImportQueue<Block, TestClient>,
+	blocks: Vec<IncomingBlock<Block>>,
+) {
+	let count = blocks.len();
+	let mut link = ImportLink::default();
+
+	import_queue.import_blocks(BlockOrigin::NetworkInitialSync, blocks);
+	block_on(poll_fn(|cx| {
+		import_queue.poll_actions(cx, &mut link);
+		if link.processed < count {
+			Poll::Pending
+		} else {
+			Poll::Ready(())
+		}
+	}));
+
+	assert_eq!(link.imported, count, "All blocks must be imported: {:?}", link.errors);
+}
+
+/// Create a chain of `count` blocks with valid solutions, sealed by the farmer.
+///
+/// Blocks are imported through the import queue of a separate client as they are created, such
+/// that every block is built on top of the state of its parent.
+fn sealed_blocks(count: usize) -> Vec<Block> {
+	let (client, mut import_queue) = new_client_and_import_queue();
+
+	let keypair = schnorrkel::Keypair::generate();
+	let public_key = keypair.public.to_bytes();
+	let spartan = Spartan::default();
+	let ctx = schnorrkel::context::signing_context(SIGNING_CONTEXT);
+	// Must match the context used by sr25519 signature verification
+	let seal_ctx = schnorrkel::context::signing_context(b"substrate");
+
+	// Encoding is expensive, so a single one is reused for all blocks. Encodings of the genesis
+	// piece remain valid, since benchmarked chains are far too short to archive
+	// `GENESIS_PIECE_SOLUTIONS_CUTOVER` segments
+	let nonce = 0;
+	let encoding = spartan.encode(&public_key, nonce);
+
+	let mut parent_hash = client.chain_info().genesis_hash;
+	(1..=count as u64)
+		.map(|slot| {
+			let parent_id = BlockId::Hash(parent_hash);
+			let salt = client.runtime_api().salt(&parent_id).expect("Salt is available; qed");
+			let tag = create_tag(&encoding, &salt.to_le_bytes());
+			let pre_digest = PreDigest {
+				slot: slot.into(),
+				solution: Solution {
+					public_key: FarmerId::from_slice(&public_key),
+					nonce,
+					encoding: encoding.to_vec(),
+					signature: keypair.sign(ctx.bytes(&tag)).to_bytes().to_vec(),
+					tag,
+					piece_inclusion_proof: None,
+					solution_data_commitment: None,
+				},
+			};
+
+			let block = client
+				.new_block_at(
+					&parent_id,
+					Digest { logs: vec![DigestItem::poc_pre_digest(pre_digest)] },
+					false,
+				)
+				.expect("Block builder is created; qed")
+				.build()
+				.expect("Block is built; qed")
+				.block;
+			let (mut header, body) = block.deconstruct();
+
+			let pre_hash = header.hash();
+			let signature = keypair.sign(seal_ctx.bytes(pre_hash.as_ref())).to_bytes().to_vec();
+			header.digest_mut().push(DigestItem::poc_seal(
+				signature.try_into().expect("Signature has correct length; qed"),
+			));
+
+			let block = Block::new(header, body);
+			import_blocks(&mut import_queue, vec![incoming_block(block.clone())]);
+
+			parent_hash = block.hash();
+			block
+		})
+		.collect()
+}
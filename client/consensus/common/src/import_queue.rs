@@ -102,6 +102,12 @@ pub trait Verifier<B: BlockT>: Send + Sync {
 		justifications: Option<Justifications>,
 		body: Option<Vec<B::Extrinsic>>,
 	) -> Result<(BlockImportParams<B, ()>, Option<Vec<(CacheKeyId, Vec<u8>)>>), String>;
+
+	/// Called with all blocks of an import batch before any of them is verified.
+	///
+	/// Allows verifiers to check the parts of many blocks that don't depend on each other at once
+	/// (for instance in parallel) ahead of the sequential `verify` calls. Does nothing by default.
+	fn prepare_batch(&mut self, _origin: &BlockOrigin, _blocks: &[IncomingBlock<B>]) {}
 }
 
 /// Blocks import queue API.
@@ -356,6 +356,7 @@ async fn import_many_blocks<B: BlockT, V: Verifier<B>, Transaction: Send + 'stat
 	let mut imported = 0;
 	let mut results = vec![];
 	let mut has_error = false;
+	verifier.prepare_batch(&blocks_origin, &blocks);
 	let mut blocks = blocks.into_iter();
 
 	// Blocks in the response/drain should be in ascending order.
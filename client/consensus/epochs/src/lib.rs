@@ -21,7 +21,7 @@
 pub mod migration;
 
 use codec::{Decode, Encode};
-use fork_tree::ForkTree;
+use fork_tree::{FilterAction, ForkTree};
 use sc_client_api::utils::is_descendent_of;
 use sp_blockchain::{Error as ClientError, HeaderBackend, HeaderMetadata};
 use sp_runtime::traits::{Block as BlockT, NumberFor, One, Zero};
@@ -400,6 +400,37 @@ where
 		Ok(())
 	}
 
+	/// Revert to a specified block given its `hash` and `number`.
+	/// This removes all the epoch changes information that were announced by
+	/// all the given block descendents.
+	pub fn revert<D: IsDescendentOfBuilder<Hash>>(
+		&mut self,
+		descendent_of_builder: D,
+		hash: Hash,
+		number: Number,
+	) {
+		let is_descendent_of = descendent_of_builder.build_is_descendent_of(None);
+
+		let filter = |node_hash: &Hash, node_num: &Number, _: &PersistedEpochHeader<E>| {
+			if number >= *node_num &&
+				(is_descendent_of(node_hash, &hash).unwrap_or_default() || *node_hash == hash)
+			{
+				// Continue the search in this subtree.
+				FilterAction::KeepNode
+			} else if number < *node_num && is_descendent_of(&hash, node_hash).unwrap_or_default() {
+				// Found a node to be removed.
+				FilterAction::Remove
+			} else {
+				// Not a parent or child of the one we're looking for, stop processing this branch.
+				FilterAction::KeepTree
+			}
+		};
+
+		for (hash, number, _) in self.inner.drain_filter(filter) {
+			self.epochs.remove(&(hash, number));
+		}
+	}
+
 	/// Get a reference to an epoch with given identifier.
 	pub fn epoch(&self, id: &EpochIdentifier<Hash, Number>) -> Option<&E> {
 		self.epochs.get(&(id.hash, id.number)).and_then(|v| match v {
@@ -893,4 +924,55 @@ mod tests {
 			assert!(epoch_for_x_child_before_genesis.is_none());
 		}
 	}
+
+	#[test]
+	fn revert_works() {
+		// 0 - A - B - C
+		//      \
+		//       — X
+		//
+		let is_descendent_of = |base: &Hash, block: &Hash| -> Result<bool, TestError> {
+			match (base, *block) {
+				(b"A", b) => Ok(b == *b"B" || b == *b"C" || b == *b"X"),
+				(b"B", b) => Ok(b == *b"C"),
+				(b"0", _) => Ok(true),
+				_ => Ok(false),
+			}
+		};
+
+		let make_genesis = |slot| Epoch { start_slot: slot, duration: 100 };
+
+		let import = |epoch_changes: &mut EpochChanges<Hash, u64, Epoch>,
+		              hash: &Hash,
+		              number: u64,
+		              parent_hash: &Hash,
+		              slot: Slot| {
+			let descriptor = epoch_changes
+				.epoch_descriptor_for_child_of(&is_descendent_of, parent_hash, number - 1, slot)
+				.unwrap()
+				.unwrap();
+			let next_epoch =
+				epoch_changes.viable_epoch(&descriptor, &make_genesis).unwrap().increment(());
+			epoch_changes
+				.import(&is_descendent_of, *hash, number, *parent_hash, next_epoch)
+				.unwrap();
+		};
+
+		let mut epoch_changes = EpochChanges::<_, _, Epoch>::new();
+		import(&mut epoch_changes, b"A", 1, b"0", 1);
+		import(&mut epoch_changes, b"B", 2, b"A", 101);
+		import(&mut epoch_changes, b"C", 3, b"B", 201);
+		import(&mut epoch_changes, b"X", 2, b"A", 101);
+
+		assert_eq!(epoch_changes.tree().iter().count(), 4);
+		assert_eq!(epoch_changes.epochs.len(), 4);
+
+		epoch_changes.revert(&is_descendent_of, *b"A", 1);
+
+		assert_eq!(
+			epoch_changes.tree().iter().map(|(h, _, _)| *h).collect::<Vec<_>>(),
+			vec![*b"A"],
+		);
+		assert_eq!(epoch_changes.epochs.keys().cloned().collect::<Vec<_>>(), vec![(*b"A", 1)]);
+	}
 }
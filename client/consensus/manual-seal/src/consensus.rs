@@ -24,6 +24,7 @@ use sp_inherents::InherentData;
 use sp_runtime::traits::{Block as BlockT, DigestFor};
 
 pub mod babe;
+pub mod poc;
 
 /// Consensus data provider, manual seal uses this trait object for authoring blocks valid
 /// for any runtime.
@@ -0,0 +1,330 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! PoC consensus data provider
+
+use super::ConsensusDataProvider;
+use crate::Error;
+use sc_client_api::{AuxStore, UsageProvider};
+use sc_consensus_epochs::{descendent_query, SharedEpochChanges, ViableEpochDescriptor};
+use sc_consensus_poc::{
+	find_pre_digest, CompatibleDigestItem, Config, Epoch, FarmerId, PoCIntermediate,
+	INTERMEDIATE_KEY,
+};
+use schnorrkel::{context::SigningContext, Keypair};
+use std::{
+	borrow::Cow,
+	sync::{atomic, Arc},
+	time::SystemTime,
+};
+
+use sc_consensus::{BlockImportParams, ForkChoiceStrategy, Verifier};
+use sp_api::{ProvideRuntimeApi, TransactionFor};
+use sp_blockchain::{HeaderBackend, HeaderMetadata};
+use sp_consensus::{BlockOrigin, CacheKeyId};
+use sp_consensus_poc::{
+	digests::{PreDigest, Solution},
+	inherents::PoCInherentData,
+	PoCApi,
+};
+use sp_consensus_slots::Slot;
+use sp_consensus_spartan::spartan::{create_tag, Piece, Spartan};
+use sp_core::crypto::Public;
+use sp_inherents::{InherentData, InherentDataProvider, InherentIdentifier};
+use sp_runtime::{
+	generic::{BlockId, Digest},
+	traits::{Block as BlockT, DigestFor, DigestItemFor, Header, NumberFor, Zero},
+	Justifications,
+};
+use sp_timestamp::{InherentType, INHERENT_IDENTIFIER};
+
+/// Nonce used for the only piece encoded by the consensus data provider.
+const NONCE: u64 = 0;
+
+/// Provides PoC-compatible predigests and BlockImportParams.
+/// Intended for use with PoC runtimes.
+///
+/// Solutions are produced for a single locally encoded genesis piece of the given keypair, they
+/// are valid apart from not necessarily being within solution range, which is fine since manual
+/// seal doesn't check it.
+pub struct PoCConsensusDataProvider<B: BlockT, C> {
+	/// Shared reference to the client.
+	client: Arc<C>,
+
+	/// Shared epoch changes
+	epoch_changes: SharedEpochChanges<B, Epoch>,
+
+	/// Keypair of the farmer that produces solutions.
+	keypair: Keypair,
+
+	/// Signing context for solutions.
+	signing_context: SigningContext,
+
+	/// Encoding of the genesis piece for `keypair`.
+	encoding: Piece,
+}
+
+/// Verifier to be used for PoC chains
+pub struct PoCVerifier<B: BlockT, C> {
+	/// Shared epoch changes
+	epoch_changes: SharedEpochChanges<B, Epoch>,
+
+	/// Shared reference to the client.
+	client: Arc<C>,
+}
+
+impl<B: BlockT, C> PoCVerifier<B, C> {
+	/// create a new verifier
+	pub fn new(epoch_changes: SharedEpochChanges<B, Epoch>, client: Arc<C>) -> PoCVerifier<B, C> {
+		PoCVerifier { epoch_changes, client }
+	}
+}
+
+/// The verifier for the manual seal engine; instantly finalizes.
+#[async_trait::async_trait]
+impl<B, C> Verifier<B> for PoCVerifier<B, C>
+where
+	B: BlockT,
+	C: HeaderBackend<B> + HeaderMetadata<B, Error = sp_blockchain::Error>,
+{
+	async fn verify(
+		&mut self,
+		origin: BlockOrigin,
+		header: B::Header,
+		justifications: Option<Justifications>,
+		body: Option<Vec<B::Extrinsic>>,
+	) -> Result<(BlockImportParams<B, ()>, Option<Vec<(CacheKeyId, Vec<u8>)>>), String> {
+		let mut import_params = BlockImportParams::new(origin, header.clone());
+		import_params.justifications = justifications;
+		import_params.body = body;
+		import_params.finalized = false;
+		import_params.fork_choice = Some(ForkChoiceStrategy::LongestChain);
+
+		let pre_digest = find_pre_digest::<B>(&header)?;
+
+		let parent_hash = header.parent_hash();
+		let parent = self
+			.client
+			.header(BlockId::Hash(*parent_hash))
+			.ok()
+			.flatten()
+			.ok_or_else(|| format!("header for block {} not found", parent_hash))?;
+		let epoch_changes = self.epoch_changes.shared_data();
+		let epoch_descriptor = epoch_changes
+			.epoch_descriptor_for_child_of(
+				descendent_query(&*self.client),
+				&parent.hash(),
+				parent.number().clone(),
+				pre_digest.slot,
+			)
+			.map_err(|e| format!("failed to fetch epoch_descriptor: {}", e))?
+			.ok_or_else(|| format!("{:?}", sp_consensus::Error::InvalidAuthoritiesSet))?;
+		// drop the lock
+		drop(epoch_changes);
+
+		import_params.intermediates.insert(
+			Cow::from(INTERMEDIATE_KEY),
+			Box::new(PoCIntermediate::<B> { epoch_descriptor }) as Box<_>,
+		);
+
+		Ok((import_params, None))
+	}
+}
+
+impl<B, C> PoCConsensusDataProvider<B, C>
+where
+	B: BlockT,
+	C: AuxStore
+		+ HeaderBackend<B>
+		+ ProvideRuntimeApi<B>
+		+ HeaderMetadata<B, Error = sp_blockchain::Error>
+		+ UsageProvider<B>,
+	C::Api: PoCApi<B>,
+{
+	pub fn new(
+		client: Arc<C>,
+		epoch_changes: SharedEpochChanges<B, Epoch>,
+		keypair: Keypair,
+	) -> Result<Self, Error> {
+		let config = Config::get_or_compute(&*client)?;
+		let spartan_parameters = &config.spartan_parameters;
+		let encoding = Spartan::new(
+			&spartan_parameters.genesis_piece_seed,
+			spartan_parameters.encode_rounds as usize,
+		)
+		.encode(&keypair.public.to_bytes(), NONCE);
+
+		Ok(Self {
+			client,
+			epoch_changes,
+			keypair,
+			signing_context: schnorrkel::context::signing_context(
+				&spartan_parameters.signing_context,
+			),
+			encoding,
+		})
+	}
+
+	fn epoch_descriptor(
+		&self,
+		parent: &B::Header,
+		slot: Slot,
+	) -> Result<ViableEpochDescriptor<B::Hash, NumberFor<B>, Epoch>, Error> {
+		self.epoch_changes
+			.shared_data()
+			.epoch_descriptor_for_child_of(
+				descendent_query(&*self.client),
+				&parent.hash(),
+				parent.number().clone(),
+				slot,
+			)
+			.map_err(|e| Error::StringError(format!("failed to fetch epoch_descriptor: {}", e)))?
+			.ok_or_else(|| sp_consensus::Error::InvalidAuthoritiesSet.into())
+	}
+}
+
+impl<B, C> ConsensusDataProvider<B> for PoCConsensusDataProvider<B, C>
+where
+	B: BlockT,
+	C: AuxStore
+		+ HeaderBackend<B>
+		+ HeaderMetadata<B, Error = sp_blockchain::Error>
+		+ UsageProvider<B>
+		+ ProvideRuntimeApi<B>,
+	C::Api: PoCApi<B>,
+{
+	type Transaction = TransactionFor<C, B>;
+
+	fn create_digest(
+		&self,
+		parent: &B::Header,
+		inherents: &InherentData,
+	) -> Result<DigestFor<B>, Error> {
+		let slot = inherents
+			.poc_inherent_data()?
+			.ok_or_else(|| Error::StringError("No PoC inherent data".into()))?;
+
+		// salt in the state of the parent block is the one that must be used for its child
+		let salt = self
+			.client
+			.runtime_api()
+			.salt(&BlockId::Hash(parent.hash()))
+			.map_err(|e| Error::StringError(format!("failed to fetch salt: {}", e)))?;
+		let tag = create_tag(&self.encoding, &salt.to_le_bytes());
+
+		let solution = Solution {
+			public_key: FarmerId::from_slice(&self.keypair.public.to_bytes()),
+			nonce: NONCE,
+			encoding: self.encoding.to_vec(),
+			signature: self.keypair.sign(self.signing_context.bytes(&tag)).to_bytes().to_vec(),
+			tag,
+			piece_inclusion_proof: None,
+			solution_data_commitment: None,
+		};
+
+		let logs = vec![<DigestItemFor<B> as CompatibleDigestItem>::poc_pre_digest(PreDigest {
+			slot,
+			solution,
+		})];
+
+		Ok(Digest { logs })
+	}
+
+	fn append_block_import(
+		&self,
+		parent: &B::Header,
+		params: &mut BlockImportParams<B, Self::Transaction>,
+		inherents: &InherentData,
+	) -> Result<(), Error> {
+		let slot = inherents
+			.poc_inherent_data()?
+			.ok_or_else(|| Error::StringError("No PoC inherent data".into()))?;
+		let epoch_descriptor = self.epoch_descriptor(parent, slot)?;
+
+		params.intermediates.insert(
+			Cow::from(INTERMEDIATE_KEY),
+			Box::new(PoCIntermediate::<B> { epoch_descriptor }) as Box<_>,
+		);
+
+		Ok(())
+	}
+}
+
+/// Provide duration since unix epoch in millisecond for timestamp inherent.
+/// Mocks the timestamp inherent to always produce the timestamp for the next PoC slot.
+pub struct SlotTimestampProvider {
+	time: atomic::AtomicU64,
+	slot_duration: u64,
+}
+
+impl SlotTimestampProvider {
+	/// Create a new mocked time stamp provider.
+	pub fn new<B, C>(client: Arc<C>) -> Result<Self, Error>
+	where
+		B: BlockT,
+		C: AuxStore + HeaderBackend<B> + ProvideRuntimeApi<B> + UsageProvider<B>,
+		C::Api: PoCApi<B>,
+	{
+		let slot_duration = Config::get_or_compute(&*client)?.slot_duration;
+		let info = client.info();
+
+		// looks like this isn't the first block, rehydrate the fake time.
+		// otherwise we'd be producing blocks for older slots.
+		let time = if info.best_number != Zero::zero() {
+			let header = client.header(BlockId::Hash(info.best_hash))?.unwrap();
+			let slot = find_pre_digest::<B>(&header).unwrap().slot;
+			// add the slot duration so there's no collision of slots
+			(*slot * slot_duration) + slot_duration
+		} else {
+			// this is the first block, use the correct time.
+			let now = SystemTime::now();
+			now.duration_since(SystemTime::UNIX_EPOCH)
+				.map_err(|err| Error::StringError(format!("{}", err)))?
+				.as_millis() as u64
+		};
+
+		Ok(Self { time: atomic::AtomicU64::new(time), slot_duration })
+	}
+
+	/// Get the current slot number
+	pub fn slot(&self) -> u64 {
+		self.time.load(atomic::Ordering::SeqCst) / self.slot_duration
+	}
+}
+
+#[async_trait::async_trait]
+impl InherentDataProvider for SlotTimestampProvider {
+	fn provide_inherent_data(
+		&self,
+		inherent_data: &mut InherentData,
+	) -> Result<(), sp_inherents::Error> {
+		// we update the time here.
+		let duration: InherentType =
+			self.time.fetch_add(self.slot_duration, atomic::Ordering::SeqCst).into();
+		inherent_data.put_data(INHERENT_IDENTIFIER, &duration)?;
+		Ok(())
+	}
+
+	async fn try_handle_error(
+		&self,
+		_: &InherentIdentifier,
+		_: &[u8],
+	) -> Option<Result<(), sp_inherents::Error>> {
+		None
+	}
+}
@@ -417,6 +417,100 @@ mod tests {
 		assert_eq!(rx.await.unwrap().unwrap(), ());
 	}
 
+	#[tokio::test]
+	async fn manual_seal_with_poc() {
+		use crate::consensus::poc::{PoCConsensusDataProvider, SlotTimestampProvider};
+		use sc_consensus_poc::find_pre_digest;
+		use sp_consensus_poc::{inherents::InherentDataProvider, FarmerId};
+		use sp_core::crypto::Public;
+		use substrate_test_runtime_client::runtime::Block;
+
+		let builder = TestClientBuilder::new();
+		let (client, select_chain) = builder.build_with_longest_chain();
+		let client = Arc::new(client);
+		let spawner = sp_core::testing::TaskExecutor::new();
+		let pool = Arc::new(BasicPool::with_revalidation_type(
+			Options::default(),
+			true.into(),
+			api(),
+			None,
+			RevalidationType::Full,
+			spawner.clone(),
+			0,
+		));
+		let env = ProposerFactory::new(spawner.clone(), client.clone(), pool.clone(), None, None);
+		let (block_import, poc_link) = sc_consensus_poc::block_import(
+			sc_consensus_poc::Config::get_or_compute(&*client).unwrap(),
+			client.clone(),
+			client.clone(),
+		)
+		.unwrap();
+		let keypair = schnorrkel::Keypair::generate();
+		let farmer_id = FarmerId::from_slice(&keypair.public.to_bytes());
+		let consensus_data_provider = PoCConsensusDataProvider::new(
+			client.clone(),
+			poc_link.epoch_changes().clone(),
+			keypair,
+		)
+		.unwrap();
+		// this test checks that PoC blocks are created and imported one by one.
+		let (mut sink, commands_stream) = futures::channel::mpsc::channel(1024);
+		let cloned_client = client.clone();
+		let future = run_manual_seal(ManualSealParams {
+			block_import,
+			env,
+			client: client.clone(),
+			pool: pool.clone(),
+			commands_stream,
+			select_chain,
+			consensus_data_provider: Some(Box::new(consensus_data_provider)),
+			create_inherent_data_providers: move |_, _| {
+				let client = cloned_client.clone();
+				async move {
+					let timestamp =
+						SlotTimestampProvider::new(client).map_err(|err| format!("{:?}", err))?;
+					let slot = InherentDataProvider::new(timestamp.slot().into());
+					Ok((timestamp, slot))
+				}
+			},
+		});
+		std::thread::spawn(|| {
+			let mut rt = tokio::runtime::Runtime::new().unwrap();
+			// spawn the background authorship task
+			rt.block_on(future);
+		});
+
+		for number in 1..=2 {
+			let (tx, rx) = futures::channel::oneshot::channel();
+			sink.send(EngineCommand::SealNewBlock {
+				parent_hash: None,
+				sender: Some(tx),
+				create_empty: true,
+				finalize: false,
+			})
+			.await
+			.unwrap();
+			// assert that the background task returns ok
+			rx.await.unwrap().unwrap();
+
+			// assert that the block was produced by our farmer
+			let header = client.header(&BlockId::Number(number)).unwrap().unwrap();
+			let pre_digest = find_pre_digest::<Block>(&header).unwrap();
+			assert_eq!(pre_digest.solution.public_key, farmer_id);
+		}
+
+		// assert that slots are increasing
+		let slot_1 =
+			find_pre_digest::<Block>(&client.header(&BlockId::Number(1)).unwrap().unwrap())
+				.unwrap()
+				.slot;
+		let slot_2 =
+			find_pre_digest::<Block>(&client.header(&BlockId::Number(2)).unwrap().unwrap())
+				.unwrap()
+				.slot;
+		assert!(slot_2 > slot_1);
+	}
+
 	#[tokio::test]
 	async fn manual_seal_fork_blocks() {
 		let builder = TestClientBuilder::new();
@@ -0,0 +1,135 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Archiving of the blockchain history into segments of pieces.
+
+use codec::{Compact, Decode, Encode};
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use sp_consensus_poc::{
+    RootBlock, PARITY_PIECES_PER_SEGMENT, PIECES_PER_SEGMENT, RECORDS_PER_SEGMENT,
+};
+use sp_consensus_spartan::{
+    merkle::MerkleTree,
+    spartan::{Piece, PIECE_SIZE},
+};
+use std::convert::TryInto;
+
+/// Size of the history contained in a segment in bytes.
+pub const SEGMENT_SIZE: usize = RECORDS_PER_SEGMENT * PIECE_SIZE;
+
+/// Segment of the blockchain history that was archived.
+#[derive(Debug, Clone)]
+pub struct ArchivedSegment {
+    /// Root block of the segment, committing to its pieces.
+    pub root_block: RootBlock,
+    /// Data records of the segment followed by parity pieces.
+    pub pieces: Vec<Piece>,
+}
+
+/// State of the [`Archiver`] that allows to continue archiving where it stopped.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode)]
+pub struct ArchiverState {
+    /// History that doesn't fill a whole segment yet.
+    pub buffer: Vec<u8>,
+    /// Index of the segment that will be archived next.
+    pub next_segment_index: u64,
+}
+
+/// Turns blocks into segments of the blockchain history.
+///
+/// Every block is appended to the history as SCALE-encoded length followed by the encoded block
+/// itself. Blocks are not aligned to segment boundaries, so a block may span several segments.
+pub struct Archiver {
+    /// History that doesn't fill a whole segment yet.
+    buffer: Vec<u8>,
+    next_segment_index: u64,
+    reed_solomon: ReedSolomon,
+}
+
+impl Default for Archiver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Archiver {
+    /// Create archiver that starts with the first segment.
+    pub fn new() -> Self {
+        Self::with_state(ArchiverState::default())
+    }
+
+    /// Create archiver that continues archiving from the given `state`.
+    pub fn with_state(state: ArchiverState) -> Self {
+        let mut buffer = state.buffer;
+        buffer.reserve(SEGMENT_SIZE.saturating_sub(buffer.len()));
+
+        Self {
+            buffer,
+            next_segment_index: state.next_segment_index,
+            reed_solomon: ReedSolomon::new(RECORDS_PER_SEGMENT, PARITY_PIECES_PER_SEGMENT)
+                .expect("Shard counts are within limits of GF(2^8); qed"),
+        }
+    }
+
+    /// Current state of the archiver, see [`Archiver::with_state`].
+    pub fn state(&self) -> ArchiverState {
+        ArchiverState {
+            buffer: self.buffer.clone(),
+            next_segment_index: self.next_segment_index,
+        }
+    }
+
+    /// Append encoded block to the history, returns segments that were filled by it.
+    pub fn add_block(&mut self, encoded_block: &[u8]) -> Vec<ArchivedSegment> {
+        Compact(encoded_block.len() as u32).encode_to(&mut self.buffer);
+        self.buffer.extend_from_slice(encoded_block);
+
+        let mut archived_segments = Vec::new();
+        while self.buffer.len() >= SEGMENT_SIZE {
+            let segment: Vec<u8> = self.buffer.drain(..SEGMENT_SIZE).collect();
+            archived_segments.push(self.archive_segment(&segment));
+        }
+
+        archived_segments
+    }
+
+    fn archive_segment(&mut self, segment: &[u8]) -> ArchivedSegment {
+        let mut pieces: Vec<Piece> = segment
+            .chunks_exact(PIECE_SIZE)
+            .map(|record| {
+                record
+                    .try_into()
+                    .expect("Chunks are exactly of piece size; qed")
+            })
+            .collect();
+        pieces.resize(PIECES_PER_SEGMENT, [0u8; PIECE_SIZE]);
+        self.reed_solomon
+            .encode(&mut pieces)
+            .expect("Number and size of shards are correct; qed");
+
+        let records_root = MerkleTree::from_leaves(pieces.iter().map(|piece| &piece[..])).root();
+
+        let root_block = RootBlock {
+            segment_index: self.next_segment_index,
+            records_root,
+        };
+        self.next_segment_index += 1;
+
+        ArchivedSegment { root_block, pieces }
+    }
+}
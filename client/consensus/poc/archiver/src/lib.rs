@@ -0,0 +1,280 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Archiver of the blockchain history for PoC consensus.
+//!
+//! Farmers need actual data to plot, so finalized blocks are archived into segments of the
+//! blockchain history. Every segment is split into `PIECE_SIZE` records that are extended with
+//! the same number of erasure-coded parity pieces, such that the segment can be recovered from any
+//! half of its pieces. All pieces of a segment are committed to by a Merkle root, which is
+//! published to the runtime in a root block through an inherent.
+//!
+//! Archiving is deterministic: every node archives the same finalized blocks into the same
+//! segments, so root blocks included by block authors are checked against locally archived ones
+//! during import.
+//!
+//! Archiver state is persisted in aux storage after every batch of archived blocks, so archiving
+//! continues where it stopped after restart. Nodes that don't have the blockchain history, like
+//! those that were fast or warp synced, can't archive, such nodes neither include root blocks into
+//! the blocks they author nor check root blocks in imported blocks.
+
+#![forbid(unsafe_code)]
+#![warn(missing_docs)]
+
+mod archiver;
+#[cfg(test)]
+mod tests;
+
+pub use archiver::{ArchivedSegment, Archiver, ArchiverState, SEGMENT_SIZE};
+pub use sp_consensus_poc::{PARITY_PIECES_PER_SEGMENT, PIECES_PER_SEGMENT, RECORDS_PER_SEGMENT};
+
+use codec::{Decode, Encode};
+use futures::StreamExt;
+use log::{debug, warn};
+use parking_lot::Mutex;
+use sc_client_api::{backend::AuxStore, BlockBackend, BlockchainEvents};
+use sp_api::{ApiError, ApiExt, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_consensus_poc::{inherents::RootBlocksInherentDataProvider, PoCApi, RootBlock};
+use sp_runtime::generic::BlockId;
+use sp_runtime::traits::{Block as BlockT, Header, NumberFor, One};
+use sp_utils::mpsc::{tracing_unbounded, TracingUnboundedReceiver, TracingUnboundedSender};
+use std::sync::Arc;
+
+/// Aux storage key of the persisted archiver state.
+const ARCHIVER_STATE_KEY: &[u8] = b"poc_archiver_state";
+
+/// Archiver state persisted in aux storage.
+#[derive(Debug, Clone, Default, Encode, Decode)]
+struct PersistedState<Number> {
+    /// Number of the next block to archive.
+    next_block_to_archive: Number,
+    /// State of the archiver after archiving all blocks before `next_block_to_archive`.
+    archiver_state: ArchiverState,
+    /// Root blocks that were archived, but are not stored on chain yet as of the last archived
+    /// block.
+    root_blocks: Vec<RootBlock>,
+}
+
+/// State shared between the archiver, block authoring and import.
+#[derive(Clone, Default)]
+pub struct ArchiverLink {
+    /// `None` until the archiver caught up with finalized blocks and if the blockchain history is
+    /// not available.
+    root_blocks: Arc<Mutex<Option<Vec<RootBlock>>>>,
+    archived_segment_sinks: Arc<Mutex<Vec<TracingUnboundedSender<Arc<ArchivedSegment>>>>>,
+}
+
+impl ArchiverLink {
+    /// Root blocks that were archived, but are not stored on chain yet as of the last finalized
+    /// block, `None` if the archiver didn't catch up with finalized blocks (yet).
+    pub fn root_blocks(&self) -> Option<Vec<RootBlock>> {
+        self.root_blocks.lock().clone()
+    }
+
+    /// Stream of segments archived from now on, for instance for farmers to plot.
+    pub fn archived_segment_notification_stream(
+        &self,
+    ) -> TracingUnboundedReceiver<Arc<ArchivedSegment>> {
+        let (sink, stream) = tracing_unbounded("mpsc_archived_segment_notification_stream");
+        self.archived_segment_sinks.lock().push(sink);
+        stream
+    }
+
+    fn set_root_blocks(&self, root_blocks: Option<Vec<RootBlock>>) {
+        *self.root_blocks.lock() = root_blocks;
+    }
+
+    fn on_archived_segment(&self, archived_segment: ArchivedSegment) {
+        let archived_segment = Arc::new(archived_segment);
+        self.archived_segment_sinks
+            .lock()
+            .retain(|sink| sink.unbounded_send(Arc::clone(&archived_segment)).is_ok());
+    }
+}
+
+/// Create inherent data provider with root blocks archived locally, but not yet stored on chain as
+/// of `parent`.
+///
+/// Used both for authoring blocks on top of `parent` and for checking inherents of blocks
+/// imported on top of it.
+pub fn root_blocks_inherent_data_provider<Block, Client>(
+    client: &Client,
+    parent: Block::Hash,
+    archiver_link: &ArchiverLink,
+) -> Result<RootBlocksInherentDataProvider, ApiError>
+where
+    Block: BlockT,
+    Client: ProvideRuntimeApi<Block>,
+    Client::Api: PoCApi<Block>,
+{
+    let runtime_api = client.runtime_api();
+    let parent_block_id = BlockId::Hash(parent);
+    // root blocks are only stored on chain since version 2 of the API
+    if !runtime_api.has_api_with::<dyn PoCApi<Block>, _>(&parent_block_id, |v| v >= 2)? {
+        return Ok(RootBlocksInherentDataProvider::new(Vec::new()));
+    }
+
+    let root_blocks = match archiver_link.root_blocks() {
+        Some(root_blocks) => root_blocks,
+        None => return Ok(RootBlocksInherentDataProvider::without_history()),
+    };
+
+    let next_segment_index = runtime_api.next_archived_segment_index(&parent_block_id)?;
+
+    let root_blocks = root_blocks
+        .into_iter()
+        .filter(|root_block| root_block.segment_index >= next_segment_index)
+        .collect();
+
+    Ok(RootBlocksInherentDataProvider::new(root_blocks))
+}
+
+/// Run archiver until the finality notification stream ends.
+///
+/// Archiving continues from the state persisted in aux storage, or from genesis if there is none,
+/// and is then extended with blocks as they are finalized. Archiving stops if any of the finalized
+/// blocks is not available, since following segments wouldn't match those of other nodes.
+pub async fn run_archiver<Block, Client>(client: Arc<Client>, archiver_link: ArchiverLink)
+where
+    Block: BlockT,
+    Client: AuxStore
+        + BlockchainEvents<Block>
+        + BlockBackend<Block>
+        + HeaderBackend<Block>
+        + ProvideRuntimeApi<Block>,
+    Client::Api: PoCApi<Block>,
+{
+    let mut finality_notifications = client.finality_notification_stream();
+
+    let mut state = match load_decode::<_, PersistedState<NumberFor<Block>>>(&*client) {
+        Ok(state) => state.unwrap_or_default(),
+        Err(error) => {
+            warn!(target: "poc", "Failed to load archiver state: {}", error);
+            return;
+        }
+    };
+    let mut archiver = Archiver::with_state(state.archiver_state.clone());
+
+    let finalized_number = client.info().finalized_number;
+    if let Err(error) = archive_blocks(
+        &*client,
+        &mut archiver,
+        &archiver_link,
+        &mut state,
+        finalized_number,
+    ) {
+        warn!(
+            target: "poc",
+            "Failed to archive blockchain history, was the node fast or warp synced? Root blocks \
+            will neither be included into authored blocks nor checked in imported ones: {}",
+            error,
+        );
+        return;
+    }
+    archiver_link.set_root_blocks(Some(state.root_blocks.clone()));
+
+    while let Some(notification) = finality_notifications.next().await {
+        if let Err(error) = archive_blocks(
+            &*client,
+            &mut archiver,
+            &archiver_link,
+            &mut state,
+            *notification.header.number(),
+        ) {
+            warn!(target: "poc", "Failed to archive blockchain history: {}", error);
+            archiver_link.set_root_blocks(None);
+            return;
+        }
+        archiver_link.set_root_blocks(Some(state.root_blocks.clone()));
+    }
+}
+
+fn load_decode<B: AuxStore, T: Decode>(backend: &B) -> sp_blockchain::Result<Option<T>> {
+    match backend.get_aux(ARCHIVER_STATE_KEY)? {
+        None => Ok(None),
+        Some(t) => T::decode(&mut &t[..])
+            .map_err(|e| {
+                sp_blockchain::Error::Backend(format!("PoC archiver DB is corrupted: {}", e))
+            })
+            .map(Some),
+    }
+}
+
+/// Archive blocks from the next block to archive up to and including `finalized_number` and
+/// persist the resulting state.
+///
+/// Finality notifications are not sent for every block, so all blocks since the last archived one
+/// are fetched by number. Root blocks that are already stored on chain as of `finalized_number`
+/// are dropped from the state.
+fn archive_blocks<Block, Client>(
+    client: &Client,
+    archiver: &mut Archiver,
+    archiver_link: &ArchiverLink,
+    state: &mut PersistedState<NumberFor<Block>>,
+    finalized_number: NumberFor<Block>,
+) -> sp_blockchain::Result<()>
+where
+    Block: BlockT,
+    Client: AuxStore + BlockBackend<Block> + HeaderBackend<Block> + ProvideRuntimeApi<Block>,
+    Client::Api: PoCApi<Block>,
+{
+    if state.next_block_to_archive > finalized_number {
+        return Ok(());
+    }
+
+    while state.next_block_to_archive <= finalized_number {
+        let block = client
+            .block(&BlockId::Number(state.next_block_to_archive))?
+            .ok_or_else(|| {
+                sp_blockchain::Error::UnknownBlock(format!(
+                    "Finalized block #{} is not available",
+                    state.next_block_to_archive
+                ))
+            })?;
+
+        for archived_segment in archiver.add_block(&block.block.encode()) {
+            debug!(
+                target: "poc",
+                "Archived segment {} up to block #{}",
+                archived_segment.root_block.segment_index,
+                state.next_block_to_archive,
+            );
+            state.root_blocks.push(archived_segment.root_block);
+            archiver_link.on_archived_segment(archived_segment);
+        }
+
+        state.next_block_to_archive += One::one();
+    }
+
+    let finalized_block_id = BlockId::Number(finalized_number);
+    let runtime_api = client.runtime_api();
+    // runtimes before version 2 of the API don't store root blocks, so there is nothing to keep
+    let next_segment_index =
+        if runtime_api.has_api_with::<dyn PoCApi<Block>, _>(&finalized_block_id, |v| v >= 2)? {
+            runtime_api.next_archived_segment_index(&finalized_block_id)?
+        } else {
+            u64::MAX
+        };
+    state
+        .root_blocks
+        .retain(|root_block| root_block.segment_index >= next_segment_index);
+
+    state.archiver_state = archiver.state();
+    client.insert_aux(&[(ARCHIVER_STATE_KEY, state.encode().as_slice())], &[])
+}
@@ -0,0 +1,125 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::*;
+use codec::Compact;
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use sp_consensus_spartan::{merkle::MerkleTree, spartan::PIECE_SIZE};
+
+#[test]
+fn segments_are_filled_with_blocks() {
+    let mut archiver = Archiver::new();
+
+    // Together with encoded length two blocks are exactly one byte short of a segment
+    let block = vec![1u8; SEGMENT_SIZE / 2 - 4];
+    assert_eq!(Compact(block.len() as u32).encode().len(), 4);
+    assert!(archiver.add_block(&block).is_empty());
+    assert!(archiver.add_block(&block[1..]).is_empty());
+
+    // Block that spans across segment boundary
+    let spanning_block = vec![2u8; SEGMENT_SIZE / 2];
+    let archived_segments = archiver.add_block(&spanning_block);
+    assert_eq!(archived_segments.len(), 1);
+
+    let archived_segment = &archived_segments[0];
+    assert_eq!(archived_segment.root_block.segment_index, 0);
+    assert_eq!(
+        archived_segment.pieces.len(),
+        RECORDS_PER_SEGMENT + PARITY_PIECES_PER_SEGMENT
+    );
+    assert_eq!(&archived_segment.pieces[0][4..], &block[..PIECE_SIZE - 4]);
+
+    let records_root =
+        MerkleTree::from_leaves(archived_segment.pieces.iter().map(|piece| &piece[..])).root();
+    assert_eq!(archived_segment.root_block.records_root, records_root);
+
+    // Remainder of the last block, together with another block, completes the next segment
+    let archived_segments = archiver.add_block(&spanning_block);
+    assert_eq!(archived_segments.len(), 1);
+    assert_eq!(archived_segments[0].root_block.segment_index, 1);
+}
+
+#[test]
+fn segment_is_recoverable_from_half_of_pieces() {
+    let mut archiver = Archiver::new();
+
+    let block: Vec<u8> = (0..SEGMENT_SIZE).map(|i| i as u8).collect();
+    let archived_segments = archiver.add_block(&block);
+    assert_eq!(archived_segments.len(), 1);
+
+    let pieces = &archived_segments[0].pieces;
+    let mut shards: Vec<Option<Vec<u8>>> = pieces
+        .iter()
+        .enumerate()
+        .map(|(index, piece)| {
+            // Lose every other piece
+            if index % 2 == 0 {
+                None
+            } else {
+                Some(piece.to_vec())
+            }
+        })
+        .collect();
+
+    ReedSolomon::new(RECORDS_PER_SEGMENT, PARITY_PIECES_PER_SEGMENT)
+        .unwrap()
+        .reconstruct_data(&mut shards)
+        .unwrap();
+
+    for (shard, piece) in shards.iter().zip(pieces).take(RECORDS_PER_SEGMENT) {
+        assert_eq!(shard.as_deref(), Some(&piece[..]));
+    }
+}
+
+#[test]
+fn archiving_continues_from_state() {
+    let block = vec![3u8; SEGMENT_SIZE / 3];
+
+    let mut archiver = Archiver::new();
+    let mut archived_segments = Vec::new();
+    for _ in 0..7 {
+        archived_segments.extend(archiver.add_block(&block));
+    }
+
+    // Restore archiver from the state in the middle of a segment, as after restart
+    let mut first_archiver = Archiver::new();
+    let mut restored_archived_segments = Vec::new();
+    for _ in 0..4 {
+        restored_archived_segments.extend(first_archiver.add_block(&block));
+    }
+    let state = ArchiverState::decode(&mut first_archiver.state().encode().as_slice()).unwrap();
+    assert!(!state.buffer.is_empty());
+
+    let mut restored_archiver = Archiver::with_state(state);
+    for _ in 0..3 {
+        restored_archived_segments.extend(restored_archiver.add_block(&block));
+    }
+
+    assert_eq!(restored_archived_segments.len(), 2);
+    assert_eq!(
+        restored_archived_segments
+            .iter()
+            .map(|archived_segment| archived_segment.root_block)
+            .collect::<Vec<_>>(),
+        archived_segments
+            .iter()
+            .map(|archived_segment| archived_segment.root_block)
+            .collect::<Vec<_>>(),
+    );
+    assert_eq!(restored_archiver.state(), archiver.state());
+}
@@ -0,0 +1,411 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! In-process reference farmer for PoC consensus.
+//!
+//! Normally block production relies on an external farmer connected over
+//! `poc_subscribeSlotInfo`. This crate implements a simple farmer that runs inside of the node
+//! instead, which is convenient for development chains and CI:
+//!
+//! * a number of genesis piece encodings is plotted to a local directory using a `poc0` key from
+//!   the node keystore (key is generated if there is none);
+//! * pieces of segments archived by the node replace genesis piece encodings in the plot, together
+//!   with their piece inclusion proofs;
+//! * commitments (tags of all pieces) are kept in memory for the current and the next salt;
+//! * solutions within solution range are sent in response to new slot notifications of the PoC
+//!   worker, and blocks produced with them are signed in response to block signing requests.
+//!
+//! Solutions with encodings of archived pieces are preferred, since solutions with encodings of the
+//! genesis piece are no longer accepted once enough segments are archived, see
+//! [`sp_consensus_poc::GENESIS_PIECE_SOLUTIONS_CUTOVER`].
+
+#![forbid(unsafe_code)]
+#![warn(missing_docs)]
+
+mod plot;
+
+use futures::executor::block_on;
+use futures::{Stream, StreamExt};
+use log::{debug, info, warn};
+use parking_lot::Mutex;
+use plot::Plot;
+use sc_consensus_poc::{
+    derive_local_challenge, hash_public_key, BlockSigningInfo, BlockSigningNotifier, NewSlotInfo,
+    NewSlotNotifier,
+};
+use sc_consensus_poc_archiver::ArchivedSegment;
+use sc_keystore::LocalKeystore;
+use schnorrkel::context::SigningContext;
+use sp_consensus_poc::{
+    digests::{PieceInclusionProof, Solution},
+    FarmerId, FarmerPair, FarmerSignature, SpartanParameters, KEY_TYPE, PIECES_PER_SEGMENT,
+};
+use sp_consensus_spartan::{
+    merkle::MerkleTree,
+    spartan::{create_tag, Salt, Spartan},
+};
+use sp_core::{sr25519, Pair};
+use sp_keystore::SyncCryptoStore;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::PathBuf,
+    sync::Arc,
+};
+
+/// Tags of pieces in the plot for a particular salt, mapped to plot offsets.
+type Commitments = BTreeMap<u64, u64>;
+
+/// Commitments for a particular salt, separately for encodings of archived pieces and of the
+/// genesis piece.
+#[derive(Default)]
+struct SaltCommitments {
+    archived_pieces: Commitments,
+    genesis_piece: Commitments,
+}
+
+/// Configuration of the in-process farmer.
+#[derive(Debug, Clone)]
+pub struct FarmerConfig {
+    /// Directory where the plot is stored.
+    pub path: PathBuf,
+    /// Number of pieces to plot.
+    pub plot_size: u64,
+}
+
+/// Errors encountered by the farmer.
+#[derive(derive_more::Display, Debug)]
+pub enum Error {
+    /// Plot I/O error
+    #[display(fmt = "Plot I/O error: {}", _0)]
+    Io(io::Error),
+    /// Keystore error
+    #[display(fmt = "Keystore error: {}", _0)]
+    Keystore(String),
+    /// Farmer key is not in the keystore
+    #[display(fmt = "Farmer key {:?} is not in the keystore", _0)]
+    MissingKey(FarmerId),
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+/// Farmer that plots pieces locally and answers PoC worker requests with them.
+pub struct Farmer {
+    pair: FarmerPair,
+    keypair: schnorrkel::Keypair,
+    signing_context: SigningContext,
+    spartan: Spartan,
+    plot: Plot,
+    commitments: Mutex<HashMap<Salt, SaltCommitments>>,
+}
+
+impl Farmer {
+    /// Create a farmer using `poc0` key from `keystore`, plotting pieces with the Spartan codec of
+    /// the chain (`spartan_parameters`) if necessary.
+    pub fn new(
+        config: &FarmerConfig,
+        spartan_parameters: &SpartanParameters,
+        keystore: &LocalKeystore,
+    ) -> Result<Self, Error> {
+        let public_key = match SyncCryptoStore::sr25519_public_keys(keystore, KEY_TYPE).pop() {
+            Some(public_key) => public_key,
+            None => SyncCryptoStore::sr25519_generate_new(keystore, KEY_TYPE, None)
+                .map_err(|error| Error::Keystore(error.to_string()))?,
+        };
+        let public_key = FarmerId::from(public_key);
+
+        let pair = keystore
+            .key_pair::<FarmerPair>(&public_key)
+            .map_err(|error| Error::Keystore(error.to_string()))?
+            .ok_or_else(|| Error::MissingKey(public_key.clone()))?;
+        let keypair = {
+            let pair: &sr25519::Pair = pair.as_ref();
+            AsRef::<schnorrkel::Keypair>::as_ref(pair).clone()
+        };
+
+        let plot = Plot::open_or_create(
+            &config.path,
+            &public_key,
+            config.plot_size,
+            spartan_parameters,
+        )?;
+
+        Ok(Self {
+            pair,
+            keypair,
+            signing_context: schnorrkel::context::signing_context(
+                &spartan_parameters.signing_context,
+            ),
+            spartan: Spartan::new(
+                &spartan_parameters.genesis_piece_seed,
+                spartan_parameters.encode_rounds as usize,
+            ),
+            plot,
+            commitments: Mutex::default(),
+        })
+    }
+
+    /// Public key of the farmer.
+    pub fn public_key(&self) -> FarmerId {
+        self.pair.public()
+    }
+
+    /// Find solutions for the slot within solution range in the plot: at most one with an encoding
+    /// of an archived piece, followed by at most one with an encoding of the genesis piece.
+    pub fn solve(&self, slot_info: &NewSlotInfo) -> Result<Vec<Solution>, Error> {
+        self.create_commitments(slot_info.salt)?;
+
+        let local_challenge = u64::from_be_bytes(derive_local_challenge(
+            &slot_info.challenge,
+            &hash_public_key(self.public_key().as_ref()),
+        ));
+
+        let found_commitments = self
+            .commitments
+            .lock()
+            .get(&slot_info.salt)
+            .map(|commitments| {
+                [
+                    find_commitment(
+                        &commitments.archived_pieces,
+                        local_challenge,
+                        slot_info.solution_range,
+                    ),
+                    find_commitment(
+                        &commitments.genesis_piece,
+                        local_challenge,
+                        slot_info.solution_range,
+                    ),
+                ]
+            })
+            .unwrap_or_default();
+
+        found_commitments
+            .iter()
+            .flatten()
+            .map(|&(tag, offset)| self.create_solution(tag, offset))
+            .collect()
+    }
+
+    /// Replace pieces in the plot with encodings of pieces of `archived_segment`.
+    pub fn plot_segment(&self, archived_segment: &ArchivedSegment) -> Result<(), Error> {
+        let public_key = self.public_key();
+        let first_piece_index =
+            archived_segment.root_block.segment_index * PIECES_PER_SEGMENT as u64;
+        let merkle_tree =
+            MerkleTree::from_leaves(archived_segment.pieces.iter().map(|piece| &piece[..]));
+
+        // Pieces are plotted at offset `piece_index` modulo plot size, so only the last pieces of
+        // the segment end up in a plot that is smaller than a segment
+        let skip = archived_segment
+            .pieces
+            .len()
+            .saturating_sub(self.plot.pieces() as usize);
+        let encodings =
+            archived_segment
+                .pieces
+                .iter()
+                .enumerate()
+                .skip(skip)
+                .map(|(position, piece)| {
+                    let piece_index = first_piece_index + position as u64;
+                    let piece_inclusion_proof = PieceInclusionProof {
+                        piece_index,
+                        piece: piece.to_vec(),
+                        merkle_proof: merkle_tree
+                            .proof(position)
+                            .expect("Position is within the segment; qed"),
+                    };
+
+                    (
+                        self.spartan
+                            .encode_piece(*piece, public_key.as_ref(), piece_index),
+                        piece_inclusion_proof,
+                    )
+                });
+        self.plot.write_archived_pieces(encodings)?;
+
+        // Tags of replaced pieces are no longer valid
+        self.commitments.lock().clear();
+
+        Ok(())
+    }
+
+    /// Sign block pre-hash, if the block was claimed with a solution of this farmer.
+    pub fn sign_block(&self, block_signing_info: &BlockSigningInfo) -> Option<FarmerSignature> {
+        if block_signing_info.public_key != self.public_key() {
+            return None;
+        }
+
+        Some(self.pair.sign(&block_signing_info.header_hash))
+    }
+
+    /// Answer new slot notifications with solutions until the PoC worker is gone.
+    ///
+    /// This is a blocking call.
+    pub fn run_slots(&self, new_slot_notifier: NewSlotNotifier) {
+        let mut new_slots = new_slot_notifier();
+
+        while let Some((slot_info, solution_sender)) = block_on(new_slots.next()) {
+            match self.solve(&slot_info) {
+                Ok(solutions) => {
+                    for solution in solutions {
+                        debug!(target: "poc-farmer", "Found solution for slot {}", slot_info.slot);
+                        let _ = solution_sender.unbounded_send(solution);
+                    }
+                }
+                Err(error) => {
+                    warn!(target: "poc-farmer", "Failed to solve slot {}: {}", slot_info.slot, error);
+                }
+            }
+
+            // Commitments for the next salt are created ahead of time, so that there is no delay
+            // when salt changes
+            if let Some(next_salt) = slot_info.next_salt {
+                if let Err(error) = self.create_commitments(next_salt) {
+                    warn!(target: "poc-farmer", "Failed to create commitments: {}", error);
+                }
+            }
+
+            self.commitments
+                .lock()
+                .retain(|salt, _| *salt == slot_info.salt || Some(*salt) == slot_info.next_salt);
+        }
+    }
+
+    /// Answer block signing requests until the PoC worker is gone.
+    ///
+    /// This is a blocking call.
+    pub fn run_block_signing(&self, block_signing_notifier: BlockSigningNotifier) {
+        let mut block_signing_requests = block_signing_notifier();
+
+        while let Some((block_signing_info, signature_sender)) =
+            block_on(block_signing_requests.next())
+        {
+            if let Some(signature) = self.sign_block(&block_signing_info) {
+                let _ = signature_sender.unbounded_send(signature);
+            }
+        }
+    }
+
+    /// Plot segments archived by the node until the archiver is gone.
+    ///
+    /// This is a blocking call.
+    pub fn run_archiving<S>(&self, archived_segments: S)
+    where
+        S: Stream<Item = Arc<ArchivedSegment>> + Unpin,
+    {
+        let mut archived_segments = archived_segments;
+
+        while let Some(archived_segment) = block_on(archived_segments.next()) {
+            let segment_index = archived_segment.root_block.segment_index;
+            match self.plot_segment(&archived_segment) {
+                Ok(()) => {
+                    info!(target: "poc-farmer", "Plotted archived segment {}", segment_index);
+                }
+                Err(error) => {
+                    warn!(
+                        target: "poc-farmer",
+                        "Failed to plot archived segment {}: {}",
+                        segment_index,
+                        error,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Create commitments for `salt`, unless they already exist.
+    fn create_commitments(&self, salt: Salt) -> Result<(), Error> {
+        if self.commitments.lock().contains_key(&salt) {
+            return Ok(());
+        }
+
+        let mut commitments = SaltCommitments::default();
+        for offset in 0..self.plot.pieces() {
+            let (encoding, piece_inclusion_proof) = self.plot.read(offset)?;
+            let tag = u64::from_be_bytes(create_tag(&encoding, &salt));
+            if piece_inclusion_proof.is_some() {
+                commitments.archived_pieces.insert(tag, offset);
+            } else {
+                commitments.genesis_piece.insert(tag, offset);
+            }
+        }
+
+        self.commitments.lock().insert(salt, commitments);
+
+        Ok(())
+    }
+
+    /// Create solution with the encoding at `offset`, which has `tag`.
+    ///
+    /// Archived pieces are encoded with their piece index as nonce, genesis piece with offset.
+    fn create_solution(&self, tag: u64, offset: u64) -> Result<Solution, Error> {
+        let tag = tag.to_be_bytes();
+        let (encoding, piece_inclusion_proof) = self.plot.read(offset)?;
+
+        Ok(Solution {
+            public_key: self.public_key(),
+            nonce: piece_inclusion_proof
+                .as_ref()
+                .map_or(offset, |piece_inclusion_proof| {
+                    piece_inclusion_proof.piece_index
+                }),
+            encoding: encoding.to_vec(),
+            signature: self
+                .keypair
+                .sign(self.signing_context.bytes(&tag))
+                .to_bytes()
+                .to_vec(),
+            tag,
+            piece_inclusion_proof,
+            solution_data_commitment: None,
+        })
+    }
+}
+
+/// Find a commitment with tag within `solution_range` around `target`, returns tag and offset.
+fn find_commitment(
+    commitments: &Commitments,
+    target: u64,
+    solution_range: u64,
+) -> Option<(u64, u64)> {
+    let (lower, is_lower_overflowed) = target.overflowing_sub(solution_range / 2);
+    let (upper, is_upper_overflowed) = target.overflowing_add(solution_range / 2);
+    if is_lower_overflowed || is_upper_overflowed {
+        commitments
+            .range(lower..)
+            .chain(commitments.range(..=upper))
+            .next()
+            .map(|(tag, offset)| (*tag, *offset))
+    } else {
+        commitments
+            .range(lower..=upper)
+            .next()
+            .map(|(tag, offset)| (*tag, *offset))
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,214 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! On-disk plot of encoded pieces.
+
+use codec::{Decode, Encode};
+use log::info;
+use parking_lot::Mutex;
+use sp_consensus_poc::{digests::PieceInclusionProof, FarmerId, SpartanParameters};
+use sp_consensus_spartan::spartan::{Piece, Spartan, PIECE_SIZE};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+/// File with encoded pieces, stored one after another.
+const PLOT_FILE: &str = "plot.bin";
+/// File with SCALE-encoded `PlotMetadata`.
+const METADATA_FILE: &str = "plot.meta";
+/// File with SCALE-encoded `PieceInclusionProofs`.
+const PROOFS_FILE: &str = "proofs.bin";
+
+/// Inclusion proofs of archived pieces in the plot, indexed by offset of their encodings.
+type PieceInclusionProofs = BTreeMap<u64, PieceInclusionProof>;
+
+/// Describes the contents of the plot file.
+#[derive(Debug, Encode, Decode)]
+struct PlotMetadata {
+    /// Farmer for which pieces were encoded
+    public_key: FarmerId,
+    /// Number of pieces in the plot file
+    pieces: u64,
+    /// Parameters of the codec pieces were encoded with
+    spartan_parameters: SpartanParameters,
+}
+
+/// Pieces of the plot file and inclusion proofs of those of them that are archived pieces.
+struct PlotFile {
+    file: File,
+    piece_inclusion_proofs: PieceInclusionProofs,
+}
+
+/// Plot of a single farmer, indexed by offset.
+///
+/// Initially plot contains encodings of the genesis piece with offset as nonce, which are
+/// replaced with encodings of archived pieces as segments of the blockchain history get archived.
+pub(crate) struct Plot {
+    file: Mutex<PlotFile>,
+    proofs_path: PathBuf,
+    pieces: u64,
+}
+
+impl Plot {
+    /// Open plot in `path`, encoding pieces for `public_key` with `spartan_parameters` until there
+    /// are `pieces` of them.
+    ///
+    /// Pieces plotted previously for the same farmer and parameters are reused, pieces of any
+    /// other farmer or encoded with other parameters are discarded together with inclusion proofs
+    /// of archived pieces.
+    pub(crate) fn open_or_create(
+        path: &Path,
+        public_key: &FarmerId,
+        pieces: u64,
+        spartan_parameters: &SpartanParameters,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(path)?;
+
+        let metadata_path = path.join(METADATA_FILE);
+        let plotted_pieces = match fs::read(&metadata_path) {
+            Ok(metadata) => match PlotMetadata::decode(&mut metadata.as_slice()) {
+                Ok(metadata)
+                    if &metadata.public_key == public_key
+                        && &metadata.spartan_parameters == spartan_parameters =>
+                {
+                    metadata.pieces.min(pieces)
+                }
+                _ => 0,
+            },
+            Err(error) if error.kind() == io::ErrorKind::NotFound => 0,
+            Err(error) => return Err(error),
+        };
+
+        let proofs_path = path.join(PROOFS_FILE);
+        let piece_inclusion_proofs = if plotted_pieces > 0 {
+            match fs::read(&proofs_path) {
+                Ok(proofs) => PieceInclusionProofs::decode(&mut proofs.as_slice())
+                    .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?
+                    .into_iter()
+                    .filter(|(offset, _)| *offset < plotted_pieces)
+                    .collect(),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                    PieceInclusionProofs::new()
+                }
+                Err(error) => return Err(error),
+            }
+        } else {
+            PieceInclusionProofs::new()
+        };
+        fs::write(&proofs_path, piece_inclusion_proofs.encode())?;
+
+        // Metadata is written before plotting so that interrupted plotting doesn't leave pieces
+        // that are not accounted for
+        let write_metadata = |pieces| {
+            fs::write(
+                &metadata_path,
+                PlotMetadata {
+                    public_key: public_key.clone(),
+                    pieces,
+                    spartan_parameters: spartan_parameters.clone(),
+                }
+                .encode(),
+            )
+        };
+        write_metadata(plotted_pieces)?;
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(path.join(PLOT_FILE))?;
+        file.set_len(plotted_pieces * PIECE_SIZE as u64)?;
+
+        if plotted_pieces < pieces {
+            info!(
+                target: "poc-farmer",
+                "Plotting {} pieces to {}",
+                pieces - plotted_pieces,
+                path.display(),
+            );
+
+            let spartan = Spartan::new(
+                &spartan_parameters.genesis_piece_seed,
+                spartan_parameters.encode_rounds as usize,
+            );
+            file.seek(SeekFrom::End(0))?;
+            for nonce in plotted_pieces..pieces {
+                file.write_all(&spartan.encode(public_key.as_ref(), nonce))?;
+            }
+            file.sync_all()?;
+
+            write_metadata(pieces)?;
+
+            info!(target: "poc-farmer", "Finished plotting");
+        }
+
+        Ok(Self {
+            file: Mutex::new(PlotFile {
+                file,
+                piece_inclusion_proofs,
+            }),
+            proofs_path,
+            pieces,
+        })
+    }
+
+    /// Number of pieces in the plot.
+    pub(crate) fn pieces(&self) -> u64 {
+        self.pieces
+    }
+
+    /// Read encoding at `offset` together with inclusion proof of the encoded piece, the latter is
+    /// `None` for encodings of the genesis piece.
+    pub(crate) fn read(&self, offset: u64) -> io::Result<(Piece, Option<PieceInclusionProof>)> {
+        let mut piece = [0u8; PIECE_SIZE];
+        let mut plot_file = self.file.lock();
+        plot_file
+            .file
+            .seek(SeekFrom::Start(offset * PIECE_SIZE as u64))?;
+        plot_file.file.read_exact(&mut piece)?;
+        Ok((
+            piece,
+            plot_file.piece_inclusion_proofs.get(&offset).cloned(),
+        ))
+    }
+
+    /// Write encodings of archived pieces, each one replaces the piece at offset `piece_index`
+    /// modulo plot size.
+    pub(crate) fn write_archived_pieces<I>(&self, encodings: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = (Piece, PieceInclusionProof)>,
+    {
+        let mut plot_file = self.file.lock();
+        for (encoding, piece_inclusion_proof) in encodings {
+            let offset = piece_inclusion_proof.piece_index % self.pieces;
+            plot_file
+                .file
+                .seek(SeekFrom::Start(offset * PIECE_SIZE as u64))?;
+            plot_file.file.write_all(&encoding)?;
+            plot_file
+                .piece_inclusion_proofs
+                .insert(offset, piece_inclusion_proof);
+        }
+        plot_file.file.sync_all()?;
+
+        fs::write(&self.proofs_path, plot_file.piece_inclusion_proofs.encode())
+    }
+}
@@ -0,0 +1,262 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Farmer tests.
+
+use super::*;
+use sp_consensus_poc::RootBlock;
+use sp_consensus_slots::Slot;
+use sp_consensus_spartan::{
+    merkle,
+    spartan::{is_commitment_valid, Piece, Spartan, PIECE_SIZE, SIGNING_CONTEXT},
+};
+use sp_runtime::RuntimeAppPublic;
+use std::convert::TryInto;
+use std::time::Instant;
+use tempfile::TempDir;
+
+const PLOT_SIZE: u64 = 4;
+
+fn new_farmer(path: &TempDir, keystore: &LocalKeystore) -> Farmer {
+    let config = FarmerConfig {
+        path: path.path().to_path_buf(),
+        plot_size: PLOT_SIZE,
+    };
+    Farmer::new(&config, &SpartanParameters::default(), keystore).unwrap()
+}
+
+fn slot_info(solution_range: u64) -> NewSlotInfo {
+    NewSlotInfo {
+        slot: Slot::from(1),
+        challenge: [1u8; 8],
+        salt: [2u8; 8],
+        next_salt: None,
+        solution_range,
+    }
+}
+
+#[test]
+fn plots_pieces_for_keystore_key() {
+    let path = TempDir::new().unwrap();
+    let keystore = LocalKeystore::in_memory();
+    let farmer = new_farmer(&path, &keystore);
+
+    let public_key = farmer.public_key();
+    assert_eq!(
+        SyncCryptoStore::sr25519_public_keys(&keystore, KEY_TYPE),
+        vec![public_key.clone().into()],
+    );
+
+    let spartan = Spartan::default();
+    assert_eq!(farmer.plot.pieces(), PLOT_SIZE);
+    for nonce in 0..PLOT_SIZE {
+        let (piece, piece_inclusion_proof) = farmer.plot.read(nonce).unwrap();
+        assert!(spartan.is_encoding_valid(piece, public_key.as_ref(), nonce));
+        assert!(piece_inclusion_proof.is_none());
+    }
+}
+
+#[test]
+fn reuses_and_extends_existing_plot() {
+    let path = TempDir::new().unwrap();
+    let keystore = LocalKeystore::in_memory();
+    let (piece, _) = new_farmer(&path, &keystore).plot.read(0).unwrap();
+
+    let config = FarmerConfig {
+        path: path.path().to_path_buf(),
+        plot_size: PLOT_SIZE * 2,
+    };
+    let farmer = Farmer::new(&config, &SpartanParameters::default(), &keystore).unwrap();
+    assert_eq!(farmer.plot.pieces(), PLOT_SIZE * 2);
+    assert_eq!(farmer.plot.read(0).unwrap().0[..], piece[..]);
+    assert!(Spartan::default().is_encoding_valid(
+        farmer.plot.read(PLOT_SIZE * 2 - 1).unwrap().0,
+        farmer.public_key().as_ref(),
+        PLOT_SIZE * 2 - 1,
+    ));
+
+    // Plot encoded with different codec parameters is discarded
+    let spartan_parameters = SpartanParameters {
+        genesis_piece_seed: b"other".to_vec(),
+        ..SpartanParameters::default()
+    };
+    let farmer = Farmer::new(&config, &spartan_parameters, &keystore).unwrap();
+    assert_eq!(farmer.plot.pieces(), PLOT_SIZE * 2);
+    assert!(Spartan::new(b"other", 1).is_encoding_valid(
+        farmer.plot.read(0).unwrap().0,
+        farmer.public_key().as_ref(),
+        0,
+    ));
+
+    // Plot of a different farmer is discarded
+    let other_farmer = new_farmer(&path, &LocalKeystore::in_memory());
+    assert_eq!(other_farmer.plot.pieces(), PLOT_SIZE);
+    assert!(Spartan::default().is_encoding_valid(
+        other_farmer.plot.read(0).unwrap().0,
+        other_farmer.public_key().as_ref(),
+        0,
+    ));
+}
+
+#[test]
+fn solves_slot_within_solution_range() {
+    let path = TempDir::new().unwrap();
+    let keystore = LocalKeystore::in_memory();
+    let farmer = new_farmer(&path, &keystore);
+
+    // Solution range that covers everything
+    let slot_info = slot_info(u64::MAX);
+    let solutions = farmer.solve(&slot_info).unwrap();
+    assert_eq!(solutions.len(), 1);
+    let solution = &solutions[0];
+
+    assert_eq!(solution.public_key, farmer.public_key());
+    let encoding: Piece = solution.encoding.as_slice().try_into().unwrap();
+    assert!(Spartan::default().is_encoding_valid(
+        encoding,
+        solution.public_key.as_ref(),
+        solution.nonce,
+    ));
+    assert!(is_commitment_valid(
+        &encoding,
+        &solution.tag,
+        &slot_info.salt
+    ));
+
+    let signature = schnorrkel::Signature::from_bytes(&solution.signature).unwrap();
+    let public_key = schnorrkel::PublicKey::from_bytes(solution.public_key.as_ref()).unwrap();
+    assert!(public_key
+        .verify(
+            schnorrkel::context::signing_context(SIGNING_CONTEXT).bytes(&solution.tag),
+            &signature
+        )
+        .is_ok());
+
+    // Nothing is within empty solution range
+    assert!(farmer.solve(&self::slot_info(0)).unwrap().is_empty());
+}
+
+#[test]
+fn plots_archived_segment_with_piece_inclusion_proofs() {
+    let path = TempDir::new().unwrap();
+    let keystore = LocalKeystore::in_memory();
+    let farmer = new_farmer(&path, &keystore);
+    let public_key = farmer.public_key();
+
+    let pieces: Vec<Piece> = (0..PIECES_PER_SEGMENT)
+        .map(|position| [position as u8; PIECE_SIZE])
+        .collect();
+    let records_root = MerkleTree::from_leaves(pieces.iter().map(|piece| &piece[..])).root();
+    let segment_index = 1;
+    farmer
+        .plot_segment(&ArchivedSegment {
+            root_block: RootBlock {
+                segment_index,
+                records_root,
+            },
+            pieces,
+        })
+        .unwrap();
+
+    // Last pieces of the segment replace all genesis piece encodings
+    let spartan = Spartan::default();
+    for offset in 0..PLOT_SIZE {
+        let (encoding, piece_inclusion_proof) = farmer.plot.read(offset).unwrap();
+        let piece_inclusion_proof = piece_inclusion_proof.unwrap();
+        let piece_index = piece_inclusion_proof.piece_index;
+        let position = (piece_index % PIECES_PER_SEGMENT as u64) as usize;
+
+        assert_eq!(piece_index / PIECES_PER_SEGMENT as u64, segment_index);
+        assert_eq!(piece_index % PLOT_SIZE, offset);
+        assert!(position >= PIECES_PER_SEGMENT - PLOT_SIZE as usize);
+        assert!(merkle::is_proof_valid(
+            &records_root,
+            &piece_inclusion_proof.piece,
+            position,
+            &piece_inclusion_proof.merkle_proof,
+        ));
+        assert!(spartan.is_piece_encoding_valid(
+            encoding,
+            piece_inclusion_proof.piece.as_slice().try_into().unwrap(),
+            public_key.as_ref(),
+            piece_index,
+        ));
+    }
+
+    let slot_info = slot_info(u64::MAX);
+    let solutions = farmer.solve(&slot_info).unwrap();
+    assert_eq!(solutions.len(), 1);
+    let solution = &solutions[0];
+    let piece_inclusion_proof = solution.piece_inclusion_proof.as_ref().unwrap();
+    assert_eq!(solution.nonce, piece_inclusion_proof.piece_index);
+    assert!(is_commitment_valid(
+        &solution.encoding.as_slice().try_into().unwrap(),
+        &solution.tag,
+        &slot_info.salt
+    ));
+
+    // Piece inclusion proofs are persisted together with the plot
+    let farmer = new_farmer(&path, &keystore);
+    for offset in 0..PLOT_SIZE {
+        assert!(farmer.plot.read(offset).unwrap().1.is_some());
+    }
+}
+
+#[test]
+fn finds_commitment_with_wrapping_solution_range() {
+    let commitments: Commitments = vec![(5, 0), (u64::MAX - 5, 1), (u64::MAX / 2, 2)]
+        .into_iter()
+        .collect();
+
+    assert_eq!(
+        find_commitment(&commitments, 0, 20),
+        Some((u64::MAX - 5, 1))
+    );
+    assert_eq!(find_commitment(&commitments, 2, 10), Some((5, 0)));
+    assert_eq!(
+        find_commitment(&commitments, u64::MAX / 2 + 1, 2),
+        Some((u64::MAX / 2, 2))
+    );
+    assert_eq!(find_commitment(&commitments, u64::MAX / 4, 10), None);
+}
+
+#[test]
+fn signs_only_own_blocks() {
+    let path = TempDir::new().unwrap();
+    let keystore = LocalKeystore::in_memory();
+    let farmer = new_farmer(&path, &keystore);
+
+    let header_hash = vec![1u8; 32];
+    let signature = farmer
+        .sign_block(&BlockSigningInfo {
+            header_hash: header_hash.clone(),
+            public_key: farmer.public_key(),
+            deadline: Instant::now(),
+        })
+        .unwrap();
+    assert!(farmer.public_key().verify(&header_hash, &signature));
+
+    let other_public_key = FarmerPair::generate().0.public();
+    assert!(farmer
+        .sign_block(&BlockSigningInfo {
+            header_hash,
+            public_key: other_public_key,
+            deadline: Instant::now(),
+        })
+        .is_none());
+}
@@ -0,0 +1,264 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! K-deep confirmation finality gadget for PoC consensus.
+//!
+//! PoC only provides probabilistic finality: the deeper a block is buried under the best chain,
+//! the less likely it is to be reverted. Without a finality gadget like GRANDPA nothing is ever
+//! finalized though, so the epoch changes tree is never pruned and constrained state pruning
+//! never kicks in.
+//!
+//! This crate finalizes the block that is `depth` blocks below the best block once at least
+//! `min_slot_age` slots passed between its slot and the slot of the best block. Finalization
+//! goes through the client as usual, so the finality notification stream and everything that
+//! relies on it (pruning of epoch changes in `sc-consensus-poc`, state pruning, etc.) works
+//! unchanged.
+
+#![forbid(unsafe_code)]
+#![warn(missing_docs)]
+
+use futures::StreamExt;
+use log::{debug, warn};
+use sc_client_api::{backend::Backend, BlockchainEvents, Finalizer};
+use serde::{Deserialize, Serialize};
+use sp_blockchain::{HeaderBackend, HeaderMetadata};
+use sp_consensus::SelectChain;
+use sp_consensus_slots::Slot;
+use sp_runtime::generic::BlockId;
+use sp_runtime::traits::{Block as BlockT, CheckedSub, Header, NumberFor, SaturatedConversion};
+use std::sync::Arc;
+
+/// Parameters of k-deep confirmation finality, usually provided by the chain spec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KDeepFinalityConfig {
+    /// Number of blocks a block needs to be buried under on the best chain to be finalized.
+    pub depth: u32,
+    /// Minimum number of slots between the slot of a block and the slot of the best block for
+    /// the block to be finalized.
+    pub min_slot_age: u64,
+}
+
+impl KDeepFinalityConfig {
+    /// Whether a block at `target_slot` is old enough to be finalized with best block at
+    /// `best_slot`.
+    pub fn is_old_enough(&self, target_slot: Slot, best_slot: Slot) -> bool {
+        u64::from(best_slot).saturating_sub(u64::from(target_slot)) >= self.min_slot_age
+    }
+}
+
+/// Parameters for [`run_k_deep_finality`].
+pub struct KDeepFinalityParams<Client, SC> {
+    /// The client to use
+    pub client: Arc<Client>,
+    /// The SelectChain Strategy, best chain is followed
+    pub select_chain: SC,
+    /// Parameters of finality
+    pub config: KDeepFinalityConfig,
+}
+
+/// Run k-deep confirmation finality gadget until the import notification stream ends.
+///
+/// Every time a new best block is imported, the ancestor `depth` blocks below the best block is
+/// finalized if it is old enough.
+pub async fn run_k_deep_finality<Block, BE, Client, SC>(params: KDeepFinalityParams<Client, SC>)
+where
+    Block: BlockT,
+    BE: Backend<Block>,
+    Client: BlockchainEvents<Block>
+        + Finalizer<Block, BE>
+        + HeaderBackend<Block>
+        + HeaderMetadata<Block, Error = sp_blockchain::Error>,
+    SC: SelectChain<Block>,
+{
+    let KDeepFinalityParams {
+        client,
+        select_chain,
+        config,
+    } = params;
+
+    let mut import_notifications = client.import_notification_stream();
+    while let Some(notification) = import_notifications.next().await {
+        if !notification.is_new_best {
+            continue;
+        }
+
+        let best_header = match select_chain.best_chain().await {
+            Ok(best_header) => best_header,
+            Err(error) => {
+                warn!(target: "poc", "Failed to get best chain for finality: {}", error);
+                continue;
+            }
+        };
+
+        if let Err(error) = finalize_k_deep(&*client, &best_header, &config) {
+            warn!(target: "poc", "Failed to finalize k-deep block: {}", error);
+        }
+    }
+}
+
+/// Finalize the ancestor of `best_header` that is `depth` blocks below it, if it is old enough
+/// and not finalized yet.
+fn finalize_k_deep<Block, BE, Client>(
+    client: &Client,
+    best_header: &Block::Header,
+    config: &KDeepFinalityConfig,
+) -> sp_blockchain::Result<()>
+where
+    Block: BlockT,
+    BE: Backend<Block>,
+    Client: Finalizer<Block, BE>
+        + HeaderBackend<Block>
+        + HeaderMetadata<Block, Error = sp_blockchain::Error>,
+{
+    let best_number = *best_header.number();
+    let target_number = match best_number.checked_sub(&config.depth.saturated_into()) {
+        Some(target_number) => target_number,
+        None => return Ok(()),
+    };
+
+    let finalized_number = client.info().finalized_number;
+    if target_number <= finalized_number {
+        return Ok(());
+    }
+
+    let target_hash = ancestor_at(client, best_header.hash(), target_number)?;
+    let target_header = client
+        .header(BlockId::Hash(target_hash))?
+        .ok_or_else(|| sp_blockchain::Error::UnknownBlock(target_hash.to_string()))?;
+
+    let best_slot = slot_of::<Block>(best_header)?;
+    let target_slot = slot_of::<Block>(&target_header)?;
+    if !config.is_old_enough(target_slot, best_slot) {
+        return Ok(());
+    }
+
+    debug!(
+        target: "poc",
+        "Finalizing block #{} ({}) that is {} blocks deep",
+        target_number,
+        target_hash,
+        config.depth,
+    );
+
+    client.finalize_block(BlockId::Hash(target_hash), None, true)
+}
+
+/// Walk back from `hash` to its ancestor at `target_number`.
+fn ancestor_at<Block, Client>(
+    client: &Client,
+    mut hash: Block::Hash,
+    target_number: NumberFor<Block>,
+) -> sp_blockchain::Result<Block::Hash>
+where
+    Block: BlockT,
+    Client: HeaderMetadata<Block, Error = sp_blockchain::Error>,
+{
+    loop {
+        let metadata = client.header_metadata(hash)?;
+        if metadata.number <= target_number {
+            return Ok(hash);
+        }
+        hash = metadata.parent;
+    }
+}
+
+fn slot_of<Block: BlockT>(header: &Block::Header) -> sp_blockchain::Result<Slot> {
+    sc_consensus_poc::find_pre_digest::<Block>(header)
+        .map(|pre_digest| pre_digest.slot)
+        .map_err(|error| sp_consensus::Error::ChainLookup(error.to_string()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use sc_block_builder::BlockBuilderProvider;
+    use sp_consensus::BlockOrigin;
+    use sp_consensus_poc::digests::{CompatibleDigestItem, PreDigest, Solution};
+    use sp_runtime::generic::{Digest, DigestItem};
+    use substrate_test_runtime_client::{prelude::*, runtime::Block as TestBlock};
+
+    // Import a chain of blocks on top of genesis with the given slots and return their headers.
+    fn import_chain(client: &mut TestClient, slots: &[u64]) -> Vec<<TestBlock as BlockT>::Header> {
+        let mut headers = Vec::new();
+        for &slot in slots {
+            let pre_digest = PreDigest {
+                slot: slot.into(),
+                solution: Solution::get_for_genesis(),
+            };
+            let block = client
+                .new_block(Digest {
+                    logs: vec![DigestItem::poc_pre_digest(pre_digest)],
+                })
+                .unwrap()
+                .build()
+                .unwrap()
+                .block;
+            headers.push(block.header.clone());
+            block_on(client.import(BlockOrigin::Own, block)).unwrap();
+        }
+        headers
+    }
+
+    #[test]
+    fn slot_age() {
+        let config = KDeepFinalityConfig {
+            depth: 10,
+            min_slot_age: 5,
+        };
+
+        assert!(config.is_old_enough(10.into(), 15.into()));
+        assert!(config.is_old_enough(10.into(), 100.into()));
+        assert!(!config.is_old_enough(10.into(), 14.into()));
+        assert!(!config.is_old_enough(10.into(), 5.into()));
+    }
+
+    #[test]
+    fn finalizes_block_depth_below_best_once_old_enough() {
+        let mut client = TestClientBuilder::new().build();
+        let config = KDeepFinalityConfig {
+            depth: 2,
+            min_slot_age: 5,
+        };
+
+        let headers = import_chain(&mut client, &[1, 2, 3, 10, 11]);
+
+        // nothing is deep enough yet
+        finalize_k_deep(&client, &headers[0], &config).unwrap();
+        finalize_k_deep(&client, &headers[1], &config).unwrap();
+        assert_eq!(client.info().finalized_number, 0);
+
+        // block #1 is deep enough, but only 2 slots passed since it was produced
+        finalize_k_deep(&client, &headers[2], &config).unwrap();
+        assert_eq!(client.info().finalized_number, 0);
+
+        // block #2 is deep enough and old enough
+        finalize_k_deep(&client, &headers[3], &config).unwrap();
+        assert_eq!(client.info().finalized_number, 2);
+        assert_eq!(client.info().finalized_hash, headers[1].hash());
+
+        finalize_k_deep(&client, &headers[4], &config).unwrap();
+        assert_eq!(client.info().finalized_number, 3);
+        assert_eq!(client.info().finalized_hash, headers[2].hash());
+
+        // finality never goes backwards
+        finalize_k_deep(&client, &headers[3], &config).unwrap();
+        assert_eq!(client.info().finalized_number, 3);
+    }
+}
@@ -17,7 +17,7 @@
 
 //! RPC api for PoC.
 
-use futures::channel::mpsc::UnboundedSender;
+use futures::channel::mpsc::{UnboundedReceiver, UnboundedSender};
 use futures::future;
 use futures::{FutureExt as _, SinkExt, StreamExt, TryFutureExt as _, TryStreamExt};
 use jsonrpc_core::{
@@ -29,23 +29,145 @@ use jsonrpc_derive::rpc;
 use jsonrpc_pubsub::{manager::SubscriptionManager, typed::Subscriber, SubscriptionId};
 use log::{debug, warn};
 use parking_lot::Mutex;
-use sc_consensus_poc::{NewSlotInfo, NewSlotNotifier};
+use sc_consensus_poc::{
+    is_solution_signature_valid, BlockSigningInfo, BlockSigningNotifier, NewSlotInfo,
+    NewSlotNotifier,
+};
 use serde::{Deserialize, Serialize};
-use sp_consensus_poc::digests::Solution;
-use sp_consensus_poc::FarmerId;
+use sp_consensus_poc::digests::{PieceInclusionProof, Solution};
+use sp_consensus_poc::{FarmerId, FarmerSignature};
 use sp_core::crypto::Public;
-use std::sync::mpsc;
-use std::time::Duration;
-use std::{collections::HashMap, sync::Arc};
+use sp_runtime::RuntimeAppPublic;
+use std::convert::{TryFrom, TryInto};
+use std::time::{Duration, Instant};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
 
-const SOLUTION_TIMEOUT: Duration = Duration::from_secs(5);
+mod state;
+
+pub use state::{
+    PoCStateApi, PoCStateRpcHandler, RpcBlockListEntry, RpcEpoch, RpcEpochChange, StateError,
+};
+
+/// Default amount of time farmers have to propose proof of space after being notified about slot.
+pub const DEFAULT_SOLUTION_TIMEOUT: Duration = Duration::from_secs(5);
+/// Maximum number of farmers statistics is kept for, statistics of the farmer that proposed proof
+/// of space least recently are evicted once exceeded.
+const MAX_FARMER_STATS: usize = 1024;
 
 type Slot = u64;
 type FutureResult<T> = Box<dyn rpc_future::Future<Item = T, Error = RpcError> + Send>;
 
+/// Identifier of the slot info subscription, farmers must include it in proposed proofs of space.
+pub type SessionId = String;
+
+/// Errors encountered by the RPC
+#[derive(Debug, derive_more::Display)]
+pub enum Error {
+    /// Session is not known, farmer is not subscribed to slot info
+    #[display(fmt = "Unknown session {}", _0)]
+    UnknownSession(SessionId),
+    /// Session was not notified about the slot
+    #[display(fmt = "Session was not notified about slot {}", _0)]
+    NotNotified(Slot),
+    /// Session has already proposed proof of space for the slot
+    #[display(fmt = "Proof of space for slot {} was already proposed", _0)]
+    Duplicate(Slot),
+    /// Solutions for the slot are no longer collected
+    #[display(fmt = "Proof of space for slot {} was proposed too late", _0)]
+    Late(Slot),
+    /// Solution signature doesn't correspond to its public key
+    #[display(fmt = "Solution for slot {} has invalid signature", _0)]
+    InvalidSignature(Slot),
+    /// Solution was proposed by a farmer other than the farmer of the session
+    #[display(
+        fmt = "Solution for slot {} was proposed by a farmer other than the one of the session",
+        _0
+    )]
+    FarmerMismatch(Slot),
+}
+
+impl Error {
+    fn rejection_reason(&self) -> RejectionReason {
+        match self {
+            Error::UnknownSession(_) => RejectionReason::UnknownSession,
+            Error::NotNotified(_) => RejectionReason::NotNotified,
+            Error::Duplicate(_) => RejectionReason::Duplicate,
+            Error::Late(_) => RejectionReason::Late,
+            Error::InvalidSignature(_) => RejectionReason::InvalidSignature,
+            Error::FarmerMismatch(_) => RejectionReason::FarmerMismatch,
+        }
+    }
+}
+
+impl From<Error> for RpcError {
+    fn from(error: Error) -> Self {
+        let code = match error {
+            Error::UnknownSession(_) => 1,
+            Error::NotNotified(_) => 2,
+            Error::Duplicate(_) => 3,
+            Error::Late(_) => 4,
+            Error::InvalidSignature(_) => 5,
+            Error::FarmerMismatch(_) => 6,
+        };
+        RpcError {
+            message: error.to_string(),
+            code: jsonrpc_core::ErrorCode::ServerError(code),
+            data: None,
+        }
+    }
+}
+
+/// Reason proposed solution was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RejectionReason {
+    /// Session is not known
+    UnknownSession,
+    /// Session was not notified about the slot
+    NotNotified,
+    /// Solution for the slot was already proposed in this session
+    Duplicate,
+    /// Solutions for the slot were no longer collected
+    Late,
+    /// Solution signature didn't correspond to its public key
+    InvalidSignature,
+    /// Solution was proposed by a farmer other than the farmer of the session
+    FarmerMismatch,
+}
+
+/// Statistics of solutions proposed by a farmer.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FarmerStats {
+    /// Solutions proposed by the farmer
+    pub solutions_offered: u64,
+    /// Solutions that were forwarded to consensus
+    pub solutions_accepted: u64,
+    /// Solutions that were rejected
+    pub solutions_rejected: u64,
+    /// Number of rejected solutions by reason
+    pub rejection_reasons: BTreeMap<RejectionReason, u64>,
+}
+
+/// Statistics of solutions proposed by the farmer with given public key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcFarmerStats {
+    /// Public key of the farmer
+    pub public_key: [u8; 32],
+    /// Statistics of solutions
+    #[serde(flatten)]
+    pub stats: FarmerStats,
+}
+
 /// Information about new slot that just arrived
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RpcNewSlotInfo {
+    /// Session this notification was sent to
+    pub session_id: SessionId,
     /// Slot number
     pub slot_number: Slot,
     /// Slot challenge
@@ -58,6 +180,13 @@ pub struct RpcNewSlotInfo {
     pub solution_range: u64,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcPieceInclusionProof {
+    pub piece_index: u64,
+    pub piece: Vec<u8>,
+    pub merkle_proof: Vec<[u8; 32]>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RpcSolution {
     pub public_key: [u8; 32],
@@ -65,13 +194,34 @@ pub struct RpcSolution {
     pub encoding: Vec<u8>,
     pub signature: Vec<u8>,
     pub tag: [u8; 8],
+    /// Missing for encodings of the genesis piece
+    #[serde(default)]
+    pub piece_inclusion_proof: Option<RpcPieceInclusionProof>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProposedProofOfSpaceResult {
+    pub session_id: SessionId,
     pub slot_number: Slot,
     pub solution: Option<RpcSolution>,
-    pub secret_key: Vec<u8>,
+}
+
+/// Information about a new block that needs to be signed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcBlockSigningInfo {
+    /// Header hash of the block to be signed
+    pub header_hash: Vec<u8>,
+    /// Public key of the farmer that is expected to sign the block
+    pub public_key: [u8; 32],
+}
+
+/// Signature of the block produced by the farmer (or lack thereof)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcBlockSignature {
+    /// Header hash of the block that was signed
+    pub header_hash: Vec<u8>,
+    /// Signature of the header hash, `None` if farmer declined to sign the block
+    pub signature: Option<Vec<u8>>,
 }
 
 /// Provides rpc methods for interacting with PoC.
@@ -105,14 +255,225 @@ pub trait PoCApi {
         metadata: Option<Self::Metadata>,
         id: SubscriptionId,
     ) -> RpcResult<bool>;
+
+    /// Statistics of solutions proposed by farmers
+    #[rpc(name = "poc_farmerStats")]
+    fn farmer_stats(&self) -> RpcResult<Vec<RpcFarmerStats>>;
+
+    #[rpc(name = "poc_submitBlockSignature")]
+    fn submit_block_signature(&self, block_signature: RpcBlockSignature) -> FutureResult<()>;
+
+    /// Block signing subscription
+    #[pubsub(
+        subscription = "poc_block_signing",
+        subscribe,
+        name = "poc_subscribeBlockSigning"
+    )]
+    fn subscribe_block_signing(
+        &self,
+        metadata: Self::Metadata,
+        subscriber: Subscriber<RpcBlockSigningInfo>,
+    );
+
+    /// Unsubscribe from block signing subscription.
+    #[pubsub(
+        subscription = "poc_block_signing",
+        unsubscribe,
+        name = "poc_unsubscribeBlockSigning"
+    )]
+    fn unsubscribe_block_signing(
+        &self,
+        metadata: Option<Self::Metadata>,
+        id: SubscriptionId,
+    ) -> RpcResult<bool>;
+}
+
+/// Slot info subscription of a farmer.
+struct Session {
+    notification_sender: UnboundedSender<RpcNewSlotInfo>,
+    last_notified_slot: Option<Slot>,
+    /// Farmer that proposed the first correctly signed solution in this session, further
+    /// solutions of the session are only accepted from the same farmer
+    farmer_id: Option<FarmerId>,
+}
+
+/// Solutions for a slot that are being collected.
+struct SlotSolutions {
+    sender: futures::channel::mpsc::Sender<Option<Solution>>,
+    /// Sessions notified about the slot and whether they have proposed proof of space already
+    sessions: HashMap<SessionId, bool>,
+}
+
+#[derive(Default)]
+struct SolutionsState {
+    sessions: HashMap<SessionId, Session>,
+    subscriptions: HashMap<SubscriptionId, SessionId>,
+    slots: HashMap<Slot, SlotSolutions>,
+    /// Statistics of farmers along with the last slot they proposed proof of space for
+    farmer_stats: HashMap<FarmerId, (Slot, FarmerStats)>,
+}
+
+impl SolutionsState {
+    /// Checks that session was notified about the slot and hasn't proposed proof of space for it
+    /// yet, returns sender for solutions of the slot.
+    fn accept_proposal(
+        &mut self,
+        session_id: &SessionId,
+        slot: Slot,
+    ) -> Result<futures::channel::mpsc::Sender<Option<Solution>>, Error> {
+        let session = self
+            .sessions
+            .get(session_id)
+            .ok_or_else(|| Error::UnknownSession(session_id.clone()))?;
+
+        let slot_solutions = match self.slots.get_mut(&slot) {
+            Some(slot_solutions) => slot_solutions,
+            None => {
+                return Err(
+                    if session
+                        .last_notified_slot
+                        .map_or(false, |last| last >= slot)
+                    {
+                        Error::Late(slot)
+                    } else {
+                        Error::NotNotified(slot)
+                    },
+                );
+            }
+        };
+
+        match slot_solutions.sessions.get_mut(session_id) {
+            Some(proposed) if *proposed => Err(Error::Duplicate(slot)),
+            Some(proposed) => {
+                *proposed = true;
+                Ok(slot_solutions.sender.clone())
+            }
+            None => Err(Error::NotNotified(slot)),
+        }
+    }
+
+    /// Checks proposal of proof of space by the session (see `accept_proposal`) and that solution
+    /// (if any) was proposed by the farmer of the session, returns sender for solutions of the slot.
+    ///
+    /// Statistics are only recorded for the verified farmer of the session, such that clients
+    /// can't affect statistics of other farmers.
+    fn propose(
+        &mut self,
+        session_id: &SessionId,
+        slot: Slot,
+        solution: Option<&Solution>,
+        signing_context: &[u8],
+    ) -> Result<futures::channel::mpsc::Sender<Option<Solution>>, Error> {
+        let farmer_id = match solution {
+            Some(solution) => {
+                Some(self.verify_farmer(session_id, slot, solution, signing_context)?)
+            }
+            None => None,
+        };
+
+        let result = self.accept_proposal(session_id, slot);
+        if let Some(farmer_id) = farmer_id {
+            self.record_stats(farmer_id, slot, result.as_ref().map(|_| ()));
+        }
+        result
+    }
+
+    /// Checks that solution is correctly signed and proposed by the farmer of the session, the
+    /// first farmer that proposes correctly signed solution in a session becomes its farmer.
+    fn verify_farmer(
+        &mut self,
+        session_id: &SessionId,
+        slot: Slot,
+        solution: &Solution,
+        signing_context: &[u8],
+    ) -> Result<FarmerId, Error> {
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or_else(|| Error::UnknownSession(session_id.clone()))?;
+
+        if !is_solution_signature_valid(signing_context, solution) {
+            return Err(Error::InvalidSignature(slot));
+        }
+
+        let farmer_id = session
+            .farmer_id
+            .get_or_insert_with(|| solution.public_key.clone())
+            .clone();
+        if farmer_id != solution.public_key {
+            let error = Error::FarmerMismatch(slot);
+            self.record_stats(farmer_id, slot, Err(&error));
+            return Err(error);
+        }
+
+        Ok(farmer_id)
+    }
+
+    fn record_stats(&mut self, farmer_id: FarmerId, slot: Slot, result: Result<(), &Error>) {
+        if !self.farmer_stats.contains_key(&farmer_id)
+            && self.farmer_stats.len() >= MAX_FARMER_STATS
+        {
+            let least_recent_farmer_id = self
+                .farmer_stats
+                .iter()
+                .min_by_key(|(_farmer_id, (last_slot, _stats))| *last_slot)
+                .map(|(farmer_id, _)| farmer_id.clone());
+            if let Some(least_recent_farmer_id) = least_recent_farmer_id {
+                self.farmer_stats.remove(&least_recent_farmer_id);
+            }
+        }
+
+        let (last_slot, stats) = self.farmer_stats.entry(farmer_id).or_default();
+        *last_slot = (*last_slot).max(slot);
+        stats.solutions_offered += 1;
+        match result {
+            Ok(()) => {
+                stats.solutions_accepted += 1;
+            }
+            Err(error) => {
+                stats.solutions_rejected += 1;
+                *stats
+                    .rejection_reasons
+                    .entry(error.rejection_reason())
+                    .or_default() += 1;
+            }
+        }
+    }
+}
+
+/// Returns signature of the block if it was provided by the farmer that is expected to sign it.
+fn verify_block_signature(
+    block_signing_info: &BlockSigningInfo,
+    block_signature: RpcBlockSignature,
+) -> Option<FarmerSignature> {
+    let signature = match FarmerSignature::try_from(block_signature.signature?) {
+        Ok(signature) => signature,
+        Err(_) => {
+            debug!("Received malformed block signature");
+            return None;
+        }
+    };
+
+    if !block_signing_info
+        .public_key
+        .verify(&block_signing_info.header_hash, &signature)
+    {
+        debug!("Received invalid block signature");
+        return None;
+    }
+
+    Some(signature)
 }
 
 /// Implements the PoCRpc trait for interacting with PoC.
 pub struct PoCRpcHandler {
     manager: SubscriptionManager,
-    notification_senders: Arc<Mutex<Vec<UnboundedSender<RpcNewSlotInfo>>>>,
-    solution_senders:
-        Arc<Mutex<HashMap<Slot, futures::channel::mpsc::Sender<ProposedProofOfSpaceResult>>>>,
+    solutions_state: Arc<Mutex<SolutionsState>>,
+    signing_context: Vec<u8>,
+    block_signing_notification_senders:
+        Arc<Mutex<HashMap<SubscriptionId, UnboundedSender<RpcBlockSigningInfo>>>>,
+    signature_senders:
+        Arc<Mutex<HashMap<Vec<u8>, futures::channel::mpsc::Sender<RpcBlockSignature>>>>,
 }
 
 /// PoCRpcHandler is used for notifying subscribers about arrival of new slots and for submission of
@@ -120,26 +481,43 @@ pub struct PoCRpcHandler {
 ///
 /// Internally every time slot notifier emits information about new slot, notification is sent to
 /// every subscriber, after which RPC server waits for the same number of `poc_proposeProofOfSpace`
-/// requests with `ProposedProofOfSpaceResult` in them or until timeout is exceeded. The first valid
-/// solution for a particular slot wins, others are ignored.
+/// requests with `ProposedProofOfSpaceResult` in them or until `solution_timeout` is exceeded. The
+/// first valid solution for a particular slot wins, others are ignored.
+///
+/// Every subscriber gets its own session id with slot info notifications and can propose proof of
+/// space once per slot it was notified about, such that a single farmer can't exhaust the number of
+/// expected responses. Duplicate, late and unexpected proposals are rejected with an error. Once
+/// the signature of the first proposed solution is verified, the session is bound to its farmer and
+/// rejections are accounted for in statistics of that farmer available through `poc_farmerStats`.
+///
+/// Once a block is built on top of the winning solution, its pre-hash is sent to every block
+/// signing subscriber and RPC server waits for `poc_submitBlockSignature` requests with
+/// `RpcBlockSignature` in them until a signature that corresponds to the public key from the
+/// solution arrives or until the end of the slot. This way secret keys of farmers never leave
+/// farmers and clients other than the farmer can't end signature collection early.
 impl PoCRpcHandler {
     /// Creates a new instance of the PoCRpc handler.
-    pub fn new<E>(executor: E, new_slot_notifier: NewSlotNotifier) -> Self
+    ///
+    /// `signing_context` is the one from `SpartanParameters` and is used to verify signatures of
+    /// proposed solutions.
+    pub fn new<E>(
+        executor: E,
+        new_slot_notifier: NewSlotNotifier,
+        block_signing_notifier: BlockSigningNotifier,
+        solution_timeout: Duration,
+        signing_context: Vec<u8>,
+    ) -> Self
     where
         E: Executor01<Box<dyn Future01<Item = (), Error = ()> + Send>> + Send + Sync + 'static,
     {
-        let notification_senders: Arc<Mutex<Vec<UnboundedSender<RpcNewSlotInfo>>>> = Arc::default();
-        let solution_senders: Arc<
-            Mutex<HashMap<Slot, futures::channel::mpsc::Sender<ProposedProofOfSpaceResult>>>,
-        > = Arc::default();
+        let solutions_state: Arc<Mutex<SolutionsState>> = Arc::default();
         std::thread::Builder::new()
             .name("poc_rpc_nsn_handler".to_string())
             .spawn({
-                let notification_senders = Arc::clone(&notification_senders);
-                let solution_senders = Arc::clone(&solution_senders);
-                let new_slot_notifier: std::sync::mpsc::Receiver<(
+                let solutions_state = Arc::clone(&solutions_state);
+                let mut new_slot_notifier: UnboundedReceiver<(
                     NewSlotInfo,
-                    mpsc::Sender<(Solution, Vec<u8>)>,
+                    UnboundedSender<Solution>,
                 )> = new_slot_notifier();
 
                 move || {
@@ -149,60 +527,61 @@ impl PoCRpcHandler {
                     // We then send slot info to all subscribers and wait for their solutions. As
                     // soon as solution is found we send it back and ignore any other solutions for
                     // that slot.
-                    while let Ok((new_slot_info, sync_solution_sender)) = new_slot_notifier.recv() {
-                        futures::executor::block_on(async {
-                            let (solution_sender, mut solution_receiver) =
-                                futures::channel::mpsc::channel(0);
-                            solution_senders
-                                .lock()
-                                .insert(new_slot_info.slot.into(), solution_sender);
-                            let mut expected_solutions_count;
-                            {
-                                let mut notification_senders = notification_senders.lock();
-                                expected_solutions_count = notification_senders.len();
-                                if expected_solutions_count == 0 {
-                                    return;
-                                }
-                                for notification_sender in notification_senders.iter_mut() {
-                                    if notification_sender
-                                        .send(RpcNewSlotInfo {
-                                            slot_number: new_slot_info.slot.into(),
-                                            challenge: new_slot_info.challenge,
-                                            salt: new_slot_info.salt,
-                                            next_salt: new_slot_info.next_salt,
-                                            solution_range: new_slot_info.solution_range,
-                                        })
-                                        .await
-                                        .is_err()
-                                    {
-                                        expected_solutions_count -= 1;
-                                    }
-                                }
+                    while let Some((new_slot_info, node_solution_sender)) =
+                        futures::executor::block_on(new_slot_notifier.next())
+                    {
+                        let slot: Slot = new_slot_info.slot.into();
+                        let (solution_sender, mut solution_receiver) =
+                            futures::channel::mpsc::channel(0);
+                        let expected_solutions_count = {
+                            let mut solutions_state = solutions_state.lock();
+                            // Sessions of subscribers that are gone are removed
+                            solutions_state.sessions.retain(|session_id, session| {
+                                session.last_notified_slot.replace(slot);
+                                session
+                                    .notification_sender
+                                    .unbounded_send(RpcNewSlotInfo {
+                                        session_id: session_id.clone(),
+                                        slot_number: slot,
+                                        challenge: new_slot_info.challenge,
+                                        salt: new_slot_info.salt,
+                                        next_salt: new_slot_info.next_salt,
+                                        solution_range: new_slot_info.solution_range,
+                                    })
+                                    .is_ok()
+                            });
+                            let sessions: HashMap<SessionId, bool> = solutions_state
+                                .sessions
+                                .keys()
+                                .map(|session_id| (session_id.clone(), false))
+                                .collect();
+                            let expected_solutions_count = sessions.len();
+                            if expected_solutions_count > 0 {
+                                solutions_state.slots.insert(
+                                    slot,
+                                    SlotSolutions {
+                                        sender: solution_sender,
+                                        sessions,
+                                    },
+                                );
                             }
+                            expected_solutions_count
+                        };
+                        if expected_solutions_count == 0 {
+                            continue;
+                        }
 
-                            let timeout = futures_timer::Delay::new(SOLUTION_TIMEOUT);
+                        futures::executor::block_on(async {
+                            let timeout = futures_timer::Delay::new(solution_timeout);
                             let solution = async {
-                                // TODO: This doesn't track what client sent a solution, allowing
-                                //  some clients to send multiple
+                                // Every session can propose once, so this can't be exhausted by
+                                // a single farmer
                                 let mut potential_solutions_left = expected_solutions_count;
-                                while let Some(proposed_proof_of_space_result) =
-                                    solution_receiver.next().await
-                                {
-                                    if let Some(solution) = proposed_proof_of_space_result.solution
-                                    {
-                                        let solution_send_result = sync_solution_sender.send((
-                                            Solution {
-                                                public_key: FarmerId::from_slice(
-                                                    &solution.public_key,
-                                                ),
-                                                nonce: solution.nonce,
-                                                encoding: solution.encoding,
-                                                signature: solution.signature,
-                                                tag: solution.tag,
-                                            },
-                                            proposed_proof_of_space_result.secret_key,
-                                        ));
-                                        if let Err(error) = solution_send_result {
+                                while let Some(solution) = solution_receiver.next().await {
+                                    if let Some(solution) = solution {
+                                        if let Err(error) =
+                                            node_solution_sender.unbounded_send(solution)
+                                        {
                                             debug!("Failed to send solution: {}", error);
                                             break;
                                         }
@@ -215,18 +594,108 @@ impl PoCRpcHandler {
                             };
 
                             future::select(timeout, Box::pin(solution)).await;
-
-                            solution_senders.lock().remove(&new_slot_info.slot.into());
                         });
+
+                        solutions_state.lock().slots.remove(&slot);
                     }
                 }
             })
             .expect("Failed to spawn poc rpc new slot notifier handler");
+
+        let block_signing_notification_senders: Arc<
+            Mutex<HashMap<SubscriptionId, UnboundedSender<RpcBlockSigningInfo>>>,
+        > = Arc::default();
+        let signature_senders: Arc<
+            Mutex<HashMap<Vec<u8>, futures::channel::mpsc::Sender<RpcBlockSignature>>>,
+        > = Arc::default();
+        std::thread::Builder::new()
+            .name("poc_rpc_bsn_handler".to_string())
+            .spawn({
+                let block_signing_notification_senders =
+                    Arc::clone(&block_signing_notification_senders);
+                let signature_senders = Arc::clone(&signature_senders);
+                let mut block_signing_notifier: UnboundedReceiver<(
+                    BlockSigningInfo,
+                    UnboundedSender<FarmerSignature>,
+                )> = block_signing_notifier();
+
+                move || {
+                    // `block_signing_notifier` receives messages with a tuple containing block
+                    // signing info and sender for signature.
+                    //
+                    // We then send block signing info to all subscribers and wait for the
+                    // signature of the farmer that claimed the slot until the end of the slot.
+                    // Only signatures that correspond to the public key of the farmer are
+                    // forwarded to the node.
+                    while let Some((block_signing_info, node_signature_sender)) =
+                        futures::executor::block_on(block_signing_notifier.next())
+                    {
+                        futures::executor::block_on(async {
+                            let (signature_sender, mut signature_receiver) =
+                                futures::channel::mpsc::channel(0);
+                            signature_senders
+                                .lock()
+                                .insert(block_signing_info.header_hash.clone(), signature_sender);
+                            {
+                                let mut block_signing_notification_senders =
+                                    block_signing_notification_senders.lock();
+                                // Senders of subscribers that are gone are removed
+                                block_signing_notification_senders.retain(
+                                    |_subscription_id, notification_sender| {
+                                        notification_sender
+                                            .unbounded_send(RpcBlockSigningInfo {
+                                                header_hash: block_signing_info.header_hash.clone(),
+                                                public_key: block_signing_info
+                                                    .public_key
+                                                    .to_raw_vec()
+                                                    .try_into()
+                                                    .expect("Public key is always 32 bytes; qed"),
+                                            })
+                                            .is_ok()
+                                    },
+                                );
+                                if block_signing_notification_senders.is_empty() {
+                                    signature_senders
+                                        .lock()
+                                        .remove(&block_signing_info.header_hash);
+                                    return;
+                                }
+                            }
+
+                            let timeout = futures_timer::Delay::new(
+                                block_signing_info
+                                    .deadline
+                                    .saturating_duration_since(Instant::now()),
+                            );
+                            let signature = async {
+                                while let Some(block_signature) = signature_receiver.next().await {
+                                    if let Some(signature) =
+                                        verify_block_signature(&block_signing_info, block_signature)
+                                    {
+                                        let _ = node_signature_sender.unbounded_send(signature);
+                                        break;
+                                    }
+                                }
+                            };
+
+                            future::select(timeout, Box::pin(signature)).await;
+
+                            signature_senders
+                                .lock()
+                                .remove(&block_signing_info.header_hash);
+                        });
+                    }
+                }
+            })
+            .expect("Failed to spawn poc rpc block signing notifier handler");
+
         let manager = SubscriptionManager::new(Arc::new(executor));
         Self {
             manager,
-            notification_senders,
-            solution_senders,
+            solutions_state,
+            signing_context,
+            block_signing_notification_senders,
+            signature_senders,
         }
     }
 }
@@ -238,14 +707,117 @@ impl PoCApi for PoCRpcHandler {
         &self,
         proposed_proof_of_space_result: ProposedProofOfSpaceResult,
     ) -> FutureResult<()> {
+        let ProposedProofOfSpaceResult {
+            session_id,
+            slot_number,
+            solution,
+        } = proposed_proof_of_space_result;
+        let solution = solution.map(|solution| Solution {
+            public_key: FarmerId::from_slice(&solution.public_key),
+            nonce: solution.nonce,
+            encoding: solution.encoding,
+            signature: solution.signature,
+            tag: solution.tag,
+            piece_inclusion_proof: solution.piece_inclusion_proof.map(|proof| {
+                PieceInclusionProof {
+                    piece_index: proof.piece_index,
+                    piece: proof.piece,
+                    merkle_proof: proof.merkle_proof,
+                }
+            }),
+            solution_data_commitment: None,
+        });
+
+        let result = self.solutions_state.lock().propose(
+            &session_id,
+            slot_number,
+            solution.as_ref(),
+            &self.signing_context,
+        );
+
+        let future = async move {
+            match result {
+                Ok(mut sender) => {
+                    let _ = sender.send(solution).await;
+                    Ok(())
+                }
+                Err(error) => {
+                    debug!("Rejected proof of space: {}", error);
+                    Err(error.into())
+                }
+            }
+        }
+        .boxed();
+        Box::new(future.compat())
+    }
+
+    fn subscribe_slot_info(
+        &self,
+        _metadata: Self::Metadata,
+        subscriber: Subscriber<RpcNewSlotInfo>,
+    ) {
+        let (tx, rx) = futures::channel::mpsc::unbounded();
+        let subscription_id = self.manager.add(subscriber, |sink| {
+            sink.sink_map_err(|e| warn!("Error sending notifications: {:?}", e))
+                .send_all(rx.map(Ok::<_, ()>).compat().map(|res| Ok(res)))
+                .map(|_| ())
+        });
+
+        // Session id is random, such that it can't be guessed by other clients
+        let session_id = format!("{:016x}", rand::random::<u64>());
+        let mut solutions_state = self.solutions_state.lock();
+        solutions_state.sessions.insert(
+            session_id.clone(),
+            Session {
+                notification_sender: tx,
+                last_notified_slot: None,
+                farmer_id: None,
+            },
+        );
+        solutions_state
+            .subscriptions
+            .insert(subscription_id, session_id);
+    }
+
+    fn unsubscribe_slot_info(
+        &self,
+        _metadata: Option<Self::Metadata>,
+        id: SubscriptionId,
+    ) -> RpcResult<bool> {
+        {
+            let mut solutions_state = self.solutions_state.lock();
+            if let Some(session_id) = solutions_state.subscriptions.remove(&id) {
+                solutions_state.sessions.remove(&session_id);
+            }
+        }
+        Ok(self.manager.cancel(id))
+    }
+
+    fn farmer_stats(&self) -> RpcResult<Vec<RpcFarmerStats>> {
+        Ok(self
+            .solutions_state
+            .lock()
+            .farmer_stats
+            .iter()
+            .map(|(public_key, (_last_slot, stats))| RpcFarmerStats {
+                public_key: public_key
+                    .to_raw_vec()
+                    .try_into()
+                    .expect("Public key is always 32 bytes; qed"),
+                stats: stats.clone(),
+            })
+            .collect())
+    }
+
+    fn submit_block_signature(&self, block_signature: RpcBlockSignature) -> FutureResult<()> {
         let sender = self
-            .solution_senders
+            .signature_senders
             .lock()
-            .get(&proposed_proof_of_space_result.slot_number)
+            .get(&block_signature.header_hash)
             .cloned();
         let future = async move {
             if let Some(mut sender) = sender {
-                let _ = sender.send(proposed_proof_of_space_result).await;
+                let _ = sender.send(block_signature).await;
             }
 
             Ok(())
@@ -254,25 +826,231 @@ impl PoCApi for PoCRpcHandler {
         Box::new(future.compat())
     }
 
-    fn subscribe_slot_info(
+    fn subscribe_block_signing(
         &self,
         _metadata: Self::Metadata,
-        subscriber: Subscriber<RpcNewSlotInfo>,
+        subscriber: Subscriber<RpcBlockSigningInfo>,
     ) {
-        self.manager.add(subscriber, |sink| {
-            let (tx, rx) = futures::channel::mpsc::unbounded();
-            self.notification_senders.lock().push(tx);
+        let (tx, rx) = futures::channel::mpsc::unbounded();
+        let subscription_id = self.manager.add(subscriber, |sink| {
             sink.sink_map_err(|e| warn!("Error sending notifications: {:?}", e))
                 .send_all(rx.map(Ok::<_, ()>).compat().map(|res| Ok(res)))
                 .map(|_| ())
         });
+
+        self.block_signing_notification_senders
+            .lock()
+            .insert(subscription_id, tx);
     }
 
-    fn unsubscribe_slot_info(
+    fn unsubscribe_block_signing(
         &self,
         _metadata: Option<Self::Metadata>,
         id: SubscriptionId,
     ) -> RpcResult<bool> {
+        self.block_signing_notification_senders.lock().remove(&id);
         Ok(self.manager.cancel(id))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNING_CONTEXT: &[u8] = b"test";
+
+    fn signed_solution(keypair: &schnorrkel::Keypair) -> Solution {
+        let tag = [0u8; 8];
+        let signature = keypair
+            .sign(schnorrkel::context::signing_context(SIGNING_CONTEXT).bytes(&tag))
+            .to_bytes()
+            .to_vec();
+
+        Solution {
+            public_key: FarmerId::from_slice(&keypair.public.to_bytes()),
+            nonce: 0,
+            encoding: Vec::new(),
+            signature,
+            tag,
+            piece_inclusion_proof: None,
+            solution_data_commitment: None,
+        }
+    }
+
+    fn state_with_session(session_id: &str, slot: Option<Slot>) -> SolutionsState {
+        let mut state = SolutionsState::default();
+        let (notification_sender, _) = futures::channel::mpsc::unbounded();
+        state.sessions.insert(
+            session_id.to_string(),
+            Session {
+                notification_sender,
+                last_notified_slot: slot,
+                farmer_id: None,
+            },
+        );
+        if let Some(slot) = slot {
+            let (sender, _) = futures::channel::mpsc::channel(0);
+            state.slots.insert(
+                slot,
+                SlotSolutions {
+                    sender,
+                    sessions: vec![(session_id.to_string(), false)].into_iter().collect(),
+                },
+            );
+        }
+        state
+    }
+
+    #[test]
+    fn accepts_single_proposal_per_session_and_slot() {
+        let mut state = state_with_session("a", Some(1));
+
+        assert!(state.accept_proposal(&"a".to_string(), 1).is_ok());
+        assert!(matches!(
+            state.accept_proposal(&"a".to_string(), 1),
+            Err(Error::Duplicate(1))
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_session() {
+        let mut state = state_with_session("a", Some(1));
+
+        assert!(matches!(
+            state.accept_proposal(&"b".to_string(), 1),
+            Err(Error::UnknownSession(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_late_and_unexpected_proposals() {
+        let mut state = state_with_session("a", Some(2));
+
+        // Slot 2 is no longer collected
+        state.slots.remove(&2);
+        assert!(matches!(
+            state.accept_proposal(&"a".to_string(), 2),
+            Err(Error::Late(2))
+        ));
+        // Session was never notified about slot 3
+        assert!(matches!(
+            state.accept_proposal(&"a".to_string(), 3),
+            Err(Error::NotNotified(3))
+        ));
+    }
+
+    #[test]
+    fn records_stats_only_for_verified_farmer_of_session() {
+        let mut state = state_with_session("a", Some(1));
+        let farmer = schnorrkel::Keypair::generate();
+        let farmer_id = FarmerId::from_slice(&farmer.public.to_bytes());
+
+        // Solution claiming to be from the farmer, but not signed by it, isn't accounted for
+        let mut forged_solution = signed_solution(&schnorrkel::Keypair::generate());
+        forged_solution.public_key = farmer_id.clone();
+        assert!(matches!(
+            state.propose(&"a".to_string(), 1, Some(&forged_solution), SIGNING_CONTEXT),
+            Err(Error::InvalidSignature(1))
+        ));
+        assert!(state.farmer_stats.is_empty());
+
+        assert!(state
+            .propose(
+                &"a".to_string(),
+                1,
+                Some(&signed_solution(&farmer)),
+                SIGNING_CONTEXT
+            )
+            .is_ok());
+        let (_last_slot, stats) = state.farmer_stats.get(&farmer_id).unwrap();
+        assert_eq!(stats.solutions_offered, 1);
+        assert_eq!(stats.solutions_accepted, 1);
+
+        // Session is bound to the farmer now, solutions of other farmers are rejected and
+        // accounted for in statistics of the farmer of the session
+        let other_farmer = schnorrkel::Keypair::generate();
+        assert!(matches!(
+            state.propose(
+                &"a".to_string(),
+                1,
+                Some(&signed_solution(&other_farmer)),
+                SIGNING_CONTEXT
+            ),
+            Err(Error::FarmerMismatch(1))
+        ));
+        assert_eq!(state.farmer_stats.len(), 1);
+        let (_last_slot, stats) = state.farmer_stats.get(&farmer_id).unwrap();
+        assert_eq!(stats.solutions_rejected, 1);
+        assert_eq!(
+            stats
+                .rejection_reasons
+                .get(&RejectionReason::FarmerMismatch),
+            Some(&1)
+        );
+    }
+
+    #[test]
+    fn accepts_only_block_signatures_of_expected_farmer() {
+        let farmer = schnorrkel::Keypair::generate();
+        let header_hash = vec![1u8; 32];
+        let block_signing_info = BlockSigningInfo {
+            header_hash: header_hash.clone(),
+            public_key: FarmerId::from_slice(&farmer.public.to_bytes()),
+            deadline: Instant::now(),
+        };
+        let sign = |keypair: &schnorrkel::Keypair| {
+            keypair
+                .sign(schnorrkel::context::signing_context(b"substrate").bytes(&header_hash))
+                .to_bytes()
+                .to_vec()
+        };
+
+        // Declined, malformed and foreign signatures are ignored
+        for signature in vec![
+            None,
+            Some(vec![1, 2, 3]),
+            Some(sign(&schnorrkel::Keypair::generate())),
+        ] {
+            assert!(verify_block_signature(
+                &block_signing_info,
+                RpcBlockSignature {
+                    header_hash: header_hash.clone(),
+                    signature,
+                },
+            )
+            .is_none());
+        }
+
+        assert!(verify_block_signature(
+            &block_signing_info,
+            RpcBlockSignature {
+                header_hash: header_hash.clone(),
+                signature: Some(sign(&farmer)),
+            },
+        )
+        .is_some());
+    }
+
+    #[test]
+    fn evicts_stats_of_least_recent_farmer() {
+        let mut state = SolutionsState::default();
+        for slot in 0..MAX_FARMER_STATS as Slot {
+            let mut public_key = [0u8; 32];
+            public_key[..8].copy_from_slice(&slot.to_le_bytes());
+            let farmer_id = FarmerId::from_slice(&public_key);
+            state
+                .farmer_stats
+                .insert(farmer_id, (slot, FarmerStats::default()));
+        }
+
+        let farmer_id = FarmerId::from_slice(&[1; 32]);
+        state.record_stats(farmer_id.clone(), MAX_FARMER_STATS as Slot, Ok(()));
+
+        assert_eq!(state.farmer_stats.len(), MAX_FARMER_STATS);
+        assert!(state.farmer_stats.contains_key(&farmer_id));
+        assert!(!state
+            .farmer_stats
+            .values()
+            .any(|(last_slot, _stats)| *last_slot == 0));
+    }
+}
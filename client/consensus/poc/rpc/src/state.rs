@@ -0,0 +1,429 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Read-only RPC methods for inspecting PoC consensus state.
+
+use jsonrpc_core::{Error as RpcError, Result as RpcResult};
+use jsonrpc_derive::rpc;
+use sc_consensus_epochs::{
+    descendent_query, Epoch as EpochT, EpochIdentifier, EpochIdentifierPosition,
+    PersistedEpochHeader, SharedEpochChanges,
+};
+use sc_consensus_poc::{find_pre_digest, Config, Epoch};
+use serde::{Deserialize, Serialize};
+use sp_api::{ApiExt, BlockId, ProvideRuntimeApi};
+use sp_blockchain::{Error as BlockChainError, HeaderBackend, HeaderMetadata};
+use sp_consensus_poc::{PoCApi as PoCRuntimeApi, Randomness, Slot};
+use sp_core::crypto::Public;
+use sp_runtime::traits::{Block as BlockT, Header as _, NumberFor};
+use std::convert::TryInto;
+use std::sync::Arc;
+
+/// Errors encountered by the PoC state RPC
+#[derive(Debug, derive_more::Display)]
+pub enum StateError {
+    /// Block is not known
+    #[display(fmt = "Unknown block {}", _0)]
+    UnknownBlock(String),
+    /// Runtime API call failed
+    #[display(fmt = "Runtime API error: {}", _0)]
+    RuntimeApi(String),
+    /// Epoch data can't be found
+    #[display(fmt = "Epoch data not found: {}", _0)]
+    EpochData(String),
+    /// Block doesn't have a PoC pre-digest (genesis block)
+    #[display(fmt = "Block {} has no PoC pre-digest", _0)]
+    NoPreDigest(String),
+    /// Runtime API version at the block doesn't support the call
+    #[display(fmt = "Unsupported runtime API version at block {}", _0)]
+    UnsupportedApiVersion(String),
+}
+
+impl From<StateError> for RpcError {
+    fn from(error: StateError) -> Self {
+        let code = match error {
+            StateError::UnknownBlock(_) => 5,
+            StateError::RuntimeApi(_) => 6,
+            StateError::EpochData(_) => 7,
+            StateError::NoPreDigest(_) => 8,
+            StateError::UnsupportedApiVersion(_) => 9,
+        };
+        RpcError {
+            message: error.to_string(),
+            code: jsonrpc_core::ErrorCode::ServerError(code),
+            data: None,
+        }
+    }
+}
+
+/// PoC epoch information
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcEpoch {
+    /// The epoch index
+    pub epoch_index: u64,
+    /// The starting slot of the epoch
+    pub start_slot: u64,
+    /// The duration of the epoch in slots
+    pub duration: u64,
+    /// Randomness for the epoch
+    pub randomness: Randomness,
+    /// Constant used in the threshold calculation formula as a rational
+    pub c: (u64, u64),
+}
+
+impl From<&Epoch> for RpcEpoch {
+    fn from(epoch: &Epoch) -> Self {
+        Self {
+            epoch_index: epoch.epoch_index,
+            start_slot: epoch.start_slot.into(),
+            duration: epoch.duration,
+            randomness: epoch.randomness,
+            c: epoch.config.c,
+        }
+    }
+}
+
+/// Epoch tracked by the node along with the block it was signaled in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcEpochChange<Hash, Number> {
+    /// Hash of the block the epoch was signaled in
+    pub block_hash: Hash,
+    /// Number of the block the epoch was signaled in
+    pub block_number: Number,
+    /// The epoch
+    pub epoch: RpcEpoch,
+}
+
+/// Farmer in block list
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcBlockListEntry {
+    /// Public key of the farmer
+    pub public_key: [u8; 32],
+    /// Slot the ban expires at
+    pub banned_until: u64,
+}
+
+/// Provides read-only rpc methods for inspecting PoC consensus state.
+///
+/// Methods that take a block hash default to the best block.
+#[rpc]
+pub trait PoCStateApi<Hash, Number> {
+    /// Epoch of the given block
+    #[rpc(name = "poc_currentEpoch")]
+    fn current_epoch(&self, at: Option<Hash>) -> RpcResult<RpcEpoch>;
+
+    /// Epoch following the epoch of the given block, it is announced at the start of the epoch
+    #[rpc(name = "poc_nextEpoch")]
+    fn next_epoch(&self, at: Option<Hash>) -> RpcResult<RpcEpoch>;
+
+    /// Solution range at the given block
+    #[rpc(name = "poc_solutionRange")]
+    fn solution_range(&self, at: Option<Hash>) -> RpcResult<u64>;
+
+    /// Salt at the given block
+    #[rpc(name = "poc_salt")]
+    fn salt(&self, at: Option<Hash>) -> RpcResult<[u8; 8]>;
+
+    /// Public key of the farmer that produced the given block
+    #[rpc(name = "poc_blockAuthor")]
+    fn block_author(&self, hash: Option<Hash>) -> RpcResult<[u8; 32]>;
+
+    /// Farmers in block list at the given block
+    #[rpc(name = "poc_blockList")]
+    fn block_list(&self, at: Option<Hash>) -> RpcResult<Vec<RpcBlockListEntry>>;
+
+    /// All epochs currently tracked by the node
+    #[rpc(name = "poc_epochChanges")]
+    fn epoch_changes(&self) -> RpcResult<Vec<RpcEpochChange<Hash, Number>>>;
+}
+
+/// Implements the PoCStateApi trait for inspecting PoC consensus state.
+pub struct PoCStateRpcHandler<B: BlockT, C> {
+    /// shared reference to the client
+    client: Arc<C>,
+    /// shared reference to EpochChanges
+    shared_epoch_changes: SharedEpochChanges<B, Epoch>,
+    /// config, used for genesis epoch
+    config: Config,
+}
+
+impl<B: BlockT, C> PoCStateRpcHandler<B, C> {
+    /// Creates a new instance of the PoCStateRpc handler.
+    pub fn new(
+        client: Arc<C>,
+        shared_epoch_changes: SharedEpochChanges<B, Epoch>,
+        config: Config,
+    ) -> Self {
+        Self {
+            client,
+            shared_epoch_changes,
+            config,
+        }
+    }
+}
+
+impl<B, C> PoCStateRpcHandler<B, C>
+where
+    B: BlockT,
+    C: ProvideRuntimeApi<B>
+        + HeaderBackend<B>
+        + HeaderMetadata<B, Error = BlockChainError>
+        + 'static,
+    C::Api: PoCRuntimeApi<B>,
+{
+    fn header(&self, hash: Option<B::Hash>) -> Result<B::Header, StateError> {
+        let hash = hash.unwrap_or_else(|| self.client.info().best_hash);
+        self.client
+            .header(BlockId::Hash(hash))
+            .map_err(|error| StateError::UnknownBlock(format!("{}: {}", hash, error)))?
+            .ok_or_else(|| StateError::UnknownBlock(hash.to_string()))
+    }
+
+    /// Epoch that a child of `parent` at `slot` would belong to.
+    fn epoch_for_child_of(&self, parent: &B::Header, slot: Slot) -> Result<Epoch, StateError> {
+        self.shared_epoch_changes
+            .shared_data()
+            .epoch_data_for_child_of(
+                descendent_query(&*self.client),
+                &parent.hash(),
+                *parent.number(),
+                slot,
+                |slot| Epoch::genesis(&self.config, slot),
+            )
+            .map_err(|error| StateError::EpochData(format!("{:?}", error)))?
+            .ok_or_else(|| StateError::EpochData(format!("slot {}", slot)))
+    }
+
+    fn current_epoch_at(&self, at: &B::Header) -> Result<Epoch, StateError> {
+        let epoch_start = self
+            .client
+            .runtime_api()
+            .current_epoch_start(&BlockId::Hash(at.hash()))
+            .map_err(|error| StateError::RuntimeApi(error.to_string()))?;
+        self.epoch_for_child_of(at, epoch_start)
+    }
+}
+
+impl<B, C> PoCStateApi<B::Hash, NumberFor<B>> for PoCStateRpcHandler<B, C>
+where
+    B: BlockT,
+    C: ProvideRuntimeApi<B>
+        + HeaderBackend<B>
+        + HeaderMetadata<B, Error = BlockChainError>
+        + Send
+        + Sync
+        + 'static,
+    C::Api: PoCRuntimeApi<B>,
+{
+    fn current_epoch(&self, at: Option<B::Hash>) -> RpcResult<RpcEpoch> {
+        let header = self.header(at)?;
+        let epoch = self.current_epoch_at(&header)?;
+        Ok(RpcEpoch::from(&epoch))
+    }
+
+    fn next_epoch(&self, at: Option<B::Hash>) -> RpcResult<RpcEpoch> {
+        let header = self.header(at)?;
+        let current_epoch = self.current_epoch_at(&header)?;
+        let epoch = self.epoch_for_child_of(&header, current_epoch.end_slot())?;
+        Ok(RpcEpoch::from(&epoch))
+    }
+
+    fn solution_range(&self, at: Option<B::Hash>) -> RpcResult<u64> {
+        let header = self.header(at)?;
+        self.client
+            .runtime_api()
+            .solution_range(&BlockId::Hash(header.hash()))
+            .map_err(|error| StateError::RuntimeApi(error.to_string()).into())
+    }
+
+    fn salt(&self, at: Option<B::Hash>) -> RpcResult<[u8; 8]> {
+        let header = self.header(at)?;
+        self.client
+            .runtime_api()
+            .salt(&BlockId::Hash(header.hash()))
+            .map(|salt| salt.to_le_bytes())
+            .map_err(|error| StateError::RuntimeApi(error.to_string()).into())
+    }
+
+    fn block_author(&self, hash: Option<B::Hash>) -> RpcResult<[u8; 32]> {
+        let header = self.header(hash)?;
+        let pre_digest = find_pre_digest::<B>(&header)
+            .map_err(|_| StateError::NoPreDigest(header.hash().to_string()))?;
+        Ok(pre_digest
+            .solution
+            .public_key
+            .to_raw_vec()
+            .try_into()
+            .expect("Public key is always 32 bytes; qed"))
+    }
+
+    fn block_list(&self, at: Option<B::Hash>) -> RpcResult<Vec<RpcBlockListEntry>> {
+        let header = self.header(at)?;
+        let block_id = BlockId::Hash(header.hash());
+        let runtime_api = self.client.runtime_api();
+        // block list with ban expiry is only available since version 2 of the API
+        if !runtime_api
+            .has_api_with::<dyn PoCRuntimeApi<B>, _>(&block_id, |v| v >= 2)
+            .map_err(|error| StateError::RuntimeApi(error.to_string()))?
+        {
+            return Err(StateError::UnsupportedApiVersion(header.hash().to_string()).into());
+        }
+        let block_list = runtime_api
+            .block_list(&block_id)
+            .map_err(|error| StateError::RuntimeApi(error.to_string()))?;
+        Ok(block_list
+            .into_iter()
+            .map(|(farmer_id, banned_until)| RpcBlockListEntry {
+                public_key: farmer_id
+                    .to_raw_vec()
+                    .try_into()
+                    .expect("Public key is always 32 bytes; qed"),
+                banned_until: banned_until.into(),
+            })
+            .collect())
+    }
+
+    fn epoch_changes(&self) -> RpcResult<Vec<RpcEpochChange<B::Hash, NumberFor<B>>>> {
+        let epoch_changes = self.shared_epoch_changes.shared_data();
+        let mut result = Vec::new();
+        for (hash, number, header) in epoch_changes.tree().iter() {
+            let positions: &[EpochIdentifierPosition] = match header {
+                PersistedEpochHeader::Genesis(..) => &[
+                    EpochIdentifierPosition::Genesis0,
+                    EpochIdentifierPosition::Genesis1,
+                ],
+                PersistedEpochHeader::Regular(_) => &[EpochIdentifierPosition::Regular],
+            };
+            for position in positions {
+                let id = EpochIdentifier {
+                    position: *position,
+                    hash: *hash,
+                    number: *number,
+                };
+                if let Some(epoch) = epoch_changes.epoch(&id) {
+                    result.push(RpcEpochChange {
+                        block_hash: *hash,
+                        block_number: *number,
+                        epoch: RpcEpoch::from(epoch),
+                    });
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpc_core::IoHandler;
+    use sc_consensus_poc::block_import;
+    use substrate_test_runtime_client::{
+        runtime::Block, DefaultTestClientBuilderExt, TestClient, TestClientBuilder,
+        TestClientBuilderExt,
+    };
+
+    fn test_poc_state_rpc_handler() -> (Arc<TestClient>, Config, IoHandler) {
+        let client = Arc::new(TestClientBuilder::new().build());
+        let config = Config::get_or_compute(&*client).expect("config available");
+        let (_, link) = block_import(config.clone(), client.clone(), client.clone())
+            .expect("can initialize block-import");
+
+        let handler = PoCStateRpcHandler::<Block, _>::new(
+            client.clone(),
+            link.epoch_changes().clone(),
+            config.clone(),
+        );
+        let mut io = IoHandler::new();
+        io.extend_with(PoCStateApi::to_delegate(handler));
+
+        (client, config, io)
+    }
+
+    fn request(io: &IoHandler, method: &str, params: &str) -> serde_json::Value {
+        let request = format!(
+            r#"{{"jsonrpc":"2.0","method":"{}","params":{},"id":1}}"#,
+            method, params
+        );
+        let response = io.handle_request_sync(&request).unwrap();
+        serde_json::from_str(&response).unwrap()
+    }
+
+    fn error_code(response: &serde_json::Value) -> i64 {
+        response["error"]["code"].as_i64().unwrap()
+    }
+
+    #[test]
+    fn current_epoch_works() {
+        let (client, config, io) = test_poc_state_rpc_handler();
+        let epoch_start = client
+            .runtime_api()
+            .current_epoch_start(&BlockId::Number(0))
+            .unwrap();
+
+        let response = request(&io, "poc_currentEpoch", "[]");
+        let epoch: RpcEpoch = serde_json::from_value(response["result"].clone()).unwrap();
+
+        assert_eq!(epoch, RpcEpoch::from(&Epoch::genesis(&config, epoch_start)));
+    }
+
+    #[test]
+    fn solution_range_and_salt_work() {
+        let (client, _config, io) = test_poc_state_rpc_handler();
+        let at = BlockId::Number(0);
+
+        let response = request(&io, "poc_solutionRange", "[]");
+        assert_eq!(
+            response["result"],
+            client.runtime_api().solution_range(&at).unwrap()
+        );
+
+        let response = request(&io, "poc_salt", "[]");
+        let salt: [u8; 8] = serde_json::from_value(response["result"].clone()).unwrap();
+        assert_eq!(salt, client.runtime_api().salt(&at).unwrap().to_le_bytes());
+    }
+
+    #[test]
+    fn block_list_is_empty_at_genesis() {
+        let (_client, _config, io) = test_poc_state_rpc_handler();
+
+        let response = request(&io, "poc_blockList", "[]");
+        assert_eq!(response["result"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn genesis_block_has_no_author() {
+        let (_client, _config, io) = test_poc_state_rpc_handler();
+
+        let response = request(&io, "poc_blockAuthor", "[]");
+        assert_eq!(error_code(&response), 8);
+    }
+
+    #[test]
+    fn unknown_block_is_refused() {
+        let (_client, _config, io) = test_poc_state_rpc_handler();
+        let unknown_hash = format!("\"0x{}\"", "01".repeat(32));
+
+        let response = request(&io, "poc_currentEpoch", &format!("[{}]", unknown_hash));
+        assert_eq!(error_code(&response), 5);
+    }
+}
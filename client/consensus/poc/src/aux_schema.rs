@@ -22,20 +22,32 @@ use codec::{Decode, Encode};
 
 use sc_client_api::backend::AuxStore;
 use sp_blockchain::{Result as ClientResult, Error as ClientError};
-use sp_runtime::traits::Block as BlockT;
+use sp_runtime::traits::{AtLeast32BitUnsigned, Block as BlockT, One};
 use sp_consensus_poc::{PoCBlockWeight, PoCGenesisConfiguration};
 use sc_consensus_epochs::{EpochChangesFor, SharedEpochChanges};
-use crate::Epoch;
+use crate::{Epoch, SolutionRangeAndSalt};
 
 const POC_EPOCH_CHANGES_VERSION: &[u8] = b"poc_epoch_changes_version";
 const POC_EPOCH_CHANGES_KEY: &[u8] = b"poc_epoch_changes";
 const POC_EPOCH_CHANGES_CURRENT_VERSION: u32 = 1;
+const POC_SOLUTION_DISTANCES_PRUNED_KEY: &[u8] = b"poc_solution_distances_pruned";
+const POC_WARP_SYNC_BLOCK_NUMBERS_KEY: &[u8] = b"poc_warp_sync_block_numbers";
 
 /// The aux storage key used to store the block weight of the given block hash.
-fn block_weight_key<H: Encode>(block_hash: H) -> Vec<u8> {
+pub(crate) fn block_weight_key<H: Encode>(block_hash: H) -> Vec<u8> {
 	(b"block_weight", block_hash).encode()
 }
 
+/// The aux storage key used to store solution range and salt for children of the given block hash.
+pub(crate) fn block_solution_range_and_salt_key<H: Encode>(block_hash: H) -> Vec<u8> {
+	(b"block_solution_range_and_salt", block_hash).encode()
+}
+
+/// The aux storage key used to store solution distances of blocks with the given block number.
+pub(crate) fn block_solution_distances_key<N: Encode>(block_number: N) -> Vec<u8> {
+	(b"block_solution_distances", block_number).encode()
+}
+
 fn load_decode<B, T>(backend: &B, key: &[u8]) -> ClientResult<Option<T>>
 	where
 		B: AuxStore,
@@ -126,3 +138,140 @@ pub fn load_block_weight<H: Encode, B: AuxStore>(
 ) -> ClientResult<Option<PoCBlockWeight>> {
 	load_decode(backend, block_weight_key(block_hash).as_slice())
 }
+
+/// Write solution range and salt that children of a block must use to aux storage.
+pub(crate) fn write_solution_range_and_salt<H: Encode, F, R>(
+	block_hash: H,
+	solution_range_and_salt: SolutionRangeAndSalt,
+	write_aux: F,
+) -> R where
+	F: FnOnce(&[(Vec<u8>, &[u8])]) -> R,
+{
+	let key = block_solution_range_and_salt_key(block_hash);
+	solution_range_and_salt.using_encoded(|s|
+		write_aux(
+			&[(key, s)],
+		)
+	)
+}
+
+/// Load solution range and salt that children of a block must use.
+pub(crate) fn load_solution_range_and_salt<H: Encode, B: AuxStore>(
+	backend: &B,
+	block_hash: H,
+) -> ClientResult<Option<SolutionRangeAndSalt>> {
+	load_decode(backend, block_solution_range_and_salt_key(block_hash).as_slice())
+}
+
+/// Write the distance of the block's solution from the local challenge to aux storage.
+///
+/// Distances are stored per block number, since they are only compared between blocks with the
+/// same number, which also allows to prune them once the number is finalized.
+pub(crate) fn write_block_solution_distance<N, H, B, F, R>(
+	backend: &B,
+	block_number: N,
+	block_hash: H,
+	solution_distance: u64,
+	write_aux: F,
+) -> ClientResult<R> where
+	N: Encode,
+	H: Encode + Decode + PartialEq,
+	B: AuxStore,
+	F: FnOnce(&[(Vec<u8>, &[u8])]) -> R,
+{
+	let key = block_solution_distances_key(block_number);
+	let mut solution_distances: Vec<(H, u64)> = load_decode(backend, &key)?.unwrap_or_default();
+	solution_distances.retain(|(hash, _)| hash != &block_hash);
+	solution_distances.push((block_hash, solution_distance));
+
+	Ok(solution_distances.using_encoded(|s|
+		write_aux(
+			&[(key, s)],
+		)
+	))
+}
+
+/// Load distances of solutions of all blocks with the given number.
+pub fn load_block_solution_distances<N: Encode, H: Decode, B: AuxStore>(
+	backend: &B,
+	block_number: N,
+) -> ClientResult<Vec<(H, u64)>> {
+	load_decode(backend, block_solution_distances_key(block_number).as_slice())
+		.map(Option::unwrap_or_default)
+}
+
+/// Load the distance of the block's solution from the local challenge.
+pub fn load_block_solution_distance<N: Encode, H: Decode + PartialEq, B: AuxStore>(
+	backend: &B,
+	block_number: N,
+	block_hash: H,
+) -> ClientResult<Option<u64>> {
+	Ok(load_block_solution_distances::<_, H, _>(backend, block_number)?
+		.into_iter()
+		.find_map(|(hash, solution_distance)| (hash == block_hash).then(|| solution_distance)))
+}
+
+/// Changes to aux storage that remove solution distances of blocks with numbers up to
+/// `finalized_number`, there is nothing to choose between at or below the finalized block.
+///
+/// Pruning progress is stored as well, such that every number is only pruned once.
+pub(crate) fn prune_block_solution_distances<N, B>(
+	backend: &B,
+	finalized_number: N,
+) -> ClientResult<Vec<(Vec<u8>, Option<Vec<u8>>)>> where
+	N: AtLeast32BitUnsigned + Encode + Decode,
+	B: AuxStore,
+{
+	let pruned_up_to = load_decode::<_, N>(backend, POC_SOLUTION_DISTANCES_PRUNED_KEY)?;
+
+	let mut changes = Vec::new();
+	match pruned_up_to {
+		Some(pruned_up_to) if pruned_up_to >= finalized_number => {
+			return Ok(changes);
+		}
+		Some(mut number) => {
+			while number < finalized_number {
+				number = number + One::one();
+				changes.push((block_solution_distances_key(number), None));
+			}
+		}
+		// Distances are only stored after pruning has started, so there is nothing to prune yet
+		None => {}
+	}
+	changes.push((POC_SOLUTION_DISTANCES_PRUNED_KEY.to_vec(), Some(finalized_number.encode())));
+
+	Ok(changes)
+}
+
+/// Add the number of a block that warp sync proofs consist of (see
+/// [`is_warp_sync_block`](crate::warp_sync::is_warp_sync_block)) to the index of such blocks.
+///
+/// Numbers are indexed for blocks on all forks, so the canonical block with the number has to be
+/// checked again when the index is used.
+pub(crate) fn write_warp_sync_block_number<N, B, F, R>(
+	backend: &B,
+	block_number: N,
+	write_aux: F,
+) -> ClientResult<R> where
+	N: Encode + Decode + Ord,
+	B: AuxStore,
+	F: FnOnce(&[(&'static [u8], &[u8])]) -> R,
+{
+	let mut block_numbers: Vec<N> = load_warp_sync_block_numbers(backend)?;
+	if let Err(position) = block_numbers.binary_search(&block_number) {
+		block_numbers.insert(position, block_number);
+	}
+
+	Ok(block_numbers.using_encoded(|s|
+		write_aux(
+			&[(POC_WARP_SYNC_BLOCK_NUMBERS_KEY, s)],
+		)
+	))
+}
+
+/// Load numbers of blocks that warp sync proofs consist of in ascending order.
+pub(crate) fn load_warp_sync_block_numbers<N: Decode, B: AuxStore>(
+	backend: &B,
+) -> ClientResult<Vec<N>> {
+	load_decode(backend, POC_WARP_SYNC_BLOCK_NUMBERS_KEY).map(Option::unwrap_or_default)
+}
@@ -47,16 +47,24 @@
 //! blocks) and will go with the longest one in case of a tie.
 #![forbid(unsafe_code)]
 #![warn(missing_docs)]
-use futures::channel::mpsc::{channel, Receiver, Sender};
+use futures::channel::mpsc::{
+    channel, unbounded, Receiver, Sender, UnboundedReceiver, UnboundedSender,
+};
 use futures::channel::oneshot;
 use parking_lot::Mutex;
-use sc_client_api::{backend::AuxStore, BlockchainEvents, ProvideUncles, UsageProvider};
+use sc_client_api::{
+    backend::AuxStore,
+    light::{Fetcher, RemoteBodyRequest},
+    BlockchainEvents, ProvideUncles, UsageProvider,
+};
 use sc_consensus::{
     block_import::{
         BlockCheckParams, BlockImport, BlockImportParams, ForkChoiceStrategy, ImportResult,
         StateAction,
     },
-    import_queue::{BasicQueue, BoxJustificationImport, DefaultImportQueue, Verifier},
+    import_queue::{
+        BasicQueue, BoxJustificationImport, DefaultImportQueue, IncomingBlock, Verifier,
+    },
 };
 pub use sc_consensus_slots::SlotProportion;
 use sc_telemetry::{telemetry, TelemetryHandle, CONSENSUS_DEBUG, CONSENSUS_TRACE};
@@ -67,22 +75,29 @@ use sp_consensus::{
     BlockOrigin, CacheKeyId, CanAuthorWith, Environment, Error as ConsensusError, Proposer,
     SelectChain, SlotData,
 };
-use sp_consensus_poc::inherents::PoCInherentData;
+use sp_consensus_poc::inherents::{PoCInherentData, SOLUTION_DATA_INHERENT_IDENTIFIER};
 pub use sp_consensus_poc::{
     digests::{CompatibleDigestItem, NextConfigDescriptor, NextEpochDescriptor, PreDigest},
-    ConsensusLog, FarmerId, PoCApi, PoCEpochConfiguration, PoCGenesisConfiguration, POC_ENGINE_ID,
+    ConsensusLog, FarmerId, FarmerSignature, PoCApi, PoCEpochConfiguration,
+    PoCGenesisConfiguration, POC_ENGINE_ID,
 };
 use sp_inherents::{CreateInherentDataProviders, InherentData, InherentDataProvider};
 use sp_runtime::{
     generic::{BlockId, OpaqueDigestItemId},
-    traits::{Block as BlockT, DigestItemFor, Header, Zero},
-    Justifications,
+    traits::{Block as BlockT, DigestItemFor, Extrinsic as ExtrinsicT, Header, Zero},
+    Justifications, RuntimeAppPublic,
 };
 use std::{
-    borrow::Cow, collections::HashMap, convert::TryInto, pin::Pin, sync::Arc, time::Duration, u64,
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+    u64,
 };
 
-use codec::{Decode, Encode};
+use codec::{Decode, DecodeAll, Encode};
 use futures::prelude::*;
 use log::{debug, info, log, trace, warn};
 use prometheus_endpoint::Registry;
@@ -91,31 +106,41 @@ use sc_consensus_epochs::{
     descendent_query, Epoch as EpochT, EpochChangesFor, SharedEpochChanges, ViableEpochDescriptor,
 };
 use sc_consensus_slots::{
-    check_equivocation, BackoffAuthoringBlocksStrategy, CheckedHeader, InherentDataProviderExt,
-    SimpleSlotWorker, SlotInfo, StorageChanges,
+    check_equivocation, has_header_at_slot, BackoffAuthoringBlocksStrategy, CheckedHeader,
+    InherentDataProviderExt, SimpleSlotWorker, SlotInfo, StorageChanges,
 };
 use schnorrkel::context::SigningContext;
-use schnorrkel::SecretKey;
 use sp_api::ApiExt;
 use sp_blockchain::{
     Error as ClientError, HeaderBackend, HeaderMetadata, ProvideCache, Result as ClientResult,
 };
 use sp_consensus_poc::digests::{
-    NextSaltDescriptor, NextSolutionRangeDescriptor, SaltDescriptor, Solution,
-    SolutionRangeDescriptor,
+    NextDurationsDescriptor, NextSaltDescriptor, NextSolutionRangeDescriptor, SaltDescriptor,
+    Solution, SolutionData, SolutionRangeDescriptor,
+};
+use sp_consensus_poc::{
+    Randomness, RootBlock, GENESIS_PIECE_SOLUTIONS_CUTOVER, PIECES_PER_SEGMENT,
 };
-use sp_consensus_poc::Randomness;
 use sp_consensus_slots::Slot;
-use sp_consensus_spartan::spartan::{Salt, Spartan, SIGNING_CONTEXT};
-use sp_core::sr25519::Pair;
-use sp_core::Pair as PairTrait;
-use std::sync::mpsc;
+use sp_consensus_spartan::spartan::{Salt, Spartan};
+use sp_core::Public;
 
+mod metrics;
+mod pending_offenders;
 mod verification;
 
+use metrics::{
+    solution_rejection_reason, AuthoringMetrics, ImportMetrics, MetricsLink, SlotSolutionsReport,
+};
+pub use pending_offenders::{PendingOffenders, PENDING_OFFENDERS_RETENTION};
+pub use verification::{
+    derive_local_challenge, hash_public_key, is_solution_signature_valid, ContextFreeVerifier,
+};
+
 pub mod aux_schema;
 #[cfg(test)]
 mod tests;
+pub mod warp_sync;
 
 /// Information about new slot that just arrived
 #[derive(Debug, Clone)]
@@ -134,8 +159,51 @@ pub struct NewSlotInfo {
 
 /// A function that can be called whenever it is necessary to create a subscription for new slots
 pub type NewSlotNotifier = Arc<
+    Box<dyn (Fn() -> UnboundedReceiver<(NewSlotInfo, UnboundedSender<Solution>)>) + Send + Sync>,
+>;
+
+/// Information about a new block that needs to be signed by the farmer that claimed the slot
+#[derive(Debug, Clone)]
+pub struct BlockSigningInfo {
+    /// Pre-hash of the block (header hash without the seal)
+    pub header_hash: Vec<u8>,
+    /// Public key of the farmer whose solution was used to claim the slot
+    pub public_key: FarmerId,
+    /// End of the slot, signatures that arrive later are ignored
+    pub deadline: Instant,
+}
+
+/// A function that fetches the body of the block with the given header from full nodes, which light
+/// clients use to verify blocks with compact solutions, see [`import_queue`]
+pub type BlockBodyFetcher<Block> = Arc<
+    dyn Fn(
+            <Block as BlockT>::Header,
+        ) -> future::BoxFuture<'static, ClientResult<Vec<<Block as BlockT>::Extrinsic>>>
+        + Send
+        + Sync,
+>;
+
+/// Create [`BlockBodyFetcher`] for light clients that fetches block bodies with `fetcher`.
+pub fn block_body_fetcher<Block, F>(fetcher: Arc<F>) -> BlockBodyFetcher<Block>
+where
+    Block: BlockT,
+    F: Fetcher<Block> + 'static,
+{
+    Arc::new(move |header| {
+        fetcher
+            .remote_body(RemoteBodyRequest {
+                header,
+                retry_count: None,
+            })
+            .boxed()
+    })
+}
+
+/// A function that can be called whenever it is necessary to create a subscription for block
+/// signing requests
+pub type BlockSigningNotifier = Arc<
     Box<
-        dyn (Fn() -> mpsc::Receiver<(NewSlotInfo, mpsc::Sender<(Solution, Vec<u8>)>)>)
+        dyn (Fn() -> UnboundedReceiver<(BlockSigningInfo, UnboundedSender<FarmerSignature>)>)
             + Send
             + Sync,
     >,
@@ -157,17 +225,18 @@ pub struct Epoch {
 }
 
 impl EpochT for Epoch {
-    type NextEpochDescriptor = (NextEpochDescriptor, PoCEpochConfiguration);
+    /// Next epoch descriptor along with its configuration and duration.
+    type NextEpochDescriptor = (NextEpochDescriptor, PoCEpochConfiguration, u64);
     type Slot = Slot;
 
     fn increment(
         &self,
-        (descriptor, config): (NextEpochDescriptor, PoCEpochConfiguration),
+        (descriptor, config, duration): (NextEpochDescriptor, PoCEpochConfiguration, u64),
     ) -> Epoch {
         Epoch {
             epoch_index: self.epoch_index + 1,
             start_slot: self.start_slot + self.duration,
-            duration: self.duration,
+            duration,
             randomness: descriptor.randomness,
             config,
         }
@@ -213,6 +282,9 @@ pub enum Error<B: BlockT> {
     /// Multiple PoC config change digests
     #[display(fmt = "Multiple PoC config change digests, rejecting!")]
     MultipleConfigChangeDigests,
+    /// Multiple PoC durations change digests
+    #[display(fmt = "Multiple PoC durations change digests, rejecting!")]
+    MultipleDurationsChangeDigests,
     /// Multiple PoC solution range digests
     #[display(fmt = "Multiple PoC solution range digests, rejecting!")]
     MultipleSolutionRangeDigests,
@@ -225,6 +297,12 @@ pub enum Error<B: BlockT> {
     /// Multiple PoC next salt digests
     #[display(fmt = "Multiple PoC next salt digests, rejecting!")]
     MultipleNextSaltDigests,
+    /// Multiple PoC eon salt digests
+    #[display(fmt = "Multiple PoC eon salt digests, rejecting!")]
+    MultipleEonSaltDigests,
+    /// Multiple PoC root blocks digests
+    #[display(fmt = "Multiple PoC root blocks digests, rejecting!")]
+    MultipleRootBlocksDigests,
     /// Could not extract timestamp and slot
     #[display(fmt = "Could not extract timestamp and slot: {:?}", _0)]
     Extraction(sp_consensus::Error),
@@ -268,30 +346,65 @@ pub enum Error<B: BlockT> {
     /// Invalid commitment for salt
     #[display(fmt = "Invalid commitment for salt for slot {}", _0)]
     InvalidCommitment(Slot),
+    /// Piece in piece inclusion proof is of wrong size
+    #[display(fmt = "Piece is of the wrong size")]
+    PieceOfWrongSize,
+    /// Records root of the segment the piece of the solution belongs to is not stored on chain
+    #[display(fmt = "Missing records root of segment {} for slot {}", _1, _0)]
+    MissingRecordsRoot(Slot, u64),
+    /// Invalid piece inclusion proof
+    #[display(fmt = "Invalid piece inclusion proof for slot {}", _0)]
+    InvalidPieceInclusionProof(Slot),
+    /// Solution with an encoding of the genesis piece after enough segments were archived
+    #[display(fmt = "Solutions with encodings of the genesis piece are no longer accepted")]
+    GenesisPieceSolutionAfterCutover,
     /// Could not fetch parent header
     #[display(fmt = "Could not fetch parent header: {:?}", _0)]
     FetchParentHeader(sp_blockchain::Error),
+    /// Could not fetch block body
+    #[display(fmt = "Could not fetch block body: {:?}", _0)]
+    FetchBody(sp_blockchain::Error),
     /// Expected epoch change to happen.
     #[display(fmt = "Expected epoch change to happen at {:?}, s{}", _0, _1)]
     ExpectedEpochChange(B::Hash, Slot),
     /// Unexpected config change.
     #[display(fmt = "Unexpected config change")]
     UnexpectedConfigChange,
+    /// Unexpected epoch duration change.
+    #[display(fmt = "Unexpected epoch duration change")]
+    UnexpectedEpochDurationChange,
     /// Unexpected epoch change
     #[display(fmt = "Unexpected epoch change")]
     UnexpectedEpochChange,
     /// Parent block has no associated weight
     #[display(fmt = "Parent block of {} has no associated weight", _0)]
     ParentBlockNoAssociatedWeight(B::Hash),
-    /// Block has no associated solution range
-    #[display(fmt = "Missing solution range for block {}", _0)]
-    MissingSolutionRange(B::Hash),
-    /// Block has no associated salt
-    #[display(fmt = "Missing salt for block {}", _0)]
-    MissingSalt(B::Hash),
+    /// Block with compact solution has no solution data in its body
+    #[display(fmt = "Missing solution data in the body of block {}", _0)]
+    MissingSolutionData(B::Hash),
+    /// Solution data in the block body doesn't match the commitment in its header
+    #[display(fmt = "Invalid solution data in the body of block {}", _0)]
+    InvalidSolutionData(B::Hash),
+    /// Solution range declared in block header doesn't match the one derived from the parent
+    #[display(
+        fmt = "Invalid solution range for block {}: expected {}, got {}",
+        _0,
+        _1,
+        _2
+    )]
+    InvalidSolutionRange(B::Hash, u64, u64),
+    /// Salt declared in block header doesn't match the one derived from the parent
+    #[display(fmt = "Invalid salt for block {}: expected {}, got {}", _0, _1, _2)]
+    InvalidSalt(B::Hash, u64, u64),
     /// Farmer in block list
     #[display(fmt = "Farmer {} is in block list", _0)]
     FarmerInBlockList(FarmerId),
+    /// Warp sync request can't be served
+    #[display(fmt = "Invalid warp sync request: {}", _0)]
+    InvalidWarpSyncRequest(String),
+    /// Warp sync proof is invalid
+    #[display(fmt = "Invalid warp sync proof: {}", _0)]
+    InvalidWarpSyncProof(String),
     /// Check inherents error
     #[display(fmt = "Checking inherents failed: {}", _0)]
     CheckInherents(sp_inherents::Error),
@@ -337,7 +450,10 @@ pub static INTERMEDIATE_KEY: &[u8] = b"poc0";
 // and `super::poc::Config` can be eliminated.
 // https://github.com/paritytech/substrate/issues/2434
 #[derive(Clone)]
-pub struct Config(sc_consensus_slots::SlotDuration<PoCGenesisConfiguration>);
+pub struct Config {
+    slot_duration: sc_consensus_slots::SlotDuration<PoCGenesisConfiguration>,
+    fork_choice_tie_breaking: bool,
+}
 
 impl Config {
     /// Either fetch the slot duration from disk or compute it from the genesis
@@ -350,8 +466,14 @@ impl Config {
         trace!(target: "poc", "Getting slot duration");
         match sc_consensus_slots::SlotDuration::get_or_compute(client, |a, b| {
             let has_api_v1 = a.has_api_with::<dyn PoCApi<B>, _>(&b, |v| v == 1)?;
+            let has_api_v2 = a.has_api_with::<dyn PoCApi<B>, _>(&b, |v| v == 2)?;
 
             if has_api_v1 {
+                #[allow(deprecated)]
+                {
+                    Ok(a.configuration_before_version_2(b)?.into())
+                }
+            } else if has_api_v2 {
                 a.configuration(b).map_err(Into::into)
             } else {
                 Err(sp_blockchain::Error::VersionInvalid(
@@ -359,8 +481,10 @@ impl Config {
                 ))
             }
         })
-        .map(Self)
-        {
+        .map(|slot_duration| Self {
+            slot_duration,
+            fork_choice_tie_breaking: false,
+        }) {
             Ok(s) => Ok(s),
             Err(s) => {
                 warn!(target: "poc", "Failed to get slot duration");
@@ -371,7 +495,22 @@ impl Config {
 
     /// Get the inner slot duration
     pub fn slot_duration(&self) -> Duration {
-        self.0.slot_duration()
+        self.slot_duration.slot_duration()
+    }
+
+    /// Break ties between blocks of equal weight and height by the distance of their solutions
+    /// from the local challenge (and then by block hash), so that all nodes converge on the same
+    /// fork after slot collisions. Disabled by default, in which case the first imported block
+    /// wins.
+    pub fn with_fork_choice_tie_breaking(mut self, enabled: bool) -> Self {
+        self.fork_choice_tie_breaking = enabled;
+        self
+    }
+
+    /// Whether ties in fork choice are broken by solution quality, see
+    /// [`Config::with_fork_choice_tie_breaking`].
+    pub fn fork_choice_tie_breaking(&self) -> bool {
+        self.fork_choice_tie_breaking
     }
 }
 
@@ -379,7 +518,7 @@ impl std::ops::Deref for Config {
     type Target = PoCGenesisConfiguration;
 
     fn deref(&self) -> &PoCGenesisConfiguration {
-        &*self.0
+        &*self.slot_duration
     }
 }
 
@@ -431,6 +570,9 @@ pub struct PoCParams<B: BlockT, C, SC, E, I, SO, L, CIDP, BS, CAW> {
     /// due to no blocks being produced.
     pub max_block_proposal_slot_portion: Option<SlotProportion>,
 
+    /// Registry for Prometheus metrics of the authoring worker.
+    pub registry: Option<Registry>,
+
     /// Handle use to report telemetries.
     pub telemetry: Option<TelemetryHandle>,
 }
@@ -451,6 +593,7 @@ pub fn start_poc<B, C, SC, E, I, SO, CIDP, BS, CAW, L, Error>(
         can_author_with,
         block_proposal_slot_portion,
         max_block_proposal_slot_portion,
+        registry,
         telemetry,
     }: PoCParams<B, C, SC, E, I, SO, L, CIDP, BS, CAW>,
 ) -> Result<PoCWorker<B>, sp_consensus::Error>
@@ -462,6 +605,7 @@ where
         + BlockchainEvents<B>
         + HeaderBackend<B>
         + HeaderMetadata<B, Error = ClientError>
+        + AuxStore
         + Send
         + Sync
         + 'static,
@@ -486,7 +630,10 @@ where
     let config = poc_link.config;
 
     let new_slot_senders: Arc<
-        Mutex<Vec<mpsc::Sender<(NewSlotInfo, mpsc::Sender<(Solution, Vec<u8>)>)>>>,
+        Mutex<Vec<UnboundedSender<(NewSlotInfo, UnboundedSender<Solution>)>>>,
+    > = Arc::default();
+    let block_signing_senders: Arc<
+        Mutex<Vec<UnboundedSender<(BlockSigningInfo, UnboundedSender<FarmerSignature>)>>>,
     > = Arc::default();
 
     let worker = PoCSlotWorker {
@@ -498,6 +645,7 @@ where
         force_authoring,
         backoff_authoring_blocks,
         epoch_changes: poc_link.epoch_changes.clone(),
+        pending_offenders: poc_link.pending_offenders.clone(),
         config: config.clone(),
         on_claim_slot: Box::new({
             let new_slot_senders = Arc::clone(&new_slot_senders);
@@ -505,15 +653,14 @@ where
             move |slot,
                   epoch,
                   salt,
+                  next_salt,
                   solution_range,
-                  solution_sender: mpsc::Sender<(Solution, Vec<u8>)>| {
+                  solution_sender: UnboundedSender<Solution>| {
                 let slot_info = NewSlotInfo {
                     slot,
                     challenge: create_global_challenge(epoch, slot),
                     salt,
-                    // TODO: This will not be the correct way in the future once salt is no longer
-                    //  just an incremented number
-                    next_salt: Some((u64::from_le_bytes(salt) + 1).to_le_bytes()),
+                    next_salt,
                     solution_range,
                 };
                 {
@@ -524,7 +671,7 @@ where
                         if new_slot_senders
                             .get_mut(i)
                             .unwrap()
-                            .send((slot_info.clone(), solution_sender.clone()))
+                            .unbounded_send((slot_info.clone(), solution_sender.clone()))
                             .is_err()
                         {
                             new_slot_senders.remove(i);
@@ -535,17 +682,44 @@ where
                 }
             }
         }),
-        spartan: Spartan::new(),
+        on_block_signing: Box::new({
+            let block_signing_senders = Arc::clone(&block_signing_senders);
+
+            move |block_signing_info, signature_sender: UnboundedSender<FarmerSignature>| {
+                // drain_filter() would be more convenient here
+                let mut block_signing_senders = block_signing_senders.lock();
+                let mut i = 0;
+                while i != block_signing_senders.len() {
+                    if block_signing_senders
+                        .get_mut(i)
+                        .unwrap()
+                        .unbounded_send((block_signing_info.clone(), signature_sender.clone()))
+                        .is_err()
+                    {
+                        block_signing_senders.remove(i);
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+        }),
+        spartan: Spartan::new(
+            &config.spartan_parameters.genesis_piece_seed,
+            config.spartan_parameters.encode_rounds as usize,
+        ),
         // TODO: Figure out how to remove explicit schnorrkel dependency
-        signing_context: schnorrkel::context::signing_context(SIGNING_CONTEXT),
+        signing_context: schnorrkel::context::signing_context(
+            &config.spartan_parameters.signing_context,
+        ),
         block_proposal_slot_portion,
         max_block_proposal_slot_portion,
+        metrics: MetricsLink::new(registry.as_ref()),
         telemetry,
     };
 
     info!(target: "poc", "🧑‍🌾 Starting PoC Authorship worker");
     let inner = sc_consensus_slots::start_slot_worker(
-        config.0.clone(),
+        config.slot_duration.clone(),
         select_chain,
         worker,
         sync_oracle,
@@ -555,12 +729,17 @@ where
 
     let (worker_tx, worker_rx) = channel(HANDLE_BUFFER_SIZE);
 
-    let answer_requests =
-        answer_requests(worker_rx, config.0, client, poc_link.epoch_changes.clone());
+    let answer_requests = answer_requests(
+        worker_rx,
+        config.slot_duration,
+        client,
+        poc_link.epoch_changes.clone(),
+    );
     Ok(PoCWorker {
         inner: Box::pin(future::join(inner, answer_requests).map(|_| ())),
         handle: PoCWorkerHandle(worker_tx),
         new_slot_senders,
+        block_signing_senders,
     })
 }
 
@@ -648,8 +827,9 @@ impl<B: BlockT> PoCWorkerHandle<B> {
 pub struct PoCWorker<B: BlockT> {
     inner: Pin<Box<dyn futures::Future<Output = ()> + Send + 'static>>,
     handle: PoCWorkerHandle<B>,
-    new_slot_senders:
-        Arc<Mutex<Vec<mpsc::Sender<(NewSlotInfo, mpsc::Sender<(Solution, Vec<u8>)>)>>>>,
+    new_slot_senders: Arc<Mutex<Vec<UnboundedSender<(NewSlotInfo, UnboundedSender<Solution>)>>>>,
+    block_signing_senders:
+        Arc<Mutex<Vec<UnboundedSender<(BlockSigningInfo, UnboundedSender<FarmerSignature>)>>>>,
 }
 
 impl<B: BlockT> PoCWorker<B> {
@@ -658,12 +838,23 @@ impl<B: BlockT> PoCWorker<B> {
     pub fn get_new_slot_notifier(&self) -> NewSlotNotifier {
         let new_slot_senders = Arc::clone(&self.new_slot_senders);
         Arc::new(Box::new(move || {
-            let (new_slot_sender, new_slot_receiver) = mpsc::channel();
+            let (new_slot_sender, new_slot_receiver) = unbounded();
             new_slot_senders.lock().push(new_slot_sender);
             new_slot_receiver
         }))
     }
 
+    /// Returns a function that can be called whenever it is necessary to create a subscription for
+    /// block signing requests
+    pub fn get_block_signing_notifier(&self) -> BlockSigningNotifier {
+        let block_signing_senders = Arc::clone(&self.block_signing_senders);
+        Arc::new(Box::new(move || {
+            let (block_signing_sender, block_signing_receiver) = unbounded();
+            block_signing_senders.lock().push(block_signing_sender);
+            block_signing_receiver
+        }))
+    }
+
     /// Get a handle to the worker.
     pub fn handle(&self) -> PoCWorkerHandle<B> {
         self.handle.clone()
@@ -690,24 +881,36 @@ struct PoCSlotWorker<B: BlockT, C, E, I, SO, L, BS> {
     force_authoring: bool,
     backoff_authoring_blocks: Option<BS>,
     epoch_changes: SharedEpochChanges<B, Epoch>,
+    pending_offenders: PendingOffenders,
     config: Config,
     on_claim_slot: Box<
-        dyn Fn(Slot, &Epoch, Salt, u64, mpsc::Sender<(Solution, Vec<u8>)>) + Send + Sync + 'static,
+        dyn Fn(Slot, &Epoch, Salt, Option<Salt>, u64, UnboundedSender<Solution>)
+            + Send
+            + Sync
+            + 'static,
     >,
+    on_block_signing:
+        Box<dyn Fn(BlockSigningInfo, UnboundedSender<FarmerSignature>) + Send + Sync + 'static>,
     spartan: Spartan,
     signing_context: SigningContext,
     block_proposal_slot_portion: SlotProportion,
     max_block_proposal_slot_portion: Option<SlotProportion>,
+    metrics: MetricsLink<AuthoringMetrics>,
     telemetry: Option<TelemetryHandle>,
 }
 
+#[async_trait::async_trait]
 impl<B, C, E, I, Error, SO, L, BS> SimpleSlotWorker<B> for PoCSlotWorker<B, C, E, I, SO, L, BS>
 where
     B: BlockT,
     C: ProvideRuntimeApi<B>
         + ProvideCache<B>
         + HeaderBackend<B>
-        + HeaderMetadata<B, Error = ClientError>,
+        + HeaderMetadata<B, Error = ClientError>
+        + AuxStore
+        + Send
+        + Sync
+        + 'static,
     C::Api: PoCApi<B>,
     E: Environment<B, Error = Error>,
     E::Proposer: Proposer<B, Error = Error, Transaction = sp_api::TransactionFor<C, B>>,
@@ -718,7 +921,7 @@ where
     Error: std::error::Error + Send + From<ConsensusError> + From<I::Error> + 'static,
 {
     type EpochData = ViableEpochDescriptor<B::Hash, NumberFor<B>, Epoch>;
-    type Claim = (PreDigest, Pair);
+    type Claim = PreDigest;
     type SyncOracle = SO;
     type JustificationSyncLink = L;
     type CreateProposer =
@@ -753,57 +956,88 @@ where
 
     fn claim_slot(
         &self,
+        _parent_header: &B::Header,
+        _slot: Slot,
+        _epoch_descriptor: &ViableEpochDescriptor<B::Hash, NumberFor<B>, Epoch>,
+    ) -> Option<Self::Claim> {
+        // slots are claimed with solutions from farmers that arrive asynchronously, see
+        // `claim_slot_async`
+        None
+    }
+
+    async fn claim_slot_async(
+        &mut self,
         parent_header: &B::Header,
         slot: Slot,
         epoch_descriptor: &ViableEpochDescriptor<B::Hash, NumberFor<B>, Epoch>,
-    ) -> Option<Self::Claim> {
+    ) -> Option<Self::Claim>
+    where
+        Self: Send,
+    {
         debug!(target: "poc", "Attempting to claim slot {}", slot);
 
-        let epoch_changes = self.epoch_changes.shared_data();
-        let epoch = epoch_changes
-            .viable_epoch(&epoch_descriptor, |slot| Epoch::genesis(&self.config, slot))?;
+        // epoch changes must not be locked while waiting for solutions
+        let epoch = self
+            .epoch_changes
+            .shared_data()
+            .viable_epoch(&epoch_descriptor, |slot| Epoch::genesis(&self.config, slot))?
+            .as_ref()
+            .clone();
         let block_id = BlockId::Hash(parent_header.hash());
-        // Here we always use parent block as the source of information, thus on the edge of the era
-        // the very first block of the era still uses solution range from the previous one, but the
-        // block after it uses "next" solution range deposited in the first block.
-        let solution_range = find_next_solution_range_digest::<B>(&parent_header)
-            .ok()?
-            .map(|d| d.solution_range)
-            .or_else(|| {
-                // We use runtime API as it will fallback to default value for genesis when there is
-                // no solution range stored yet
-                self.client.runtime_api().solution_range(&block_id).ok()
-            })?;
-        // Here we always use parent block as the source of information, thus on the edge of the eon
-        // the very first block of the eon still uses salt from the previous one, but the
-        // block after it uses "next" salt deposited in the first block.
-        let salt = find_next_salt_digest::<B>(&parent_header)
+        let SolutionRangeAndSalt {
+            solution_range,
+            salt,
+        } = extract_solution_range_and_salt_for_child(&*self.client, parent_header).ok()?;
+        // Salt for the next eon is deposited in every block once revealed by the runtime
+        let next_salt = find_next_salt_digest::<B>(parent_header)
             .ok()?
-            .map(|d| d.salt)
-            .or_else(|| {
-                // We use runtime API as it will fallback to default value for genesis when there is
-                // no salt stored yet
-                self.client.runtime_api().salt(&block_id).ok()
-            })?;
+            .map(|next_salt| next_salt.salt.to_le_bytes());
 
-        let (solution_sender, solution_receiver) = mpsc::channel();
+        let (solution_sender, mut solution_receiver) = unbounded();
+        let mut slot_solutions_report = SlotSolutionsReport::new(self.metrics.clone());
 
         (self.on_claim_slot)(
             slot,
-            epoch.as_ref(),
+            &epoch,
             salt.to_le_bytes(),
+            next_salt,
             solution_range,
             solution_sender,
         );
 
-        while let Ok((solution, secret_key)) = solution_receiver.recv() {
-            // TODO: We need also need to check for equivocation of farmers connected to *this node*
-            //  during block import, currently farmers connected to this node are considered trusted
-            if self
-                .client
-                .runtime_api()
-                .is_in_block_list(&block_id, &solution.public_key)
+        // `on_slot` stops waiting once there is not enough time left to build a block
+        while let Some(solution) = solution_receiver.next().await {
+            slot_solutions_report.received();
+
+            // Farmers connected to this node may equivocate too, if the farmer already has a
+            // header at this slot (authored locally or imported), authoring another block would
+            // be an equivocation, so we refuse to do that.
+            if has_header_at_slot::<_, B::Header, _>(&*self.client, slot, &solution.public_key)
                 .ok()?
+            {
+                warn!(
+                    target: "poc",
+                    "Ignoring solution for slot {} provided by farmer that already has a block at \
+                    this slot: {}",
+                    slot,
+                    solution.public_key,
+                );
+                self.metrics.report(|metrics| {
+                    metrics
+                        .solutions_rejected
+                        .with_label_values(&["FarmerHasBlockAtSlot"])
+                        .inc()
+                });
+
+                continue;
+            }
+
+            if self.pending_offenders.contains(&solution.public_key, slot)
+                || self
+                    .client
+                    .runtime_api()
+                    .is_in_block_list(&block_id, &solution.public_key)
+                    .ok()?
             {
                 warn!(
                     target: "poc",
@@ -811,28 +1045,64 @@ where
                     slot,
                     solution.public_key,
                 );
+                self.metrics.report(|metrics| {
+                    metrics
+                        .solutions_rejected
+                        .with_label_values(&["FarmerInBlockList"])
+                        .inc()
+                });
 
                 continue;
             }
 
-            let secret_key = SecretKey::from_bytes(&secret_key).ok()?;
+            let records_root =
+                match extract_records_root_for_solution(&*self.client, &block_id, &solution) {
+                    Ok(records_root) => records_root,
+                    Err(error) => {
+                        warn!(target: "poc", "Ignoring solution for slot {}: {}", slot, error);
+                        self.metrics.report(|metrics| {
+                            metrics
+                                .solutions_rejected
+                                .with_label_values(&[solution_rejection_reason(&error)])
+                                .inc()
+                        });
+
+                        continue;
+                    }
+                };
 
-            match verification::verify_solution::<B>(
+            let verify_started = Instant::now();
+            let result = verification::verify_solution::<B>(
                 &solution,
-                epoch.as_ref(),
+                &epoch,
                 solution_range,
                 slot,
                 salt.to_le_bytes(),
+                records_root,
                 &self.spartan,
                 &self.signing_context,
-            ) {
+            );
+            let verify_elapsed = verify_started.elapsed();
+            self.metrics.report(|metrics| {
+                metrics
+                    .verify_solution_time
+                    .observe(verify_elapsed.as_secs_f64())
+            });
+
+            match result {
                 Ok(_) => {
                     debug!(target: "poc", "Claimed slot {}", slot);
 
-                    return Some((PreDigest { solution, slot }, secret_key.into()));
+                    return Some(PreDigest { solution, slot });
                 }
                 Err(error) => {
                     warn!(target: "poc", "Invalid solution received for slot {}: {:?}", slot, error);
+                    self.metrics.report(|metrics| {
+                        metrics
+                            .solutions_rejected
+                            .with_label_values(&[solution_rejection_reason(&error)])
+                            .inc()
+                    });
                 }
             }
         }
@@ -845,11 +1115,36 @@ where
         _slot: Slot,
         claim: &Self::Claim,
     ) -> Vec<sp_runtime::DigestItem<B::Hash>> {
+        let pre_digest = if self.config.compact_pre_digest {
+            PreDigest {
+                slot: claim.slot,
+                solution: claim.solution.to_compact(),
+            }
+        } else {
+            claim.clone()
+        };
+
         vec![<DigestItemFor<B> as CompatibleDigestItem>::poc_pre_digest(
-            claim.0.clone(),
+            pre_digest,
         )]
     }
 
+    fn claim_inherent_data(
+        &self,
+        claim: &Self::Claim,
+        inherent_data: &mut InherentData,
+    ) -> Result<(), sp_inherents::Error> {
+        // solution data of compact solutions is included in the block body instead of the header
+        if self.config.compact_pre_digest {
+            inherent_data.put_data(
+                SOLUTION_DATA_INHERENT_IDENTIFIER,
+                &claim.solution.solution_data(),
+            )?;
+        }
+
+        Ok(())
+    }
+
     fn block_import_params(
         &self,
     ) -> Box<
@@ -865,35 +1160,75 @@ where
             + Send
             + 'static,
     > {
+        // blocks are sealed with signatures from farmers that arrive asynchronously, see
+        // `block_import_params_async`
         Box::new(
-            move |header,
-                  header_hash,
-                  body,
-                  storage_changes,
-                  (_pre_digest, keypair),
-                  epoch_descriptor| {
-                // sign the pre-sealed hash of the block and then
-                // add it to a digest item.
-                let signature = keypair.sign(header_hash.as_ref());
-                let digest_item =
-                    <DigestItemFor<B> as CompatibleDigestItem>::poc_seal(signature.into());
-
-                let mut import_block = BlockImportParams::new(BlockOrigin::Own, header);
-                import_block.post_digests.push(digest_item);
-                import_block.body = Some(body);
-                import_block.state_action = StateAction::ApplyChanges(
-                    sc_consensus::StorageChanges::Changes(storage_changes),
-                );
-                import_block.intermediates.insert(
-                    Cow::from(INTERMEDIATE_KEY),
-                    Box::new(PoCIntermediate::<B> { epoch_descriptor }) as Box<_>,
-                );
-
-                Ok(import_block)
+            |_header,
+             _header_hash,
+             _body,
+             _storage_changes,
+             pre_digest: PreDigest,
+             _epoch_descriptor| {
+                Err(sp_consensus::Error::CannotSign(
+                    pre_digest.solution.public_key.to_raw_vec(),
+                    "PoC blocks can only be sealed asynchronously".to_string(),
+                ))
             },
         )
     }
 
+    async fn block_import_params_async(
+        &mut self,
+        header: B::Header,
+        header_hash: &B::Hash,
+        body: Vec<B::Extrinsic>,
+        storage_changes: StorageChanges<I::Transaction, B>,
+        pre_digest: Self::Claim,
+        epoch_descriptor: Self::EpochData,
+    ) -> Result<sc_consensus::BlockImportParams<B, I::Transaction>, sp_consensus::Error>
+    where
+        Self: Send,
+    {
+        // ask farmers to sign the pre-sealed hash of the block, only the farmer that claimed the
+        // slot can produce a valid signature, which is then added to a digest item.
+        let public_key = pre_digest.solution.public_key.clone();
+        let (signature_sender, signature_receiver) = unbounded();
+
+        (self.on_block_signing)(
+            BlockSigningInfo {
+                header_hash: header_hash.as_ref().to_vec(),
+                public_key: public_key.clone(),
+                deadline: slot_ends_at(pre_digest.slot, self.config.slot_duration()),
+            },
+            signature_sender,
+        );
+
+        // `on_slot` stops waiting once the slot is over
+        let signature = signature_receiver
+            .filter(|signature| future::ready(public_key.verify(header_hash, signature)))
+            .next()
+            .await
+            .ok_or_else(|| {
+                sp_consensus::Error::CannotSign(
+                    public_key.to_raw_vec(),
+                    "Farmer didn't provide a valid block signature".to_string(),
+                )
+            })?;
+        let digest_item = <DigestItemFor<B> as CompatibleDigestItem>::poc_seal(signature);
+
+        let mut import_block = BlockImportParams::new(BlockOrigin::Own, header);
+        import_block.post_digests.push(digest_item);
+        import_block.body = Some(body);
+        import_block.state_action =
+            StateAction::ApplyChanges(sc_consensus::StorageChanges::Changes(storage_changes));
+        import_block.intermediates.insert(
+            Cow::from(INTERMEDIATE_KEY),
+            Box::new(PoCIntermediate::<B> { epoch_descriptor }) as Box<_>,
+        );
+
+        Ok(import_block)
+    }
+
     fn force_authoring(&self) -> bool {
         self.force_authoring
     }
@@ -954,6 +1289,18 @@ where
     }
 }
 
+/// Instant at which the given slot ends according to the local clock.
+fn slot_ends_at(slot: Slot, slot_duration: Duration) -> Instant {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let slot_end = Duration::from_millis(
+        (u64::from(slot) + 1).saturating_mul(slot_duration.as_millis() as u64),
+    );
+
+    Instant::now() + slot_end.checked_sub(now).unwrap_or_default()
+}
+
 /// Extract the PoC pre digest from the given header. Pre-runtime digests are
 /// mandatory, the function will return `Err` if none is found.
 pub fn find_pre_digest<B: BlockT>(header: &B::Header) -> Result<PreDigest, Error<B>> {
@@ -1024,6 +1371,31 @@ where
     Ok(config_digest)
 }
 
+/// Extract the PoC durations change digest from the given header, if it exists.
+fn find_next_durations_digest<B: BlockT>(
+    header: &B::Header,
+) -> Result<Option<NextDurationsDescriptor>, Error<B>>
+where
+    DigestItemFor<B>: CompatibleDigestItem,
+{
+    let mut durations_digest: Option<_> = None;
+    for log in header.digest().logs() {
+        trace!(target: "poc", "Checking log {:?}, looking for durations change digest.", log);
+        let log = log.try_to::<ConsensusLog>(OpaqueDigestItemId::Consensus(&POC_ENGINE_ID));
+        match (log, durations_digest.is_some()) {
+            (Some(ConsensusLog::NextDurationsData(_)), true) => {
+                return Err(poc_err(Error::MultipleDurationsChangeDigests))
+            }
+            (Some(ConsensusLog::NextDurationsData(durations)), false) => {
+                durations_digest = Some(durations)
+            }
+            _ => trace!(target: "poc", "Ignoring digest not meant for us"),
+        }
+    }
+
+    Ok(durations_digest)
+}
+
 /// Extract the PoC solution range digest from the given header.
 fn find_solution_range_digest<B: BlockT>(
     header: &B::Header,
@@ -1118,11 +1490,206 @@ where
     Ok(next_salt_digest)
 }
 
+/// Extract the PoC eon salt digest from the given header if it exists.
+fn find_eon_salt_digest<B: BlockT>(header: &B::Header) -> Result<Option<SaltDescriptor>, Error<B>>
+where
+    DigestItemFor<B>: CompatibleDigestItem,
+{
+    let mut eon_salt_digest: Option<_> = None;
+    for log in header.digest().logs() {
+        trace!(target: "poc", "Checking log {:?}, looking for eon salt digest.", log);
+        let log = log.try_to::<ConsensusLog>(OpaqueDigestItemId::Consensus(&POC_ENGINE_ID));
+        match (log, eon_salt_digest.is_some()) {
+            (Some(ConsensusLog::EonSaltData(_)), true) => {
+                return Err(poc_err(Error::MultipleEonSaltDigests))
+            }
+            (Some(ConsensusLog::EonSaltData(salt)), false) => eon_salt_digest = Some(salt),
+            _ => trace!(target: "poc", "Ignoring digest not meant for us"),
+        }
+    }
+
+    Ok(eon_salt_digest)
+}
+
+/// Extract the PoC root blocks digest from the given header if it exists.
+fn find_root_blocks_digest<B: BlockT>(
+    header: &B::Header,
+) -> Result<Option<Vec<RootBlock>>, Error<B>>
+where
+    DigestItemFor<B>: CompatibleDigestItem,
+{
+    let mut root_blocks_digest: Option<_> = None;
+    for log in header.digest().logs() {
+        trace!(target: "poc", "Checking log {:?}, looking for root blocks digest.", log);
+        let log = log.try_to::<ConsensusLog>(OpaqueDigestItemId::Consensus(&POC_ENGINE_ID));
+        match (log, root_blocks_digest.is_some()) {
+            (Some(ConsensusLog::RootBlocksData(_)), true) => {
+                return Err(poc_err(Error::MultipleRootBlocksDigests))
+            }
+            (Some(ConsensusLog::RootBlocksData(root_blocks)), false) => {
+                root_blocks_digest = Some(root_blocks)
+            }
+            _ => trace!(target: "poc", "Ignoring digest not meant for us"),
+        }
+    }
+
+    Ok(root_blocks_digest)
+}
+
+/// Solution range and salt that must be used by children of a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub(crate) struct SolutionRangeAndSalt {
+    /// Acceptable solution range
+    pub(crate) solution_range: u64,
+    /// Salt
+    pub(crate) salt: u64,
+}
+
+impl SolutionRangeAndSalt {
+    /// Solution range and salt for children of the block with the given header, given the ones
+    /// used by the block itself.
+    ///
+    /// The very first block of the era/eon still uses solution range/salt from the previous one,
+    /// and announces the ones used by the blocks after it with `NextSolutionRangeData` and
+    /// `EonSaltData` digests (salt for the next eon is revealed in advance, so `NextSaltData`
+    /// can't tell when the eon actually changes).
+    fn for_child_of<B: BlockT>(self, header: &B::Header) -> Result<Self, Error<B>>
+    where
+        DigestItemFor<B>: CompatibleDigestItem,
+    {
+        Ok(Self {
+            solution_range: find_next_solution_range_digest::<B>(header)?
+                .map_or(self.solution_range, |next_solution_range| {
+                    next_solution_range.solution_range
+                }),
+            salt: find_eon_salt_digest::<B>(header)?.map_or(self.salt, |eon_salt| eon_salt.salt),
+        })
+    }
+}
+
+/// Extract solution range and salt that must be used by the child of the given parent block.
+///
+/// These are tracked in aux storage for every imported block from the digests of its header (see
+/// [`SolutionRangeAndSalt::for_child_of`]), such that verification doesn't depend on the state of
+/// the parent, which light clients and warp synced nodes don't have. Only the genesis block and
+/// blocks imported before these were tracked fall back to the state of the parent block.
+fn extract_solution_range_and_salt_for_child<B, C>(
+    client: &C,
+    parent_header: &B::Header,
+) -> Result<SolutionRangeAndSalt, Error<B>>
+where
+    B: BlockT,
+    C: ProvideRuntimeApi<B> + AuxStore,
+    C::Api: PoCApi<B>,
+{
+    let parent_hash = parent_header.hash();
+
+    if let Some(solution_range_and_salt) =
+        aux_schema::load_solution_range_and_salt(client, parent_hash).map_err(Error::Client)?
+    {
+        return Ok(solution_range_and_salt);
+    }
+
+    // We use runtime API as it will fallback to default value for genesis when there is no
+    // solution range stored yet, genesis state is available to light clients too
+    let parent_block_id = BlockId::Hash(parent_hash);
+    let runtime_api = client.runtime_api();
+
+    Ok(SolutionRangeAndSalt {
+        solution_range: runtime_api
+            .solution_range(&parent_block_id)
+            .map_err(Error::RuntimeApi)?,
+        salt: runtime_api
+            .salt(&parent_block_id)
+            .map_err(Error::RuntimeApi)?,
+    })
+}
+
+/// Extract records root of the segment the piece of `solution` belongs to from the state of the
+/// parent block, `None` if solution has no piece inclusion proof or the root is not stored on chain.
+///
+/// Fails with [`Error::GenesisPieceSolutionAfterCutover`] for solutions with encodings of the
+/// genesis piece once [`GENESIS_PIECE_SOLUTIONS_CUTOVER`] segments were archived.
+fn extract_records_root_for_solution<B, C>(
+    client: &C,
+    parent_block_id: &BlockId<B>,
+    solution: &Solution,
+) -> Result<Option<[u8; 32]>, Error<B>>
+where
+    B: BlockT,
+    C: ProvideRuntimeApi<B>,
+    C::Api: PoCApi<B>,
+{
+    let runtime_api = client.runtime_api();
+    // records roots are only stored on chain since version 2 of the API
+    if !runtime_api
+        .has_api_with::<dyn PoCApi<B>, _>(parent_block_id, |v| v >= 2)
+        .map_err(Error::RuntimeApi)?
+    {
+        return Ok(None);
+    }
+
+    let piece_inclusion_proof = match &solution.piece_inclusion_proof {
+        Some(piece_inclusion_proof) => piece_inclusion_proof,
+        None => {
+            if solution.version() == SolutionVersion::GenesisPiece
+                && runtime_api
+                    .next_archived_segment_index(parent_block_id)
+                    .map_err(Error::RuntimeApi)?
+                    >= GENESIS_PIECE_SOLUTIONS_CUTOVER
+            {
+                return Err(Error::GenesisPieceSolutionAfterCutover);
+            }
+
+            return Ok(None);
+        }
+    };
+
+    runtime_api
+        .records_root(
+            parent_block_id,
+            piece_inclusion_proof.piece_index / PIECES_PER_SEGMENT as u64,
+        )
+        .map_err(Error::RuntimeApi)
+}
+
+/// Upper bound of the length of the encoding of an extrinsic before the arguments of its call, that
+/// is the length prefix, version and call index of FRAME extrinsics.
+const MAX_EXTRINSIC_CALL_PREFIX_LENGTH: usize = 8;
+
+/// Extract solution data of the block with the compact `solution` from inherents in its `body`,
+/// `None` if there is none matching the commitment of the solution.
+///
+/// Extrinsics are opaque to the client and light clients can't call the runtime for blocks other
+/// than genesis, so solution data is decoded from the encoding of inherents directly. It is the last
+/// argument of the call and fills the rest of the encoding after a short prefix, the commitment
+/// makes sure it is the solution data the block was authored with.
+fn extract_solution_data<B: BlockT>(
+    solution: &Solution,
+    body: &[B::Extrinsic],
+) -> Option<SolutionData> {
+    body.iter()
+        // inherents are unsigned and precede all signed extrinsics
+        .take_while(|extrinsic| !extrinsic.is_signed().unwrap_or(false))
+        .find_map(|extrinsic| {
+            let encoded_extrinsic = extrinsic.encode();
+            (0..MAX_EXTRINSIC_CALL_PREFIX_LENGTH.min(encoded_extrinsic.len())).find_map(|offset| {
+                SolutionData::decode_all(&encoded_extrinsic[offset..])
+                    .ok()
+                    .filter(|solution_data| {
+                        solution.solution_data_commitment
+                            == Some(solution_data.commitment(&solution.tag))
+                    })
+            })
+        })
+}
+
 /// State that must be shared between the import queue and the authoring logic.
 #[derive(Clone)]
 pub struct PoCLink<Block: BlockT> {
     epoch_changes: SharedEpochChanges<Block, Epoch>,
     config: Config,
+    pending_offenders: PendingOffenders,
 }
 
 impl<Block: BlockT> PoCLink<Block> {
@@ -1135,6 +1702,11 @@ impl<Block: BlockT> PoCLink<Block> {
     pub fn config(&self) -> &Config {
         &self.config
     }
+
+    /// Get the pending offenders of this link.
+    pub fn pending_offenders(&self) -> &PendingOffenders {
+        &self.pending_offenders
+    }
 }
 
 /// A verifier for PoC blocks.
@@ -1144,10 +1716,15 @@ pub struct PoCVerifier<Block: BlockT, Client, SelectChain, CAW, CIDP> {
     create_inherent_data_providers: CIDP,
     config: Config,
     epoch_changes: SharedEpochChanges<Block, Epoch>,
+    pending_offenders: PendingOffenders,
     can_author_with: CAW,
     telemetry: Option<TelemetryHandle>,
-    spartan: Spartan,
-    signing_context: SigningContext,
+    context_free_verifier: ContextFreeVerifier,
+    /// Hashes of headers in the current import batch that passed context-free verification
+    context_free_verified: HashSet<Block::Hash>,
+    /// Fetcher of block bodies on light clients
+    block_body_fetcher: Option<BlockBodyFetcher<Block>>,
+    metrics: MetricsLink<ImportMetrics>,
 }
 
 impl<Block, Client, SelectChain, CAW, CIDP> PoCVerifier<Block, Client, SelectChain, CAW, CIDP>
@@ -1228,6 +1805,9 @@ where
             equivocation_proof.second_header.hash(),
         );
 
+        // don't accept blocks from the offender until the report is included in a block
+        self.pending_offenders.insert(author.clone(), slot);
+
         // get the best block on which we will build and send the equivocation report.
         let best_id = self
             .select_chain
@@ -1274,6 +1854,28 @@ where
     CIDP: CreateInherentDataProviders<Block, ()> + Send + Sync,
     CIDP::InherentDataProviders: InherentDataProviderExt + Send + Sync,
 {
+    fn prepare_batch(&mut self, origin: &BlockOrigin, blocks: &[IncomingBlock<Block>]) {
+        // Only worth it for batches during sync, single blocks are verified sequentially anyway
+        if blocks.len() < 2 {
+            self.context_free_verified.clear();
+            return;
+        }
+
+        let headers = blocks
+            .iter()
+            .filter_map(|block| block.header.clone())
+            .collect::<Vec<_>>();
+
+        trace!(
+            target: "poc",
+            "Verifying {} headers from {:?} in parallel",
+            headers.len(),
+            origin,
+        );
+
+        self.context_free_verified = self.context_free_verifier.verify_headers::<Block>(headers);
+    }
+
     async fn verify(
         &mut self,
         origin: BlockOrigin,
@@ -1307,9 +1909,30 @@ where
             .client
             .header_metadata(parent_hash)
             .map_err(Error::<Block>::FetchParentHeader)?;
+        let parent_header = self
+            .client
+            .header(BlockId::Hash(parent_hash))
+            .map_err(Error::<Block>::FetchParentHeader)?
+            .ok_or_else(|| Error::<Block>::ParentUnavailable(parent_hash, hash))?;
 
         let pre_digest = find_pre_digest::<Block>(&header)?;
-        let (check_header, epoch_descriptor) = {
+
+        // Light clients (the only ones with block body fetcher) have no state other than genesis,
+        // checks against the state of the parent are left to the full nodes they sync from
+        let has_parent_state = self.block_body_fetcher.is_none();
+
+        // Light clients don't import block bodies, but need solution data from them to verify
+        // compact solutions
+        let fetched_body = match (&body, &self.block_body_fetcher) {
+            (None, Some(block_body_fetcher)) if pre_digest.solution.is_compact() => Some(
+                block_body_fetcher(header.clone())
+                    .await
+                    .map_err(Error::<Block>::FetchBody)?,
+            ),
+            _ => None,
+        };
+
+        let (check_header, epoch_descriptor, solution_range, salt) = {
             let epoch_changes = self.epoch_changes.shared_data();
             let epoch_descriptor = epoch_changes
                 .epoch_descriptor_for_child_of(
@@ -1323,47 +1946,114 @@ where
             let viable_epoch = epoch_changes
                 .viable_epoch(&epoch_descriptor, |slot| Epoch::genesis(&self.config, slot))
                 .ok_or_else(|| Error::<Block>::FetchEpoch(parent_hash))?;
-            // TODO: Is it actually secure to validate it using solution range digest?
-            let solution_range = find_solution_range_digest::<Block>(&header)?
-                .ok_or_else(|| Error::<Block>::MissingSolutionRange(hash))?
-                .solution_range;
-            let salt = find_salt_digest::<Block>(&header)?
-                .ok_or_else(|| Error::<Block>::MissingSalt(hash))?
-                .salt;
+            // Solution range and salt are derived from the parent rather than taken from the header
+            // itself, otherwise farmer would be able to declare arbitrary values that favor them.
+            let SolutionRangeAndSalt {
+                solution_range,
+                salt,
+            } = extract_solution_range_and_salt_for_child(&*self.client, &parent_header)?;
+
+            // Solution range and salt are only declared in the header when they change
+            if let Some(declared_solution_range) = find_solution_range_digest::<Block>(&header)? {
+                let declared_solution_range = declared_solution_range.solution_range;
+                if declared_solution_range != solution_range {
+                    return Err(Error::<Block>::InvalidSolutionRange(
+                        hash,
+                        solution_range,
+                        declared_solution_range,
+                    )
+                    .into());
+                }
+            }
+
+            if let Some(declared_salt) = find_salt_digest::<Block>(&header)? {
+                let declared_salt = declared_salt.salt;
+                if declared_salt != salt {
+                    return Err(Error::<Block>::InvalidSalt(hash, salt, declared_salt).into());
+                }
+            }
 
+            // equivocation reports of pending offenders may not be included in a block yet, so
+            // runtime block list alone is not sufficient
             if self
-                .client
-                .runtime_api()
-                .is_in_block_list(&BlockId::Hash(parent_hash), &pre_digest.solution.public_key)
-                .map_err(Error::<Block>::RuntimeApi)?
+                .pending_offenders
+                .contains(&pre_digest.solution.public_key, slot_now)
+                || (has_parent_state
+                    && self
+                        .client
+                        .runtime_api()
+                        .is_in_block_list(
+                            &BlockId::Hash(parent_hash),
+                            &pre_digest.solution.public_key,
+                        )
+                        .map_err(Error::<Block>::RuntimeApi)?)
             {
                 warn!(
                     target: "poc",
                     "Ignoring block with solution provided by farmer in block list: {}",
                     pre_digest.solution.public_key
                 );
+                self.metrics
+                    .report(|metrics| metrics.blocks_rejected_block_list.inc());
 
                 return Err(
                     Error::<Block>::FarmerInBlockList(pre_digest.solution.public_key).into(),
                 );
             }
 
+            // Compact solutions are verified fully with solution data from the block body, blocks
+            // without one can't be verified
+            let solution = if pre_digest.solution.is_compact() {
+                let body = body
+                    .as_ref()
+                    .or_else(|| fetched_body.as_ref())
+                    .ok_or_else(|| Error::<Block>::MissingSolutionData(hash))?;
+                let solution_data = extract_solution_data::<Block>(&pre_digest.solution, body)
+                    .ok_or_else(|| Error::<Block>::MissingSolutionData(hash))?;
+
+                pre_digest
+                    .solution
+                    .clone()
+                    .with_solution_data(solution_data)
+                    .ok_or_else(|| Error::<Block>::InvalidSolutionData(hash))?
+            } else {
+                pre_digest.solution.clone()
+            };
+
+            let records_root = if has_parent_state {
+                extract_records_root_for_solution(
+                    &*self.client,
+                    &BlockId::Hash(parent_hash),
+                    &solution,
+                )?
+            } else {
+                None
+            };
+
             // We add one to the current slot to allow for some small drift.
             // FIXME #1019 in the future, alter this queue to allow deferring of headers
             let v_params = verification::VerificationParams {
                 header: header.clone(),
                 pre_digest: Some(pre_digest),
+                solution,
                 slot_now: slot_now + 1,
                 epoch: viable_epoch.as_ref(),
                 solution_range,
                 salt: salt.to_le_bytes(),
-                spartan: &self.spartan,
-                signing_context: &self.signing_context,
+                records_root,
+                check_piece_inclusion: has_parent_state,
+                context_free_verifier: &self.context_free_verifier,
+                context_free_verified: self.context_free_verified.remove(&hash),
+                verify_solution_time: self
+                    .metrics
+                    .report(|metrics| metrics.verify_solution_time.clone()),
             };
 
             (
                 verification::check_header::<Block>(v_params)?,
                 epoch_descriptor,
+                solution_range,
+                salt,
             )
         };
 
@@ -1375,6 +2065,11 @@ where
                     .expect("check_header always returns a pre-digest digest item; qed");
                 let slot = poc_pre_digest.slot;
 
+                self.metrics.report(|metrics| {
+                    metrics.solution_range.set(solution_range);
+                    metrics.salt.set(salt);
+                });
+
                 // the header is valid but let's check if there was something else already
                 // proposed at the same slot by the given author. if there was, we will
                 // report the equivocation to the runtime.
@@ -1600,11 +2295,37 @@ where
 
             let total_weight = parent_weight + pre_digest.added_weight();
 
+            // distance of the solution from the local challenge, used to break ties in fork choice
+            let solution_distance = {
+                let viable_epoch = epoch_changes
+                    .viable_epoch(&epoch_descriptor, |slot| Epoch::genesis(&self.config, slot))
+                    .ok_or_else(|| {
+                        ConsensusError::ClientImport(Error::<Block>::FetchEpoch(parent_hash).into())
+                    })?;
+                verification::solution_distance(
+                    &pre_digest.solution,
+                    create_global_challenge(viable_epoch.as_ref(), slot),
+                )
+            };
+
             // search for this all the time so we can reject unexpected announcements.
             let next_epoch_digest = find_next_epoch_digest::<Block>(&block.header)
                 .map_err(|e| ConsensusError::ClientImport(e.to_string()))?;
             let next_config_digest = find_next_config_digest::<Block>(&block.header)
                 .map_err(|e| ConsensusError::ClientImport(e.to_string()))?;
+            // era and eon durations are tracked by the runtime, only epoch duration matters here
+            let next_epoch_duration = find_next_durations_digest::<Block>(&block.header)
+                .map_err(|e| ConsensusError::ClientImport(e.to_string()))?
+                .and_then(|durations| durations.epoch_duration);
+
+            if next_epoch_duration.is_some() && next_epoch_digest.is_none() {
+                return Err(ConsensusError::ClientImport(
+                    poc_err(Error::<Block>::UnexpectedEpochDurationChange).into(),
+                ));
+            }
+
+            let is_warp_sync_block = warp_sync::is_warp_sync_block::<Block>(&block.header)
+                .map_err(|e| ConsensusError::ClientImport(e.to_string()))?;
 
             match (
                 first_in_epoch,
@@ -1661,7 +2382,11 @@ where
                      viable_epoch.as_ref().start_slot,
                 );
 
-                let next_epoch = viable_epoch.increment((next_epoch_descriptor, epoch_config));
+                let epoch_duration =
+                    next_epoch_duration.unwrap_or_else(|| viable_epoch.as_ref().duration);
+
+                let next_epoch =
+                    viable_epoch.increment((next_epoch_descriptor, epoch_config, epoch_duration));
 
                 log!(target: "poc",
                      log_level,
@@ -1711,10 +2436,53 @@ where
                     .auxiliary
                     .extend(values.iter().map(|(k, v)| (k.to_vec(), Some(v.to_vec()))))
             });
+            aux_schema::write_block_solution_distance(
+                &*self.client,
+                number,
+                hash,
+                solution_distance,
+                |values| {
+                    block
+                        .auxiliary
+                        .extend(values.iter().map(|(k, v)| (k.to_vec(), Some(v.to_vec()))))
+                },
+            )
+            .map_err(|e| ConsensusError::ClientImport(e.to_string()))?;
+            // solution range and salt are tracked from digests, such that children of the block
+            // are verified without the state of the block
+            let solution_range_and_salt =
+                extract_solution_range_and_salt_for_child(&*self.client, &parent_header)
+                    .and_then(|solution_range_and_salt| {
+                        solution_range_and_salt.for_child_of::<Block>(&block.header)
+                    })
+                    .map_err(|e| ConsensusError::ClientImport(e.to_string()))?;
+            aux_schema::write_solution_range_and_salt(hash, solution_range_and_salt, |values| {
+                block
+                    .auxiliary
+                    .extend(values.iter().map(|(k, v)| (k.to_vec(), Some(v.to_vec()))))
+            });
+            // blocks that warp sync proofs consist of are indexed, such that proofs are generated
+            // without scanning all finalized headers
+            if is_warp_sync_block {
+                aux_schema::write_warp_sync_block_number(&*self.client, number, |values| {
+                    block
+                        .auxiliary
+                        .extend(values.iter().map(|(k, v)| (k.to_vec(), Some(v.to_vec()))))
+                })
+                .map_err(|e| ConsensusError::ClientImport(e.to_string()))?;
+            }
+            // solution distances are only used to choose between blocks with the same number, so
+            // they are pruned once that number is finalized, much like the epoch changes tree
+            block.auxiliary.extend(
+                aux_schema::prune_block_solution_distances(&*self.client, info.finalized_number)
+                    .map_err(|e| ConsensusError::ClientImport(e.to_string()))?,
+            );
 
             // The fork choice rule is that we pick the heaviest chain (i.e.
             // more primary blocks), if there's a tie we go with the longest
-            // chain.
+            // chain. If both are the same and tie-breaking is enabled, we pick
+            // the block with the solution closest to the local challenge and
+            // then the one with the lowest hash.
             block.fork_choice = {
                 let (last_best, last_best_number) = (info.best_hash, info.best_number);
 
@@ -1736,7 +2504,21 @@ where
                     if total_weight > last_best_weight {
                         true
                     } else if total_weight == last_best_weight {
-                        number > last_best_number
+                        if number == last_best_number && self.config.fork_choice_tie_breaking() {
+                            // blocks imported before tie-breaking was introduced have no
+                            // distance stored, keep whatever is the best block in that case
+                            aux_schema::load_block_solution_distance(
+                                &*self.client,
+                                last_best_number,
+                                last_best,
+                            )
+                            .map_err(|e| ConsensusError::ChainLookup(format!("{:?}", e)))?
+                            .map_or(false, |last_best_distance| {
+                                (solution_distance, hash) < (last_best_distance, last_best)
+                            })
+                        } else {
+                            number > last_best_number
+                        }
                     } else {
                         false
                     },
@@ -1747,6 +2529,12 @@ where
             epoch_changes.release_mutex()
         };
 
+        let own_header = if block.origin == BlockOrigin::Own {
+            Some(block.post_header())
+        } else {
+            None
+        };
+
         let import_result = self.inner.import_block(block, new_cache).await;
 
         // revert to the original epoch changes in case there's an error
@@ -1757,6 +2545,33 @@ where
             }
         }
 
+        // record locally authored block as the claim of its farmer for the slot once it is
+        // imported, the same way headers from the network are recorded during verification, such
+        // that a conflicting header of the same farmer is reported as an equivocation
+        if let (Ok(ImportResult::Imported(_)), Some(own_header)) = (&import_result, own_header) {
+            let public_key = &pre_digest.solution.public_key;
+            match check_equivocation(&*self.client, slot, slot, &own_header, public_key) {
+                Ok(Some(_)) => {
+                    warn!(
+                        target: "poc",
+                        "Locally authored block {} is an equivocation of farmer {} at slot {}",
+                        hash,
+                        public_key,
+                        slot,
+                    );
+                }
+                Ok(None) => {}
+                Err(error) => {
+                    warn!(
+                        target: "poc",
+                        "Failed to record locally authored block {}: {}",
+                        hash,
+                        error,
+                    );
+                }
+            }
+        }
+
         import_result.map_err(Into::into)
     }
 
@@ -1825,6 +2640,7 @@ where
     let link = PoCLink {
         epoch_changes: epoch_changes.clone(),
         config: config.clone(),
+        pending_offenders: PendingOffenders::default(),
     };
 
     // NOTE: this isn't entirely necessary, but since we didn't use to prune the
@@ -1837,6 +2653,124 @@ where
     Ok((import, link))
 }
 
+/// Revert PoC aux data (epoch changes, block weights, solution ranges and salts and solution
+/// distances) to the state at `blocks` below the best block, but not below the last finalized
+/// block.
+///
+/// Must be called before the blocks themselves are reverted, otherwise epoch changes and block
+/// weights keep referencing blocks that no longer exist.
+pub fn revert<Block, Client, Backend>(
+    client: Arc<Client>,
+    backend: Arc<Backend>,
+    blocks: NumberFor<Block>,
+) -> ClientResult<()>
+where
+    Block: BlockT,
+    Client: AuxStore
+        + HeaderMetadata<Block, Error = sp_blockchain::Error>
+        + HeaderBackend<Block>
+        + ProvideRuntimeApi<Block>
+        + UsageProvider<Block>,
+    Client::Api: PoCApi<Block>,
+    Backend: sc_client_api::Backend<Block>,
+{
+    let best_number = client.info().best_number;
+    let finalized = client.info().finalized_number;
+
+    let revertible = blocks.min(best_number - finalized);
+    if revertible.is_zero() {
+        return Ok(());
+    }
+
+    let revert_up_to_number = best_number - revertible;
+    let revert_up_to_hash = client.hash(revert_up_to_number)?.ok_or_else(|| {
+        ClientError::Backend(format!(
+            "Unexpected hash lookup failure for block number: {}",
+            revert_up_to_number
+        ))
+    })?;
+
+    // Revert epoch changes tree.
+
+    let config = Config::get_or_compute(&*client)?;
+    let epoch_changes = aux_schema::load_epoch_changes::<Block, Client>(&*client, &config)?;
+    let mut epoch_changes = epoch_changes.shared_data();
+
+    if revert_up_to_number.is_zero() {
+        // Special case, no epoch changes data were present on genesis.
+        *epoch_changes = EpochChangesFor::<Block, Epoch>::default();
+    } else {
+        epoch_changes.revert(
+            descendent_query(&*client),
+            revert_up_to_hash,
+            revert_up_to_number,
+        );
+    }
+
+    // Remove block weights, solution ranges and salts and solution distances added after the
+    // revert point.
+
+    let mut keys = HashSet::new();
+    let mut reverted_hashes_by_number = HashMap::<NumberFor<Block>, Vec<Block::Hash>>::new();
+
+    let leaves = backend.blockchain().leaves()?.into_iter().filter(|&leaf| {
+        sp_blockchain::tree_route(&*client, revert_up_to_hash, leaf)
+            .map(|route| route.retracted().is_empty())
+            .unwrap_or_default()
+    });
+
+    for leaf in leaves {
+        let mut hash = leaf;
+        loop {
+            let meta = client.header_metadata(hash)?;
+            if meta.number <= revert_up_to_number
+                || !keys.insert(aux_schema::block_weight_key(hash))
+            {
+                // We've reached the revert point or an already processed branch, stop here.
+                break;
+            }
+            keys.insert(aux_schema::block_solution_range_and_salt_key(hash));
+            reverted_hashes_by_number
+                .entry(meta.number)
+                .or_default()
+                .push(hash);
+            hash = meta.parent;
+        }
+    }
+
+    // Solution distances are stored per block number, keep those of blocks that are not reverted.
+    let mut solution_distances = Vec::new();
+    for (number, reverted_hashes) in reverted_hashes_by_number {
+        let remaining =
+            aux_schema::load_block_solution_distances::<_, Block::Hash, _>(&*client, number)?
+                .into_iter()
+                .filter(|(hash, _)| !reverted_hashes.contains(hash))
+                .collect::<Vec<_>>();
+        let key = aux_schema::block_solution_distances_key(number);
+        if remaining.is_empty() {
+            keys.insert(key);
+        } else {
+            solution_distances.push((key, remaining.encode()));
+        }
+    }
+
+    let keys: Vec<_> = keys.iter().map(|key| key.as_slice()).collect();
+
+    // Write epoch changes and remove block data in one shot.
+    aux_schema::write_epoch_changes::<Block, _, _>(&epoch_changes, |values| {
+        let values = values
+            .iter()
+            .copied()
+            .chain(
+                solution_distances
+                    .iter()
+                    .map(|(key, value)| (key.as_slice(), value.as_slice())),
+            )
+            .collect::<Vec<_>>();
+        client.insert_aux(&values, keys.iter())
+    })
+}
+
 /// Start an import queue for the PoC consensus algorithm.
 ///
 /// This method returns the import queue, some data that needs to be passed to the block authoring
@@ -1846,6 +2780,10 @@ where
 ///
 /// The block import object provided must be the `PocBlockImport` or a wrapper
 /// of it, otherwise crucial import logic will be omitted.
+///
+/// Light clients must provide `block_body_fetcher`, since blocks with compact solutions can't be
+/// verified without their bodies. Full nodes must not, since the verifier doesn't check blocks
+/// against the state of their parent when it is provided.
 pub fn import_queue<Block: BlockT, Client, SelectChain, Inner, CAW, CIDP>(
     poc_link: PoCLink<Block>,
     block_import: Inner,
@@ -1857,6 +2795,7 @@ pub fn import_queue<Block: BlockT, Client, SelectChain, Inner, CAW, CIDP>(
     registry: Option<&Registry>,
     can_author_with: CAW,
     telemetry: Option<TelemetryHandle>,
+    block_body_fetcher: Option<BlockBodyFetcher<Block>>,
 ) -> ClientResult<DefaultImportQueue<Block, Client>>
 where
     Inner: BlockImport<
@@ -1880,17 +2819,21 @@ where
     CIDP: CreateInherentDataProviders<Block, ()> + Send + Sync + 'static,
     CIDP::InherentDataProviders: InherentDataProviderExt + Send + Sync,
 {
+    let context_free_verifier =
+        ContextFreeVerifier::new(&poc_link.config.spartan_parameters, num_cpus::get());
     let verifier = PoCVerifier {
         select_chain,
         create_inherent_data_providers,
         config: poc_link.config,
         epoch_changes: poc_link.epoch_changes,
+        pending_offenders: poc_link.pending_offenders,
         can_author_with,
         telemetry,
         client,
-        spartan: Spartan::new(),
-        // TODO: Figure out how to remove explicit schnorrkel dependency
-        signing_context: schnorrkel::context::signing_context(SIGNING_CONTEXT),
+        context_free_verifier,
+        context_free_verified: HashSet::new(),
+        block_body_fetcher,
+        metrics: MetricsLink::new(registry),
     };
 
     Ok(BasicQueue::new(
@@ -0,0 +1,211 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Prometheus metrics of PoC authoring and verification.
+
+use crate::Error;
+use prometheus_endpoint::{
+    register, Counter, CounterVec, Gauge, Histogram, HistogramOpts, Opts, PrometheusError,
+    Registry, U64,
+};
+use sp_runtime::traits::Block as BlockT;
+use std::time::Instant;
+
+/// Set of metrics that can be registered with Prometheus.
+pub(crate) trait Metrics: Sized {
+    /// Register metrics with the given registry.
+    fn register(registry: &Registry) -> Result<Self, PrometheusError>;
+}
+
+/// Optional shareable link to PoC metrics.
+#[derive(Clone)]
+pub(crate) struct MetricsLink<M>(Option<M>);
+
+impl<M> Default for MetricsLink<M> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<M: Metrics> MetricsLink<M> {
+    pub(crate) fn new(registry: Option<&Registry>) -> Self {
+        Self(registry.and_then(|registry| {
+            M::register(registry)
+                .map_err(|err| log::warn!("Failed to register PoC prometheus metrics: {}", err))
+                .ok()
+        }))
+    }
+
+    pub(crate) fn report<O>(&self, do_this: impl FnOnce(&M) -> O) -> Option<O> {
+        Some(do_this(self.0.as_ref()?))
+    }
+}
+
+/// Metrics of slot claiming, registered by the authoring worker.
+#[derive(Clone)]
+pub(crate) struct AuthoringMetrics {
+    pub(crate) slots_challenged: Counter<U64>,
+    pub(crate) solutions_per_slot: Histogram,
+    pub(crate) first_solution_time: Histogram,
+    pub(crate) solutions_rejected: CounterVec<U64>,
+    pub(crate) verify_solution_time: Histogram,
+}
+
+impl Metrics for AuthoringMetrics {
+    fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+        Ok(Self {
+            slots_challenged: register(
+                Counter::new(
+                    "poc_slots_challenged_total",
+                    "Number of slots where a challenge was broadcast to farmers",
+                )?,
+                registry,
+            )?,
+            solutions_per_slot: register(
+                Histogram::with_opts(
+                    HistogramOpts::new(
+                        "poc_solutions_per_slot",
+                        "Histogram of number of solutions received per slot",
+                    )
+                    .buckets(vec![0.0, 1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0]),
+                )?,
+                registry,
+            )?,
+            first_solution_time: register(
+                Histogram::with_opts(HistogramOpts::new(
+                    "poc_first_solution_time",
+                    "Histogram of time taken to receive the first solution after a challenge",
+                ))?,
+                registry,
+            )?,
+            solutions_rejected: register(
+                CounterVec::new(
+                    Opts::new(
+                        "poc_solutions_rejected_total",
+                        "Number of solutions from farmers that were rejected",
+                    ),
+                    &["reason"],
+                )?,
+                registry,
+            )?,
+            verify_solution_time: register(
+                Histogram::with_opts(HistogramOpts::new(
+                    "poc_authoring_verify_solution_time",
+                    "Histogram of time taken to verify solutions from farmers",
+                ))?,
+                registry,
+            )?,
+        })
+    }
+}
+
+/// Metrics of block verification, registered by the import queue.
+#[derive(Clone)]
+pub(crate) struct ImportMetrics {
+    pub(crate) blocks_rejected_block_list: Counter<U64>,
+    pub(crate) verify_solution_time: Histogram,
+    pub(crate) solution_range: Gauge<U64>,
+    pub(crate) salt: Gauge<U64>,
+}
+
+impl Metrics for ImportMetrics {
+    fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+        Ok(Self {
+            blocks_rejected_block_list: register(
+                Counter::new(
+                    "poc_blocks_rejected_block_list_total",
+                    "Number of blocks rejected because their author is in block list",
+                )?,
+                registry,
+            )?,
+            verify_solution_time: register(
+                Histogram::with_opts(HistogramOpts::new(
+                    "poc_import_verify_solution_time",
+                    "Histogram of time taken to verify solutions of imported blocks",
+                ))?,
+                registry,
+            )?,
+            solution_range: register(
+                Gauge::new(
+                    "poc_solution_range",
+                    "Solution range of the most recently verified block",
+                )?,
+                registry,
+            )?,
+            salt: register(
+                Gauge::new("poc_salt", "Salt of the most recently verified block")?,
+                registry,
+            )?,
+        })
+    }
+}
+
+/// Counts solutions received for a slot, the count is reported once claiming of the slot is over,
+/// including when claiming is abandoned by the slot worker.
+pub(crate) struct SlotSolutionsReport {
+    metrics: MetricsLink<AuthoringMetrics>,
+    challenged_at: Instant,
+    received: u64,
+}
+
+impl SlotSolutionsReport {
+    /// Start counting solutions for a slot whose challenge was just broadcast.
+    pub(crate) fn new(metrics: MetricsLink<AuthoringMetrics>) -> Self {
+        metrics.report(|metrics| metrics.slots_challenged.inc());
+
+        Self {
+            metrics,
+            challenged_at: Instant::now(),
+            received: 0,
+        }
+    }
+
+    /// Account for a solution received from a farmer.
+    pub(crate) fn received(&mut self) {
+        if self.received == 0 {
+            let elapsed = self.challenged_at.elapsed();
+            self.metrics
+                .report(|metrics| metrics.first_solution_time.observe(elapsed.as_secs_f64()));
+        }
+        self.received += 1;
+    }
+}
+
+impl Drop for SlotSolutionsReport {
+    fn drop(&mut self) {
+        let received = self.received;
+        self.metrics
+            .report(|metrics| metrics.solutions_per_slot.observe(received as f64));
+    }
+}
+
+/// Label of the error returned by `verify_solution` for `poc_solutions_rejected_total`.
+pub(crate) fn solution_rejection_reason<B: BlockT>(error: &Error<B>) -> &'static str {
+    match error {
+        Error::OutsideOfSolutionRange(_) => "OutsideOfSolutionRange",
+        Error::EncodingOfWrongSize => "EncodingOfWrongSize",
+        Error::InvalidCommitment(_) => "InvalidCommitment",
+        Error::BadSolutionSignature(_) => "BadSolutionSignature",
+        Error::InvalidEncoding(_) => "InvalidEncoding",
+        Error::PieceOfWrongSize => "PieceOfWrongSize",
+        Error::MissingRecordsRoot(..) => "MissingRecordsRoot",
+        Error::InvalidPieceInclusionProof(_) => "InvalidPieceInclusionProof",
+        Error::GenesisPieceSolutionAfterCutover => "GenesisPieceSolutionAfterCutover",
+        _ => "Other",
+    }
+}
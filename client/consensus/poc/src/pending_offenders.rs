@@ -0,0 +1,59 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Cache of farmers caught equivocating by this node.
+//!
+//! Runtime block list only takes effect once an equivocation report is included in a block, until
+//! then equivocating farmer would be able to keep producing blocks. Offenders are tracked here
+//! from the moment equivocation is detected, such that this node neither builds on their solutions
+//! nor imports their blocks in the meantime.
+
+use parking_lot::Mutex;
+use sp_consensus_poc::FarmerId;
+use sp_consensus_slots::Slot;
+use std::{collections::HashMap, sync::Arc};
+
+/// Number of slots after equivocation during which offender is considered pending. Equivocation
+/// report is expected to be included in a block long before that, after which the runtime block
+/// list takes over.
+pub const PENDING_OFFENDERS_RETENTION: u64 = 256;
+
+/// Farmers whose equivocation reports may not be included in a block yet, shared between the
+/// authoring logic and the import queue.
+#[derive(Debug, Clone, Default)]
+pub struct PendingOffenders {
+    offenders: Arc<Mutex<HashMap<FarmerId, Slot>>>,
+}
+
+impl PendingOffenders {
+    /// Record `offender` that equivocated at `slot`.
+    pub fn insert(&self, offender: FarmerId, slot: Slot) {
+        let mut offenders = self.offenders.lock();
+        let entry = offenders.entry(offender).or_insert(slot);
+        if *entry < slot {
+            *entry = slot;
+        }
+    }
+
+    /// Check whether `farmer_id` is a pending offender as of `slot_now`, pruning expired entries.
+    pub fn contains(&self, farmer_id: &FarmerId, slot_now: Slot) -> bool {
+        let mut offenders = self.offenders.lock();
+        offenders.retain(|_, slot| slot_now < slot.saturating_add(PENDING_OFFENDERS_RETENTION));
+        offenders.contains_key(farmer_id)
+    }
+}
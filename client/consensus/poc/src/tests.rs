@@ -34,9 +34,17 @@ use sc_network::config::ProtocolConfig;
 use sc_network_test::{Block as TestBlock, *};
 use schnorrkel::{Keypair, PublicKey};
 use sp_consensus::{AlwaysCanAuthor, DisableProofRecording, NoNetwork as DummyOracle, Proposal};
-use sp_consensus_poc::{inherents::InherentDataProvider, Slot};
-use sp_consensus_spartan::spartan::{
-    Piece, Tag, ENCODE_ROUNDS, GENESIS_PIECE_SEED, PIECE_SIZE, PRIME_SIZE_BYTES,
+use sp_consensus_poc::{
+    digests::{PieceInclusionProof, SolutionVersion},
+    inherents::InherentDataProvider,
+    Slot,
+};
+use sp_consensus_spartan::{
+    merkle::MerkleTree,
+    spartan::{
+        Piece, Tag, ENCODE_ROUNDS, GENESIS_PIECE_SEED, PIECE_SIZE, PRIME_SIZE_BYTES,
+        SIGNING_CONTEXT,
+    },
 };
 use sp_core::Public;
 use sp_runtime::{
@@ -384,10 +392,13 @@ impl TestNetFactory for PoCTestNet {
                 }),
                 config: data.link.config.clone(),
                 epoch_changes: data.link.epoch_changes.clone(),
+                pending_offenders: data.link.pending_offenders.clone(),
                 can_author_with: AlwaysCanAuthor,
                 telemetry: None,
-                spartan: Spartan::new(),
-                signing_context: schnorrkel::context::signing_context(SIGNING_CONTEXT),
+                metrics: Default::default(),
+                context_free_verifier: ContextFreeVerifier::new(&Default::default(), 1),
+                context_free_verified: HashSet::new(),
+                block_body_fetcher: None,
             },
             mutator: MUTATOR.with(|m| m.borrow().clone()),
         }
@@ -504,35 +515,56 @@ fn run_one_test(mutator: impl Fn(&mut TestHeader, Stage) + Send + Sync + 'static
             justification_sync_link: (),
             block_proposal_slot_portion: SlotProportion::new(0.5),
             max_block_proposal_slot_portion: None,
+            registry: None,
             telemetry: None,
         })
         .expect("Starts poc");
 
-        let notifier = poc_worker.get_new_slot_notifier()();
-        std::thread::spawn(move || {
-            let spartan = spartan_codec::Spartan::<PRIME_SIZE_BYTES, PIECE_SIZE>::new(
-                genesis_piece_from_seed(GENESIS_PIECE_SEED),
-            );
-            let keypair = Keypair::generate();
-            let public_key_hash = hash_public_key(&keypair.public);
-            let ctx = schnorrkel::context::signing_context(SIGNING_CONTEXT);
-            let nonce = 0;
-            let encoding: Piece = spartan.encode(public_key_hash, nonce, ENCODE_ROUNDS);
-
-            while let Ok((new_slot_info, solution_sender)) = notifier.recv() {
-                if Into::<u64>::into(new_slot_info.slot) % 3 == (*peer_id) as u64 {
-                    let tag: Tag = create_tag(&encoding, &new_slot_info.salt);
-
-                    let _ = solution_sender.send((
-                        Solution {
+        let keypair = Keypair::generate();
+
+        let mut notifier = poc_worker.get_new_slot_notifier()();
+        std::thread::spawn({
+            let keypair = keypair.clone();
+
+            move || {
+                let spartan = spartan_codec::Spartan::<PRIME_SIZE_BYTES, PIECE_SIZE>::new(
+                    genesis_piece_from_seed(GENESIS_PIECE_SEED),
+                );
+                let public_key_hash = hash_public_key(&keypair.public);
+                let ctx = schnorrkel::context::signing_context(SIGNING_CONTEXT);
+                let nonce = 0;
+                let encoding: Piece = spartan.encode(public_key_hash, nonce, ENCODE_ROUNDS);
+
+                while let Some((new_slot_info, solution_sender)) = block_on(notifier.next()) {
+                    if Into::<u64>::into(new_slot_info.slot) % 3 == (*peer_id) as u64 {
+                        let tag: Tag = create_tag(&encoding, &new_slot_info.salt);
+
+                        let _ = solution_sender.unbounded_send(Solution {
                             public_key: FarmerId::from_slice(&keypair.public.to_bytes()),
                             nonce,
                             encoding: encoding.to_vec(),
                             signature: keypair.sign(ctx.bytes(&tag)).to_bytes().to_vec(),
                             tag,
-                        },
-                        keypair.secret.to_bytes().into(),
-                    ));
+                            piece_inclusion_proof: None,
+                            solution_data_commitment: None,
+                        });
+                    }
+                }
+            }
+        });
+
+        let mut block_signing_notifier = poc_worker.get_block_signing_notifier()();
+        std::thread::spawn(move || {
+            let pair = sp_core::sr25519::Pair::from(keypair.secret.clone());
+            let public_key = FarmerId::from_slice(&keypair.public.to_bytes());
+
+            while let Some((block_signing_info, signature_sender)) =
+                block_on(block_signing_notifier.next())
+            {
+                if block_signing_info.public_key == public_key {
+                    let _ = signature_sender.unbounded_send(
+                        sp_core::Pair::sign(&pair, &block_signing_info.header_hash).into(),
+                    );
                 }
             }
         });
@@ -653,6 +685,290 @@ fn sig_is_not_pre_digest() {
     assert!(bad_seal.as_poc_seal().is_some())
 }
 
+#[test]
+fn context_free_verifier_rejects_bad_seals() {
+    sp_tracing::try_init_simple();
+    let farmer_keypair = Keypair::generate();
+    let other_keypair = Keypair::generate();
+
+    let mut header = TestHeader::new(
+        1,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    );
+    header.digest_mut().push(Item::poc_pre_digest(PreDigest {
+        slot: 1.into(),
+        solution: Solution {
+            public_key: FarmerId::from_slice(&farmer_keypair.public.to_bytes()),
+            nonce: 0,
+            encoding: vec![0u8; 4096],
+            signature: vec![0u8; 64],
+            tag: Default::default(),
+            piece_inclusion_proof: None,
+            solution_data_commitment: None,
+        },
+    }));
+    let unsealed_header = header.clone();
+
+    let pre_hash = header.hash();
+    let signature = other_keypair
+        .sign(schnorrkel::context::signing_context(b"substrate").bytes(pre_hash.as_ref()))
+        .to_bytes()
+        .to_vec();
+    header
+        .digest_mut()
+        .push(Item::poc_seal(signature.try_into().unwrap()));
+
+    let verifier = ContextFreeVerifier::new(&Default::default(), 2);
+    assert!(matches!(
+        verifier.verify_header::<TestBlock>(&unsealed_header),
+        Err(crate::Error::HeaderBadSeal(_))
+    ));
+    assert!(matches!(
+        verifier.verify_header::<TestBlock>(&header),
+        Err(crate::Error::BadSignature(_))
+    ));
+    assert!(verifier
+        .verify_headers::<TestBlock>(vec![unsealed_header, header])
+        .is_empty());
+}
+
+#[test]
+fn solution_distance_wraps_around() {
+    let keypair = Keypair::generate();
+    let global_challenge = [1u8; 8];
+    let local_challenge =
+        derive_local_challenge(&global_challenge, &hash_public_key(&keypair.public));
+    let target = u64::from_be_bytes(local_challenge);
+    let solution_with_tag = |tag: u64| Solution {
+        public_key: FarmerId::from_slice(&keypair.public.to_bytes()),
+        nonce: 0,
+        encoding: vec![],
+        signature: vec![],
+        tag: tag.to_be_bytes(),
+        piece_inclusion_proof: None,
+        solution_data_commitment: None,
+    };
+
+    assert_eq!(
+        verification::solution_distance(&solution_with_tag(target), global_challenge),
+        0
+    );
+    assert_eq!(
+        verification::solution_distance(
+            &solution_with_tag(target.wrapping_sub(5)),
+            global_challenge
+        ),
+        5
+    );
+    assert_eq!(
+        verification::solution_distance(
+            &solution_with_tag(target.wrapping_add(5)),
+            global_challenge
+        ),
+        5
+    );
+    assert_eq!(
+        verification::solution_distance(
+            &solution_with_tag(target.wrapping_add(u64::MAX / 2 + 1)),
+            global_challenge
+        ),
+        u64::MAX / 2 + 1
+    );
+}
+
+#[test]
+fn piece_inclusion_proof_is_verified() {
+    let keypair = Keypair::generate();
+    let public_key = FarmerId::from_slice(&keypair.public.to_bytes());
+    let spartan = Spartan::default();
+    let signing_context = schnorrkel::context::signing_context(SIGNING_CONTEXT);
+    let salt = [1u8; 8];
+    let epoch = Epoch {
+        start_slot: 0.into(),
+        randomness: [0; 32],
+        epoch_index: 0,
+        duration: 100,
+        config: PoCEpochConfiguration { c: (3, 10) },
+    };
+
+    let pieces: Vec<Piece> = (0..PIECES_PER_SEGMENT)
+        .map(|position| [position as u8; PIECE_SIZE])
+        .collect();
+    let merkle_tree = MerkleTree::from_leaves(pieces.iter().map(|piece| &piece[..]));
+    let position = 5;
+    let piece_index = PIECES_PER_SEGMENT as u64 + position as u64;
+
+    let encoding = spartan.encode_piece(pieces[position], public_key.as_ref(), piece_index);
+    let tag = create_tag(&encoding, &salt);
+    let solution = Solution {
+        public_key,
+        nonce: piece_index,
+        encoding: encoding.to_vec(),
+        signature: keypair
+            .sign(signing_context.bytes(&tag))
+            .to_bytes()
+            .to_vec(),
+        tag,
+        piece_inclusion_proof: Some(PieceInclusionProof {
+            piece_index,
+            piece: pieces[position].to_vec(),
+            merkle_proof: merkle_tree.proof(position).unwrap(),
+        }),
+        solution_data_commitment: None,
+    };
+
+    let verify = |solution: &Solution, records_root| {
+        verification::verify_solution::<TestBlock>(
+            solution,
+            &epoch,
+            u64::MAX,
+            1.into(),
+            salt,
+            records_root,
+            &spartan,
+            &signing_context,
+        )
+    };
+
+    assert!(verify(&solution, Some(merkle_tree.root())).is_ok());
+    assert!(matches!(
+        verify(&solution, None),
+        Err(crate::Error::MissingRecordsRoot(_, 1))
+    ));
+
+    let mut solution_with_wrong_proof = solution.clone();
+    solution_with_wrong_proof
+        .piece_inclusion_proof
+        .as_mut()
+        .unwrap()
+        .merkle_proof = merkle_tree.proof(position + 1).unwrap();
+    assert!(matches!(
+        verify(&solution_with_wrong_proof, Some(merkle_tree.root())),
+        Err(crate::Error::InvalidPieceInclusionProof(_))
+    ));
+
+    // Archived pieces must be encoded with piece index as nonce
+    let mut solution_with_wrong_nonce = solution.clone();
+    solution_with_wrong_nonce.nonce += 1;
+    assert!(matches!(
+        verify(&solution_with_wrong_nonce, Some(merkle_tree.root())),
+        Err(crate::Error::InvalidEncoding(_))
+    ));
+
+    // Both versions of solutions round-trip through encoding, even when followed by other data
+    assert_eq!(solution.version(), SolutionVersion::ArchivedPiece);
+    let (decoded_solution, trailing) =
+        <(Solution, u32)>::decode(&mut (solution.clone(), 42u32).encode().as_slice()).unwrap();
+    assert_eq!(
+        decoded_solution.piece_inclusion_proof,
+        solution.piece_inclusion_proof
+    );
+    assert_eq!(trailing, 42);
+
+    let genesis_solution = Solution {
+        piece_inclusion_proof: None,
+        ..solution
+    };
+    assert_eq!(genesis_solution.version(), SolutionVersion::GenesisPiece);
+    let (decoded_solution, trailing) =
+        <(Solution, u32)>::decode(&mut (genesis_solution.clone(), 42u32).encode().as_slice())
+            .unwrap();
+    assert_eq!(decoded_solution.encoding, genesis_solution.encoding);
+    assert!(decoded_solution.piece_inclusion_proof.is_none());
+    assert_eq!(trailing, 42);
+
+    // Version is the first byte and unknown versions are rejected
+    let mut encoded_solution = genesis_solution.encode();
+    assert_eq!(encoded_solution[0], 0);
+    encoded_solution[0] = 3;
+    assert!(Solution::decode(&mut encoded_solution.as_slice()).is_err());
+}
+
+#[test]
+fn compact_solution_is_verified() {
+    let keypair = Keypair::generate();
+    let public_key = FarmerId::from_slice(&keypair.public.to_bytes());
+    let spartan = Spartan::default();
+    let signing_context = schnorrkel::context::signing_context(SIGNING_CONTEXT);
+    let salt = [1u8; 8];
+    let epoch = Epoch {
+        start_slot: 0.into(),
+        randomness: [0; 32],
+        epoch_index: 0,
+        duration: 100,
+        config: PoCEpochConfiguration { c: (3, 10) },
+    };
+
+    let encoding = spartan.encode(public_key.as_ref(), 0);
+    let tag = create_tag(&encoding, &salt);
+    let solution = Solution {
+        public_key,
+        nonce: 0,
+        encoding: encoding.to_vec(),
+        signature: keypair
+            .sign(signing_context.bytes(&tag))
+            .to_bytes()
+            .to_vec(),
+        tag,
+        piece_inclusion_proof: None,
+        solution_data_commitment: None,
+    };
+
+    let verify = |solution: &Solution| {
+        verification::verify_solution::<TestBlock>(
+            solution,
+            &epoch,
+            u64::MAX,
+            1.into(),
+            salt,
+            None,
+            &spartan,
+            &signing_context,
+        )
+    };
+
+    // Compact solution only keeps a commitment to solution data and round-trips through encoding
+    let compact_solution = solution.to_compact();
+    assert!(compact_solution.is_compact());
+    assert!(compact_solution.encoding.is_empty());
+    let decoded_solution = Solution::decode(&mut compact_solution.encode().as_slice()).unwrap();
+    assert_eq!(
+        decoded_solution.solution_data_commitment,
+        compact_solution.solution_data_commitment
+    );
+    assert!(decoded_solution.piece_inclusion_proof.is_none());
+
+    // Compact solution can't be verified without solution data
+    assert!(matches!(
+        verify(&compact_solution),
+        Err(crate::Error::EncodingOfWrongSize)
+    ));
+
+    // Full solution can only be restored with solution data matching the commitment, which also
+    // commits to the tag
+    let mut wrong_solution_data = solution.solution_data();
+    wrong_solution_data.encoding[0] ^= 1;
+    assert!(compact_solution
+        .clone()
+        .with_solution_data(wrong_solution_data)
+        .is_none());
+    let mut compact_solution_with_wrong_tag = compact_solution.clone();
+    compact_solution_with_wrong_tag.tag[0] ^= 1;
+    assert!(compact_solution_with_wrong_tag
+        .with_solution_data(solution.solution_data())
+        .is_none());
+
+    let restored_solution = compact_solution
+        .with_solution_data(solution.solution_data())
+        .unwrap();
+    assert!(!restored_solution.is_compact());
+    assert_eq!(restored_solution.encoding, solution.encoding);
+    assert!(verify(&restored_solution).is_ok());
+}
+
 /// Claims the given slot number. always returning a dummy block.
 pub fn dummy_claim_slot(slot: Slot, _epoch: &Epoch) -> Option<(PreDigest, FarmerId)> {
     return Some((
@@ -663,6 +979,8 @@ pub fn dummy_claim_slot(slot: Slot, _epoch: &Epoch) -> Option<(PreDigest, Farmer
                 encoding: vec![],
                 signature: vec![],
                 tag: Default::default(),
+                piece_inclusion_proof: None,
+                solution_data_commitment: None,
             },
             slot,
         },
@@ -688,6 +1006,8 @@ fn can_author_block() {
         epoch_length: 100,
         c: (3, 10),
         randomness: [0; 32],
+        spartan_parameters: Default::default(),
+        compact_pre_digest: false,
     };
 
     // we might need to try a couple of times
@@ -709,8 +1029,6 @@ fn propose_and_import_block<Transaction: Send + 'static>(
     proposer_factory: &mut DummyFactory,
     block_import: &mut BoxBlockImport<TestBlock, Transaction>,
 ) -> sp_core::H256 {
-    let mut proposer = futures::executor::block_on(proposer_factory.init(parent)).unwrap();
-
     let slot = slot.unwrap_or_else(|| {
         let parent_pre_digest = find_pre_digest::<TestBlock>(parent).unwrap();
         parent_pre_digest.slot + 1
@@ -719,27 +1037,47 @@ fn propose_and_import_block<Transaction: Send + 'static>(
     let keypair = Keypair::generate();
     let ctx = schnorrkel::context::signing_context(SIGNING_CONTEXT);
 
-    let (pre_digest, signature) = {
+    let solution = {
         let encoding: Piece = [0u8; 4096];
         let tag: Tag = [0u8; 8];
 
         let signature = keypair.sign(ctx.bytes(&tag)).to_bytes().to_vec();
 
-        (
-            sp_runtime::generic::Digest {
-                logs: vec![Item::poc_pre_digest(PreDigest {
-                    slot,
-                    solution: Solution {
-                        public_key: FarmerId::from_slice(&keypair.public.to_bytes()),
-                        nonce: 0,
-                        encoding: encoding.to_vec(),
-                        signature: signature.clone(),
-                        tag,
-                    },
-                })],
-            },
+        Solution {
+            public_key: FarmerId::from_slice(&keypair.public.to_bytes()),
+            nonce: 0,
+            encoding: encoding.to_vec(),
             signature,
-        )
+            tag,
+            piece_inclusion_proof: None,
+            solution_data_commitment: None,
+        }
+    };
+
+    propose_and_import_block_with_solution(
+        parent,
+        slot,
+        &keypair,
+        solution,
+        proposer_factory,
+        block_import,
+    )
+}
+
+// Propose and import a new PoC block on top of the given parent, authored by the farmer with
+// `keypair` using `solution`.
+fn propose_and_import_block_with_solution<Transaction: Send + 'static>(
+    parent: &TestHeader,
+    slot: Slot,
+    keypair: &Keypair,
+    solution: Solution,
+    proposer_factory: &mut DummyFactory,
+    block_import: &mut BoxBlockImport<TestBlock, Transaction>,
+) -> sp_core::H256 {
+    let mut proposer = futures::executor::block_on(proposer_factory.init(parent)).unwrap();
+
+    let pre_digest = sp_runtime::generic::Digest {
+        logs: vec![Item::poc_pre_digest(PreDigest { slot, solution })],
     };
 
     let parent_hash = parent.hash();
@@ -760,7 +1098,16 @@ fn propose_and_import_block<Transaction: Send + 'static>(
         .unwrap()
         .unwrap();
 
-    let seal = Item::poc_seal(signature.try_into().unwrap());
+    let seal = {
+        let signature = keypair
+            .sign(
+                schnorrkel::context::signing_context(b"substrate")
+                    .bytes(block.header.hash().as_ref()),
+            )
+            .to_bytes()
+            .to_vec();
+        Item::poc_seal(signature.try_into().unwrap())
+    };
 
     let post_hash = {
         block.header.digest_mut().push(seal.clone());
@@ -787,6 +1134,41 @@ fn propose_and_import_block<Transaction: Send + 'static>(
     post_hash
 }
 
+#[test]
+fn config_cached_in_legacy_encoding_is_recomputed() {
+    let client = substrate_test_runtime_client::new();
+
+    let legacy_config = sp_consensus_poc::PoCGenesisConfigurationV1 {
+        slot_duration: 2000,
+        epoch_length: 10,
+        c: (1, 4),
+        randomness: [1; 32],
+    };
+    client
+        .insert_aux(
+            &[(&b"poc_configuration"[..], &legacy_config.encode()[..])],
+            &[],
+        )
+        .unwrap();
+
+    let config = Config::get_or_compute(&client).expect("config available");
+
+    let runtime_config = client
+        .runtime_api()
+        .configuration(&BlockId::Number(0))
+        .unwrap();
+    assert_eq!(*config, runtime_config);
+
+    let cached_config = client
+        .get_aux(<PoCGenesisConfiguration as SlotData>::SLOT_KEY)
+        .unwrap()
+        .expect("config is cached");
+    assert_eq!(
+        PoCGenesisConfiguration::decode(&mut &cached_config[..]).unwrap(),
+        runtime_config,
+    );
+}
+
 #[test]
 fn importing_block_one_sets_genesis_epoch() {
     let mut net = PoCTestNet::new(1);
@@ -841,6 +1223,61 @@ fn importing_block_one_sets_genesis_epoch() {
     assert_eq!(epoch_for_second_block, genesis_epoch);
 }
 
+#[test]
+fn importing_own_block_records_farmer_claim_for_slot() {
+    let mut net = PoCTestNet::new(1);
+
+    let peer = net.peer(0);
+    let data = peer
+        .data
+        .as_ref()
+        .expect("poc link set up during initialization");
+    let client = peer
+        .client()
+        .as_full()
+        .expect("Only full clients are used in tests")
+        .clone();
+
+    let mut proposer_factory = DummyFactory {
+        client: client.clone(),
+        config: data.link.config.clone(),
+        epoch_changes: data.link.epoch_changes.clone(),
+        mutator: Arc::new(|_, _| ()),
+    };
+
+    let mut block_import = data
+        .block_import
+        .lock()
+        .take()
+        .expect("import set up during init");
+
+    let genesis_header = client.header(&BlockId::Number(0)).unwrap().unwrap();
+
+    let block_hash = propose_and_import_block(
+        &genesis_header,
+        Some(999.into()),
+        &mut proposer_factory,
+        &mut block_import,
+    );
+
+    let header = client.header(&BlockId::Hash(block_hash)).unwrap().unwrap();
+    let farmer_id = find_pre_digest::<TestBlock>(&header)
+        .unwrap()
+        .solution
+        .public_key;
+
+    // the farmer has a block at this slot now, so authoring another one with the same farmer at
+    // the same slot is refused
+    assert!(has_header_at_slot::<_, TestHeader, _>(&*client, 999.into(), &farmer_id).unwrap());
+
+    // but not at a different slot or by a different farmer
+    assert!(!has_header_at_slot::<_, TestHeader, _>(&*client, 1000.into(), &farmer_id).unwrap());
+    let other_farmer_id = FarmerId::from_slice(&Keypair::generate().public.to_bytes());
+    assert!(
+        !has_header_at_slot::<_, TestHeader, _>(&*client, 999.into(), &other_farmer_id).unwrap()
+    );
+}
+
 #[test]
 fn importing_epoch_change_block_prunes_tree() {
     use sc_client_api::Finalizer;
@@ -972,6 +1409,415 @@ fn importing_epoch_change_block_prunes_tree() {
         .any(|h| fork_3.contains(h)),);
 }
 
+// Set up a block import with fork choice tie-breaking enabled on top of the peer's client.
+fn tie_breaking_block_import(
+    client: Arc<TestClient>,
+    config: &Config,
+) -> (
+    BoxBlockImport<TestBlock, TransactionFor<substrate_test_runtime_client::Backend, TestBlock>>,
+    DummyFactory,
+) {
+    let (block_import, link) = crate::block_import(
+        config.clone().with_fork_choice_tie_breaking(true),
+        client.clone(),
+        client.clone(),
+    )
+    .expect("can initialize block-import");
+
+    let proposer_factory = DummyFactory {
+        client,
+        config: link.config.clone(),
+        epoch_changes: link.epoch_changes.clone(),
+        mutator: Arc::new(|_, _| ()),
+    };
+
+    (Box::new(block_import), proposer_factory)
+}
+
+#[test]
+fn equal_weight_fork_with_lower_solution_distance_becomes_best() {
+    let mut net = PoCTestNet::new(1);
+
+    let peer = net.peer(0);
+    let data = peer
+        .data
+        .as_ref()
+        .expect("poc link set up during initialization");
+    let client = peer
+        .client()
+        .as_full()
+        .expect("Only full clients are used in tests")
+        .clone();
+
+    let (mut block_import, mut proposer_factory) =
+        tie_breaking_block_import(client.clone(), &data.link.config);
+
+    let genesis_header = client.header(&BlockId::Number(0)).unwrap().unwrap();
+
+    // every child of genesis has the same weight and number, so the best block must always be
+    // the one whose solution is closest to its local challenge, ties going to the lowest hash
+    let mut siblings = Vec::new();
+    for _ in 0..5 {
+        let block_hash = propose_and_import_block(
+            &genesis_header,
+            Some(999.into()),
+            &mut proposer_factory,
+            &mut block_import,
+        );
+        let solution_distance =
+            aux_schema::load_block_solution_distance(&*client, 1u64, block_hash)
+                .unwrap()
+                .expect("solution distance is stored on import");
+        siblings.push((solution_distance, block_hash));
+
+        let (_, expected_best) = siblings.iter().min().copied().unwrap();
+        assert_eq!(client.info().best_hash, expected_best);
+    }
+}
+
+#[test]
+fn finalization_prunes_solution_distances() {
+    use sc_client_api::Finalizer;
+
+    let mut net = PoCTestNet::new(1);
+
+    let peer = net.peer(0);
+    let data = peer
+        .data
+        .as_ref()
+        .expect("poc link set up during initialization");
+    let client = peer
+        .client()
+        .as_full()
+        .expect("Only full clients are used in tests")
+        .clone();
+
+    let (mut block_import, mut proposer_factory) =
+        tie_breaking_block_import(client.clone(), &data.link.config);
+
+    let mut parent_header = client.header(&BlockId::Number(0)).unwrap().unwrap();
+    let mut hashes = Vec::new();
+    for _ in 0..3 {
+        let block_hash = propose_and_import_block(
+            &parent_header,
+            None,
+            &mut proposer_factory,
+            &mut block_import,
+        );
+        hashes.push(block_hash);
+        parent_header = client.header(&BlockId::Hash(block_hash)).unwrap().unwrap();
+    }
+
+    for (number, hash) in (1u64..).zip(&hashes) {
+        assert!(
+            aux_schema::load_block_solution_distance(&*client, number, *hash)
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    // distances are pruned when importing the first block after finalization
+    client
+        .finalize_block(BlockId::Hash(hashes[1]), None, false)
+        .unwrap();
+    let block_hash = propose_and_import_block(
+        &parent_header,
+        None,
+        &mut proposer_factory,
+        &mut block_import,
+    );
+    hashes.push(block_hash);
+
+    for number in 1u64..=2 {
+        assert!(
+            aux_schema::load_block_solution_distances::<_, sp_core::H256, _>(&*client, number)
+                .unwrap()
+                .is_empty()
+        );
+    }
+    for (number, hash) in (3u64..).zip(&hashes[2..]) {
+        assert!(
+            aux_schema::load_block_solution_distance(&*client, number, *hash)
+                .unwrap()
+                .is_some()
+        );
+    }
+}
+
+#[test]
+fn revert_removes_epoch_changes_and_block_aux_data() {
+    let mut net = PoCTestNet::new(1);
+
+    let peer = net.peer(0);
+    let data = peer
+        .data
+        .as_ref()
+        .expect("poc link set up during initialization");
+    let (client, backend) = match peer.client() {
+        PeersClient::Full(client, backend) => (client.clone(), backend.clone()),
+        PeersClient::Light(..) => unreachable!("Only full clients are used in tests"),
+    };
+    let mut block_import = data
+        .block_import
+        .lock()
+        .take()
+        .expect("import set up during init");
+
+    let mut proposer_factory = DummyFactory {
+        client: client.clone(),
+        config: data.link.config.clone(),
+        epoch_changes: data.link.epoch_changes.clone(),
+        mutator: Arc::new(|_, _| ()),
+    };
+
+    let mut propose_and_import_blocks = |parent_id, n| {
+        let mut hashes = Vec::new();
+        let mut parent_header = client.header(&parent_id).unwrap().unwrap();
+
+        for _ in 0..n {
+            let block_hash = propose_and_import_block(
+                &parent_header,
+                None,
+                &mut proposer_factory,
+                &mut block_import,
+            );
+            hashes.push(block_hash);
+            parent_header = client.header(&BlockId::Hash(block_hash)).unwrap().unwrap();
+        }
+
+        hashes
+    };
+
+    // The epoch duration is 6 slots, so epochs change at #1, #7, #13 and #19 of the canon chain
+    // and at #13 of the fork.
+    //
+    // (#1) - ... - (#9) - (#10) - ... - (#21)
+    //                         \
+    //                          *------ (#11) - ... - (#15)
+    let canon_hashes = propose_and_import_blocks(BlockId::Number(0), 21);
+    let fork_hashes = propose_and_import_blocks(BlockId::Hash(canon_hashes[9]), 5);
+
+    assert_eq!(client.info().best_hash, canon_hashes[20]);
+
+    // revert down to #9
+    crate::revert(client.clone(), backend, 12).unwrap();
+
+    let retained = &canon_hashes[..9];
+    let reverted = canon_hashes[9..].iter().chain(&fork_hashes);
+
+    let epoch_changes =
+        aux_schema::load_epoch_changes::<TestBlock, _>(&*client, &data.link.config).unwrap();
+    let epoch_change_hashes = epoch_changes
+        .shared_data()
+        .tree()
+        .iter()
+        .map(|(hash, _, _)| *hash)
+        .collect::<Vec<_>>();
+    assert_eq!(epoch_change_hashes.len(), 2);
+    assert!(epoch_change_hashes
+        .iter()
+        .all(|hash| retained.contains(hash)));
+
+    for (number, hash) in (1u64..).zip(retained) {
+        assert!(aux_schema::load_block_weight(&*client, hash)
+            .unwrap()
+            .is_some());
+        assert!(aux_schema::load_solution_range_and_salt(&*client, hash)
+            .unwrap()
+            .is_some());
+        assert!(
+            aux_schema::load_block_solution_distance(&*client, number, *hash)
+                .unwrap()
+                .is_some()
+        );
+    }
+    for hash in reverted {
+        let number = *client
+            .header(&BlockId::Hash(*hash))
+            .unwrap()
+            .unwrap()
+            .number();
+        assert!(aux_schema::load_block_weight(&*client, hash)
+            .unwrap()
+            .is_none());
+        assert!(aux_schema::load_solution_range_and_salt(&*client, hash)
+            .unwrap()
+            .is_none());
+        assert!(
+            aux_schema::load_block_solution_distance(&*client, number, *hash)
+                .unwrap()
+                .is_none()
+        );
+    }
+    // no empty solution distance entries are left behind either
+    for number in 10u64..=21 {
+        assert!(client
+            .get_aux(&aux_schema::block_solution_distances_key(number))
+            .unwrap()
+            .is_none());
+    }
+}
+
+#[test]
+fn warp_sync_proof_seeds_epoch_changes() {
+    use sc_client_api::Finalizer;
+
+    let mut net = PoCTestNet::new(1);
+
+    let peer = net.peer(0);
+    let data = peer
+        .data
+        .as_ref()
+        .expect("poc link set up during initialization");
+
+    let client = peer
+        .client()
+        .as_full()
+        .expect("Only full clients are used in tests")
+        .clone();
+    let mut block_import = data
+        .block_import
+        .lock()
+        .take()
+        .expect("import set up during init");
+    let epoch_changes = data.link.epoch_changes.clone();
+
+    let mut proposer_factory = DummyFactory {
+        client: client.clone(),
+        config: data.link.config.clone(),
+        epoch_changes: data.link.epoch_changes.clone(),
+        // like the runtime, only declare solution range and salt when they change
+        mutator: Arc::new(|header, stage| {
+            if stage == Stage::PreSeal && *header.number() != 1 {
+                header.digest_mut().logs.retain(|log| {
+                    !matches!(
+                        log.try_to::<ConsensusLog>(OpaqueDigestItemId::Consensus(&POC_ENGINE_ID)),
+                        Some(ConsensusLog::SolutionRangeData(_)) | Some(ConsensusLog::SaltData(_))
+                    )
+                });
+            }
+        }),
+    };
+
+    // Warp sync verifies solutions, so blocks are authored with a valid one, test runtime uses `0`
+    // as salt
+    let keypair = Keypair::generate();
+    let public_key = FarmerId::from_slice(&keypair.public.to_bytes());
+    let encoding = Spartan::default().encode(public_key.as_ref(), 0);
+    let tag = create_tag(&encoding, &0u64.to_le_bytes());
+    let solution = Solution {
+        public_key,
+        nonce: 0,
+        encoding: encoding.to_vec(),
+        signature: keypair
+            .sign(schnorrkel::context::signing_context(SIGNING_CONTEXT).bytes(&tag))
+            .to_bytes()
+            .to_vec(),
+        tag,
+        piece_inclusion_proof: None,
+        solution_data_commitment: None,
+    };
+
+    let genesis_header = client.header(&BlockId::Number(0)).unwrap().unwrap();
+    let mut parent_header = genesis_header.clone();
+    let mut hashes = Vec::new();
+    for _ in 0..30 {
+        let block_hash = propose_and_import_block_with_solution(
+            &parent_header,
+            find_pre_digest::<TestBlock>(&parent_header).unwrap().slot + 1,
+            &keypair,
+            solution.clone(),
+            &mut proposer_factory,
+            &mut block_import,
+        );
+        hashes.push(block_hash);
+        parent_header = client.header(&BlockId::Hash(block_hash)).unwrap().unwrap();
+    }
+
+    // Epochs change at blocks #1, #7, #13, #19 and #25, solution range and salt are only declared
+    // by block #1
+    assert_eq!(
+        aux_schema::load_warp_sync_block_numbers::<u64, _>(&*client).unwrap(),
+        vec![1, 7, 13, 19, 25],
+    );
+
+    // Warp sync ends at the finalized block #24
+    let target_hash = hashes[23];
+    client
+        .finalize_block(BlockId::Hash(target_hash), None, false)
+        .unwrap();
+
+    assert!(matches!(
+        warp_sync::WarpSyncProof::<TestBlock>::generate(
+            &*client,
+            genesis_header.hash(),
+            hashes[29]
+        ),
+        Err(crate::Error::InvalidWarpSyncRequest(_))
+    ));
+
+    let proof = warp_sync::WarpSyncProof::<TestBlock>::generate(
+        &*client,
+        genesis_header.hash(),
+        target_hash,
+    )
+    .unwrap();
+    assert!(proof.is_finished());
+
+    // Proof must end at the checkpoint
+    assert!(matches!(
+        proof.verify(
+            &data.link.config,
+            hashes[22],
+            warp_sync::WarpSyncState::new(genesis_header.clone()),
+        ),
+        Err(crate::Error::InvalidWarpSyncProof(_))
+    ));
+
+    let state = proof
+        .verify(
+            &data.link.config,
+            target_hash,
+            warp_sync::WarpSyncState::new(genesis_header),
+        )
+        .unwrap();
+    assert_eq!(state.header.hash(), target_hash);
+    assert_eq!(state.solution_range, Some(u64::MAX));
+    assert_eq!(state.salt, Some(0));
+
+    let (epoch, next_epoch) = state.epochs.clone().unwrap();
+    assert_eq!(epoch.epoch_index, 3);
+    assert_eq!(next_epoch.epoch_index, 4);
+
+    let block_weight = aux_schema::load_block_weight(&*client, target_hash).unwrap();
+
+    let seeded_epoch_changes = SharedEpochChanges::<TestBlock, Epoch>::new(Default::default());
+    warp_sync::import_warp_sync_state(&*client, &seeded_epoch_changes, &state).unwrap();
+
+    // Children of the last block of warp sync are verified under the same epochs as without warp
+    // sync, both in the current and in the next epoch
+    let epoch_for_child = |epoch_changes: &SharedEpochChanges<TestBlock, Epoch>, slot| {
+        epoch_changes
+            .shared_data()
+            .epoch_data_for_child_of(descendent_query(&*client), &target_hash, 24, slot, |slot| {
+                Epoch::genesis(&data.link.config, slot)
+            })
+            .unwrap()
+            .unwrap()
+    };
+    for slot in vec![epoch.start_slot, next_epoch.start_slot] {
+        assert_eq!(
+            epoch_for_child(&seeded_epoch_changes, slot),
+            epoch_for_child(&epoch_changes, slot),
+        );
+    }
+
+    assert_eq!(
+        aux_schema::load_block_weight(&*client, target_hash).unwrap(),
+        block_weight,
+    );
+}
+
 #[test]
 #[should_panic]
 fn verify_slots_are_strictly_increasing() {
@@ -1022,3 +1868,434 @@ fn verify_slots_are_strictly_increasing() {
         &mut block_import,
     );
 }
+
+// Verify a block built on top of genesis that declares given solution range and salt in its header.
+fn verify_block_with_solution_range_and_salt(
+    solution_range: u64,
+    salt: u64,
+) -> (
+    sp_core::H256,
+    Result<
+        (
+            BlockImportParams<TestBlock, ()>,
+            Option<Vec<(CacheKeyId, Vec<u8>)>>,
+        ),
+        String,
+    >,
+) {
+    verify_block(solution_range, salt, false)
+}
+
+/// Verifies block with given solution range and salt digests, optionally making its author a
+/// pending offender beforehand.
+fn verify_block(
+    solution_range: u64,
+    salt: u64,
+    is_pending_offender: bool,
+) -> (
+    sp_core::H256,
+    Result<
+        (
+            BlockImportParams<TestBlock, ()>,
+            Option<Vec<(CacheKeyId, Vec<u8>)>>,
+        ),
+        String,
+    >,
+) {
+    let net = PoCTestNet::new(1);
+
+    let peer = &net.peers()[0];
+    let data = peer
+        .data
+        .as_ref()
+        .expect("poc link set up during initialization");
+
+    let client = peer
+        .client()
+        .as_full()
+        .expect("Only full clients are used in tests")
+        .clone();
+
+    let mut verifier = net.make_verifier(
+        peer.client().clone(),
+        &PoCTestNet::default_config(),
+        &peer.data,
+    );
+
+    let mut proposer_factory = DummyFactory {
+        client: client.clone(),
+        config: data.link.config.clone(),
+        epoch_changes: data.link.epoch_changes.clone(),
+        mutator: Arc::new(move |header, stage| {
+            if stage == Stage::PreSeal {
+                header.digest_mut().logs.retain(|log| {
+                    !matches!(
+                        log.try_to::<ConsensusLog>(OpaqueDigestItemId::Consensus(&POC_ENGINE_ID)),
+                        Some(ConsensusLog::SolutionRangeData(_)) | Some(ConsensusLog::SaltData(_))
+                    )
+                });
+                header.digest_mut().push(DigestItem::Consensus(
+                    POC_ENGINE_ID,
+                    ConsensusLog::SolutionRangeData(SolutionRangeDescriptor { solution_range })
+                        .encode(),
+                ));
+                header.digest_mut().push(DigestItem::Consensus(
+                    POC_ENGINE_ID,
+                    ConsensusLog::SaltData(SaltDescriptor { salt }).encode(),
+                ));
+            }
+        }),
+    };
+
+    let genesis_header = client.header(&BlockId::Number(0)).unwrap().unwrap();
+    let mut proposer = block_on(proposer_factory.init(&genesis_header)).unwrap();
+
+    let keypair = Keypair::generate();
+    if is_pending_offender {
+        // same slot the verifier derives from the current time
+        let slot_now = InherentDataProvider::from_timestamp_and_duration(
+            *TimestampInherentDataProvider::from_system_time(),
+            Duration::from_secs(6),
+        )
+        .slot();
+        data.link
+            .pending_offenders
+            .insert(FarmerId::from_slice(&keypair.public.to_bytes()), slot_now);
+    }
+    let pre_digest = sp_runtime::generic::Digest {
+        logs: vec![Item::poc_pre_digest(PreDigest {
+            slot: 1.into(),
+            solution: Solution {
+                public_key: FarmerId::from_slice(&keypair.public.to_bytes()),
+                nonce: 0,
+                encoding: vec![0u8; PIECE_SIZE],
+                signature: vec![0u8; 64],
+                tag: [0u8; 8],
+                piece_inclusion_proof: None,
+                solution_data_commitment: None,
+            },
+        })],
+    };
+
+    let mut block = block_on(proposer.propose_with(pre_digest)).unwrap().block;
+
+    let pair = sp_core::sr25519::Pair::from(keypair.secret.clone());
+    let signature = sp_core::Pair::sign(&pair, block.header.hash().as_ref());
+    block
+        .header
+        .digest_mut()
+        .push(Item::poc_seal(signature.into()));
+    let hash = block.header.hash();
+
+    (
+        hash,
+        block_on(verifier.verify(
+            BlockOrigin::NetworkBroadcast,
+            block.header,
+            None,
+            Some(block.extrinsics),
+        )),
+    )
+}
+
+#[test]
+fn rejects_solution_range_not_derived_from_parent() {
+    // Test runtime uses `u64::MAX` as solution range and `0` as salt
+    let (hash, result) = verify_block_with_solution_range_and_salt(u64::MAX - 1, 0);
+
+    assert_eq!(
+        result.err(),
+        Some(crate::Error::<TestBlock>::InvalidSolutionRange(hash, u64::MAX, u64::MAX - 1).into()),
+    );
+}
+
+#[test]
+fn rejects_salt_not_derived_from_parent() {
+    // Test runtime uses `u64::MAX` as solution range and `0` as salt
+    let (hash, result) = verify_block_with_solution_range_and_salt(u64::MAX, 1);
+
+    assert_eq!(
+        result.err(),
+        Some(crate::Error::<TestBlock>::InvalidSalt(hash, 0, 1).into()),
+    );
+}
+
+#[test]
+fn verifies_solution_range_and_salt_announced_by_parent_chain() {
+    let net = PoCTestNet::new(1);
+
+    let peer = &net.peers()[0];
+    let data = peer
+        .data
+        .as_ref()
+        .expect("poc link set up during initialization");
+
+    let client = peer
+        .client()
+        .as_full()
+        .expect("Only full clients are used in tests")
+        .clone();
+
+    let mut block_import = data
+        .block_import
+        .lock()
+        .take()
+        .expect("import set up during init");
+
+    let mut verifier = net.make_verifier(
+        peer.client().clone(),
+        &PoCTestNet::default_config(),
+        &peer.data,
+    );
+
+    let mut proposer_factory = DummyFactory {
+        client: client.clone(),
+        config: data.link.config.clone(),
+        epoch_changes: data.link.epoch_changes.clone(),
+        // the first block announces solution range and salt of the blocks after it
+        mutator: Arc::new(|header, stage| {
+            if stage == Stage::PreSeal && *header.number() == 1 {
+                header.digest_mut().push(DigestItem::Consensus(
+                    POC_ENGINE_ID,
+                    ConsensusLog::NextSolutionRangeData(NextSolutionRangeDescriptor {
+                        solution_range: u64::MAX - 1,
+                    })
+                    .encode(),
+                ));
+                header.digest_mut().push(DigestItem::Consensus(
+                    POC_ENGINE_ID,
+                    ConsensusLog::EonSaltData(SaltDescriptor { salt: 1 }).encode(),
+                ));
+            }
+        }),
+    };
+
+    let genesis_header = client.header(&BlockId::Number(0)).unwrap().unwrap();
+    let block_1 = propose_and_import_block(
+        &genesis_header,
+        None,
+        &mut proposer_factory,
+        &mut block_import,
+    );
+    let block_1_header = client.header(&BlockId::Hash(block_1)).unwrap().unwrap();
+    let block_2 = propose_and_import_block(
+        &block_1_header,
+        None,
+        &mut proposer_factory,
+        &mut block_import,
+    );
+    let block_2_header = client.header(&BlockId::Hash(block_2)).unwrap().unwrap();
+
+    // Announced values are tracked for all blocks after the first one without the state
+    for hash in vec![block_1, block_2] {
+        assert_eq!(
+            aux_schema::load_solution_range_and_salt(&*client, hash).unwrap(),
+            Some(SolutionRangeAndSalt {
+                solution_range: u64::MAX - 1,
+                salt: 1,
+            }),
+        );
+    }
+
+    // Test runtime uses `u64::MAX` as solution range and `0` as salt, which proposer keeps
+    // declaring
+    let mut proposer = block_on(proposer_factory.init(&block_2_header)).unwrap();
+    let keypair = Keypair::generate();
+    let pre_digest = sp_runtime::generic::Digest {
+        logs: vec![Item::poc_pre_digest(PreDigest {
+            slot: 3.into(),
+            solution: Solution {
+                public_key: FarmerId::from_slice(&keypair.public.to_bytes()),
+                nonce: 0,
+                encoding: vec![0u8; PIECE_SIZE],
+                signature: vec![0u8; 64],
+                tag: [0u8; 8],
+                piece_inclusion_proof: None,
+                solution_data_commitment: None,
+            },
+        })],
+    };
+
+    let mut block = block_on(proposer.propose_with(pre_digest)).unwrap().block;
+
+    let pair = sp_core::sr25519::Pair::from(keypair.secret.clone());
+    let signature = sp_core::Pair::sign(&pair, block.header.hash().as_ref());
+    block
+        .header
+        .digest_mut()
+        .push(Item::poc_seal(signature.into()));
+    let hash = block.header.hash();
+
+    let result = block_on(verifier.verify(
+        BlockOrigin::NetworkBroadcast,
+        block.header,
+        None,
+        Some(block.extrinsics),
+    ));
+
+    assert_eq!(
+        result.err(),
+        Some(crate::Error::<TestBlock>::InvalidSolutionRange(hash, u64::MAX - 1, u64::MAX).into()),
+    );
+}
+
+#[test]
+fn light_client_imports_blocks_with_compact_solutions() {
+    use sc_client_api::BlockBackend;
+    use substrate_test_runtime_client::{
+        runtime::Extrinsic, ClientBlockImportExt, DefaultTestClientBuilderExt,
+    };
+
+    let full_client = Arc::new(substrate_test_runtime_client::new());
+    let (light_client, _) = substrate_test_runtime_client::new_light();
+    let light_client = Arc::new(light_client);
+
+    let config = Config::get_or_compute(&*light_client).expect("config available");
+    let (mut block_import, link) =
+        crate::block_import(config, light_client.clone(), light_client.clone())
+            .expect("can initialize block-import");
+
+    // light client fetches bodies of blocks with compact solutions from the full client
+    let block_body_fetcher: BlockBodyFetcher<TestBlock> = {
+        let full_client = full_client.clone();
+        Arc::new(move |header: TestHeader| {
+            future::ready(Ok(full_client
+                .block_body(&BlockId::Hash(header.hash()))
+                .unwrap()
+                .expect("block is imported by the full client")))
+            .boxed()
+        })
+    };
+    let create_inherent_data_providers: Box<
+        dyn CreateInherentDataProviders<
+            TestBlock,
+            (),
+            InherentDataProviders = (TimestampInherentDataProvider, InherentDataProvider),
+        >,
+    > = Box::new(|_, _| async {
+        let timestamp = TimestampInherentDataProvider::from_system_time();
+        let slot =
+            InherentDataProvider::from_timestamp_and_duration(*timestamp, Duration::from_secs(6));
+
+        Ok((timestamp, slot))
+    });
+    let (_, longest_chain) = TestClientBuilder::new().build_with_longest_chain();
+    let mut verifier = PoCVerifier {
+        client: light_client.clone(),
+        select_chain: longest_chain,
+        create_inherent_data_providers,
+        config: link.config.clone(),
+        epoch_changes: link.epoch_changes.clone(),
+        pending_offenders: link.pending_offenders.clone(),
+        can_author_with: AlwaysCanAuthor,
+        telemetry: None,
+        metrics: Default::default(),
+        context_free_verifier: ContextFreeVerifier::new(&Default::default(), 1),
+        context_free_verified: HashSet::new(),
+        block_body_fetcher: Some(block_body_fetcher),
+    };
+
+    let keypair = Keypair::generate();
+    let public_key = FarmerId::from_slice(&keypair.public.to_bytes());
+    let spartan = Spartan::default();
+    let signing_context = schnorrkel::context::signing_context(SIGNING_CONTEXT);
+    let encoding = spartan.encode(public_key.as_ref(), 0);
+    // Test runtime uses `u64::MAX` as solution range and `0` as salt
+    let tag = create_tag(&encoding, &0u64.to_le_bytes());
+    let solution = Solution {
+        public_key,
+        nonce: 0,
+        encoding: encoding.to_vec(),
+        signature: keypair
+            .sign(signing_context.bytes(&tag))
+            .to_bytes()
+            .to_vec(),
+        tag,
+        piece_inclusion_proof: None,
+        solution_data_commitment: None,
+    };
+
+    // Blocks carry compact solutions in the header and solution data in the body, like the inherent
+    // of the runtime would include it
+    let author_block = |parent_hash: Hash, slot: Slot| {
+        let pre_digest = sp_runtime::generic::Digest {
+            logs: vec![Item::poc_pre_digest(PreDigest {
+                slot,
+                solution: solution.to_compact(),
+            })],
+        };
+        let mut block_builder = full_client
+            .new_block_at(&BlockId::Hash(parent_hash), pre_digest, false)
+            .unwrap();
+        block_builder
+            .push(Extrinsic::IncludeData(solution.solution_data().encode()))
+            .unwrap();
+        let mut block = block_builder.build().unwrap().block;
+
+        // block #1 starts the genesis epoch and announces the next one
+        if *block.header.number() == 1 {
+            block.header.digest_mut().push(DigestItem::Consensus(
+                POC_ENGINE_ID,
+                ConsensusLog::NextEpochData(NextEpochDescriptor {
+                    randomness: link.config.randomness,
+                })
+                .encode(),
+            ));
+        }
+
+        let pair = sp_core::sr25519::Pair::from(keypair.secret.clone());
+        let signature = sp_core::Pair::sign(&pair, block.header.hash().as_ref());
+        block
+            .header
+            .digest_mut()
+            .push(Item::poc_seal(signature.into()));
+        block
+    };
+
+    let mut full_block_import = full_client.clone();
+    let block_1 = author_block(full_client.info().genesis_hash, 1.into());
+    block_on(full_block_import.import(BlockOrigin::Own, block_1.clone())).unwrap();
+    let block_2 = author_block(block_1.header.hash(), 2.into());
+    block_on(full_block_import.import(BlockOrigin::Own, block_2.clone())).unwrap();
+
+    // Parent of block #2 is not genesis, so its state is not available to the light client
+    for block in vec![block_1, block_2] {
+        let hash = block.header.hash();
+        let (import_params, _) =
+            block_on(verifier.verify(BlockOrigin::NetworkBroadcast, block.header, None, None))
+                .unwrap();
+        let import_result = block_on(block_import.import_block(
+            import_params.clear_storage_changes_and_mutate(),
+            Default::default(),
+        ))
+        .unwrap();
+
+        assert!(matches!(import_result, ImportResult::Imported(_)));
+        assert_eq!(light_client.info().best_hash, hash);
+    }
+}
+
+#[test]
+fn rejects_block_of_pending_offender() {
+    // Test runtime uses `u64::MAX` as solution range and `0` as salt
+    let (_, result) = verify_block(u64::MAX, 0, true);
+
+    assert!(matches!(
+        result.err(),
+        Some(error) if error.contains("in block list")
+    ));
+}
+
+#[test]
+fn pending_offenders_expire() {
+    let pending_offenders = PendingOffenders::default();
+    let farmer_id = FarmerId::from_slice(&Keypair::generate().public.to_bytes());
+
+    pending_offenders.insert(farmer_id.clone(), 10.into());
+    assert!(pending_offenders.contains(&farmer_id, 10.into()));
+    assert!(pending_offenders.contains(&farmer_id, (10 + PENDING_OFFENDERS_RETENTION - 1).into()));
+    assert!(!pending_offenders.contains(&farmer_id, (10 + PENDING_OFFENDERS_RETENTION).into()));
+
+    // expired entries are pruned
+    assert!(!pending_offenders.contains(&farmer_id, 10.into()));
+}
@@ -18,15 +18,27 @@
 //! Verification for PoC headers.
 use super::{find_pre_digest, poc_err, BlockT, Epoch, Error};
 use log::{debug, trace};
+use prometheus_endpoint::Histogram;
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
 use ring::digest;
 use sc_consensus_slots::CheckedHeader;
 use schnorrkel::context::SigningContext;
-use sp_consensus_poc::digests::{CompatibleDigestItem, PreDigest, Solution};
+use sp_consensus_poc::{
+    digests::{CompatibleDigestItem, PreDigest, Solution},
+    SpartanParameters, PIECES_PER_SEGMENT,
+};
 use sp_consensus_slots::Slot;
-use sp_consensus_spartan::spartan::{self, Piece, Salt, Spartan};
+use sp_consensus_spartan::{
+    merkle,
+    spartan::{self, Piece, Salt, Spartan},
+};
 use sp_core::Public;
 use sp_runtime::{traits::DigestItemFor, traits::Header, RuntimeAppPublic};
+use std::collections::HashSet;
 use std::convert::TryInto;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::Instant;
 
 /// PoC verification parameters
 pub(super) struct VerificationParams<'a, B: 'a + BlockT> {
@@ -36,6 +48,9 @@ pub(super) struct VerificationParams<'a, B: 'a + BlockT> {
     /// verification code had to read it, it can be included here to avoid duplicate
     /// work.
     pub(super) pre_digest: Option<PreDigest>,
+    /// Solution to verify, which is the solution from the pre-digest, completed with solution
+    /// data from the block body if it is compact and the body is available.
+    pub(super) solution: Solution,
     /// The slot number of the current time.
     pub(super) slot_now: Slot,
     /// Epoch descriptor of the epoch this block _should_ be under, if it's valid.
@@ -44,10 +59,19 @@ pub(super) struct VerificationParams<'a, B: 'a + BlockT> {
     pub(super) solution_range: u64,
     /// Salt corresponding to this block.
     pub(super) salt: Salt,
-    /// Spartan instance
-    pub(super) spartan: &'a Spartan,
-    /// Signing context for verifying signatures
-    pub(super) signing_context: &'a SigningContext,
+    /// Records root of the segment the piece of the solution belongs to, if solution has a piece
+    /// inclusion proof and the root is stored on chain.
+    pub(super) records_root: Option<[u8; 32]>,
+    /// Whether piece inclusion proof of the solution is checked against `records_root`, light
+    /// clients don't have the state records roots are stored in
+    pub(super) check_piece_inclusion: bool,
+    /// Verifier of checks that don't depend on chain state
+    pub(super) context_free_verifier: &'a ContextFreeVerifier,
+    /// Whether checks that don't depend on chain state were already done for this header, see
+    /// [`ContextFreeVerifier::verify_headers`]
+    pub(super) context_free_verified: bool,
+    /// Histogram of time taken to verify the solution, if metrics are enabled
+    pub(super) verify_solution_time: Option<Histogram>,
 }
 
 /// Check a header has been signed by the right key. If the slot is too far in
@@ -67,12 +91,16 @@ where
     let VerificationParams {
         mut header,
         pre_digest,
+        solution,
         slot_now,
         epoch,
         solution_range,
         salt,
-        spartan,
-        signing_context,
+        records_root,
+        check_piece_inclusion,
+        context_free_verifier,
+        context_free_verified,
+        verify_solution_time,
     } = params;
 
     let pre_digest = pre_digest
@@ -104,21 +132,46 @@ where
         pre_digest.slot,
     );
 
-    // Verify that block is signed properly
-    if !pre_digest.solution.public_key.verify(&pre_hash, &sig) {
-        return Err(poc_err(Error::BadSignature(pre_hash)));
-    }
+    let verify_started = Instant::now();
+    let result = if context_free_verified {
+        verify_solution_in_context(
+            &solution,
+            epoch,
+            solution_range,
+            pre_digest.slot,
+            salt,
+            records_root,
+            check_piece_inclusion,
+        )
+    } else {
+        // Verify that block is signed properly
+        if !pre_digest.solution.public_key.verify(&pre_hash, &sig) {
+            return Err(poc_err(Error::BadSignature(pre_hash)));
+        }
 
-    // Verify that solution is valid
-    verify_solution(
-        &pre_digest.solution,
-        epoch,
-        solution_range,
-        pre_digest.slot,
-        salt,
-        spartan,
-        signing_context,
-    )?;
+        // Verify that solution is valid
+        verify_solution_in_context(
+            &solution,
+            epoch,
+            solution_range,
+            pre_digest.slot,
+            salt,
+            records_root,
+            check_piece_inclusion,
+        )
+        .and_then(|()| {
+            verify_solution_context_free(
+                &solution,
+                pre_digest.slot,
+                &context_free_verifier.spartan,
+                &context_free_verifier.signing_context,
+            )
+        })
+    };
+    if let Some(verify_solution_time) = verify_solution_time {
+        verify_solution_time.observe(verify_started.elapsed().as_secs_f64());
+    }
+    result?;
 
     let info = VerifiedHeaderInfo {
         pre_digest: CompatibleDigestItem::poc_pre_digest(pre_digest),
@@ -138,8 +191,36 @@ pub(crate) fn verify_solution<B: BlockT + Sized>(
     solution_range: u64,
     slot: Slot,
     salt: Salt,
+    records_root: Option<[u8; 32]>,
     spartan: &Spartan,
     signing_context: &SigningContext,
+) -> Result<(), Error<B>> {
+    verify_solution_in_context(
+        solution,
+        epoch,
+        solution_range,
+        slot,
+        salt,
+        records_root,
+        true,
+    )?;
+    verify_solution_context_free(solution, slot, spartan, signing_context)
+}
+
+/// Checks of the solution that depend on chain state: solution range, commitment for the salt and
+/// inclusion of the encoded piece into an archived segment, if it is not the genesis piece and
+/// `check_piece_inclusion` is set.
+///
+/// Compact solutions must be completed with their solution data first, otherwise they fail with
+/// [`Error::EncodingOfWrongSize`].
+fn verify_solution_in_context<B: BlockT>(
+    solution: &Solution,
+    epoch: &Epoch,
+    solution_range: u64,
+    slot: Slot,
+    salt: Salt,
+    records_root: Option<[u8; 32]>,
+    check_piece_inclusion: bool,
 ) -> Result<(), Error<B>> {
     if !is_within_solution_range(
         &solution,
@@ -159,26 +240,195 @@ pub(crate) fn verify_solution<B: BlockT + Sized>(
         return Err(Error::InvalidCommitment(slot));
     }
 
+    if let Some(piece_inclusion_proof) = solution
+        .piece_inclusion_proof
+        .as_ref()
+        .filter(|_| check_piece_inclusion)
+    {
+        let segment_index = piece_inclusion_proof.piece_index / PIECES_PER_SEGMENT as u64;
+        let records_root = records_root.ok_or(Error::MissingRecordsRoot(slot, segment_index))?;
+        let position = (piece_inclusion_proof.piece_index % PIECES_PER_SEGMENT as u64) as usize;
+
+        if !merkle::is_proof_valid(
+            &records_root,
+            &piece_inclusion_proof.piece,
+            position,
+            &piece_inclusion_proof.merkle_proof,
+        ) {
+            return Err(Error::InvalidPieceInclusionProof(slot));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks of the solution that don't depend on chain state: solution signature and encoding, the
+/// latter is the most expensive part of verification.
+///
+/// Archived pieces are encoded with their piece index as nonce, such that every piece yields a
+/// single encoding per farmer.
+///
+/// Compact solutions must be completed with their solution data first, otherwise they fail with
+/// [`Error::EncodingOfWrongSize`].
+fn verify_solution_context_free<B: BlockT>(
+    solution: &Solution,
+    slot: Slot,
+    spartan: &Spartan,
+    signing_context: &SigningContext,
+) -> Result<(), Error<B>> {
     if !is_signature_valid(signing_context, &solution) {
         return Err(Error::BadSolutionSignature(slot));
     }
 
-    if !spartan.is_encoding_valid(piece, solution.public_key.as_ref(), solution.nonce) {
+    let piece: Piece = solution
+        .encoding
+        .as_slice()
+        .try_into()
+        .map_err(|_error| Error::EncodingOfWrongSize)?;
+
+    let is_encoding_valid = match &solution.piece_inclusion_proof {
+        Some(piece_inclusion_proof) => {
+            let original_piece: Piece = piece_inclusion_proof
+                .piece
+                .as_slice()
+                .try_into()
+                .map_err(|_error| Error::PieceOfWrongSize)?;
+
+            solution.nonce == piece_inclusion_proof.piece_index
+                && spartan.is_piece_encoding_valid(
+                    piece,
+                    original_piece,
+                    solution.public_key.as_ref(),
+                    solution.nonce,
+                )
+        }
+        None => spartan.is_encoding_valid(piece, solution.public_key.as_ref(), solution.nonce),
+    };
+
+    if !is_encoding_valid {
         return Err(Error::InvalidEncoding(slot));
     }
 
     Ok(())
 }
 
+/// Verifier of the parts of PoC headers that don't depend on chain state: seal signature, solution
+/// signature and encoding.
+///
+/// These checks are independent for every header, which allows verifying many headers in parallel
+/// ahead of the sequential verification of the rest, for instance during initial sync.
+#[derive(Clone)]
+pub struct ContextFreeVerifier {
+    spartan: Arc<Spartan>,
+    signing_context: SigningContext,
+    /// Thread pool shared by all clones of the verifier, such that verification of batches of
+    /// headers doesn't spawn new threads
+    thread_pool: Arc<ThreadPool>,
+}
+
+impl ContextFreeVerifier {
+    /// Create a verifier for the Spartan codec with `spartan_parameters` that uses a pool of
+    /// `threads` threads for batches of headers.
+    pub fn new(spartan_parameters: &SpartanParameters, threads: usize) -> Self {
+        Self {
+            spartan: Arc::new(Spartan::new(
+                &spartan_parameters.genesis_piece_seed,
+                spartan_parameters.encode_rounds as usize,
+            )),
+            // TODO: Figure out how to remove explicit schnorrkel dependency
+            signing_context: schnorrkel::context::signing_context(
+                &spartan_parameters.signing_context,
+            ),
+            thread_pool: Arc::new(
+                ThreadPoolBuilder::new()
+                    .num_threads(threads.max(1))
+                    .thread_name(|index| format!("poc-verifier-{}", index))
+                    .build()
+                    .expect("Failed to create PoC verification thread pool"),
+            ),
+        }
+    }
+
+    /// Verify seal signature and solution of a single sealed header.
+    ///
+    /// Headers with compact solutions are rejected, since solution data in the block body is
+    /// necessary to verify them fully.
+    pub fn verify_header<B: BlockT>(&self, header: &B::Header) -> Result<(), Error<B>>
+    where
+        DigestItemFor<B>: CompatibleDigestItem,
+    {
+        let pre_digest = find_pre_digest::<B>(header)?;
+        if pre_digest.solution.is_compact() {
+            return Err(Error::MissingSolutionData(header.hash()));
+        }
+
+        verify_seal::<B>(header, &pre_digest)?;
+
+        verify_solution_context_free(
+            &pre_digest.solution,
+            pre_digest.slot,
+            &self.spartan,
+            &self.signing_context,
+        )
+    }
+
+    /// Verify seal signatures and solutions of sealed headers in parallel, returns hashes of
+    /// headers that passed verification.
+    pub fn verify_headers<B: BlockT>(&self, headers: Vec<B::Header>) -> HashSet<B::Hash>
+    where
+        DigestItemFor<B>: CompatibleDigestItem,
+    {
+        if headers.is_empty() {
+            return HashSet::new();
+        }
+
+        self.thread_pool.install(|| {
+            headers
+                .par_iter()
+                .filter(|header| {
+                    // headers whose verification panicked are verified again sequentially later
+                    panic::catch_unwind(AssertUnwindSafe(|| {
+                        self.verify_header::<B>(header).is_ok()
+                    }))
+                    .unwrap_or(false)
+                })
+                .map(|header| header.hash())
+                .collect()
+        })
+    }
+}
+
+/// Verify that sealed `header` is signed by the farmer whose solution is in its `pre_digest`.
+pub(crate) fn verify_seal<B: BlockT>(
+    header: &B::Header,
+    pre_digest: &PreDigest,
+) -> Result<(), Error<B>>
+where
+    DigestItemFor<B>: CompatibleDigestItem,
+{
+    let mut header = header.clone();
+    let seal = header
+        .digest_mut()
+        .pop()
+        .ok_or_else(|| Error::HeaderUnsealed(header.hash()))?;
+    let sig = seal
+        .as_poc_seal()
+        .ok_or_else(|| Error::HeaderBadSeal(header.hash()))?;
+    let pre_hash = header.hash();
+
+    if !pre_digest.solution.public_key.verify(&pre_hash, &sig) {
+        return Err(Error::BadSignature(pre_hash));
+    }
+
+    Ok(())
+}
+
 fn is_within_solution_range(
     solution: &Solution,
     global_challenge: [u8; 8],
     solution_range: u64,
 ) -> bool {
-    let farmer_id = hash_public_key(solution.public_key.as_ref());
-    let local_challenge = derive_local_challenge(&global_challenge, &farmer_id);
-
-    let target = u64::from_be_bytes(local_challenge);
+    let target = local_challenge_target(solution, global_challenge);
     let tag = u64::from_be_bytes(solution.tag);
 
     let (lower, is_lower_overflowed) = target.overflowing_sub(solution_range / 2);
@@ -190,6 +440,32 @@ fn is_within_solution_range(
     }
 }
 
+/// Distance between the solution tag and the local challenge of the farmer, wrapping around `u64`.
+///
+/// The lower the distance, the better the solution, which is used to break ties in fork choice.
+pub(crate) fn solution_distance(solution: &Solution, global_challenge: [u8; 8]) -> u64 {
+    let target = local_challenge_target(solution, global_challenge);
+    let tag = u64::from_be_bytes(solution.tag);
+
+    tag.wrapping_sub(target).min(target.wrapping_sub(tag))
+}
+
+fn local_challenge_target(solution: &Solution, global_challenge: [u8; 8]) -> u64 {
+    let farmer_id = hash_public_key(solution.public_key.as_ref());
+    let local_challenge = derive_local_challenge(&global_challenge, &farmer_id);
+
+    u64::from_be_bytes(local_challenge)
+}
+
+/// Check that signature of the solution tag corresponds to the public key of the farmer that
+/// proposed the solution, `signing_context` is the one from `SpartanParameters`.
+pub fn is_solution_signature_valid(signing_context: &[u8], solution: &Solution) -> bool {
+    is_signature_valid(
+        &schnorrkel::context::signing_context(signing_context),
+        solution,
+    )
+}
+
 fn is_signature_valid(signing_context: &SigningContext, solution: &Solution) -> bool {
     let public_key = match schnorrkel::PublicKey::from_bytes(solution.public_key.as_slice()) {
         Ok(public_key) => public_key,
@@ -208,7 +484,9 @@ fn is_signature_valid(signing_context: &SigningContext, solution: &Solution) ->
         .is_ok()
 }
 
-pub(crate) fn derive_local_challenge(global_challenge: &[u8], farmer_id: &[u8]) -> [u8; 8] {
+/// Derive local challenge of the farmer identified by `farmer_id` (hash of its public key) from
+/// the global slot challenge.
+pub fn derive_local_challenge(global_challenge: &[u8], farmer_id: &[u8]) -> [u8; 8] {
     digest::digest(&digest::SHA256, &{
         let mut data = Vec::with_capacity(global_challenge.len() + farmer_id.len());
         data.extend_from_slice(global_challenge);
@@ -220,7 +498,8 @@ pub(crate) fn derive_local_challenge(global_challenge: &[u8], farmer_id: &[u8])
         .unwrap()
 }
 
-pub(crate) fn hash_public_key(public_key: &[u8]) -> [u8; 8] {
+/// Hash of the farmer public key as used for local challenge derivation.
+pub fn hash_public_key(public_key: &[u8]) -> [u8; 8] {
     let mut array = [0u8; 8];
     let hash = digest::digest(&digest::SHA256, public_key);
     array.copy_from_slice(&hash.as_ref()[..8]);
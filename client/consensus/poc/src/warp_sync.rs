@@ -0,0 +1,528 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Warp sync support for PoC.
+//!
+//! Nodes that skip history during sync only import the most recent finalized block, which leaves
+//! their epoch changes tree empty, so none of the blocks that follow can be verified. Warp sync
+//! proof consists of headers of finalized blocks that announce epochs (`NextEpochData` along with
+//! `NextConfigData` and `NextDurationsData` if any) or declare solution range, salt or root blocks,
+//! which is enough to reconstruct the current and the next epoch of the most recent finalized
+//! block and to verify solutions of all blocks in the proof.
+//!
+//! Serving node generates proofs with [`WarpSyncProof::generate`] from the index of such blocks it
+//! builds on import, syncing node verifies them one after another with [`WarpSyncProof::verify`]
+//! up to a trusted checkpoint and once the checkpoint block is imported, seeds epoch changes with
+//! [`import_warp_sync_state`].
+
+use crate::{
+    aux_schema, extract_solution_data, find_next_config_digest, find_next_durations_digest,
+    find_next_epoch_digest, find_pre_digest, find_root_blocks_digest, find_salt_digest,
+    find_solution_range_digest, verification, Epoch, Error, SolutionRangeAndSalt,
+};
+use codec::{Decode, Encode};
+use sc_client_api::{backend::AuxStore, BlockBackend};
+use sc_consensus_epochs::{
+    descendent_query, Epoch as EpochT, EpochChangesFor, SharedEpochChanges, ViableEpoch,
+};
+use sp_api::NumberFor;
+use sp_blockchain::{HeaderBackend, HeaderMetadata};
+use sp_consensus_poc::{
+    digests::{NextEpochDescriptor, SolutionData, SolutionVersion},
+    PoCBlockWeight, PoCGenesisConfiguration, GENESIS_PIECE_SOLUTIONS_CUTOVER, PIECES_PER_SEGMENT,
+};
+use sp_consensus_spartan::spartan::Spartan;
+use sp_runtime::{
+    generic::BlockId,
+    traits::{Block as BlockT, Header as HeaderT, One, UniqueSaturatedInto},
+};
+use std::iter;
+
+/// The maximum size in bytes of the `WarpSyncProof`.
+pub const MAX_WARP_SYNC_PROOF_SIZE: usize = 16 * 1024 * 1024;
+
+/// A finalized block that announces the next epoch or declares solution range, salt or root blocks.
+#[derive(Decode, Encode)]
+pub struct WarpSyncFragment<Block: BlockT> {
+    /// Sealed header of the block, the digests in it are what the syncing node is interested in.
+    pub header: Block::Header,
+    /// Solution data from the body of the block if its solution is compact, such that the solution
+    /// can be verified without the body.
+    pub solution_data: Option<SolutionData>,
+}
+
+/// An accumulated proof of multiple epoch changes.
+#[derive(Decode, Encode)]
+pub struct WarpSyncProof<Block: BlockT> {
+    fragments: Vec<WarpSyncFragment<Block>>,
+    is_finished: bool,
+}
+
+/// Epochs, solution range, salt and records roots reconstructed from warp sync proofs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WarpSyncState<Block: BlockT> {
+    /// Header of the last verified block, genesis header initially.
+    pub header: Block::Header,
+    /// Epoch of the last verified block and the epoch announced by the first block of it, `None`
+    /// for genesis.
+    pub epochs: Option<(Epoch, Epoch)>,
+    /// Solution range declared by the last verified block that declared one.
+    pub solution_range: Option<u64>,
+    /// Salt declared by the last verified block that declared one.
+    pub salt: Option<u64>,
+    /// Records roots of segments whose root blocks were stored on chain up to the last verified
+    /// block, indexed by segment index.
+    pub records_roots: Vec<[u8; 32]>,
+}
+
+impl<Block: BlockT> WarpSyncState<Block> {
+    /// Create the state to start warp sync from genesis with.
+    pub fn new(genesis_header: Block::Header) -> Self {
+        Self {
+            header: genesis_header,
+            epochs: None,
+            solution_range: None,
+            salt: None,
+            records_roots: Vec::new(),
+        }
+    }
+}
+
+/// Whether warp sync proofs include the block, which is the case for blocks that announce the next
+/// epoch or declare solution range, salt or root blocks.
+pub(crate) fn is_warp_sync_block<Block: BlockT>(
+    header: &Block::Header,
+) -> Result<bool, Error<Block>> {
+    Ok(find_next_epoch_digest::<Block>(header)?.is_some()
+        || find_solution_range_digest::<Block>(header)?.is_some()
+        || find_salt_digest::<Block>(header)?.is_some()
+        || find_root_blocks_digest::<Block>(header)?.is_some())
+}
+
+/// Number of block `hash` (`which` block of the request) that must be finalized.
+fn finalized_block_number<Block, Client>(
+    client: &Client,
+    hash: Block::Hash,
+    which: &str,
+) -> Result<NumberFor<Block>, Error<Block>>
+where
+    Block: BlockT,
+    Client: HeaderBackend<Block>,
+{
+    let number = client
+        .block_number_from_id(&BlockId::Hash(hash))
+        .map_err(Error::Client)?
+        .ok_or_else(|| Error::InvalidWarpSyncRequest(format!("Missing {} block", which)))?;
+
+    if number > client.info().finalized_number {
+        return Err(Error::InvalidWarpSyncRequest(format!(
+            "{} block is not finalized",
+            which
+        )));
+    }
+
+    let canon_hash = client.hash(number).map_err(Error::Client)?.expect(
+        "number is lower than finalized number; \
+         all blocks below finalized number must have been imported; \
+         qed.",
+    );
+
+    if canon_hash != hash {
+        return Err(Error::InvalidWarpSyncRequest(format!(
+            "{} block is not in the finalized chain",
+            which
+        )));
+    }
+
+    Ok(number)
+}
+
+impl<Block: BlockT> WarpSyncProof<Block> {
+    /// Generates a warp sync proof from block `begin` to block `end`, both must be finalized. It
+    /// will include headers of all blocks after `begin` that announce epochs or declare solution
+    /// range, salt or root blocks, followed by the header of `end` (capped by
+    /// MAX_WARP_SYNC_PROOF_SIZE).
+    ///
+    /// Blocks are looked up in the index built by [`PoCBlockImport`](crate::PoCBlockImport), so
+    /// only blocks imported by this node itself are found.
+    pub fn generate<Client>(
+        client: &Client,
+        begin: Block::Hash,
+        end: Block::Hash,
+    ) -> Result<Self, Error<Block>>
+    where
+        Client: HeaderBackend<Block> + BlockBackend<Block> + AuxStore,
+    {
+        let begin_number = finalized_block_number(client, begin, "start")?;
+        let end_number = finalized_block_number(client, end, "end")?;
+        if begin_number >= end_number {
+            return Err(Error::InvalidWarpSyncRequest(
+                "End block is not after start block".to_string(),
+            ));
+        }
+
+        let block_numbers = aux_schema::load_warp_sync_block_numbers::<NumberFor<Block>, _>(client)
+            .map_err(Error::Client)?;
+
+        let mut fragments = Vec::new();
+        let mut fragments_encoded_len = 0;
+        let mut proof_limit_reached = false;
+
+        // `end` is where warp sync ends, so it is always included
+        for number in block_numbers
+            .into_iter()
+            .filter(|number| *number > begin_number && *number < end_number)
+            .chain(iter::once(end_number))
+        {
+            let header = client
+                .header(BlockId::Number(number))
+                .map_err(Error::Client)?
+                .expect(
+                    "number is not higher than finalized number; \
+                     all blocks below finalized number must have been imported; \
+                     qed.",
+                );
+
+            // numbers of blocks on other forks are indexed as well
+            if number != end_number && !is_warp_sync_block::<Block>(&header)? {
+                continue;
+            }
+
+            let hash = header.hash();
+            let solution = find_pre_digest::<Block>(&header)?.solution;
+            let solution_data = if solution.is_compact() {
+                let body = client
+                    .block_body(&BlockId::Hash(hash))
+                    .map_err(Error::Client)?
+                    .ok_or_else(|| Error::MissingSolutionData(hash))?;
+                let solution_data = extract_solution_data::<Block>(&solution, &body)
+                    .ok_or_else(|| Error::MissingSolutionData(hash))?;
+                Some(solution_data)
+            } else {
+                None
+            };
+
+            let fragment = WarpSyncFragment {
+                header,
+                solution_data,
+            };
+            let fragment_size = fragment.encoded_size();
+
+            // Check for the limit. We remove some bytes from the maximum size, because we're only
+            // counting the size of the `WarpSyncFragment`s. The extra margin is here to leave
+            // room for rest of the data (the size of the `Vec` and the boolean).
+            if fragments_encoded_len + fragment_size >= MAX_WARP_SYNC_PROOF_SIZE - 50 {
+                proof_limit_reached = true;
+                break;
+            }
+
+            fragments_encoded_len += fragment_size;
+            fragments.push(fragment);
+        }
+
+        let final_outcome = WarpSyncProof {
+            fragments,
+            is_finished: !proof_limit_reached,
+        };
+        debug_assert!(final_outcome.encoded_size() <= MAX_WARP_SYNC_PROOF_SIZE);
+        Ok(final_outcome)
+    }
+
+    /// Whether the proof reaches the requested end block, otherwise the next proof should be
+    /// requested starting at the last block of this one.
+    pub fn is_finished(&self) -> bool {
+        self.is_finished
+    }
+
+    /// Verifies the warp sync proof starting at the last block of the given state. If the proof is
+    /// valid the state of the last block of the proof is returned.
+    ///
+    /// Finished proof must end at `checkpoint`, a block trusted by the syncing node, such as the
+    /// block whose state it downloads.
+    ///
+    /// Headers in the proof are not consecutive, so besides seals and solutions only the epoch
+    /// changes are checked to follow the rules of [`PoCBlockImport`](crate::PoCBlockImport).
+    /// Solutions are verified against solution range, salt and records roots declared by the
+    /// blocks of the proof, the block list of farmers is only available in state and isn't checked.
+    pub fn verify(
+        &self,
+        genesis_config: &PoCGenesisConfiguration,
+        checkpoint: Block::Hash,
+        mut state: WarpSyncState<Block>,
+    ) -> Result<WarpSyncState<Block>, Error<Block>> {
+        if self.fragments.is_empty() && !self.is_finished {
+            return Err(Error::InvalidWarpSyncProof(
+                "Unfinished proof without blocks".to_string(),
+            ));
+        }
+
+        let spartan = Spartan::new(
+            &genesis_config.spartan_parameters.genesis_piece_seed,
+            genesis_config.spartan_parameters.encode_rounds as usize,
+        );
+        // TODO: Figure out how to remove explicit schnorrkel dependency
+        let signing_context = schnorrkel::context::signing_context(
+            &genesis_config.spartan_parameters.signing_context,
+        );
+
+        for (fragment_num, fragment) in self.fragments.iter().enumerate() {
+            let header = &fragment.header;
+            let invalid_proof = |reason: &str| {
+                Error::InvalidWarpSyncProof(format!("{} in block {}", reason, header.hash()))
+            };
+
+            if header.number() <= state.header.number() {
+                return Err(invalid_proof("Block number is not increasing"));
+            }
+
+            if *header.number() == *state.header.number() + One::one()
+                && *header.parent_hash() != state.header.hash()
+            {
+                return Err(invalid_proof("Parent block mismatch"));
+            }
+
+            let pre_digest = find_pre_digest::<Block>(header)?;
+            let last_slot = find_pre_digest::<Block>(&state.header)?.slot;
+            if pre_digest.slot <= last_slot {
+                return Err(Error::SlotMustIncrease(last_slot, pre_digest.slot));
+            }
+
+            verification::verify_seal::<Block>(header, &pre_digest)?;
+
+            // only the last block of the proof is allowed to have none of the digests
+            let is_last = self.is_finished && fragment_num == self.fragments.len() - 1;
+            if !is_last && !is_warp_sync_block::<Block>(header)? {
+                return Err(invalid_proof(
+                    "Header is missing epoch, solution range, salt and root blocks digests",
+                ));
+            }
+
+            let next_epoch_digest = find_next_epoch_digest::<Block>(header)?;
+            let next_config_digest = find_next_config_digest::<Block>(header)?;
+            let next_epoch_duration = find_next_durations_digest::<Block>(header)?
+                .and_then(|durations| durations.epoch_duration);
+            let solution_range_digest = find_solution_range_digest::<Block>(header)?;
+            let salt_digest = find_salt_digest::<Block>(header)?;
+            let root_blocks_digest = find_root_blocks_digest::<Block>(header)?;
+
+            // `next_epoch` is `None` if this is the first block of `epoch`
+            let (epoch, next_epoch) = match state.epochs.take() {
+                Some((_, next_epoch)) if pre_digest.slot >= next_epoch.start_slot => {
+                    (next_epoch, None)
+                }
+                Some((epoch, next_epoch)) => (epoch, Some(next_epoch)),
+                // genesis epoch starts at the slot of block #1
+                None if *header.number() == One::one() => {
+                    (Epoch::genesis(genesis_config, pre_digest.slot), None)
+                }
+                None => return Err(invalid_proof("Missing genesis epoch")),
+            };
+
+            // solution range and salt are only declared by blocks whose parent's state has
+            // different ones, blocks are verified with the ones from the state of their parent
+            let solution_range = solution_range_digest
+                .map(|solution_range_digest| solution_range_digest.solution_range)
+                .or(state.solution_range)
+                .ok_or_else(|| invalid_proof("Missing solution range"))?;
+            let salt = salt_digest
+                .map(|salt_digest| salt_digest.salt)
+                .or(state.salt)
+                .ok_or_else(|| invalid_proof("Missing salt"))?;
+
+            let solution = match (pre_digest.solution.is_compact(), &fragment.solution_data) {
+                (true, Some(solution_data)) => pre_digest
+                    .solution
+                    .clone()
+                    .with_solution_data(solution_data.clone())
+                    .ok_or_else(|| Error::InvalidSolutionData(header.hash()))?,
+                (true, None) => return Err(Error::MissingSolutionData(header.hash())),
+                (false, Some(_)) => return Err(invalid_proof("Unexpected solution data")),
+                (false, None) => pre_digest.solution.clone(),
+            };
+
+            // the same rules as in `extract_records_root_for_solution`, with the number of root
+            // blocks seen so far being the index of the next archived segment
+            let records_root = match &solution.piece_inclusion_proof {
+                Some(piece_inclusion_proof) => state
+                    .records_roots
+                    .get((piece_inclusion_proof.piece_index / PIECES_PER_SEGMENT as u64) as usize)
+                    .copied(),
+                None => {
+                    if solution.version() == SolutionVersion::GenesisPiece
+                        && state.records_roots.len() as u64 >= GENESIS_PIECE_SOLUTIONS_CUTOVER
+                    {
+                        return Err(Error::GenesisPieceSolutionAfterCutover);
+                    }
+
+                    None
+                }
+            };
+
+            verification::verify_solution::<Block>(
+                &solution,
+                &epoch,
+                solution_range,
+                pre_digest.slot,
+                salt.to_le_bytes(),
+                records_root,
+                &spartan,
+                &signing_context,
+            )?;
+
+            let next_epoch = match (next_epoch, next_epoch_digest) {
+                (None, Some(next_epoch_descriptor)) => {
+                    let epoch_config = next_config_digest
+                        .map(Into::into)
+                        .unwrap_or_else(|| epoch.config.clone());
+                    let epoch_duration = next_epoch_duration.unwrap_or(epoch.duration);
+                    epoch.increment((next_epoch_descriptor, epoch_config, epoch_duration))
+                }
+                (Some(next_epoch), None)
+                    if next_config_digest.is_none() && next_epoch_duration.is_none() =>
+                {
+                    next_epoch
+                }
+                (None, None) => return Err(invalid_proof("Expected epoch change")),
+                _ => return Err(invalid_proof("Unexpected epoch change")),
+            };
+
+            // root blocks stored with this block are used to verify solutions of the blocks after
+            for root_block in root_blocks_digest.unwrap_or_default() {
+                if root_block.segment_index != state.records_roots.len() as u64 {
+                    return Err(invalid_proof("Unexpected segment index of root block"));
+                }
+                state.records_roots.push(root_block.records_root);
+            }
+
+            state.epochs = Some((epoch, next_epoch));
+            state.solution_range = Some(solution_range);
+            state.salt = Some(salt);
+            state.header = header.clone();
+        }
+
+        if self.is_finished && state.header.hash() != checkpoint {
+            return Err(Error::InvalidWarpSyncProof(format!(
+                "Proof ends at block {} instead of checkpoint {}",
+                state.header.hash(),
+                checkpoint
+            )));
+        }
+
+        Ok(state)
+    }
+}
+
+/// Seed `epoch_changes` with the current and the next epoch of the last block of verified warp
+/// sync `state` and store its block weight, solution range and salt in aux storage, such that
+/// blocks built on top of it can be verified and imported.
+///
+/// Must be called once the last block of warp sync is imported (after state sync), previous epoch
+/// changes are replaced.
+pub fn import_warp_sync_state<Block, Client>(
+    client: &Client,
+    epoch_changes: &SharedEpochChanges<Block, Epoch>,
+    state: &WarpSyncState<Block>,
+) -> Result<(), Error<Block>>
+where
+    Block: BlockT,
+    Client: HeaderBackend<Block> + HeaderMetadata<Block, Error = sp_blockchain::Error> + AuxStore,
+{
+    let (epoch, next_epoch) = match &state.epochs {
+        Some(epochs) => epochs,
+        // genesis epoch is created as usual when block #1 is imported
+        None => return Ok(()),
+    };
+
+    let header = &state.header;
+    let hash = header.hash();
+    let number = *header.number();
+
+    if client
+        .header(BlockId::Hash(hash))
+        .map_err(Error::Client)?
+        .is_none()
+    {
+        return Err(Error::Client(sp_blockchain::Error::UnknownBlock(
+            hash.to_string(),
+        )));
+    }
+
+    // the last block of warp sync becomes the root of the tree and holds both epochs, the same way
+    // block #1 holds the genesis epoch and the one after it
+    let incremented_epoch = ViableEpoch::<Epoch>::UnimportedGenesis(epoch.clone()).increment((
+        NextEpochDescriptor {
+            randomness: next_epoch.randomness,
+        },
+        next_epoch.config.clone(),
+        next_epoch.duration,
+    ));
+
+    let mut new_epoch_changes = EpochChangesFor::<Block, Epoch>::default();
+    new_epoch_changes
+        .import(
+            descendent_query(client),
+            hash,
+            number,
+            *header.parent_hash(),
+            incremented_epoch,
+        )
+        .map_err(|e| Error::ForkTree(Box::new(e)))?;
+
+    // every block adds the same weight, so the weight of the chain is proportional to its length
+    let chain_length: PoCBlockWeight = number.unique_saturated_into();
+    let block_weight = find_pre_digest::<Block>(header)?.added_weight() * chain_length;
+
+    let mut epoch_changes = epoch_changes.shared_data();
+    *epoch_changes = new_epoch_changes;
+
+    aux_schema::write_epoch_changes::<Block, _, _>(&*epoch_changes, |values| {
+        client.insert_aux(values, &[])
+    })
+    .map_err(Error::Client)?;
+    aux_schema::write_block_weight(hash, block_weight, |values| {
+        client.insert_aux(
+            &values
+                .iter()
+                .map(|(k, v)| (k.as_slice(), *v))
+                .collect::<Vec<_>>(),
+            &[],
+        )
+    })
+    .map_err(Error::Client)?;
+    // children of the last block of warp sync are verified with solution range and salt tracked
+    // from digests, the same way as if all blocks before it were imported
+    if let (Some(solution_range), Some(salt)) = (state.solution_range, state.salt) {
+        let solution_range_and_salt = SolutionRangeAndSalt {
+            solution_range,
+            salt,
+        }
+        .for_child_of::<Block>(header)?;
+        aux_schema::write_solution_range_and_salt(hash, solution_range_and_salt, |values| {
+            client.insert_aux(
+                &values
+                    .iter()
+                    .map(|(k, v)| (k.as_slice(), *v))
+                    .collect::<Vec<_>>(),
+                &[],
+            )
+        })
+        .map_err(Error::Client)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,378 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Helper for handling (i.e. answering) PoC warp sync requests from a remote peer, for
+//! requesting warp sync proofs from one and for warp syncing a node that downloads state of a
+//! recent finalized block instead of importing all blocks.
+
+use codec::{Decode, Encode};
+use futures::{
+    channel::{mpsc, oneshot},
+    future::{self, Either},
+    stream::{BoxStream, StreamExt},
+};
+use futures_timer::Delay;
+use log::{debug, info, warn};
+use sc_client_api::{backend::AuxStore, BlockBackend};
+use sc_consensus_poc::{
+    warp_sync::{import_warp_sync_state, WarpSyncProof, WarpSyncState},
+    PoCApi, PoCGenesisConfiguration, PoCLink,
+};
+use sc_network::{
+    config::{IncomingRequest, OutgoingResponse, ProtocolId, RequestResponseConfig},
+    Event, ExHashT, IfDisconnected, NetworkService, PeerId, RequestFailure,
+};
+use sc_service::{
+    config::{Configuration, Role},
+    SpawnTaskHandle,
+};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::{HeaderBackend, HeaderMetadata};
+use sp_runtime::{
+    generic::BlockId,
+    traits::{Block as BlockT, Header as HeaderT, Zero},
+};
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+/// Generates the appropriate [`RequestResponseConfig`] for a given chain configuration.
+pub fn request_response_config_for_chain<TBlock, TClient>(
+    config: &Configuration,
+    spawn_handle: SpawnTaskHandle,
+    client: Arc<TClient>,
+) -> RequestResponseConfig
+where
+    TBlock: BlockT,
+    TClient: HeaderBackend<TBlock>
+        + BlockBackend<TBlock>
+        + ProvideRuntimeApi<TBlock>
+        + AuxStore
+        + 'static,
+    TClient::Api: PoCApi<TBlock>,
+{
+    let protocol_id = config.protocol_id();
+
+    if matches!(config.role, Role::Light) {
+        // Allow outgoing requests but deny incoming requests.
+        generate_request_response_config(protocol_id)
+    } else {
+        // Allow both outgoing and incoming requests.
+        let (handler, request_response_config) =
+            PoCWarpSyncRequestHandler::new(protocol_id, client);
+        spawn_handle.spawn("poc-warp-sync", handler.run());
+        request_response_config
+    }
+}
+
+const LOG_TARGET: &str = "poc-warp-sync-request-handler";
+
+/// How often warp sync checks whether the state was downloaded or retries failed requests.
+const WARP_SYNC_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Generates a [`RequestResponseConfig`] for the PoC warp sync request protocol, refusing incoming
+/// requests.
+pub fn generate_request_response_config(protocol_id: ProtocolId) -> RequestResponseConfig {
+    RequestResponseConfig {
+        name: generate_protocol_name(protocol_id).into(),
+        max_request_size: 64,
+        max_response_size: sc_consensus_poc::warp_sync::MAX_WARP_SYNC_PROOF_SIZE as u64,
+        request_timeout: Duration::from_secs(10),
+        inbound_queue: None,
+    }
+}
+
+/// Generate the PoC warp sync protocol name from chain specific protocol identifier.
+fn generate_protocol_name(protocol_id: ProtocolId) -> String {
+    let mut s = String::new();
+    s.push_str("/");
+    s.push_str(protocol_id.as_ref());
+    s.push_str("/sync/poc-warp");
+    s
+}
+
+#[derive(Encode, Decode)]
+struct Request<B: BlockT> {
+    begin: B::Hash,
+    end: B::Hash,
+}
+
+/// Handler for incoming PoC warp sync requests from a remote peer.
+pub struct PoCWarpSyncRequestHandler<TClient, TBlock: BlockT> {
+    client: Arc<TClient>,
+    request_receiver: mpsc::Receiver<IncomingRequest>,
+    _phantom: std::marker::PhantomData<TBlock>,
+}
+
+impl<TBlock, TClient> PoCWarpSyncRequestHandler<TClient, TBlock>
+where
+    TBlock: BlockT,
+    TClient: HeaderBackend<TBlock> + BlockBackend<TBlock> + ProvideRuntimeApi<TBlock> + AuxStore,
+    TClient::Api: PoCApi<TBlock>,
+{
+    /// Create a new [`PoCWarpSyncRequestHandler`].
+    pub fn new(protocol_id: ProtocolId, client: Arc<TClient>) -> (Self, RequestResponseConfig) {
+        let (tx, request_receiver) = mpsc::channel(20);
+
+        let mut request_response_config = generate_request_response_config(protocol_id);
+        request_response_config.inbound_queue = Some(tx);
+
+        (
+            Self {
+                client,
+                request_receiver,
+                _phantom: std::marker::PhantomData,
+            },
+            request_response_config,
+        )
+    }
+
+    fn handle_request(
+        &self,
+        payload: Vec<u8>,
+        pending_response: oneshot::Sender<OutgoingResponse>,
+    ) -> Result<(), HandleRequestError> {
+        let request = Request::<TBlock>::decode(&mut &payload[..])?;
+
+        let proof = WarpSyncProof::<TBlock>::generate(&*self.client, request.begin, request.end)?;
+
+        pending_response
+            .send(OutgoingResponse {
+                result: Ok(proof.encode()),
+                reputation_changes: Vec::new(),
+                sent_feedback: None,
+            })
+            .map_err(|_| HandleRequestError::SendResponse)
+    }
+
+    /// Run [`PoCWarpSyncRequestHandler`].
+    pub async fn run(mut self) {
+        while let Some(request) = self.request_receiver.next().await {
+            let IncomingRequest {
+                peer,
+                payload,
+                pending_response,
+            } = request;
+
+            match self.handle_request(payload, pending_response) {
+                Ok(()) => {
+                    debug!(target: LOG_TARGET, "Handled PoC warp sync request from {}.", peer)
+                }
+                Err(e) => debug!(
+                    target: LOG_TARGET,
+                    "Failed to handle PoC warp sync request from {}: {}", peer, e,
+                ),
+            }
+        }
+    }
+}
+
+/// Request warp sync proofs from `peer` starting at genesis until the trusted `checkpoint` block
+/// and verify them.
+///
+/// Returned state is supposed to be imported with
+/// [`import_warp_sync_state`](sc_consensus_poc::warp_sync::import_warp_sync_state) once the
+/// checkpoint block is imported along with its state.
+pub async fn request_warp_sync_state<TBlock, H>(
+    network: &NetworkService<TBlock, H>,
+    peer: PeerId,
+    protocol_id: ProtocolId,
+    genesis_config: &PoCGenesisConfiguration,
+    genesis_header: TBlock::Header,
+    checkpoint: TBlock::Hash,
+) -> Result<WarpSyncState<TBlock>, HandleRequestError>
+where
+    TBlock: BlockT,
+    H: ExHashT,
+{
+    let protocol_name = generate_protocol_name(protocol_id);
+    let mut state = WarpSyncState::new(genesis_header);
+
+    loop {
+        let request = Request::<TBlock> {
+            begin: state.header.hash(),
+            end: checkpoint,
+        };
+        let response = network
+            .request(
+                peer,
+                protocol_name.clone(),
+                request.encode(),
+                IfDisconnected::ImmediateError,
+            )
+            .await?;
+
+        let proof = WarpSyncProof::<TBlock>::decode(&mut &response[..])?;
+        state = proof
+            .verify(genesis_config, checkpoint, state)
+            .map_err(|e| HandleRequestError::InvalidProof(e.to_string()))?;
+
+        if proof.is_finished() {
+            return Ok(state);
+        }
+    }
+}
+
+/// Warp sync a node that downloads state of a recent finalized block instead of importing all
+/// blocks (fast sync).
+///
+/// Once sync downloads the state, that block becomes the checkpoint warp sync proofs are requested
+/// up to from connected peers until one of them provides a valid proof whose verified state is
+/// imported into epoch changes of `poc_link`. Does nothing if the node already has state.
+pub async fn run_warp_sync<TBlock, TClient, H>(
+    client: Arc<TClient>,
+    network: Arc<NetworkService<TBlock, H>>,
+    protocol_id: ProtocolId,
+    poc_link: PoCLink<TBlock>,
+) where
+    TBlock: BlockT,
+    TClient:
+        HeaderBackend<TBlock> + HeaderMetadata<TBlock, Error = sp_blockchain::Error> + AuxStore,
+    H: ExHashT,
+{
+    if client.info().finalized_state.is_some() {
+        return;
+    }
+
+    let mut network_events = network.event_stream("poc-warp-sync").boxed();
+    let mut peers = HashSet::new();
+
+    let checkpoint = loop {
+        match client.info().finalized_state {
+            Some((hash, number)) if !number.is_zero() => break hash,
+            _ => {}
+        }
+
+        if !track_peers(&mut network_events, &mut peers).await {
+            return;
+        }
+    };
+
+    let genesis_header = match client.header(BlockId::Number(Zero::zero())) {
+        Ok(Some(genesis_header)) => genesis_header,
+        Ok(None) => {
+            warn!(target: "poc-warp-sync", "Genesis header is missing, can't warp sync");
+            return;
+        }
+        Err(error) => {
+            warn!(target: "poc-warp-sync", "Failed to get genesis header: {}", error);
+            return;
+        }
+    };
+
+    info!(target: "poc-warp-sync", "🧑‍🌾 Warp syncing epochs up to block {}", checkpoint);
+
+    loop {
+        for peer in peers.iter().cloned() {
+            let result = request_warp_sync_state(
+                &network,
+                peer,
+                protocol_id.clone(),
+                poc_link.config(),
+                genesis_header.clone(),
+                checkpoint,
+            )
+            .await;
+
+            match result {
+                Ok(state) => {
+                    match import_warp_sync_state(&*client, poc_link.epoch_changes(), &state) {
+                        Ok(()) => {
+                            info!(
+                                target: "poc-warp-sync",
+                                "🧑‍🌾 Warp synced epochs up to block {}",
+                                checkpoint,
+                            );
+                            return;
+                        }
+                        // state from the next peer may import fine, otherwise import is retried
+                        // along with the requests
+                        Err(error) => warn!(
+                            target: "poc-warp-sync",
+                            "Failed to import warp sync state from {}: {}",
+                            peer,
+                            error,
+                        ),
+                    }
+                }
+                Err(error) => debug!(
+                    target: "poc-warp-sync",
+                    "Failed to warp sync from {}: {}",
+                    peer,
+                    error,
+                ),
+            }
+        }
+
+        if !track_peers(&mut network_events, &mut peers).await {
+            return;
+        }
+    }
+}
+
+/// Update `peers` we sync with from the next network event, waits up to
+/// [`WARP_SYNC_RETRY_INTERVAL`] for it. Returns `false` if the network is gone.
+async fn track_peers(
+    network_events: &mut BoxStream<'static, Event>,
+    peers: &mut HashSet<PeerId>,
+) -> bool {
+    let event =
+        match future::select(network_events.next(), Delay::new(WARP_SYNC_RETRY_INTERVAL)).await {
+            Either::Left((Some(event), _)) => event,
+            Either::Left((None, _)) => return false,
+            Either::Right(_) => return true,
+        };
+
+    match event {
+        Event::SyncConnected { remote } => {
+            peers.insert(remote);
+        }
+        Event::SyncDisconnected { remote } => {
+            peers.remove(&remote);
+        }
+        _ => {}
+    }
+
+    true
+}
+
+#[derive(Debug, derive_more::Display, derive_more::From)]
+pub enum HandleRequestError {
+    #[display(fmt = "Failed to decode request or response: {}.", _0)]
+    DecodeScale(codec::Error),
+    Client(sp_blockchain::Error),
+    #[display(fmt = "Request failed: {}.", _0)]
+    Request(RequestFailure),
+    #[from(ignore)]
+    InvalidRequest(String),
+    #[from(ignore)]
+    InvalidProof(String),
+    #[from(ignore)]
+    #[display(fmt = "PoC error: {}.", _0)]
+    Consensus(String),
+    #[display(fmt = "Failed to send response.")]
+    SendResponse,
+}
+
+impl<B: BlockT> From<sc_consensus_poc::Error<B>> for HandleRequestError {
+    fn from(error: sc_consensus_poc::Error<B>) -> Self {
+        match error {
+            sc_consensus_poc::Error::InvalidWarpSyncRequest(reason) => Self::InvalidRequest(reason),
+            sc_consensus_poc::Error::Client(error) => Self::Client(error),
+            error => Self::Consensus(error.to_string()),
+        }
+    }
+}
@@ -132,13 +132,30 @@ where
 	Ok(None)
 }
 
+/// Returns `true` if a header of `signer` at `slot` was already recorded by
+/// [`check_equivocation`], i.e. signing another header at this slot would be an equivocation.
+pub fn has_header_at_slot<C, H, P>(backend: &C, slot: Slot, signer: &P) -> ClientResult<bool>
+where
+	H: Header,
+	C: AuxStore,
+	P: Decode + PartialEq,
+{
+	let mut curr_slot_key = SLOT_HEADER_MAP_KEY.to_vec();
+	slot.using_encoded(|s| curr_slot_key.extend(s));
+
+	let headers_with_sig =
+		load_decode::<_, Vec<(H, P)>>(backend, &curr_slot_key[..])?.unwrap_or_else(Vec::new);
+
+	Ok(headers_with_sig.iter().any(|(_, prev_signer)| prev_signer == signer))
+}
+
 #[cfg(test)]
 mod test {
 	use sp_core::{hash::H256, sr25519, Pair};
 	use sp_runtime::testing::{Digest as DigestTest, Header as HeaderTest};
 	use substrate_test_runtime_client;
 
-	use super::{check_equivocation, MAX_SLOT_CAPACITY, PRUNING_BOUND};
+	use super::{check_equivocation, has_header_at_slot, MAX_SLOT_CAPACITY, PRUNING_BOUND};
 
 	fn create_header(number: u64) -> HeaderTest {
 		// so that different headers for the same number get different hashes
@@ -220,4 +237,24 @@ mod test {
 		.unwrap()
 		.is_none(),);
 	}
+
+	#[test]
+	fn has_header_at_slot_works() {
+		let client = substrate_test_runtime_client::new();
+		let (pair, _seed) = sr25519::Pair::generate();
+		let public = pair.public();
+		let (other_pair, _seed) = sr25519::Pair::generate();
+		let other_public = other_pair.public();
+
+		assert!(!has_header_at_slot::<_, HeaderTest, _>(&client, 2.into(), &public).unwrap());
+
+		assert!(check_equivocation(&client, 2.into(), 2.into(), &create_header(1), &public)
+			.unwrap()
+			.is_none());
+
+		assert!(has_header_at_slot::<_, HeaderTest, _>(&client, 2.into(), &public).unwrap());
+		// Only the signer of the recorded header and only at its slot
+		assert!(!has_header_at_slot::<_, HeaderTest, _>(&client, 2.into(), &other_public).unwrap());
+		assert!(!has_header_at_slot::<_, HeaderTest, _>(&client, 3.into(), &public).unwrap());
+	}
 }
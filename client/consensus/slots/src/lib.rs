@@ -28,7 +28,7 @@
 mod aux_schema;
 mod slots;
 
-pub use aux_schema::{check_equivocation, MAX_SLOT_CAPACITY, PRUNING_BOUND};
+pub use aux_schema::{check_equivocation, has_header_at_slot, MAX_SLOT_CAPACITY, PRUNING_BOUND};
 pub use slots::SlotInfo;
 use slots::Slots;
 
@@ -48,7 +48,15 @@ use sp_runtime::{
 	traits::{Block as BlockT, HashFor, Header as HeaderT, NumberFor},
 };
 use sp_timestamp::Timestamp;
-use std::{fmt::Debug, ops::Deref, time::Duration};
+use std::{
+	fmt::Debug,
+	ops::Deref,
+	time::{Duration, Instant},
+};
+
+/// Portion of the time left for proposing that [`SimpleSlotWorker::on_slot`] waits for
+/// [`SimpleSlotWorker::claim_slot_async`] to resolve, the rest is left for building the block.
+pub const CLAIM_SLOT_PROPORTION: f32 = 0.5;
 
 /// The changes that need to applied to the storage to create the state for a block.
 ///
@@ -108,7 +116,7 @@ pub trait SimpleSlotWorker<B: BlockT> {
 	type Claim: Send + 'static;
 
 	/// Epoch data necessary for authoring.
-	type EpochData: Send + 'static;
+	type EpochData: Send + Sync + 'static;
 
 	/// The logging target to use when logging messages.
 	fn logging_target(&self) -> &'static str;
@@ -136,6 +144,21 @@ pub trait SimpleSlotWorker<B: BlockT> {
 		epoch_data: &Self::EpochData,
 	) -> Option<Self::Claim>;
 
+	/// Tries to claim the given slot asynchronously, returning an object with claim data if
+	/// successful.
+	///
+	/// This is what [`SimpleSlotWorker::on_slot`] uses to claim slots, it is abandoned if the claim
+	/// doesn't resolve within a portion of the time left for proposing, see
+	/// [`CLAIM_SLOT_PROPORTION`]. By default this calls [`SimpleSlotWorker::claim_slot`].
+	async fn claim_slot_async(
+		&mut self,
+		header: &B::Header,
+		slot: Slot,
+		epoch_data: &Self::EpochData,
+	) -> Option<Self::Claim> {
+		self.claim_slot(header, slot, epoch_data)
+	}
+
 	/// Notifies the given slot. Similar to `claim_slot`, but will be called no matter whether we
 	/// need to author blocks or not.
 	fn notify_slot(&self, _header: &B::Header, _slot: Slot, _epoch_data: &Self::EpochData) {}
@@ -147,6 +170,17 @@ pub trait SimpleSlotWorker<B: BlockT> {
 		claim: &Self::Claim,
 	) -> Vec<sp_runtime::DigestItem<B::Hash>>;
 
+	/// Add data of the given claim that doesn't belong in the pre digest to the inherent data of
+	/// the block authored with it, such that it is included in the block body. By default this
+	/// adds nothing.
+	fn claim_inherent_data(
+		&self,
+		_claim: &Self::Claim,
+		_inherent_data: &mut sp_inherents::InherentData,
+	) -> Result<(), sp_inherents::Error> {
+		Ok(())
+	}
+
 	/// Returns a function which produces a `BlockImportParams`.
 	fn block_import_params(
 		&self,
@@ -168,6 +202,27 @@ pub trait SimpleSlotWorker<B: BlockT> {
 			+ 'static,
 	>;
 
+	/// Produces `BlockImportParams` for the proposed block asynchronously, e.g. when sealing the
+	/// block requires a signature from a remote party.
+	///
+	/// This is what [`SimpleSlotWorker::on_slot`] uses to create block import params, it is
+	/// abandoned if it doesn't resolve before the end of the slot. By default this calls the
+	/// function returned by [`SimpleSlotWorker::block_import_params`].
+	async fn block_import_params_async(
+		&mut self,
+		header: B::Header,
+		header_hash: &B::Hash,
+		body: Vec<B::Extrinsic>,
+		storage_changes: StorageChanges<<Self::BlockImport as BlockImport<B>>::Transaction, B>,
+		claim: Self::Claim,
+		epoch_data: Self::EpochData,
+	) -> Result<
+		sc_consensus::BlockImportParams<B, <Self::BlockImport as BlockImport<B>>::Transaction>,
+		sp_consensus::Error,
+	> {
+		self.block_import_params()(header, header_hash, body, storage_changes, claim, epoch_data)
+	}
+
 	/// Whether to force authoring if offline.
 	fn force_authoring(&self) -> bool;
 
@@ -202,6 +257,7 @@ pub trait SimpleSlotWorker<B: BlockT> {
 		slot_info: SlotInfo<B>,
 	) -> Option<SlotResult<B, <Self::Proposer as Proposer<B>>::Proof>> {
 		let (timestamp, slot) = (slot_info.timestamp, slot_info.slot);
+		let slot_ends_at = slot_info.ends_at;
 		let telemetry = self.telemetry();
 		let logging_target = self.logging_target();
 
@@ -259,7 +315,28 @@ pub trait SimpleSlotWorker<B: BlockT> {
 			return None
 		}
 
-		let claim = self.claim_slot(&slot_info.chain_head, slot, &epoch_data)?;
+		let claim_started = Instant::now();
+		let claim_remaining =
+			Delay::new(proposing_remaining_duration.mul_f32(CLAIM_SLOT_PROPORTION));
+		let claim = match futures::future::select(
+			self.claim_slot_async(&slot_info.chain_head, slot, &epoch_data),
+			claim_remaining,
+		)
+		.await
+		{
+			Either::Left((claim, _)) => claim?,
+			Either::Right(_) => {
+				debug!(
+					target: logging_target,
+					"Giving up on claiming slot {}; no claim in time to build a block", slot,
+				);
+
+				return None
+			},
+		};
+		// whatever time the claim took is no longer available for proposing
+		let proposing_remaining_duration =
+			proposing_remaining_duration.saturating_sub(claim_started.elapsed());
 
 		if self.should_backoff(slot, &slot_info.chain_head) {
 			return None
@@ -302,12 +379,22 @@ pub trait SimpleSlotWorker<B: BlockT> {
 
 		let logs = self.pre_digest_data(slot, &claim);
 
+		let mut inherent_data = slot_info.inherent_data;
+		if let Err(err) = self.claim_inherent_data(&claim, &mut inherent_data) {
+			warn!(
+				target: logging_target,
+				"Unable to add claim to inherent data in slot {:?}: {:?}", slot, err,
+			);
+
+			return None
+		}
+
 		// deadline our production to 98% of the total time left for proposing. As we deadline
 		// the proposing below to the same total time left, the 2% margin should be enough for
 		// the result to be returned.
 		let proposing = proposer
 			.propose(
-				slot_info.inherent_data,
+				inherent_data,
 				sp_runtime::generic::Digest { logs },
 				proposing_remaining_duration.mul_f32(0.98),
 				None,
@@ -343,31 +430,45 @@ pub trait SimpleSlotWorker<B: BlockT> {
 			},
 		};
 
-		let block_import_params_maker = self.block_import_params();
-		let block_import = self.block_import();
-
 		let (block, storage_proof) = (proposal.block, proposal.proof);
 		let (header, body) = block.deconstruct();
 		let header_num = *header.number();
 		let header_hash = header.hash();
 		let parent_hash = *header.parent_hash();
 
-		let block_import_params = match block_import_params_maker(
-			header,
-			&header_hash,
-			body.clone(),
-			proposal.storage_changes,
-			claim,
-			epoch_data,
-		) {
-			Ok(bi) => bi,
-			Err(err) => {
+		let sealing_remaining = Delay::new(slot_ends_at.saturating_duration_since(Instant::now()));
+		let block_import_params = match futures::future::select(
+			self.block_import_params_async(
+				header,
+				&header_hash,
+				body.clone(),
+				proposal.storage_changes,
+				claim,
+				epoch_data,
+			),
+			sealing_remaining,
+		)
+		.await
+		{
+			Either::Left((Ok(bi), _)) => bi,
+			Either::Left((Err(err), _)) => {
 				warn!(target: logging_target, "Failed to create block import params: {:?}", err);
 
+				return None
+			},
+			Either::Right(_) => {
+				info!(
+					target: logging_target,
+					"⌛️ Discarding proposal for slot {}; block wasn't sealed before the end of the slot",
+					slot,
+				);
+
 				return None
 			},
 		};
 
+		let block_import = self.block_import();
+
 		info!(
 			target: logging_target,
 			"🔖 Pre-sealed block for proposal at {}. Hash now {:?}, previously {:?}.",
@@ -18,7 +18,7 @@
 
 //! # Offences PoC Pallet
 //!
-//! Tracks reported offences
+//! Tracks reported offences and rewards their reporters from a reward pot
 
 // Ensure we're `no_std` when compiling for Wasm.
 #![cfg_attr(not(feature = "std"), no_std)]
@@ -27,12 +27,17 @@ mod mock;
 mod tests;
 
 use codec::{Decode, Encode};
-use frame_support::weights::Weight;
+use frame_support::{
+    traits::{Currency, ExistenceRequirement, Get, StorageVersion},
+    weights::Weight,
+};
 use sp_consensus_poc::{
-    offence::{Kind, Offence, OffenceDetails, OffenceError, OnOffenceHandler, ReportOffence},
+    offence::{
+        Kind, Offence, OffenceDetails, OffenceError, OffenceRecord, OnOffenceHandler, ReportOffence,
+    },
     FarmerId,
 };
-use sp_runtime::traits::Hash;
+use sp_runtime::traits::{Hash, Zero};
 use sp_std::prelude::*;
 
 pub use pallet::*;
@@ -43,6 +48,9 @@ type OpaqueTimeSlot = Vec<u8>;
 /// A type alias for a report identifier.
 type ReportIdOf<T> = <T as frame_system::Config>::Hash;
 
+type BalanceOf<T> =
+    <<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+
 pub trait WeightInfo {
     fn on_initialize(d: u32) -> Weight;
 }
@@ -53,29 +61,52 @@ impl WeightInfo for () {
     }
 }
 
+/// The current storage version.
+const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
+
 #[frame_support::pallet]
 pub mod pallet {
     use super::*;
     use frame_support::pallet_prelude::*;
+    use frame_system::pallet_prelude::*;
 
     #[pallet::pallet]
     #[pallet::generate_store(pub(super) trait Store)]
+    #[pallet::storage_version(STORAGE_VERSION)]
     pub struct Pallet<T>(_);
 
     /// The pallet's config trait.
     #[pallet::config]
     pub trait Config: frame_system::Config {
         /// The overarching event type.
-        type Event: From<Event> + IsType<<Self as frame_system::Config>::Event>;
+        type Event: From<Event<Self>> + IsType<<Self as frame_system::Config>::Event>;
         /// A handler called for every offence report.
-        type OnOffenceHandler: OnOffenceHandler<FarmerId>;
+        type OnOffenceHandler: OnOffenceHandler<Self::AccountId, FarmerId>;
+        /// Currency reporters are rewarded in.
+        type Currency: Currency<Self::AccountId>;
+        /// Reward paid for every offence report that contains at least one new offender, split
+        /// equally between all reporters.
+        #[pallet::constant]
+        type ReporterReward: Get<BalanceOf<Self>>;
+        /// Account reporter rewards are paid from, reporters are not rewarded once it runs out.
+        ///
+        /// Rewards are never minted: offenders are only blocked and not slashed, so an offender
+        /// reporting its own offence would otherwise profit from it.
+        type RewardPot: Get<Self::AccountId>;
+    }
+
+    #[pallet::hooks]
+    impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+        fn on_runtime_upgrade() -> Weight {
+            migrations::migrate_reports_to_include_reporters::<T>()
+        }
     }
 
     /// The primary structure that holds all offence records keyed by report identifiers.
     #[pallet::storage]
     #[pallet::getter(fn reports)]
     pub type Reports<T: Config> =
-        StorageMap<_, Twox64Concat, ReportIdOf<T>, OffenceDetails<FarmerId>>;
+        StorageMap<_, Twox64Concat, ReportIdOf<T>, OffenceDetails<T::AccountId, FarmerId>>;
 
     /// A vector of reports of the same kind that happened at the same time slot.
     #[pallet::storage]
@@ -104,28 +135,39 @@ pub mod pallet {
         ValueQuery,
     >;
 
-    // TODO: We probably don't need this in PoC
+    /// Offences reported against each farmer, in the order they were reported.
+    #[pallet::storage]
+    #[pallet::getter(fn farmer_offences)]
+    pub type FarmerOffences<T> =
+        StorageMap<_, Blake2_128Concat, FarmerId, Vec<OffenceRecord>, ValueQuery>;
+
     /// Events type.
     #[pallet::event]
     #[pallet::generate_deposit(pub(super) fn deposit_event)]
-    pub enum Event {
-        /// There is an offence reported of the given `kind` happened at the `session_index` and
-        /// (kind-specific) time slot. This event is not deposited for duplicate slashes.
-        /// \[kind, timeslot\].
-        Offence(Kind, OpaqueTimeSlot),
+    #[pallet::metadata(T::AccountId = "AccountId", BalanceOf<T> = "Balance")]
+    pub enum Event<T: Config> {
+        /// There is an offence reported against farmer of the given `kind` happened at the
+        /// (kind-specific) time slot. This event is deposited once for every new offender and is
+        /// not deposited for duplicate reports.
+        /// \[kind, timeslot, offender\].
+        Offence(Kind, OpaqueTimeSlot, FarmerId),
+        /// Reporter of an offence was rewarded.
+        /// \[reporter, reward\].
+        ReporterRewarded(T::AccountId, BalanceOf<T>),
     }
 }
 
-impl<T: Config, O: Offence<FarmerId>> ReportOffence<FarmerId, O> for Pallet<T> {
-    fn report_offence(offence: O) -> Result<(), OffenceError> {
+impl<T: Config, O: Offence<FarmerId>> ReportOffence<T::AccountId, FarmerId, O> for Pallet<T> {
+    fn report_offence(reporters: Vec<T::AccountId>, offence: O) -> Result<(), OffenceError> {
         let offenders = offence.offenders();
         let time_slot = offence.time_slot();
 
         // Go through all offenders in the offence report and find all offenders that were spotted
         // in unique reports.
         let TriageOutcome {
+            new_offenders,
             concurrent_offenders,
-        } = match Self::triage_offence_report::<O>(&time_slot, offenders) {
+        } = match Self::triage_offence_report::<O>(&reporters, &time_slot, offenders) {
             Some(triage) => triage,
             // The report contained only duplicates, so there is no need to slash again.
             None => return Err(OffenceError::DuplicateReport),
@@ -133,8 +175,20 @@ impl<T: Config, O: Offence<FarmerId>> ReportOffence<FarmerId, O> for Pallet<T> {
 
         T::OnOffenceHandler::on_offence(&concurrent_offenders);
 
-        // Deposit the event.
-        Self::deposit_event(Event::Offence(O::ID, time_slot.encode()));
+        let opaque_time_slot = time_slot.encode();
+        for offender in new_offenders {
+            FarmerOffences::<T>::append(
+                &offender,
+                OffenceRecord {
+                    kind: O::ID,
+                    time_slot: opaque_time_slot.clone(),
+                },
+            );
+
+            Self::deposit_event(Event::Offence(O::ID, opaque_time_slot.clone(), offender));
+        }
+
+        Self::reward_reporters(&reporters);
 
         Ok(())
     }
@@ -160,27 +214,65 @@ impl<T: Config> Pallet<T> {
         (O::ID, time_slot.encode(), offender).using_encoded(T::Hashing::hash)
     }
 
+    /// Split `ReporterReward` equally between `reporters`, paying it from `RewardPot`.
+    fn reward_reporters(reporters: &[T::AccountId]) {
+        if reporters.is_empty() {
+            return;
+        }
+
+        let reward = T::ReporterReward::get() / (reporters.len() as u32).into();
+        if reward.is_zero() {
+            return;
+        }
+
+        let reward_pot = T::RewardPot::get();
+        for reporter in reporters {
+            if let Err(error) = T::Currency::transfer(
+                &reward_pot,
+                reporter,
+                reward,
+                ExistenceRequirement::KeepAlive,
+            ) {
+                log::warn!(
+                    target: "runtime::offences-poc",
+                    "Failed to pay offence reporter reward from reward pot: {:?}",
+                    error,
+                );
+                continue;
+            }
+
+            Self::deposit_event(Event::ReporterRewarded(reporter.clone(), reward));
+        }
+    }
+
     /// Triages the offence report and returns the set of offenders that was involved in unique
     /// reports along with the list of the concurrent offences.
     fn triage_offence_report<O: Offence<FarmerId>>(
+        reporters: &[T::AccountId],
         time_slot: &O::TimeSlot,
         offenders: Vec<FarmerId>,
-    ) -> Option<TriageOutcome> {
+    ) -> Option<TriageOutcome<T>> {
         let mut storage = ReportIndexStorage::<T, O>::load(time_slot);
 
-        let mut any_new = false;
+        let mut new_offenders = Vec::new();
         for offender in offenders {
             let report_id = Self::report_id::<O>(time_slot, &offender);
 
             if !<Reports<T>>::contains_key(&report_id) {
-                any_new = true;
-                <Reports<T>>::insert(&report_id, OffenceDetails { offender });
+                <Reports<T>>::insert(
+                    &report_id,
+                    OffenceDetails {
+                        offender: offender.clone(),
+                        reporters: reporters.to_vec(),
+                    },
+                );
 
                 storage.insert(time_slot, report_id);
+                new_offenders.push(offender);
             }
         }
 
-        if any_new {
+        if !new_offenders.is_empty() {
             // Load report details for the all reports happened at the same time.
             let concurrent_offenders = storage
                 .concurrent_reports
@@ -191,6 +283,7 @@ impl<T: Config> Pallet<T> {
             storage.save();
 
             Some(TriageOutcome {
+                new_offenders,
                 concurrent_offenders,
             })
         } else {
@@ -199,9 +292,11 @@ impl<T: Config> Pallet<T> {
     }
 }
 
-struct TriageOutcome {
+struct TriageOutcome<T: Config> {
+    /// Offenders that were not reported at this time slot before.
+    new_offenders: Vec<FarmerId>,
     /// Other reports for the same report kinds.
-    concurrent_offenders: Vec<OffenceDetails<FarmerId>>,
+    concurrent_offenders: Vec<OffenceDetails<T::AccountId, FarmerId>>,
 }
 
 /// An auxiliary struct for working with storage of indexes localized for a specific offence
@@ -259,3 +354,38 @@ impl<T: Config, O: Offence<FarmerId>> ReportIndexStorage<T, O> {
         );
     }
 }
+
+pub mod migrations {
+    use super::*;
+
+    /// A storage migration that records no reporters for offences in `Reports` stored before
+    /// reporters were recorded (storage version 0, when the value only contained the offender).
+    ///
+    /// Does nothing if storage version is already 1 or higher, so it is safe to call it multiple
+    /// times.
+    pub fn migrate_reports_to_include_reporters<T: Config>() -> Weight {
+        let on_chain_version = StorageVersion::get::<Pallet<T>>();
+        if on_chain_version >= 1 {
+            return T::DbWeight::get().reads(1);
+        }
+
+        let mut translated = 0u64;
+        Reports::<T>::translate::<FarmerId, _>(|_report_id, offender| {
+            translated += 1;
+            Some(OffenceDetails {
+                offender,
+                reporters: Vec::new(),
+            })
+        });
+
+        StorageVersion::new(1).put::<Pallet<T>>();
+
+        log::info!(
+            target: "runtime::offences-poc",
+            "Migrated {} offence reports to include reporters",
+            translated,
+        );
+
+        T::DbWeight::get().reads_writes(translated + 1, translated + 1)
+    }
+}
@@ -47,8 +47,8 @@ thread_local! {
     pub static OFFENCE_WEIGHT: RefCell<Weight> = RefCell::new(Default::default());
 }
 
-impl<Offender> offence::OnOffenceHandler<Offender> for OnOffenceHandler {
-    fn on_offence(_offenders: &[OffenceDetails<Offender>]) {
+impl<Reporter, Offender> offence::OnOffenceHandler<Reporter, Offender> for OnOffenceHandler {
+    fn on_offence(_offenders: &[OffenceDetails<Reporter, Offender>]) {
         ON_OFFENCE_PERBILL.with(|f| {
             *f.borrow_mut() = vec![Perbill::from_percent(25)];
         });
@@ -69,7 +69,8 @@ frame_support::construct_runtime!(
         UncheckedExtrinsic = UncheckedExtrinsic,
     {
         System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
-        OffencesPoC: offences_poc::{Pallet, Storage, Event},
+        Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
+        OffencesPoC: offences_poc::{Pallet, Storage, Event<T>},
     }
 );
 
@@ -96,7 +97,7 @@ impl frame_system::Config for Runtime {
     type BlockHashCount = BlockHashCount;
     type Version = ();
     type PalletInfo = PalletInfo;
-    type AccountData = ();
+    type AccountData = pallet_balances::AccountData<u128>;
     type OnNewAccount = ();
     type OnKilledAccount = ();
     type SystemWeightInfo = ();
@@ -104,15 +105,49 @@ impl frame_system::Config for Runtime {
     type OnSetCode = ();
 }
 
+parameter_types! {
+    pub const ExistentialDeposit: u128 = 1;
+}
+
+impl pallet_balances::Config for Runtime {
+    type MaxLocks = ();
+    type MaxReserves = ();
+    type ReserveIdentifier = [u8; 8];
+    type Balance = u128;
+    type DustRemoval = ();
+    type Event = Event;
+    type ExistentialDeposit = ExistentialDeposit;
+    type AccountStore = System;
+    type WeightInfo = ();
+}
+
+pub const REPORTER_REWARD: u128 = 100;
+/// Account that has funds for reporter rewards in genesis.
+pub const REWARD_POT_ACCOUNT: u64 = 1000;
+pub const REWARD_POT_BALANCE: u128 = 1_000;
+
+parameter_types! {
+    pub const ReporterReward: u128 = REPORTER_REWARD;
+    pub const RewardPot: u64 = REWARD_POT_ACCOUNT;
+}
+
 impl Config for Runtime {
     type Event = Event;
     type OnOffenceHandler = OnOffenceHandler;
+    type Currency = Balances;
+    type ReporterReward = ReporterReward;
+    type RewardPot = RewardPot;
 }
 
 pub fn new_test_ext() -> sp_io::TestExternalities {
-    let t = frame_system::GenesisConfig::default()
+    let mut t = frame_system::GenesisConfig::default()
         .build_storage::<Runtime>()
         .unwrap();
+    pallet_balances::GenesisConfig::<Runtime> {
+        balances: vec![(REWARD_POT_ACCOUNT, REWARD_POT_BALANCE)],
+    }
+    .assimilate_storage(&mut t)
+    .unwrap();
     let mut ext = sp_io::TestExternalities::new(t);
     ext.execute_with(|| System::set_block_number(1));
     ext
@@ -121,7 +156,7 @@ pub fn new_test_ext() -> sp_io::TestExternalities {
 pub const KIND: [u8; 16] = *b"test_report_1234";
 
 /// Returns all offence details for the specific `kind` happened at the specific time slot.
-pub fn offence_reports(kind: Kind, time_slot: u128) -> Vec<OffenceDetails<FarmerId>> {
+pub fn offence_reports(kind: Kind, time_slot: u128) -> Vec<OffenceDetails<u64, FarmerId>> {
     <crate::ConcurrentReportsIndex<Runtime>>::get(&kind, &time_slot.encode())
         .into_iter()
         .map(|report_id| {
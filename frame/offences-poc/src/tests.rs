@@ -22,8 +22,9 @@
 
 use super::*;
 use crate::mock::{
-    new_test_ext, offence_reports, report_id, with_on_offence_fractions, Event, Offence,
-    OffencesPoC, System, KIND,
+    new_test_ext, offence_reports, report_id, with_on_offence_fractions, Balances, Event,
+    ExistentialDeposit, Offence, OffencesPoC, Runtime, System, KIND, REPORTER_REWARD,
+    REWARD_POT_ACCOUNT, REWARD_POT_BALANCE,
 };
 use frame_system::{EventRecord, Phase};
 use schnorrkel::Keypair;
@@ -48,7 +49,7 @@ fn should_report_an_farmer_and_trigger_on_offence() {
         };
 
         // when
-        OffencesPoC::report_offence(offence).unwrap();
+        OffencesPoC::report_offence(vec![], offence).unwrap();
 
         // then
         with_on_offence_fractions(|f| {
@@ -68,7 +69,7 @@ fn should_not_report_the_same_farmer_twice_in_the_same_slot() {
             time_slot,
             offenders: vec![generate_farmer_id()],
         };
-        OffencesPoC::report_offence(offence.clone()).unwrap();
+        OffencesPoC::report_offence(vec![], offence.clone()).unwrap();
         with_on_offence_fractions(|f| {
             assert_eq!(f.clone(), vec![Perbill::from_percent(25)]);
             f.clear();
@@ -77,7 +78,7 @@ fn should_not_report_the_same_farmer_twice_in_the_same_slot() {
         // when
         // report for the second time
         assert_eq!(
-            OffencesPoC::report_offence(offence),
+            OffencesPoC::report_offence(vec![], offence),
             Err(OffenceError::DuplicateReport)
         );
 
@@ -99,7 +100,7 @@ fn should_report_in_different_time_slot() {
             time_slot,
             offenders: vec![generate_farmer_id()],
         };
-        OffencesPoC::report_offence(offence.clone()).unwrap();
+        OffencesPoC::report_offence(vec![], offence.clone()).unwrap();
         with_on_offence_fractions(|f| {
             assert_eq!(f.clone(), vec![Perbill::from_percent(25)]);
             f.clear();
@@ -108,7 +109,7 @@ fn should_report_in_different_time_slot() {
         // when
         // report for the second time
         offence.time_slot += 1;
-        OffencesPoC::report_offence(offence).unwrap();
+        OffencesPoC::report_offence(vec![], offence).unwrap();
 
         // then
         with_on_offence_fractions(|f| {
@@ -124,20 +125,25 @@ fn should_deposit_event() {
         let time_slot = 42;
         assert_eq!(offence_reports(KIND, time_slot), vec![]);
 
+        let farmer_id = generate_farmer_id();
         let offence = Offence {
             time_slot,
-            offenders: vec![generate_farmer_id()],
+            offenders: vec![farmer_id.clone()],
         };
 
         // when
-        OffencesPoC::report_offence(offence).unwrap();
+        OffencesPoC::report_offence(vec![], offence).unwrap();
 
         // then
         assert_eq!(
             System::events(),
             vec![EventRecord {
                 phase: Phase::Initialization,
-                event: Event::OffencesPoC(crate::Event::Offence(KIND, time_slot.encode())),
+                event: Event::OffencesPoC(crate::Event::Offence(
+                    KIND,
+                    time_slot.encode(),
+                    farmer_id
+                )),
                 topics: vec![],
             }]
         );
@@ -151,11 +157,12 @@ fn doesnt_deposit_event_for_dups() {
         let time_slot = 42;
         assert_eq!(offence_reports(KIND, time_slot), vec![]);
 
+        let farmer_id = generate_farmer_id();
         let offence = Offence {
             time_slot,
-            offenders: vec![generate_farmer_id()],
+            offenders: vec![farmer_id.clone()],
         };
-        OffencesPoC::report_offence(offence.clone()).unwrap();
+        OffencesPoC::report_offence(vec![], offence.clone()).unwrap();
         with_on_offence_fractions(|f| {
             assert_eq!(f.clone(), vec![Perbill::from_percent(25)]);
             f.clear();
@@ -164,7 +171,7 @@ fn doesnt_deposit_event_for_dups() {
         // when
         // report for the second time
         assert_eq!(
-            OffencesPoC::report_offence(offence),
+            OffencesPoC::report_offence(vec![], offence),
             Err(OffenceError::DuplicateReport)
         );
 
@@ -174,7 +181,11 @@ fn doesnt_deposit_event_for_dups() {
             System::events(),
             vec![EventRecord {
                 phase: Phase::Initialization,
-                event: Event::OffencesPoC(crate::Event::Offence(KIND, time_slot.encode())),
+                event: Event::OffencesPoC(crate::Event::Offence(
+                    KIND,
+                    time_slot.encode(),
+                    farmer_id
+                )),
                 topics: vec![],
             }]
         );
@@ -202,18 +213,18 @@ fn reports_if_an_offence_is_dup() {
         // the report for farmer 0 at time slot 42 should not be a known
         // offence
         assert!(
-            !<OffencesPoC as ReportOffence<_, TestOffence>>::is_known_offence(
+            !<OffencesPoC as ReportOffence<_, _, TestOffence>>::is_known_offence(
                 &test_offence.offenders,
                 &test_offence.time_slot
             )
         );
 
         // we report an offence for farmer 0 at time slot 42
-        OffencesPoC::report_offence(test_offence.clone()).unwrap();
+        OffencesPoC::report_offence(vec![], test_offence.clone()).unwrap();
 
         // the same report should be a known offence now
         assert!(
-            <OffencesPoC as ReportOffence<_, TestOffence>>::is_known_offence(
+            <OffencesPoC as ReportOffence<_, _, TestOffence>>::is_known_offence(
                 &test_offence.offenders,
                 &test_offence.time_slot
             )
@@ -221,7 +232,7 @@ fn reports_if_an_offence_is_dup() {
 
         // and reporting it again should yield a duplicate report error
         assert_eq!(
-            OffencesPoC::report_offence(test_offence.clone()),
+            OffencesPoC::report_offence(vec![], test_offence.clone()),
             Err(OffenceError::DuplicateReport)
         );
 
@@ -230,20 +241,23 @@ fn reports_if_an_offence_is_dup() {
 
         // it should not be a known offence anymore
         assert!(
-            !<OffencesPoC as ReportOffence<_, TestOffence>>::is_known_offence(
+            !<OffencesPoC as ReportOffence<_, _, TestOffence>>::is_known_offence(
                 &test_offence.offenders,
                 &test_offence.time_slot
             )
         );
 
         // and reporting it again should work without any error
-        assert_eq!(OffencesPoC::report_offence(test_offence.clone()), Ok(()));
+        assert_eq!(
+            OffencesPoC::report_offence(vec![], test_offence.clone()),
+            Ok(())
+        );
 
         // creating a new offence for the same farmers on the next slot
         // should be considered a new offence and therefore not known
         let test_offence_next_slot = offence(time_slot + 1, vec![farmer_0, farmer_1]);
         assert!(
-            !<OffencesPoC as ReportOffence<_, TestOffence>>::is_known_offence(
+            !<OffencesPoC as ReportOffence<_, _, TestOffence>>::is_known_offence(
                 &test_offence_next_slot.offenders,
                 &test_offence_next_slot.time_slot
             )
@@ -271,7 +285,7 @@ fn should_properly_count_offences() {
             time_slot,
             offenders: vec![farmer_2.clone()],
         };
-        OffencesPoC::report_offence(offence1).unwrap();
+        OffencesPoC::report_offence(vec![], offence1).unwrap();
         with_on_offence_fractions(|f| {
             assert_eq!(f.clone(), vec![Perbill::from_percent(25)]);
             f.clear();
@@ -279,15 +293,21 @@ fn should_properly_count_offences() {
 
         // when
         // report for the second time
-        OffencesPoC::report_offence(offence2).unwrap();
+        OffencesPoC::report_offence(vec![], offence2).unwrap();
 
         // then
         // the 1st farmer should have count 2 and the 2nd one should be reported only once.
         assert_eq!(
             offence_reports(KIND, time_slot),
             vec![
-                OffenceDetails { offender: farmer_1 },
-                OffenceDetails { offender: farmer_2 },
+                OffenceDetails {
+                    offender: farmer_1,
+                    reporters: vec![],
+                },
+                OffenceDetails {
+                    offender: farmer_2,
+                    reporters: vec![],
+                },
             ]
         );
     });
@@ -324,7 +344,7 @@ fn should_properly_sort_offences() {
             time_slot: time_slot - 1,
             offenders: vec![farmer_3.clone()],
         };
-        OffencesPoC::report_offence(offence1).unwrap();
+        OffencesPoC::report_offence(vec![], offence1).unwrap();
         with_on_offence_fractions(|f| {
             assert_eq!(f.clone(), vec![Perbill::from_percent(25)]);
             f.clear();
@@ -332,9 +352,9 @@ fn should_properly_sort_offences() {
 
         // when
         // report for the second time
-        OffencesPoC::report_offence(offence2).unwrap();
-        OffencesPoC::report_offence(offence3).unwrap();
-        OffencesPoC::report_offence(offence4).unwrap();
+        OffencesPoC::report_offence(vec![], offence2).unwrap();
+        OffencesPoC::report_offence(vec![], offence3).unwrap();
+        OffencesPoC::report_offence(vec![], offence4).unwrap();
 
         // then
         let same_kind_reports = Vec::<(u128, sp_core::H256)>::decode(
@@ -353,3 +373,172 @@ fn should_properly_sort_offences() {
         );
     });
 }
+
+#[test]
+fn should_reward_reporters_from_reward_pot() {
+    new_test_ext().execute_with(|| {
+        // given
+        let time_slot = 42;
+        let farmer_id = generate_farmer_id();
+        let offence = Offence {
+            time_slot,
+            offenders: vec![farmer_id.clone()],
+        };
+        let total_issuance = Balances::total_issuance();
+
+        // when
+        OffencesPoC::report_offence(vec![1, 2], offence).unwrap();
+
+        // then
+        // the reward is split equally between reporters and nothing is minted
+        assert_eq!(Balances::free_balance(1), REPORTER_REWARD / 2);
+        assert_eq!(Balances::free_balance(2), REPORTER_REWARD / 2);
+        assert_eq!(
+            Balances::free_balance(REWARD_POT_ACCOUNT),
+            REWARD_POT_BALANCE - REPORTER_REWARD
+        );
+        assert_eq!(Balances::total_issuance(), total_issuance);
+        assert_eq!(
+            offence_reports(KIND, time_slot),
+            vec![OffenceDetails {
+                offender: farmer_id,
+                reporters: vec![1, 2],
+            }]
+        );
+        assert!(System::events().iter().any(|record| record.event
+            == Event::OffencesPoC(crate::Event::ReporterRewarded(1, REPORTER_REWARD / 2))));
+        assert!(System::events().iter().any(|record| record.event
+            == Event::OffencesPoC(crate::Event::ReporterRewarded(2, REPORTER_REWARD / 2))));
+    });
+}
+
+#[test]
+fn should_not_reward_reporters_once_reward_pot_runs_out() {
+    new_test_ext().execute_with(|| {
+        // given
+        Balances::make_free_balance_be(&REWARD_POT_ACCOUNT, ExistentialDeposit::get());
+        let offence = Offence {
+            time_slot: 42,
+            offenders: vec![generate_farmer_id()],
+        };
+        let total_issuance = Balances::total_issuance();
+
+        // when
+        OffencesPoC::report_offence(vec![1], offence).unwrap();
+
+        // then
+        // the offence is still reported, but the reward is neither paid nor minted
+        assert_eq!(Balances::free_balance(1), 0);
+        assert_eq!(
+            Balances::free_balance(REWARD_POT_ACCOUNT),
+            ExistentialDeposit::get()
+        );
+        assert_eq!(Balances::total_issuance(), total_issuance);
+        assert!(!System::events().iter().any(|record| matches!(
+            record.event,
+            Event::OffencesPoC(crate::Event::ReporterRewarded(..))
+        )));
+    });
+}
+
+#[test]
+fn should_not_reward_reporters_of_duplicate_offences() {
+    new_test_ext().execute_with(|| {
+        // given
+        let offence = Offence {
+            time_slot: 42,
+            offenders: vec![generate_farmer_id()],
+        };
+        OffencesPoC::report_offence(vec![1], offence.clone()).unwrap();
+        assert_eq!(Balances::free_balance(1), REPORTER_REWARD);
+
+        // when
+        assert_eq!(
+            OffencesPoC::report_offence(vec![2], offence),
+            Err(OffenceError::DuplicateReport)
+        );
+
+        // then
+        assert_eq!(Balances::free_balance(2), 0);
+    });
+}
+
+#[test]
+fn should_record_offences_per_farmer() {
+    new_test_ext().execute_with(|| {
+        // given
+        let farmer_1 = generate_farmer_id();
+        let farmer_2 = generate_farmer_id();
+        assert_eq!(OffencesPoC::farmer_offences(&farmer_1), vec![]);
+
+        // when
+        OffencesPoC::report_offence(
+            vec![],
+            Offence {
+                time_slot: 42,
+                offenders: vec![farmer_1.clone(), farmer_2.clone()],
+            },
+        )
+        .unwrap();
+        OffencesPoC::report_offence(
+            vec![],
+            Offence {
+                time_slot: 43,
+                offenders: vec![farmer_1.clone()],
+            },
+        )
+        .unwrap();
+
+        // then
+        assert_eq!(
+            OffencesPoC::farmer_offences(&farmer_1),
+            vec![
+                OffenceRecord {
+                    kind: KIND,
+                    time_slot: 42u128.encode(),
+                },
+                OffenceRecord {
+                    kind: KIND,
+                    time_slot: 43u128.encode(),
+                },
+            ]
+        );
+        assert_eq!(
+            OffencesPoC::farmer_offences(&farmer_2),
+            vec![OffenceRecord {
+                kind: KIND,
+                time_slot: 42u128.encode(),
+            }]
+        );
+    });
+}
+
+#[test]
+fn migrate_reports_to_include_reporters_works() {
+    use frame_support::{storage::unhashed, traits::StorageVersion};
+
+    new_test_ext().execute_with(|| {
+        let farmer_id = generate_farmer_id();
+        let report_id = report_id(42, farmer_id.clone());
+
+        // entries of storage version 0 only contained the offender
+        StorageVersion::new(0).put::<OffencesPoC>();
+        unhashed::put(&Reports::<Runtime>::hashed_key_for(&report_id), &farmer_id);
+
+        migrations::migrate_reports_to_include_reporters::<Runtime>();
+
+        let offence_details = OffenceDetails {
+            offender: farmer_id,
+            reporters: vec![],
+        };
+        assert_eq!(
+            OffencesPoC::reports(&report_id),
+            Some(offence_details.clone())
+        );
+        assert_eq!(StorageVersion::get::<OffencesPoC>(), 1);
+
+        // running migration again does nothing
+        migrations::migrate_reports_to_include_reporters::<Runtime>();
+        assert_eq!(OffencesPoC::reports(&report_id), Some(offence_details));
+    });
+}
@@ -0,0 +1,171 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # PoC Solution Data Pallet
+//!
+//! Blocks with compact PoC solutions only carry a commitment to the solution data (encoding and
+//! piece inclusion proof) in their header, this pallet includes the solution data itself in the
+//! block body with an inherent and checks it against the commitment.
+
+// Ensure we're `no_std` when compiling for Wasm.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+mod mock;
+mod tests;
+
+use codec::Decode;
+use frame_support::weights::Weight;
+use sp_consensus_poc::{
+    digests::{PreDigest, Solution, SolutionData},
+    inherents::{SolutionDataInherentType, SOLUTION_DATA_INHERENT_IDENTIFIER},
+    POC_ENGINE_ID,
+};
+
+pub use pallet::*;
+
+pub trait WeightInfo {
+    fn include_solution_data() -> Weight;
+    fn on_finalize() -> Weight;
+}
+
+impl WeightInfo for () {
+    fn include_solution_data() -> Weight {
+        10_000_000
+    }
+
+    fn on_finalize() -> Weight {
+        5_000_000
+    }
+}
+
+#[frame_support::pallet]
+pub mod pallet {
+    use super::*;
+    use frame_support::pallet_prelude::*;
+    use frame_system::pallet_prelude::*;
+    use sp_inherents::{InherentData, InherentIdentifier, MakeFatalError};
+
+    #[pallet::pallet]
+    #[pallet::generate_store(pub(super) trait Store)]
+    pub struct Pallet<T>(_);
+
+    /// The pallet's config trait.
+    #[pallet::config]
+    pub trait Config: frame_system::Config {
+        /// Weight information for extrinsics in this pallet.
+        type WeightInfo: WeightInfo;
+    }
+
+    /// Whether solution data was included in the current block, taken at the end of the block.
+    #[pallet::storage]
+    pub(super) type DidInclude<T> = StorageValue<_, bool, ValueQuery>;
+
+    #[pallet::error]
+    pub enum Error<T> {
+        /// Solution data was already included in this block.
+        AlreadyIncluded,
+        /// Block doesn't have a compact solution in its pre-digest.
+        NotCompactSolution,
+        /// Solution data doesn't match the commitment in the pre-digest.
+        InvalidSolutionData,
+    }
+
+    #[pallet::hooks]
+    impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+        fn on_initialize(_n: BlockNumberFor<T>) -> Weight {
+            // weight of `on_finalize`
+            T::WeightInfo::on_finalize()
+        }
+
+        fn on_finalize(_n: BlockNumberFor<T>) {
+            assert!(
+                DidInclude::<T>::take() || Self::compact_solution().is_none(),
+                "Solution data must be included in blocks with compact solutions"
+            );
+        }
+    }
+
+    #[pallet::call]
+    impl<T: Config> Pallet<T> {
+        /// Include solution data of the compact solution in the pre-digest of this block. Solution
+        /// data must match the commitment in the pre-digest.
+        ///
+        /// This is an inherent, solution data is provided by the block author. It must remain the
+        /// last argument, clients expect it to fill the rest of the encoded extrinsic.
+        #[pallet::weight((
+            T::WeightInfo::include_solution_data(),
+            DispatchClass::Mandatory
+        ))]
+        pub fn include_solution_data(
+            origin: OriginFor<T>,
+            solution_data: SolutionData,
+        ) -> DispatchResult {
+            ensure_none(origin)?;
+            ensure!(!DidInclude::<T>::get(), Error::<T>::AlreadyIncluded);
+
+            let compact_solution =
+                Self::compact_solution().ok_or(Error::<T>::NotCompactSolution)?;
+            ensure!(
+                compact_solution.with_solution_data(solution_data).is_some(),
+                Error::<T>::InvalidSolutionData
+            );
+
+            DidInclude::<T>::put(true);
+
+            Ok(())
+        }
+    }
+
+    #[pallet::inherent]
+    impl<T: Config> ProvideInherent for Pallet<T> {
+        type Call = Call<T>;
+        type Error = MakeFatalError<()>;
+        const INHERENT_IDENTIFIER: InherentIdentifier = SOLUTION_DATA_INHERENT_IDENTIFIER;
+
+        fn create_inherent(data: &InherentData) -> Option<Self::Call> {
+            data.get_data::<SolutionDataInherentType>(&SOLUTION_DATA_INHERENT_IDENTIFIER)
+                .ok()
+                .flatten()
+                .map(Call::include_solution_data)
+        }
+
+        fn is_inherent(call: &Self::Call) -> bool {
+            matches!(call, Call::include_solution_data(_))
+        }
+    }
+}
+
+impl<T: Config> Pallet<T> {
+    /// Compact solution in the pre-digest of the current block, `None` if the block doesn't have
+    /// one.
+    pub fn compact_solution() -> Option<Solution> {
+        <frame_system::Pallet<T>>::digest()
+            .logs
+            .iter()
+            .filter_map(|s| s.as_pre_runtime())
+            .filter_map(|(id, mut data)| {
+                if id == POC_ENGINE_ID {
+                    PreDigest::decode(&mut data).ok()
+                } else {
+                    None
+                }
+            })
+            .next()
+            .map(|pre_digest| pre_digest.solution)
+            .filter(Solution::is_compact)
+    }
+}
@@ -0,0 +1,130 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test utilities
+
+#![cfg(test)]
+
+use crate as poc_solution_data;
+use crate::Config;
+use codec::Encode;
+use frame_support::{parameter_types, traits::OnFinalize, weights::constants::RocksDbWeight};
+use frame_system::InitKind;
+use sp_consensus_poc::{
+    digests::{PreDigest, Solution, SolutionData},
+    FarmerId, POC_ENGINE_ID,
+};
+use sp_core::H256;
+use sp_runtime::testing::{Digest, DigestItem, Header};
+use sp_runtime::traits::{BlakeTwo256, IdentityLookup};
+
+type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Runtime>;
+type Block = frame_system::mocking::MockBlock<Runtime>;
+
+frame_support::construct_runtime!(
+    pub enum Runtime where
+        Block = Block,
+        NodeBlock = Block,
+        UncheckedExtrinsic = UncheckedExtrinsic,
+    {
+        System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
+        PoCSolutionData: poc_solution_data::{Pallet, Call, Storage, Inherent},
+    }
+);
+
+parameter_types! {
+    pub const BlockHashCount: u64 = 250;
+}
+
+impl frame_system::Config for Runtime {
+    type BaseCallFilter = frame_support::traits::AllowAll;
+    type BlockWeights = ();
+    type BlockLength = ();
+    type DbWeight = RocksDbWeight;
+    type Origin = Origin;
+    type Index = u64;
+    type BlockNumber = u64;
+    type Call = Call;
+    type Hash = H256;
+    type Hashing = BlakeTwo256;
+    type AccountId = u64;
+    type Lookup = IdentityLookup<Self::AccountId>;
+    type Header = Header;
+    type Event = Event;
+    type BlockHashCount = BlockHashCount;
+    type Version = ();
+    type PalletInfo = PalletInfo;
+    type AccountData = ();
+    type OnNewAccount = ();
+    type OnKilledAccount = ();
+    type SystemWeightInfo = ();
+    type SS58Prefix = ();
+    type OnSetCode = ();
+}
+
+impl Config for Runtime {
+    type WeightInfo = ();
+}
+
+pub fn new_test_ext() -> sp_io::TestExternalities {
+    let t = frame_system::GenesisConfig::default()
+        .build_storage::<Runtime>()
+        .unwrap();
+    sp_io::TestExternalities::new(t)
+}
+
+/// Solution data of a genesis piece encoding, contents don't matter for this pallet.
+pub fn solution_data() -> SolutionData {
+    SolutionData {
+        encoding: vec![1u8; 4096],
+        piece_inclusion_proof: None,
+    }
+}
+
+/// Initialize block 1 with a pre-digest that has a compact solution with `solution_data` if
+/// `compact` is `true`, or a full solution otherwise.
+pub fn initialize_block(solution_data: &SolutionData, compact: bool) {
+    let solution = Solution {
+        public_key: FarmerId::default(),
+        nonce: 0,
+        encoding: solution_data.encoding.clone(),
+        signature: vec![0u8; 64],
+        tag: [0u8; 8],
+        piece_inclusion_proof: solution_data.piece_inclusion_proof.clone(),
+        solution_data_commitment: None,
+    };
+    let solution = if compact {
+        solution.to_compact()
+    } else {
+        solution
+    };
+
+    let pre_digest = PreDigest {
+        slot: 1.into(),
+        solution,
+    };
+    let digest = Digest {
+        logs: vec![DigestItem::PreRuntime(POC_ENGINE_ID, pre_digest.encode())],
+    };
+
+    System::initialize(&1, &Default::default(), &digest, InitKind::Full);
+}
+
+/// Finalize the current block, which checks that solution data was included if necessary.
+pub fn finalize_block() {
+    PoCSolutionData::on_finalize(System::block_number());
+}
@@ -0,0 +1,87 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the PoC solution data module.
+
+#![cfg(test)]
+
+use super::*;
+use crate::mock::{
+    finalize_block, initialize_block, new_test_ext, solution_data, Origin, PoCSolutionData,
+};
+use frame_support::{assert_noop, assert_ok};
+
+#[test]
+fn can_include_solution_data() {
+    new_test_ext().execute_with(|| {
+        let solution_data = solution_data();
+        initialize_block(&solution_data, true);
+
+        assert_ok!(PoCSolutionData::include_solution_data(
+            Origin::none(),
+            solution_data.clone()
+        ));
+        assert_noop!(
+            PoCSolutionData::include_solution_data(Origin::none(), solution_data),
+            Error::<mock::Runtime>::AlreadyIncluded
+        );
+
+        finalize_block();
+    });
+}
+
+#[test]
+fn rejects_solution_data_not_matching_commitment() {
+    new_test_ext().execute_with(|| {
+        let solution_data = solution_data();
+        initialize_block(&solution_data, true);
+
+        let mut other_solution_data = solution_data;
+        other_solution_data.encoding[0] ^= 1;
+
+        assert_noop!(
+            PoCSolutionData::include_solution_data(Origin::none(), other_solution_data),
+            Error::<mock::Runtime>::InvalidSolutionData
+        );
+    });
+}
+
+#[test]
+fn rejects_solution_data_without_compact_solution() {
+    new_test_ext().execute_with(|| {
+        let solution_data = solution_data();
+        initialize_block(&solution_data, false);
+
+        assert_noop!(
+            PoCSolutionData::include_solution_data(Origin::none(), solution_data),
+            Error::<mock::Runtime>::NotCompactSolution
+        );
+
+        // solution data is not required for blocks with full solutions
+        finalize_block();
+    });
+}
+
+#[test]
+#[should_panic(expected = "Solution data must be included in blocks with compact solutions")]
+fn solution_data_is_required_for_compact_solution() {
+    new_test_ext().execute_with(|| {
+        initialize_block(&solution_data(), true);
+
+        finalize_block();
+    });
+}
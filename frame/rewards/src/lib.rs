@@ -0,0 +1,219 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Rewards Pallet
+//!
+//! Rewards PoC farmers for block production.
+//!
+//! Farmers are identified by `FarmerId` in the block pre-digest, which is not an account, so each
+//! farmer has to register a reward address first using `register_reward_address`. Block author
+//! with registered reward address receives a fixed block reward on every block and a share of
+//! transaction fees collected in that block (when this pallet is used as `OnUnbalanced` handler for
+//! fees). Blocks produced by farmers without reward address are not rewarded.
+
+// Ensure we're `no_std` when compiling for Wasm.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+mod mock;
+mod tests;
+
+use codec::Encode;
+use frame_support::{
+    traits::{Currency, FindAuthor, Get, Imbalance, OnUnbalanced},
+    weights::Weight,
+};
+use sp_consensus_poc::{FarmerId, FarmerSignature};
+use sp_runtime::{traits::Zero, ConsensusEngineId, Percent, RuntimeAppPublic};
+use sp_std::prelude::*;
+
+pub use pallet::*;
+
+/// Context that is prepended to the genesis hash and account when farmer signs reward address
+/// registration.
+pub const REWARD_ADDRESS_SIGNING_CONTEXT: &[u8] = b"poc_reward_address";
+
+type BalanceOf<T> =
+    <<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+
+type NegativeImbalanceOf<T> = <<T as Config>::Currency as Currency<
+    <T as frame_system::Config>::AccountId,
+>>::NegativeImbalance;
+
+pub trait WeightInfo {
+    fn on_initialize() -> Weight;
+    fn register_reward_address() -> Weight;
+}
+
+impl WeightInfo for () {
+    fn on_initialize() -> Weight {
+        100_000_000
+    }
+    fn register_reward_address() -> Weight {
+        100_000_000
+    }
+}
+
+#[frame_support::pallet]
+pub mod pallet {
+    use super::*;
+    use frame_support::pallet_prelude::*;
+    use frame_system::pallet_prelude::*;
+
+    #[pallet::pallet]
+    #[pallet::generate_store(pub(super) trait Store)]
+    pub struct Pallet<T>(_);
+
+    /// The pallet's config trait.
+    #[pallet::config]
+    pub trait Config: frame_system::Config {
+        /// The overarching event type.
+        type Event: From<Event<Self>> + IsType<<Self as frame_system::Config>::Event>;
+
+        /// Currency in which rewards are paid.
+        type Currency: Currency<Self::AccountId>;
+
+        /// Find the farmer that authored the current block, typically `pallet_spartan`.
+        type FindAuthor: FindAuthor<FarmerId>;
+
+        /// Amount of currency minted for the author of every block.
+        #[pallet::constant]
+        type BlockReward: Get<BalanceOf<Self>>;
+
+        /// Share of transaction fees paid to the author of the block, the rest is burned.
+        #[pallet::constant]
+        type AuthorFeeShare: Get<Percent>;
+
+        /// Weight information for extrinsics and hooks in this pallet.
+        type WeightInfo: WeightInfo;
+    }
+
+    /// Accounts that receive rewards for blocks produced by farmers.
+    #[pallet::storage]
+    #[pallet::getter(fn reward_address)]
+    pub type RewardAddress<T: Config> = StorageMap<_, Blake2_128Concat, FarmerId, T::AccountId>;
+
+    /// Account that receives rewards for the current block, if any.
+    #[pallet::storage]
+    pub(super) type BlockAuthor<T: Config> = StorageValue<_, T::AccountId>;
+
+    #[pallet::event]
+    #[pallet::generate_deposit(pub(super) fn deposit_event)]
+    #[pallet::metadata(T::AccountId = "AccountId", BalanceOf<T> = "Balance")]
+    pub enum Event<T: Config> {
+        /// Farmer has registered an account for rewards. \[farmer_id, account\]
+        RewardAddressRegistered(FarmerId, T::AccountId),
+        /// Block author was rewarded for producing a block. \[account, reward\]
+        BlockReward(T::AccountId, BalanceOf<T>),
+        /// Block author received a share of transaction fees. \[account, reward\]
+        FeeReward(T::AccountId, BalanceOf<T>),
+    }
+
+    #[pallet::error]
+    pub enum Error<T> {
+        /// Signature of the farmer over the reward address is invalid.
+        InvalidSignature,
+    }
+
+    #[pallet::hooks]
+    impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+        fn on_initialize(_now: BlockNumberFor<T>) -> Weight {
+            Self::do_initialize();
+            T::WeightInfo::on_initialize()
+        }
+
+        fn on_finalize(_now: BlockNumberFor<T>) {
+            BlockAuthor::<T>::kill();
+        }
+    }
+
+    #[pallet::call]
+    impl<T: Config> Pallet<T> {
+        /// Register an account that will receive rewards for blocks produced by `farmer_id`,
+        /// replacing previously registered one.
+        ///
+        /// The dispatch origin for this call must be _Signed_ by the account being registered and
+        /// `signature` must be a signature of the farmer over
+        /// `reward_address_registration_message` of that account.
+        #[pallet::weight(T::WeightInfo::register_reward_address())]
+        pub fn register_reward_address(
+            origin: OriginFor<T>,
+            farmer_id: FarmerId,
+            signature: FarmerSignature,
+        ) -> DispatchResult {
+            let who = ensure_signed(origin)?;
+
+            ensure!(
+                farmer_id.verify(&Self::reward_address_registration_message(&who), &signature),
+                Error::<T>::InvalidSignature
+            );
+
+            RewardAddress::<T>::insert(&farmer_id, &who);
+
+            Self::deposit_event(Event::RewardAddressRegistered(farmer_id, who));
+
+            Ok(())
+        }
+    }
+}
+
+impl<T: Config> Pallet<T> {
+    /// Message that farmer needs to sign in order to register `account` as its reward address.
+    ///
+    /// Message includes genesis hash, such that signature can't be replayed on a different chain.
+    pub fn reward_address_registration_message(account: &T::AccountId) -> Vec<u8> {
+        let genesis_hash = frame_system::Pallet::<T>::block_hash(T::BlockNumber::zero());
+        (REWARD_ADDRESS_SIGNING_CONTEXT, genesis_hash, account).encode()
+    }
+
+    fn do_initialize() {
+        let digest = frame_system::Pallet::<T>::digest();
+        let pre_runtime_digests = digest.logs.iter().filter_map(|d| d.as_pre_runtime());
+
+        if let Some(author) = Self::find_author(pre_runtime_digests) {
+            let reward = T::Currency::deposit_creating(&author, T::BlockReward::get()).peek();
+
+            Self::deposit_event(Event::BlockReward(author.clone(), reward));
+
+            BlockAuthor::<T>::put(author);
+        }
+    }
+}
+
+impl<T: Config> FindAuthor<T::AccountId> for Pallet<T> {
+    fn find_author<'a, I>(digests: I) -> Option<T::AccountId>
+    where
+        I: 'a + IntoIterator<Item = (ConsensusEngineId, &'a [u8])>,
+    {
+        T::FindAuthor::find_author(digests).and_then(RewardAddress::<T>::get)
+    }
+}
+
+impl<T: Config> OnUnbalanced<NegativeImbalanceOf<T>> for Pallet<T> {
+    fn on_nonzero_unbalanced(amount: NegativeImbalanceOf<T>) {
+        // Without block author all of the fees are burned
+        if let Some(author) = BlockAuthor::<T>::get() {
+            let author_share = T::AuthorFeeShare::get() * amount.peek();
+            // The rest is dropped, which burns it
+            let (author_share, _rest) = amount.split(author_share);
+            let reward = author_share.peek();
+
+            T::Currency::resolve_creating(&author, author_share);
+
+            Self::deposit_event(Event::FeeReward(author, reward));
+        }
+    }
+}
@@ -0,0 +1,160 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test utilities
+
+#![cfg(test)]
+
+use crate::{self as pallet_rewards, Config};
+use codec::{Decode, Encode};
+use frame_support::{
+    parameter_types,
+    traits::{FindAuthor, OnFinalize, OnInitialize},
+};
+use sp_consensus_poc::FarmerId;
+use sp_core::H256;
+use sp_runtime::{
+    testing::{Digest, DigestItem, Header},
+    traits::{BlakeTwo256, IdentityLookup},
+    ConsensusEngineId, Percent,
+};
+
+pub const TEST_ENGINE_ID: ConsensusEngineId = *b"TEST";
+
+pub const BLOCK_REWARD: u128 = 1000;
+
+type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+type Block = frame_system::mocking::MockBlock<Test>;
+
+frame_support::construct_runtime!(
+    pub enum Test where
+        Block = Block,
+        NodeBlock = Block,
+        UncheckedExtrinsic = UncheckedExtrinsic,
+    {
+        System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
+        Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
+        Rewards: pallet_rewards::{Pallet, Call, Storage, Event<T>},
+    }
+);
+
+parameter_types! {
+    pub const BlockHashCount: u64 = 250;
+}
+
+impl frame_system::Config for Test {
+    type BaseCallFilter = frame_support::traits::AllowAll;
+    type BlockWeights = ();
+    type BlockLength = ();
+    type DbWeight = ();
+    type Origin = Origin;
+    type Index = u64;
+    type BlockNumber = u64;
+    type Call = Call;
+    type Hash = H256;
+    type Version = ();
+    type Hashing = BlakeTwo256;
+    type AccountId = u64;
+    type Lookup = IdentityLookup<Self::AccountId>;
+    type Header = Header;
+    type Event = Event;
+    type BlockHashCount = BlockHashCount;
+    type PalletInfo = PalletInfo;
+    type AccountData = pallet_balances::AccountData<u128>;
+    type OnNewAccount = ();
+    type OnKilledAccount = ();
+    type SystemWeightInfo = ();
+    type SS58Prefix = ();
+    type OnSetCode = ();
+}
+
+parameter_types! {
+    pub const ExistentialDeposit: u128 = 1;
+}
+
+impl pallet_balances::Config for Test {
+    type MaxLocks = ();
+    type MaxReserves = ();
+    type ReserveIdentifier = [u8; 8];
+    type Balance = u128;
+    type DustRemoval = ();
+    type Event = Event;
+    type ExistentialDeposit = ExistentialDeposit;
+    type AccountStore = System;
+    type WeightInfo = ();
+}
+
+/// Finds farmer encoded in pre-runtime digest with `TEST_ENGINE_ID`.
+pub struct FarmerFromDigest;
+
+impl FindAuthor<FarmerId> for FarmerFromDigest {
+    fn find_author<'a, I>(digests: I) -> Option<FarmerId>
+    where
+        I: 'a + IntoIterator<Item = (ConsensusEngineId, &'a [u8])>,
+    {
+        digests.into_iter().find_map(|(id, mut data)| {
+            if id == TEST_ENGINE_ID {
+                FarmerId::decode(&mut data).ok()
+            } else {
+                None
+            }
+        })
+    }
+}
+
+parameter_types! {
+    pub const BlockReward: u128 = BLOCK_REWARD;
+    pub const AuthorFeeShare: Percent = Percent::from_percent(20);
+}
+
+impl Config for Test {
+    type Event = Event;
+    type Currency = Balances;
+    type FindAuthor = FarmerFromDigest;
+    type BlockReward = BlockReward;
+    type AuthorFeeShare = AuthorFeeShare;
+    type WeightInfo = ();
+}
+
+pub fn new_test_ext() -> sp_io::TestExternalities {
+    let mut t = frame_system::GenesisConfig::default()
+        .build_storage::<Test>()
+        .unwrap();
+
+    pallet_balances::GenesisConfig::<Test> {
+        balances: vec![(1, 1_000_000), (2, 1_000_000)],
+    }
+    .assimilate_storage(&mut t)
+    .unwrap();
+
+    t.into()
+}
+
+/// Initializes block `n` authored by `farmer_id`, finalizing the previous one.
+pub fn go_to_block(n: u64, farmer_id: &FarmerId) {
+    if System::block_number() > 0 {
+        Rewards::on_finalize(System::block_number());
+        System::finalize();
+    }
+
+    let digest = Digest {
+        logs: vec![DigestItem::PreRuntime(TEST_ENGINE_ID, farmer_id.encode())],
+    };
+    System::initialize(&n, &Default::default(), &digest, Default::default());
+
+    Rewards::on_initialize(n);
+}
@@ -0,0 +1,214 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the rewards module.
+
+#![cfg(test)]
+
+use super::*;
+use crate::mock::{
+    go_to_block, new_test_ext, Balances, Event, Origin, Rewards, System, Test, BLOCK_REWARD,
+    TEST_ENGINE_ID,
+};
+use frame_support::{
+    assert_noop, assert_ok,
+    traits::{ExistenceRequirement, WithdrawReasons},
+};
+use sp_core::{sr25519::Pair, Pair as PairTrait, H256};
+
+fn generate_farmer() -> (Pair, FarmerId) {
+    let (pair, _) = Pair::generate();
+    let farmer_id = FarmerId::from(pair.public());
+    (pair, farmer_id)
+}
+
+fn register(pair: &Pair, farmer_id: &FarmerId, account: u64) {
+    let message = Rewards::reward_address_registration_message(&account);
+    assert_ok!(Rewards::register_reward_address(
+        Origin::signed(account),
+        farmer_id.clone(),
+        pair.sign(&message).into(),
+    ));
+}
+
+#[test]
+fn can_register_reward_address() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        let (pair, farmer_id) = generate_farmer();
+
+        register(&pair, &farmer_id, 1);
+        assert_eq!(Rewards::reward_address(&farmer_id), Some(1));
+        assert_eq!(
+            System::events().last().unwrap().event,
+            Event::Rewards(crate::Event::RewardAddressRegistered(farmer_id.clone(), 1))
+        );
+
+        // Registering another account replaces the previous one
+        register(&pair, &farmer_id, 2);
+        assert_eq!(Rewards::reward_address(&farmer_id), Some(2));
+    })
+}
+
+#[test]
+fn rejects_reward_address_not_signed_by_farmer() {
+    new_test_ext().execute_with(|| {
+        let (pair, farmer_id) = generate_farmer();
+
+        // Signature is for a different account
+        let message = Rewards::reward_address_registration_message(&2);
+        assert_noop!(
+            Rewards::register_reward_address(
+                Origin::signed(1),
+                farmer_id.clone(),
+                pair.sign(&message).into(),
+            ),
+            Error::<Test>::InvalidSignature
+        );
+
+        // Signature is made by a different farmer
+        let (other_pair, _) = generate_farmer();
+        let message = Rewards::reward_address_registration_message(&1);
+        assert_noop!(
+            Rewards::register_reward_address(
+                Origin::signed(1),
+                farmer_id.clone(),
+                other_pair.sign(&message).into(),
+            ),
+            Error::<Test>::InvalidSignature
+        );
+    })
+}
+
+#[test]
+fn rejects_reward_address_signed_for_different_chain() {
+    new_test_ext().execute_with(|| {
+        let (pair, farmer_id) = generate_farmer();
+        let message = Rewards::reward_address_registration_message(&1);
+
+        // Same signature is not valid on a chain with a different genesis hash
+        frame_system::BlockHash::<Test>::insert(0, H256::repeat_byte(1));
+        assert_noop!(
+            Rewards::register_reward_address(
+                Origin::signed(1),
+                farmer_id.clone(),
+                pair.sign(&message).into(),
+            ),
+            Error::<Test>::InvalidSignature
+        );
+    })
+}
+
+#[test]
+fn rewards_block_author() {
+    new_test_ext().execute_with(|| {
+        let (pair, farmer_id) = generate_farmer();
+        register(&pair, &farmer_id, 3);
+
+        let total_issuance = Balances::total_issuance();
+
+        go_to_block(1, &farmer_id);
+        assert_eq!(Balances::free_balance(3), BLOCK_REWARD);
+        assert_eq!(Balances::total_issuance(), total_issuance + BLOCK_REWARD);
+        assert_eq!(
+            System::events().last().unwrap().event,
+            Event::Rewards(crate::Event::BlockReward(3, BLOCK_REWARD))
+        );
+
+        go_to_block(2, &farmer_id);
+        assert_eq!(Balances::free_balance(3), 2 * BLOCK_REWARD);
+    })
+}
+
+#[test]
+fn does_not_reward_unregistered_farmer() {
+    new_test_ext().execute_with(|| {
+        let (_, farmer_id) = generate_farmer();
+
+        let total_issuance = Balances::total_issuance();
+
+        go_to_block(1, &farmer_id);
+        assert_eq!(Balances::total_issuance(), total_issuance);
+        assert!(System::events().is_empty());
+    })
+}
+
+#[test]
+fn shares_fees_with_block_author() {
+    new_test_ext().execute_with(|| {
+        let (pair, farmer_id) = generate_farmer();
+        register(&pair, &farmer_id, 3);
+
+        go_to_block(1, &farmer_id);
+        let total_issuance = Balances::total_issuance();
+
+        let fee = Balances::withdraw(
+            &1,
+            100,
+            WithdrawReasons::TRANSACTION_PAYMENT,
+            ExistenceRequirement::KeepAlive,
+        )
+        .unwrap();
+        Rewards::on_unbalanced(fee);
+
+        // 20% goes to the author and the rest is burned
+        assert_eq!(Balances::free_balance(3), BLOCK_REWARD + 20);
+        assert_eq!(Balances::total_issuance(), total_issuance - 80);
+        assert_eq!(
+            System::events().last().unwrap().event,
+            Event::Rewards(crate::Event::FeeReward(3, 20))
+        );
+    })
+}
+
+#[test]
+fn burns_fees_without_block_author() {
+    new_test_ext().execute_with(|| {
+        let (_, farmer_id) = generate_farmer();
+
+        go_to_block(1, &farmer_id);
+        let total_issuance = Balances::total_issuance();
+
+        let fee = Balances::withdraw(
+            &1,
+            100,
+            WithdrawReasons::TRANSACTION_PAYMENT,
+            ExistenceRequirement::KeepAlive,
+        )
+        .unwrap();
+        Rewards::on_unbalanced(fee);
+
+        assert_eq!(Balances::total_issuance(), total_issuance - 100);
+    })
+}
+
+#[test]
+fn can_find_author_account() {
+    new_test_ext().execute_with(|| {
+        let (pair, farmer_id) = generate_farmer();
+        let encoded_farmer_id = farmer_id.encode();
+        let digests = vec![(TEST_ENGINE_ID, encoded_farmer_id.as_slice())];
+
+        assert_eq!(
+            <Rewards as FindAuthor<u64>>::find_author(digests.clone()),
+            None
+        );
+
+        register(&pair, &farmer_id, 3);
+        assert_eq!(<Rewards as FindAuthor<u64>>::find_author(digests), Some(3));
+    })
+}
@@ -26,8 +26,16 @@ impl crate::WeightInfo for () {
 		DbWeight::get().writes(1)
 	}
 
+	fn plan_durations_change() -> Weight {
+		DbWeight::get().writes(3)
+	}
+
 	fn report_equivocation() -> Weight {
 		// TODO: Proper value
 		1
 	}
+
+	fn store_root_blocks(root_blocks: u32) -> Weight {
+		DbWeight::get().reads_writes(1, root_blocks as Weight + 1)
+	}
 }
@@ -34,7 +34,7 @@
 //! definition.
 //!
 
-use frame_support::traits::Get;
+use frame_support::traits::{FindAuthor, Get};
 use sp_consensus_poc::offence::{Kind, Offence, OffenceError, ReportOffence};
 use sp_consensus_poc::{EquivocationProof, FarmerId, Slot};
 use sp_runtime::transaction_validity::{
@@ -57,7 +57,10 @@ pub trait HandleEquivocation<T: Config> {
     type ReportLongevity: Get<u64>;
 
     /// Report an offence proved by the given reporters.
-    fn report_offence(offence: PoCEquivocationOffence<FarmerId>) -> Result<(), OffenceError>;
+    fn report_offence(
+        reporters: Vec<T::AccountId>,
+        offence: PoCEquivocationOffence<FarmerId>,
+    ) -> Result<(), OffenceError>;
 
     /// Returns true if all of the offenders at the given time slot have already been reported.
     fn is_known_offence(offenders: &[FarmerId], time_slot: &Slot) -> bool;
@@ -66,12 +69,18 @@ pub trait HandleEquivocation<T: Config> {
     fn submit_unsigned_equivocation_report(
         equivocation_proof: EquivocationProof<T::Header>,
     ) -> DispatchResult;
+
+    /// Fetch the current block author id, if defined.
+    fn block_author() -> Option<T::AccountId>;
 }
 
 impl<T: Config> HandleEquivocation<T> for () {
     type ReportLongevity = ();
 
-    fn report_offence(_offence: PoCEquivocationOffence<FarmerId>) -> Result<(), OffenceError> {
+    fn report_offence(
+        _reporters: Vec<T::AccountId>,
+        _offence: PoCEquivocationOffence<FarmerId>,
+    ) -> Result<(), OffenceError> {
         Ok(())
     }
 
@@ -84,17 +93,21 @@ impl<T: Config> HandleEquivocation<T> for () {
     ) -> DispatchResult {
         Ok(())
     }
+
+    fn block_author() -> Option<T::AccountId> {
+        None
+    }
 }
 
 /// Generic equivocation handler. This type implements `HandleEquivocation`
 /// using existing subsystems that are part of frame (type bounds described
 /// below) and will dispatch to them directly, it's only purpose is to wire all
 /// subsystems together.
-pub struct EquivocationHandler<R, L> {
-    _phantom: sp_std::marker::PhantomData<(R, L)>,
+pub struct EquivocationHandler<R, L, A = ()> {
+    _phantom: sp_std::marker::PhantomData<(R, L, A)>,
 }
 
-impl<R, L> Default for EquivocationHandler<R, L> {
+impl<R, L, A> Default for EquivocationHandler<R, L, A> {
     fn default() -> Self {
         Self {
             _phantom: Default::default(),
@@ -102,20 +115,26 @@ impl<R, L> Default for EquivocationHandler<R, L> {
     }
 }
 
-impl<T, R, L> HandleEquivocation<T> for EquivocationHandler<R, L>
+impl<T, R, L, A> HandleEquivocation<T> for EquivocationHandler<R, L, A>
 where
     T: Config + frame_system::offchain::SendTransactionTypes<Call<T>>,
     // A system for reporting offences after valid equivocation reports are
     // processed.
-    R: ReportOffence<FarmerId, PoCEquivocationOffence<FarmerId>>,
+    R: ReportOffence<T::AccountId, FarmerId, PoCEquivocationOffence<FarmerId>>,
     // The longevity (in blocks) that the equivocation report is valid for. When using the staking
     // pallet this should be the bonding duration.
     L: Get<u64>,
+    // A way to find the account of the current block author from pre-runtime digests, it is
+    // declared as the reporter of unsigned equivocation reports.
+    A: FindAuthor<T::AccountId>,
 {
     type ReportLongevity = L;
 
-    fn report_offence(offence: PoCEquivocationOffence<FarmerId>) -> Result<(), OffenceError> {
-        R::report_offence(offence)
+    fn report_offence(
+        reporters: Vec<T::AccountId>,
+        offence: PoCEquivocationOffence<FarmerId>,
+    ) -> Result<(), OffenceError> {
+        R::report_offence(reporters, offence)
     }
 
     fn is_known_offence(offenders: &[FarmerId], time_slot: &Slot) -> bool {
@@ -143,6 +162,13 @@ where
 
         Ok(())
     }
+
+    fn block_author() -> Option<T::AccountId> {
+        let digest = frame_system::Pallet::<T>::digest();
+        let pre_runtime_digests = digest.logs.iter().filter_map(|d| d.as_pre_runtime());
+
+        A::find_author(pre_runtime_digests)
+    }
 }
 
 /// Methods for the `ValidateUnsigned` implementation:
@@ -24,28 +24,31 @@
 use codec::{Decode, Encode};
 use frame_support::{
     dispatch::DispatchResultWithPostInfo,
-    traits::{Get, OnTimestampSet},
+    traits::{FindAuthor, Get, OnTimestampSet, StorageVersion},
     weights::{Pays, Weight},
 };
-#[cfg(not(feature = "std"))]
-use num_traits::float::FloatCore;
+use sp_arithmetic::{FixedPointNumber, FixedU128};
 use sp_consensus_poc::{
     digests::{
-        NextConfigDescriptor, NextEpochDescriptor, NextSaltDescriptor, NextSolutionRangeDescriptor,
-        PreDigest, SaltDescriptor, SolutionRangeDescriptor,
+        NextConfigDescriptor, NextDurationsDescriptor, NextEpochDescriptor, NextSaltDescriptor,
+        NextSolutionRangeDescriptor, PreDigest, SaltDescriptor, SolutionRangeDescriptor,
     },
+    inherents::{InherentError, RootBlocksInherentType, ROOT_BLOCKS_INHERENT_IDENTIFIER},
     offence::{OffenceDetails, OnOffenceHandler},
-    ConsensusLog, Epoch, EquivocationProof, PoCEpochConfiguration, Slot, POC_ENGINE_ID,
+    ConsensusLog, Epoch, EquivocationProof, PoCEpochConfiguration, RootBlock, Slot,
+    SpartanParameters, POC_ENGINE_ID,
 };
 pub use sp_consensus_poc::{FarmerId, RANDOMNESS_LENGTH};
 use sp_runtime::{
     generic::DigestItem,
     traits::{One, SaturatedConversion, Saturating, Zero},
+    ConsensusEngineId,
 };
 use sp_std::prelude::*;
 
 mod default_weights;
 mod equivocation;
+mod randomness;
 
 #[cfg(all(feature = "std", test))]
 mod mock;
@@ -53,12 +56,17 @@ mod mock;
 mod tests;
 
 pub use equivocation::{EquivocationHandler, HandleEquivocation, PoCEquivocationOffence};
+pub use randomness::{
+    CurrentBlockRandomness, RandomnessFromOneEpochAgo, RandomnessFromTwoEpochsAgo,
+};
 
 pub use pallet::*;
 
 pub trait WeightInfo {
     fn plan_config_change() -> Weight;
+    fn plan_durations_change() -> Weight;
     fn report_equivocation() -> Weight;
+    fn store_root_blocks(root_blocks: u32) -> Weight;
 }
 
 /// Trigger an epoch change, if any should take place.
@@ -112,6 +120,9 @@ impl EonChangeTrigger for NormalEonChange {
         if <Pallet<T>>::should_eon_change(now) {
             <Pallet<T>>::enact_eon_change();
         }
+        if <Pallet<T>>::should_reveal_next_salt() {
+            <Pallet<T>>::enact_next_salt_reveal();
+        }
     }
 }
 
@@ -119,6 +130,9 @@ const UNDER_CONSTRUCTION_SEGMENT_LENGTH: usize = 256;
 
 type MaybeRandomness = Option<sp_consensus_poc::Randomness>;
 
+/// The current storage version.
+const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
+
 #[frame_support::pallet]
 pub mod pallet {
     use super::*;
@@ -128,29 +142,36 @@ pub mod pallet {
     /// The PoC Pallet
     #[pallet::pallet]
     #[pallet::generate_store(pub(super) trait Store)]
+    #[pallet::storage_version(STORAGE_VERSION)]
     pub struct Pallet<T>(_);
 
     #[pallet::config]
     #[pallet::disable_frame_system_supertrait_check]
     pub trait Config: pallet_timestamp::Config {
-        /// The amount of time, in slots, that each epoch should last.
-        /// NOTE: Currently it is not possible to change the epoch duration after
-        /// the chain has started. Attempting to do so will brick block production.
+        /// The amount of time, in slots, that each epoch should last initially.
+        /// NOTE: Changing this constant after the chain has started will brick block production,
+        /// use `plan_durations_change` instead.
         #[pallet::constant]
         type EpochDuration: Get<u64>;
 
-        /// The amount of time, in blocks, that each era should last.
-        /// NOTE: Currently it is not possible to change the era duration after
-        /// the chain has started. Attempting to do so will brick block production.
+        /// The amount of time, in blocks, that each era should last initially.
+        /// NOTE: Changing this constant after the chain has started will brick block production,
+        /// use `plan_durations_change` instead.
         #[pallet::constant]
         type EraDuration: Get<u32>;
 
-        /// The amount of time, in slots, that each eon should last.
-        /// NOTE: Currently it is not possible to change the eon duration after
-        /// the chain has started. Attempting to do so will brick block production.
+        /// The amount of time, in slots, that each eon should last initially.
+        /// NOTE: Changing this constant after the chain has started will brick block production,
+        /// use `plan_durations_change` instead.
         #[pallet::constant]
         type EonDuration: Get<u64>;
 
+        /// The amount of time, in slots, since the start of an eon after which salt for the next
+        /// eon is revealed. Must be smaller than `EonDuration`, the difference between the two is
+        /// the lookahead farmers get to prepare for the next eon.
+        #[pallet::constant]
+        type EonNextSaltReveal: Get<u64>;
+
         /// Initial solution range used for challenges during the very first era.
         #[pallet::constant]
         type InitialSolutionRange: Get<u64>;
@@ -163,6 +184,23 @@ pub mod pallet {
         #[pallet::constant]
         type SlotProbability: Get<(u64, u64)>;
 
+        /// Maximum factor by which solution range can increase on a single era change.
+        ///
+        /// Expressed as a rational where the first member of the tuple is the
+        /// numerator and the second is the denominator. The rational should
+        /// represent a value that is greater than or equal to 1.
+        #[pallet::constant]
+        type MaxSolutionRangeIncrease: Get<(u64, u64)>;
+
+        /// Maximum factor by which solution range can decrease on a single era change, `(4, 1)`
+        /// means that new solution range can't be smaller than 1/4 of the previous one.
+        ///
+        /// Expressed as a rational where the first member of the tuple is the
+        /// numerator and the second is the denominator. The rational should
+        /// represent a value that is greater than or equal to 1.
+        #[pallet::constant]
+        type MaxSolutionRangeDecrease: Get<(u64, u64)>;
+
         /// The expected average block time at which PoC should be creating
         /// blocks. Since PoC is probabilistic it is not trivial to figure out
         /// what the expected average block time should be based on the slot
@@ -194,6 +232,11 @@ pub mod pallet {
         /// definition.
         type HandleEquivocation: HandleEquivocation<Self>;
 
+        /// The amount of time, in slots, that farmer stays in block list after being reported for
+        /// equivocation.
+        #[pallet::constant]
+        type BanDuration: Get<u64>;
+
         type WeightInfo: WeightInfo;
     }
 
@@ -205,6 +248,10 @@ pub mod pallet {
         InvalidKeyOwnershipProof,
         /// A given equivocation report is valid but already previously reported.
         DuplicateOffenceReport,
+        /// Planned durations are zero or eon duration is not larger than `EonNextSaltReveal`.
+        InvalidDurations,
+        /// Root block doesn't follow the last root block stored on chain.
+        UnexpectedSegmentIndex,
     }
 
     /// Current epoch index.
@@ -255,6 +302,21 @@ pub mod pallet {
     #[pallet::getter(fn salt)]
     pub type Salt<T> = StorageValue<_, u64, ValueQuery>;
 
+    /// Salt for the *next* eon, `None` until it is revealed.
+    #[pallet::storage]
+    #[pallet::getter(fn next_salt)]
+    pub type NextSalt<T> = StorageValue<_, u64>;
+
+    /// Solution range that was last deposited in a block header, such that it is only deposited
+    /// again once changed.
+    #[pallet::storage]
+    pub(super) type LastDepositedSolutionRange<T> = StorageValue<_, u64>;
+
+    /// Salt that was last deposited in a block header, such that it is only deposited again once
+    /// changed.
+    #[pallet::storage]
+    pub(super) type LastDepositedSalt<T> = StorageValue<_, u64>;
+
     /// The solution range for *current* era.
     #[pallet::storage]
     pub type EraStartSlot<T> = StorageValue<_, Slot>;
@@ -263,6 +325,55 @@ pub mod pallet {
     #[pallet::storage]
     pub(super) type PendingEpochConfigChange<T> = StorageValue<_, NextConfigDescriptor>;
 
+    /// Duration of the *current* epoch, `None` if it was never changed (you can fallback to
+    /// `Config::EpochDuration` in that case).
+    #[pallet::storage]
+    pub(super) type CurrentEpochDuration<T> = StorageValue<_, u64>;
+
+    /// Duration of the *next* epoch, `None` if it will not change.
+    #[pallet::storage]
+    pub(super) type NextEpochDuration<T> = StorageValue<_, u64>;
+
+    /// Duration of the *current* era, `None` if it was never changed (you can fallback to
+    /// `Config::EraDuration` in that case).
+    #[pallet::storage]
+    pub(super) type CurrentEraDuration<T> = StorageValue<_, u32>;
+
+    /// Duration of the *current* eon, `None` if it was never changed (you can fallback to
+    /// `Config::EonDuration` in that case).
+    #[pallet::storage]
+    pub(super) type CurrentEonDuration<T> = StorageValue<_, u64>;
+
+    /// Pending epoch duration change that will be applied when the next epoch is enacted.
+    #[pallet::storage]
+    pub(super) type PendingEpochDuration<T> = StorageValue<_, u64>;
+
+    /// Pending era duration change that will be applied when the next era is enacted.
+    #[pallet::storage]
+    pub(super) type PendingEraDuration<T> = StorageValue<_, u32>;
+
+    /// Pending eon duration change that will be applied when the next eon is enacted.
+    #[pallet::storage]
+    pub(super) type PendingEonDuration<T> = StorageValue<_, u64>;
+
+    /// Temporary value (cleared in the same block) with durations that changed in current block.
+    #[pallet::storage]
+    pub(super) type DurationsChanged<T> = StorageValue<_, NextDurationsDescriptor>;
+
+    /// The slot at which the current epoch started, `None` until the first epoch change (you can
+    /// derive it from `GenesisSlot` in that case).
+    #[pallet::storage]
+    pub(super) type EpochStartSlot<T> = StorageValue<_, Slot>;
+
+    /// The block at which the current era started, `None` until the first era change.
+    #[pallet::storage]
+    pub(super) type EraStartBlock<T: Config> = StorageValue<_, T::BlockNumber>;
+
+    /// The slot at which the current eon started, `None` until the first eon change (you can
+    /// derive it from `GenesisSlot` in that case).
+    #[pallet::storage]
+    pub(super) type EonStartSlot<T> = StorageValue<_, Slot>;
+
     /// Next epoch randomness.
     #[pallet::storage]
     pub(super) type NextRandomness<T> = StorageValue<_, sp_consensus_poc::Randomness, ValueQuery>;
@@ -324,13 +435,31 @@ pub mod pallet {
     #[pallet::storage]
     pub(super) type NextEpochConfig<T> = StorageValue<_, PoCEpochConfiguration>;
 
-    /// A set of blocked farmers keyed by their public key.
+    /// A set of blocked farmers keyed by their public key, along with the slot at which the ban
+    /// expires.
+    #[pallet::storage]
+    pub(super) type BlockList<T> = StorageMap<_, Twox64Concat, FarmerId, Slot>;
+
+    /// Merkle roots of pieces of archived segments of the blockchain history, keyed by segment
+    /// index.
+    #[pallet::storage]
+    #[pallet::getter(fn records_root)]
+    pub(super) type RecordsRoot<T> = StorageMap<_, Twox64Concat, u64, [u8; 32]>;
+
+    /// Index of the next archived segment whose root block is expected to be stored.
     #[pallet::storage]
-    pub(super) type BlockList<T> = StorageMap<_, Twox64Concat, FarmerId, ()>;
+    #[pallet::getter(fn next_archived_segment_index)]
+    pub(super) type NextArchivedSegmentIndex<T> = StorageValue<_, u64, ValueQuery>;
+
+    /// Parameters of the Spartan codec, set in genesis and never changed afterwards.
+    #[pallet::storage]
+    #[pallet::getter(fn spartan_parameters)]
+    pub(super) type SpartanParams<T> = StorageValue<_, SpartanParameters, ValueQuery>;
 
     #[pallet::genesis_config]
     pub struct GenesisConfig {
         pub epoch_config: Option<PoCEpochConfiguration>,
+        pub spartan_parameters: SpartanParameters,
     }
 
     #[cfg(feature = "std")]
@@ -338,6 +467,7 @@ pub mod pallet {
         fn default() -> Self {
             GenesisConfig {
                 epoch_config: Default::default(),
+                spartan_parameters: Default::default(),
             }
         }
     }
@@ -351,6 +481,7 @@ pub mod pallet {
                     .clone()
                     .expect("epoch_config must not be None"),
             );
+            SpartanParams::<T>::put(self.spartan_parameters.clone());
         }
     }
 
@@ -376,6 +507,25 @@ pub mod pallet {
             // remove temporary "environment" entry from storage
             Lateness::<T>::kill();
         }
+
+        fn on_runtime_upgrade() -> Weight {
+            migrations::migrate_block_list_to_expiring_bans::<T>()
+        }
+
+        fn integrity_test() {
+            assert!(
+                T::EpochDuration::get() > 0,
+                "`EpochDuration` must be greater than zero"
+            );
+            assert!(
+                T::EraDuration::get() > 0,
+                "`EraDuration` must be greater than zero"
+            );
+            assert!(
+                T::EonNextSaltReveal::get() < T::EonDuration::get(),
+                "`EonNextSaltReveal` must be smaller than `EonDuration`"
+            );
+        }
     }
 
     #[pallet::call]
@@ -386,10 +536,12 @@ pub mod pallet {
         /// be reported.
         #[pallet::weight(<T as Config>::WeightInfo::report_equivocation())]
         pub fn report_equivocation(
-            _origin: OriginFor<T>,
+            origin: OriginFor<T>,
             equivocation_proof: EquivocationProof<T::Header>,
         ) -> DispatchResultWithPostInfo {
-            Self::do_report_equivocation(equivocation_proof)
+            let reporter = ensure_signed(origin)?;
+
+            Self::do_report_equivocation(Some(reporter), equivocation_proof)
         }
 
         /// Report authority equivocation/misbehavior. This method will verify
@@ -407,7 +559,7 @@ pub mod pallet {
         ) -> DispatchResultWithPostInfo {
             ensure_none(origin)?;
 
-            Self::do_report_equivocation(equivocation_proof)
+            Self::do_report_equivocation(T::HandleEquivocation::block_author(), equivocation_proof)
         }
 
         /// Plan an epoch config change. The epoch config change is recorded and will be enacted on
@@ -423,6 +575,140 @@ pub mod pallet {
             PendingEpochConfigChange::<T>::put(config);
             Ok(())
         }
+
+        /// Plan a change of epoch, era and/or eon durations, `None` keeps the duration unchanged.
+        ///
+        /// Epoch duration change is enacted on the next call to `enact_epoch_change` and is
+        /// activated one epoch after, just like epoch config change. Era and eon duration changes
+        /// are activated with the next era and eon respectively. Multiple calls to this method
+        /// will replace any existing planned durations change that had not been enacted yet.
+        #[pallet::weight(<T as Config>::WeightInfo::plan_durations_change())]
+        pub fn plan_durations_change(
+            origin: OriginFor<T>,
+            durations: NextDurationsDescriptor,
+        ) -> DispatchResult {
+            ensure_root(origin)?;
+
+            let NextDurationsDescriptor {
+                epoch_duration,
+                era_duration,
+                eon_duration,
+            } = durations;
+
+            ensure!(
+                epoch_duration != Some(0)
+                    && era_duration != Some(0)
+                    && eon_duration.map_or(true, |eon_duration| {
+                        eon_duration > T::EonNextSaltReveal::get()
+                    }),
+                Error::<T>::InvalidDurations
+            );
+
+            if let Some(epoch_duration) = epoch_duration {
+                PendingEpochDuration::<T>::put(epoch_duration);
+            }
+            if let Some(era_duration) = era_duration {
+                PendingEraDuration::<T>::put(era_duration);
+            }
+            if let Some(eon_duration) = eon_duration {
+                PendingEonDuration::<T>::put(eon_duration);
+            }
+            Ok(())
+        }
+
+        /// Store root blocks of archived segments of the blockchain history. Root blocks must
+        /// follow each other and the last root block stored on chain without gaps.
+        ///
+        /// This is an inherent, root blocks are provided by the block author's archiver.
+        #[pallet::weight((
+            <T as Config>::WeightInfo::store_root_blocks(root_blocks.len() as u32),
+            DispatchClass::Mandatory
+        ))]
+        pub fn store_root_blocks(
+            origin: OriginFor<T>,
+            root_blocks: Vec<RootBlock>,
+        ) -> DispatchResult {
+            ensure_none(origin)?;
+
+            let mut next_segment_index = NextArchivedSegmentIndex::<T>::get();
+            for root_block in &root_blocks {
+                ensure!(
+                    root_block.segment_index == next_segment_index,
+                    Error::<T>::UnexpectedSegmentIndex
+                );
+                RecordsRoot::<T>::insert(root_block.segment_index, root_block.records_root);
+                next_segment_index += 1;
+            }
+            NextArchivedSegmentIndex::<T>::put(next_segment_index);
+
+            // Deposit root blocks such that nodes without state (warp sync) can verify piece
+            // inclusion proofs of solutions later
+            Self::deposit_consensus(ConsensusLog::RootBlocksData(root_blocks));
+
+            Ok(())
+        }
+    }
+
+    #[pallet::inherent]
+    impl<T: Config> ProvideInherent for Pallet<T> {
+        type Call = Call<T>;
+        type Error = InherentError;
+        const INHERENT_IDENTIFIER: InherentIdentifier = ROOT_BLOCKS_INHERENT_IDENTIFIER;
+
+        fn create_inherent(data: &InherentData) -> Option<Self::Call> {
+            let mut root_blocks = data
+                .get_data::<RootBlocksInherentType>(&ROOT_BLOCKS_INHERENT_IDENTIFIER)
+                .ok()
+                .flatten()?;
+
+            let next_segment_index = NextArchivedSegmentIndex::<T>::get();
+            root_blocks.retain(|root_block| root_block.segment_index >= next_segment_index);
+
+            if root_blocks.is_empty() {
+                None
+            } else {
+                Some(Call::store_root_blocks(root_blocks))
+            }
+        }
+
+        fn check_inherent(call: &Self::Call, data: &InherentData) -> Result<(), Self::Error> {
+            let root_blocks = match call {
+                Call::store_root_blocks(root_blocks) => root_blocks,
+                _ => return Ok(()),
+            };
+
+            // Nodes that don't have the blockchain history (yet) don't provide root blocks at all
+            // and can't check them, everyone else must have archived every included root block
+            let local_root_blocks = match data
+                .get_data::<RootBlocksInherentType>(&ROOT_BLOCKS_INHERENT_IDENTIFIER)
+                .ok()
+                .flatten()
+            {
+                Some(local_root_blocks) => local_root_blocks,
+                None => return Ok(()),
+            };
+
+            for root_block in root_blocks {
+                let local_root_block = local_root_blocks
+                    .iter()
+                    .find(|local| local.segment_index == root_block.segment_index);
+                match local_root_block {
+                    Some(local_root_block) if local_root_block == root_block => {}
+                    Some(_) => {
+                        return Err(InherentError::MismatchedRootBlock(root_block.segment_index));
+                    }
+                    None => {
+                        return Err(InherentError::UnknownRootBlock(root_block.segment_index));
+                    }
+                }
+            }
+
+            Ok(())
+        }
+
+        fn is_inherent(call: &Self::Call) -> bool {
+            matches!(call, Call::store_root_blocks(_))
+        }
     }
 
     #[pallet::validate_unsigned]
@@ -433,6 +719,11 @@ pub mod pallet {
         }
 
         fn pre_dispatch(call: &Self::Call) -> Result<(), TransactionValidityError> {
+            // Root blocks inherent is checked by `ProvideInherent` and the call itself
+            if let Call::store_root_blocks(_) = call {
+                return Ok(());
+            }
+
             Self::pre_dispatch(call)
         }
     }
@@ -459,7 +750,7 @@ impl<T: Config> Pallet<T> {
         // so we don't rotate the epoch.
         now != One::one() && {
             let diff = CurrentSlot::<T>::get().saturating_sub(Self::current_epoch_start());
-            *diff >= T::EpochDuration::get()
+            *diff >= Self::epoch_duration()
         }
     }
 
@@ -469,7 +760,14 @@ impl<T: Config> Pallet<T> {
         // The era has technically ended during the passage of time
         // between this block and the last, but we have to "end" the era now,
         // since there is no earlier possible block we could have done it.
-        now != One::one() && now % T::EraDuration::get().into() == 1_u32.into()
+        now != One::one()
+            && match EraStartBlock::<T>::get() {
+                Some(era_start_block) => {
+                    now.saturating_sub(era_start_block) >= Self::era_duration().into()
+                }
+                // eras started at block 1 and had the same duration until the first era change
+                None => now % Self::era_duration().into() == 1_u32.into(),
+            }
     }
 
     /// Determine whether an eon change should take place at this block.
@@ -480,7 +778,16 @@ impl<T: Config> Pallet<T> {
         // since there is no earlier possible block we could have done it.
         now != One::one() && {
             let diff = CurrentSlot::<T>::get().saturating_sub(Self::current_eon_start());
-            *diff >= T::EonDuration::get()
+            *diff >= Self::eon_duration()
+        }
+    }
+
+    /// Determine whether salt for the next eon should be revealed at this block.
+    /// Assumes that initialization has already taken place.
+    pub fn should_reveal_next_salt() -> bool {
+        NextSalt::<T>::get().is_none() && {
+            let diff = CurrentSlot::<T>::get().saturating_sub(Self::current_eon_start());
+            *diff >= T::EonNextSaltReveal::get()
         }
     }
 
@@ -501,7 +808,7 @@ impl<T: Config> Pallet<T> {
     // WEIGHT NOTE: This function is tied to the weight of `EstimateNextSessionRotation`. If you
     // update this function, you must also update the corresponding weight.
     pub fn next_expected_epoch_change(now: T::BlockNumber) -> Option<T::BlockNumber> {
-        let next_slot = Self::current_epoch_start().saturating_add(T::EpochDuration::get());
+        let next_slot = Self::current_epoch_start().saturating_add(Self::epoch_duration());
         next_slot
             .checked_sub(*CurrentSlot::<T>::get())
             .map(|slots_remaining| {
@@ -521,6 +828,14 @@ impl<T: Config> Pallet<T> {
         // by the session module to be called before this.
         debug_assert!(Self::initialized().is_some());
 
+        // Update epoch start slot and duration, this must happen before epoch index is updated
+        EpochStartSlot::<T>::put(
+            Self::current_epoch_start().saturating_add(Self::epoch_duration()),
+        );
+        if let Some(epoch_duration) = NextEpochDuration::<T>::take() {
+            CurrentEpochDuration::<T>::put(epoch_duration);
+        }
+
         // Update epoch index
         let epoch_index = EpochIndex::<T>::get()
             .checked_add(1)
@@ -564,6 +879,16 @@ impl<T: Config> Pallet<T> {
 
             Self::deposit_consensus(ConsensusLog::NextConfigData(pending_epoch_config_change));
         }
+
+        if let Some(pending_epoch_duration) = PendingEpochDuration::<T>::take() {
+            NextEpochDuration::<T>::put(pending_epoch_duration);
+
+            DurationsChanged::<T>::mutate(|durations_changed| {
+                durations_changed
+                    .get_or_insert_with(Default::default)
+                    .epoch_duration = Some(pending_epoch_duration);
+            });
+        }
     }
 
     /// DANGEROUS: Enact era change. Should be done on every block where `should_era_change` has
@@ -573,8 +898,6 @@ impl<T: Config> Pallet<T> {
         // be called before this.
         debug_assert!(Self::initialized().is_some());
 
-        let slot_probability = T::SlotProbability::get();
-
         let previous_solution_range =
             SolutionRange::<T>::get().unwrap_or_else(T::InitialSolutionRange::get);
 
@@ -583,17 +906,28 @@ impl<T: Config> Pallet<T> {
         let era_start_slot = EraStartSlot::<T>::get().unwrap_or_else(|| GenesisSlot::<T>::get());
         let era_slot_count = u64::from(current_slot) - u64::from(era_start_slot);
 
-        // Now we need to re-calculate solution range. The idea here is to keep block production at
-        // the same pace while space pledged on the network changes. For this we adjust previous
-        // solution range according to actual and expected number of blocks per era.
-        let actual_slots_per_block = era_slot_count as f64 / T::EraDuration::get() as f64;
-        let expected_slots_per_block = slot_probability.1 as f64 / slot_probability.0 as f64;
-        let adjustment_factor = actual_slots_per_block / expected_slots_per_block;
-
-        let solution_range = (previous_solution_range as f64 * adjustment_factor).round() as u64;
+        let solution_range = next_solution_range(
+            previous_solution_range,
+            era_slot_count,
+            Self::era_duration(),
+            T::SlotProbability::get(),
+            T::MaxSolutionRangeIncrease::get(),
+            T::MaxSolutionRangeDecrease::get(),
+        );
 
         SolutionRange::<T>::put(solution_range);
         EraStartSlot::<T>::put(current_slot);
+        EraStartBlock::<T>::put(<frame_system::Pallet<T>>::block_number());
+
+        if let Some(pending_era_duration) = PendingEraDuration::<T>::take() {
+            CurrentEraDuration::<T>::put(pending_era_duration);
+
+            DurationsChanged::<T>::mutate(|durations_changed| {
+                durations_changed
+                    .get_or_insert_with(Default::default)
+                    .era_duration = Some(pending_era_duration);
+            });
+        }
 
         Self::deposit_consensus(ConsensusLog::NextSolutionRangeData(
             NextSolutionRangeDescriptor { solution_range },
@@ -610,6 +944,18 @@ impl<T: Config> Pallet<T> {
         // by the session module to be called before this.
         debug_assert!(Self::initialized().is_some());
 
+        // Update eon start slot and duration, this must happen before eon index is updated
+        EonStartSlot::<T>::put(Self::current_eon_start().saturating_add(Self::eon_duration()));
+        if let Some(pending_eon_duration) = PendingEonDuration::<T>::take() {
+            CurrentEonDuration::<T>::put(pending_eon_duration);
+
+            DurationsChanged::<T>::mutate(|durations_changed| {
+                durations_changed
+                    .get_or_insert_with(Default::default)
+                    .eon_duration = Some(pending_eon_duration);
+            });
+        }
+
         // Update eon index
         let eon_index = EonIndex::<T>::get()
             .checked_add(1)
@@ -617,25 +963,69 @@ impl<T: Config> Pallet<T> {
 
         EonIndex::<T>::put(eon_index);
 
-        let salt = eon_index;
+        // Salt is normally revealed ahead of time, but if there were no blocks between reveal and
+        // the end of the eon we derive it right here.
+        let salt = NextSalt::<T>::take().unwrap_or_else(|| Self::derive_salt(eon_index));
 
         Salt::<T>::put(salt);
 
-        Self::deposit_consensus(ConsensusLog::NextSaltData(NextSaltDescriptor { salt }));
+        // Salt for the next block is signalled explicitly, since the next salt that was revealed
+        // in advance doesn't tell when the eon actually changes.
+        Self::deposit_consensus(ConsensusLog::EonSaltData(SaltDescriptor { salt }));
+    }
+
+    /// Reveal salt for the next eon. Should be done on every block where
+    /// `should_reveal_next_salt` has returned `true`, and the caller is the only caller of this
+    /// function.
+    pub fn enact_next_salt_reveal() {
+        let next_eon_index = EonIndex::<T>::get()
+            .checked_add(1)
+            .expect("eon indices will never reach 2^64 before the death of the universe; qed");
+
+        NextSalt::<T>::put(Self::derive_salt(next_eon_index));
+    }
+
+    /// Derive salt for the eon with the given index from the current epoch randomness, so that it
+    /// can't be known before that randomness is.
+    fn derive_salt(eon_index: u64) -> u64 {
+        let mut subject = Vec::with_capacity(RANDOMNESS_LENGTH + 8);
+        subject.extend_from_slice(&Self::randomness());
+        subject.extend_from_slice(&eon_index.to_le_bytes());
+
+        let hash = sp_io::hashing::blake2_256(&subject);
+        let mut salt = [0u8; 8];
+        salt.copy_from_slice(&hash[..8]);
+
+        u64::from_le_bytes(salt)
     }
 
     /// Finds the start slot of the current epoch. only guaranteed to
     /// give correct results after `do_initialize` of the first block
     /// in the chain (as its result is based off of `GenesisSlot`).
     pub fn current_epoch_start() -> Slot {
-        Self::epoch_start(EpochIndex::<T>::get())
+        EpochStartSlot::<T>::get().unwrap_or_else(|| Self::epoch_start(EpochIndex::<T>::get()))
     }
 
     /// Finds the start slot of the current eon. only guaranteed to
     /// give correct results after `do_initialize` of the first block
     /// in the chain (as its result is based off of `GenesisSlot`).
     pub fn current_eon_start() -> Slot {
-        Self::eon_start(EonIndex::<T>::get())
+        EonStartSlot::<T>::get().unwrap_or_else(|| Self::eon_start(EonIndex::<T>::get()))
+    }
+
+    /// Duration of the current epoch in slots.
+    pub fn epoch_duration() -> u64 {
+        CurrentEpochDuration::<T>::get().unwrap_or_else(T::EpochDuration::get)
+    }
+
+    /// Duration of the current era in blocks.
+    pub fn era_duration() -> u32 {
+        CurrentEraDuration::<T>::get().unwrap_or_else(T::EraDuration::get)
+    }
+
+    /// Duration of the current eon in slots.
+    pub fn eon_duration() -> u64 {
+        CurrentEonDuration::<T>::get().unwrap_or_else(T::EonDuration::get)
     }
 
     /// Produces information about the current epoch.
@@ -643,7 +1033,7 @@ impl<T: Config> Pallet<T> {
         Epoch {
             epoch_index: EpochIndex::<T>::get(),
             start_slot: Self::current_epoch_start(),
-            duration: T::EpochDuration::get(),
+            duration: Self::epoch_duration(),
             randomness: Self::randomness(),
             config: EpochConfig::<T>::get()
                 .expect("EpochConfig is initialized in genesis; we never `take` or `kill` it; qed"),
@@ -660,8 +1050,8 @@ impl<T: Config> Pallet<T> {
 
         Epoch {
             epoch_index: next_epoch_index,
-            start_slot: Self::epoch_start(next_epoch_index),
-            duration: T::EpochDuration::get(),
+            start_slot: Self::current_epoch_start().saturating_add(Self::epoch_duration()),
+            duration: NextEpochDuration::<T>::get().unwrap_or_else(Self::epoch_duration),
             randomness: NextRandomness::<T>::get(),
             config: NextEpochConfig::<T>::get().unwrap_or_else(|| {
                 EpochConfig::<T>::get().expect(
@@ -671,6 +1061,7 @@ impl<T: Config> Pallet<T> {
         }
     }
 
+    /// Start of the epoch with the given index, assuming epoch duration never changed.
     fn epoch_start(epoch_index: u64) -> Slot {
         // (epoch_index * epoch_duration) + genesis_slot
 
@@ -687,6 +1078,7 @@ impl<T: Config> Pallet<T> {
             .into()
     }
 
+    /// Start of the eon with the given index, assuming eon duration never changed.
     fn eon_start(eon_index: u64) -> Slot {
         // (eon_index * eon_duration) + genesis_slot
 
@@ -763,6 +1155,15 @@ impl<T: Config> Pallet<T> {
             Lateness::<T>::put(lateness);
             CurrentSlot::<T>::put(current_slot);
 
+            // remove farmer from block list once it produces a block after the ban has expired,
+            // such that expired entries don't accumulate
+            let farmer_id = &digest.solution.public_key;
+            if BlockList::<T>::get(farmer_id)
+                .map_or(false, |banned_until| banned_until <= current_slot)
+            {
+                BlockList::<T>::remove(farmer_id);
+            }
+
             sp_io::hashing::blake2_256(&digest.solution.signature)
         });
 
@@ -774,14 +1175,22 @@ impl<T: Config> Pallet<T> {
         // Place PoR output into the `AuthorPorRandomness` storage item.
         AuthorPorRandomness::<T>::put(maybe_randomness);
 
-        // Deposit solution range data such that light client can validate blocks later.
-        Self::deposit_consensus(ConsensusLog::SolutionRangeData(SolutionRangeDescriptor {
-            solution_range: SolutionRange::<T>::get().unwrap_or_else(T::InitialSolutionRange::get),
-        }));
-        // Deposit salt data such that light client can validate blocks later.
-        Self::deposit_consensus(ConsensusLog::SaltData(SaltDescriptor {
-            salt: Salt::<T>::get(),
-        }));
+        // Deposit solution range data such that light client can validate blocks later, only when
+        // it changes to keep headers small.
+        let solution_range = SolutionRange::<T>::get().unwrap_or_else(T::InitialSolutionRange::get);
+        if LastDepositedSolutionRange::<T>::get() != Some(solution_range) {
+            LastDepositedSolutionRange::<T>::put(solution_range);
+            Self::deposit_consensus(ConsensusLog::SolutionRangeData(SolutionRangeDescriptor {
+                solution_range,
+            }));
+        }
+        // Deposit salt data such that light client can validate blocks later, only when it
+        // changes to keep headers small.
+        let salt = Salt::<T>::get();
+        if LastDepositedSalt::<T>::get() != Some(salt) {
+            LastDepositedSalt::<T>::put(salt);
+            Self::deposit_consensus(ConsensusLog::SaltData(SaltDescriptor { salt }));
+        }
 
         // enact epoch change, if necessary.
         T::EpochChangeTrigger::trigger::<T>(now);
@@ -789,6 +1198,16 @@ impl<T: Config> Pallet<T> {
         T::EraChangeTrigger::trigger::<T>(now);
         // enact eon change, if necessary.
         T::EonChangeTrigger::trigger::<T>(now);
+
+        // Deposit salt for the next eon, if revealed already, such that farmers can prepare for it.
+        if let Some(salt) = NextSalt::<T>::get() {
+            Self::deposit_consensus(ConsensusLog::NextSaltData(NextSaltDescriptor { salt }));
+        }
+
+        // Deposit durations that changed with this block, if any, such that nodes can track them.
+        if let Some(durations_changed) = DurationsChanged::<T>::take() {
+            Self::deposit_consensus(ConsensusLog::NextDurationsData(durations_changed));
+        }
     }
 
     /// Call this function exactly once when an epoch changes, to update the
@@ -811,6 +1230,7 @@ impl<T: Config> Pallet<T> {
     }
 
     fn do_report_equivocation(
+        reporter: Option<T::AccountId>,
         equivocation_proof: EquivocationProof<T::Header>,
     ) -> DispatchResultWithPostInfo {
         let offender = equivocation_proof.offender.clone();
@@ -823,7 +1243,9 @@ impl<T: Config> Pallet<T> {
 
         let offence = PoCEquivocationOffence { slot, offender };
 
-        T::HandleEquivocation::report_offence(offence)
+        let reporters = reporter.into_iter().collect();
+
+        T::HandleEquivocation::report_offence(reporters, offence)
             .map_err(|_| Error::<T>::DuplicateOffenceReport)?;
 
         // waive the fee since the report is valid and beneficial
@@ -844,13 +1266,33 @@ impl<T: Config> Pallet<T> {
     pub fn submit_test_equivocation_report(
         equivocation_proof: EquivocationProof<T::Header>,
     ) -> Option<()> {
-        BlockList::<T>::insert(equivocation_proof.offender.clone(), ());
+        Self::block_farmer(equivocation_proof.offender);
         Some(())
     }
 
-    /// Check if `farmer_id` is in block list (due to equivocation)
+    /// Check if `farmer_id` is in block list (due to equivocation) and the ban hasn't expired yet
     pub fn is_in_block_list(farmer_id: &FarmerId) -> bool {
-        BlockList::<T>::contains_key(farmer_id)
+        BlockList::<T>::get(farmer_id)
+            .map_or(false, |banned_until| CurrentSlot::<T>::get() < banned_until)
+    }
+
+    /// Farmers in block list whose ban hasn't expired yet along with the slot ban expires at
+    pub fn block_list() -> Vec<(FarmerId, Slot)> {
+        let current_slot = CurrentSlot::<T>::get();
+        BlockList::<T>::iter()
+            .filter(|(_farmer_id, banned_until)| current_slot < *banned_until)
+            .collect()
+    }
+
+    /// Add `farmer_id` to block list for `BanDuration` slots starting with the current slot,
+    /// extending existing ban if necessary.
+    fn block_farmer(farmer_id: FarmerId) {
+        let banned_until = CurrentSlot::<T>::get().saturating_add(T::BanDuration::get());
+        BlockList::<T>::mutate(farmer_id, |existing| {
+            if existing.map_or(true, |existing| existing < banned_until) {
+                *existing = Some(banned_until);
+            }
+        });
     }
 }
 
@@ -872,6 +1314,23 @@ impl<T: Config> OnTimestampSet<T::Moment> for Pallet<T> {
     }
 }
 
+impl<T: Config> FindAuthor<FarmerId> for Pallet<T> {
+    fn find_author<'a, I>(digests: I) -> Option<FarmerId>
+    where
+        I: 'a + IntoIterator<Item = (ConsensusEngineId, &'a [u8])>,
+    {
+        digests.into_iter().find_map(|(id, mut data)| {
+            if id == POC_ENGINE_ID {
+                PreDigest::decode(&mut data)
+                    .ok()
+                    .map(|pre_digest| pre_digest.solution.public_key)
+            } else {
+                None
+            }
+        })
+    }
+}
+
 impl<T: Config> frame_support::traits::Lateness<T::BlockNumber> for Pallet<T> {
     fn lateness(&self) -> T::BlockNumber {
         Self::lateness()
@@ -882,10 +1341,10 @@ impl<T: Config> sp_runtime::BoundToRuntimeAppPublic for Pallet<T> {
     type Public = FarmerId;
 }
 
-impl<T: Config> OnOffenceHandler<FarmerId> for Pallet<T> {
-    fn on_offence(offenders: &[OffenceDetails<FarmerId>]) {
+impl<T: Config> OnOffenceHandler<T::AccountId, FarmerId> for Pallet<T> {
+    fn on_offence(offenders: &[OffenceDetails<T::AccountId, FarmerId>]) {
         for offender in offenders {
-            BlockList::<T>::insert(offender.offender.clone(), ());
+            Self::block_farmer(offender.offender.clone());
         }
     }
 }
@@ -911,6 +1370,39 @@ fn compute_randomness(
     sp_io::hashing::blake2_256(&s)
 }
 
+// Compute solution range for the next era. The idea here is to keep block production at the same
+// pace while space pledged on the network changes. For this we adjust previous solution range
+// according to actual and expected number of blocks per era.
+//
+// Adjustment factor is bounded by `max_increase` and `max_decrease` such that a single era can't
+// swing solution range too much in either direction.
+fn next_solution_range(
+    previous_solution_range: u64,
+    era_slot_count: u64,
+    era_duration: u32,
+    slot_probability: (u64, u64),
+    max_increase: (u64, u64),
+    max_decrease: (u64, u64),
+) -> u64 {
+    // Adjustment factor is `actual_slots_per_block / expected_slots_per_block`, where
+    // `actual_slots_per_block = era_slot_count / era_duration` and
+    // `expected_slots_per_block = slot_probability.1 / slot_probability.0`
+    let adjustment_factor = FixedU128::saturating_from_rational(
+        u128::from(era_slot_count) * u128::from(slot_probability.0),
+        u128::from(era_duration) * u128::from(slot_probability.1),
+    )
+    .min(FixedU128::saturating_from_rational(
+        max_increase.0,
+        max_increase.1,
+    ))
+    .max(FixedU128::saturating_from_rational(
+        max_decrease.1,
+        max_decrease.0,
+    ));
+
+    adjustment_factor.saturating_mul_int(previous_solution_range)
+}
+
 pub mod migrations {
     use super::*;
     use frame_support::pallet_prelude::{StorageValue, ValueQuery};
@@ -956,4 +1448,35 @@ pub mod migrations {
 
         T::DbWeight::get().writes(writes) + T::DbWeight::get().reads(reads)
     }
+
+    /// A storage migration that gives farmers in `BlockList` stored before bans started to expire
+    /// (storage version 0, when the value was `()`) a ban expiring `BanDuration` slots after the
+    /// current slot.
+    ///
+    /// Does nothing if storage version is already 1 or higher, so it is safe to call it multiple
+    /// times.
+    pub fn migrate_block_list_to_expiring_bans<T: Config>() -> Weight {
+        let on_chain_version = StorageVersion::get::<Pallet<T>>();
+        if on_chain_version >= 1 {
+            return T::DbWeight::get().reads(1);
+        }
+
+        let banned_until = CurrentSlot::<T>::get().saturating_add(T::BanDuration::get());
+        let mut translated = 0u64;
+        BlockList::<T>::translate::<(), _>(|_farmer_id, ()| {
+            translated += 1;
+            Some(banned_until)
+        });
+
+        StorageVersion::new(1).put::<Pallet<T>>();
+
+        log::info!(
+            target: "runtime::poc",
+            "Migrated {} block list entries to bans expiring at slot {:?}",
+            translated,
+            banned_until,
+        );
+
+        T::DbWeight::get().reads_writes(translated + 2, translated + 1)
+    }
 }
@@ -55,7 +55,7 @@ frame_support::construct_runtime!(
         System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
         Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
         Spartan: pallet_spartan::{Pallet, Call, Storage, Config, ValidateUnsigned},
-        OffencesPoC: pallet_offences_poc::{Pallet, Storage, Event},
+        OffencesPoC: pallet_offences_poc::{Pallet, Storage, Event<T>},
         Timestamp: pallet_timestamp::{Pallet, Call, Storage, Inherent},
     }
 );
@@ -131,14 +131,30 @@ impl pallet_balances::Config for Test {
     type WeightInfo = ();
 }
 
+pub const REPORTER_REWARD: u128 = 100;
+/// Account that has funds for reporter rewards in genesis.
+pub const REWARD_POT_ACCOUNT: u64 = 1000;
+
+parameter_types! {
+    pub const ReporterReward: u128 = REPORTER_REWARD;
+    pub const RewardPot: u64 = REWARD_POT_ACCOUNT;
+}
+
 impl pallet_offences_poc::Config for Test {
     type Event = Event;
     type OnOffenceHandler = Spartan;
+    type Currency = Balances;
+    type ReporterReward = ReporterReward;
+    type RewardPot = RewardPot;
 }
 
 /// 1 in 6 slots (on average, not counting collisions) will have a block.
 pub const SLOT_PROBABILITY: (u64, u64) = (3, 10);
 
+/// Solution range can change by at most 4x in either direction on every era change.
+pub const MAX_SOLUTION_RANGE_INCREASE: (u64, u64) = (4, 1);
+pub const MAX_SOLUTION_RANGE_DECREASE: (u64, u64) = (4, 1);
+
 pub const INITIAL_SOLUTION_RANGE: u64 =
     u64::MAX / (1024 * 1024 * 1024 / 4096) * SLOT_PROBABILITY.0 / SLOT_PROBABILITY.1;
 
@@ -146,25 +162,33 @@ parameter_types! {
     pub const EpochDuration: u64 = 3;
     pub const EraDuration: u32 = 4;
     pub const EonDuration: u32 = 5;
+    pub const EonNextSaltReveal: u64 = 3;
     // 1GB
     pub const InitialSolutionRange: u64 = INITIAL_SOLUTION_RANGE;
     pub const SlotProbability: (u64, u64) = SLOT_PROBABILITY;
+    pub const MaxSolutionRangeIncrease: (u64, u64) = MAX_SOLUTION_RANGE_INCREASE;
+    pub const MaxSolutionRangeDecrease: (u64, u64) = MAX_SOLUTION_RANGE_DECREASE;
     pub const ExpectedBlockTime: u64 = 1;
     pub const ReportLongevity: u64 = 34;
+    pub const BanDuration: u64 = 10;
 }
 
 impl Config for Test {
     type EpochDuration = EpochDuration;
     type EraDuration = EraDuration;
     type EonDuration = EonDuration;
+    type EonNextSaltReveal = EonNextSaltReveal;
     type InitialSolutionRange = InitialSolutionRange;
     type SlotProbability = SlotProbability;
+    type MaxSolutionRangeIncrease = MaxSolutionRangeIncrease;
+    type MaxSolutionRangeDecrease = MaxSolutionRangeDecrease;
     type ExpectedBlockTime = ExpectedBlockTime;
     type EpochChangeTrigger = NormalEpochChange;
     type EraChangeTrigger = NormalEraChange;
     type EonChangeTrigger = NormalEonChange;
 
     type HandleEquivocation = super::EquivocationHandler<OffencesPoC, ReportLongevity>;
+    type BanDuration = BanDuration;
 
     type WeightInfo = ();
 }
@@ -198,6 +222,8 @@ pub fn go_to_block(keypair: &Keypair, block: u64, slot: u64) {
             encoding: encoding.to_vec(),
             signature: keypair.sign(ctx.bytes(&tag)).to_bytes().to_vec(),
             tag,
+            piece_inclusion_proof: None,
+            solution_data_commitment: None,
         },
     );
 
@@ -246,10 +272,15 @@ pub fn make_pre_digest(slot: Slot, solution: Solution) -> Digest {
 }
 
 pub fn new_test_ext() -> sp_io::TestExternalities {
-    frame_system::GenesisConfig::default()
+    let mut t = frame_system::GenesisConfig::default()
         .build_storage::<Test>()
-        .unwrap()
-        .into()
+        .unwrap();
+    pallet_balances::GenesisConfig::<Test> {
+        balances: vec![(REWARD_POT_ACCOUNT, 1_000)],
+    }
+    .assimilate_storage(&mut t)
+    .unwrap();
+    t.into()
 }
 
 /// Creates an equivocation at the current block, by generating two headers.
@@ -279,6 +310,8 @@ pub fn generate_equivocation_proof(
                 encoding: encoding.to_vec(),
                 signature: signature.clone(),
                 tag,
+                piece_inclusion_proof: None,
+                solution_data_commitment: None,
             },
         );
         System::initialize(&current_block, &parent_hash, &pre_digest, InitKind::Full);
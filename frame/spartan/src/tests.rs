@@ -20,12 +20,21 @@
 
 use super::{Call, *};
 use frame_support::{
-    assert_err, assert_noop, assert_ok, traits::OnFinalize, weights::GetDispatchInfo,
+    assert_err, assert_noop, assert_ok,
+    inherent::{InherentData, ProvideInherent},
+    traits::{OnFinalize, Randomness as RandomnessT},
+    weights::GetDispatchInfo,
 };
 use mock::*;
+use quickcheck::{QuickCheck, TestResult};
 use schnorrkel::Keypair;
-use sp_consensus_poc::{digests::Solution, PoCEpochConfiguration, Slot};
+use sp_consensus_poc::{
+    digests::Solution,
+    inherents::{InherentError, ROOT_BLOCKS_INHERENT_IDENTIFIER},
+    PoCEpochConfiguration, RootBlock, Slot,
+};
 use sp_core::Public;
+use sp_runtime::traits::Hash;
 
 const EMPTY_RANDOMNESS: [u8; 32] = [
     74, 25, 49, 128, 53, 97, 244, 49, 222, 202, 176, 2, 231, 66, 95, 10, 133, 49, 213, 228, 86,
@@ -102,6 +111,27 @@ fn author_por_output() {
     })
 }
 
+#[test]
+fn can_find_author() {
+    new_test_ext().execute_with(|| {
+        let keypair = Keypair::generate();
+
+        progress_to_block(&keypair, 1);
+
+        let digest = System::digest();
+        let pre_runtime_digests = digest.logs.iter().filter_map(|d| d.as_pre_runtime());
+        assert_eq!(
+            <Spartan as FindAuthor<FarmerId>>::find_author(pre_runtime_digests),
+            Some(FarmerId::from_slice(&keypair.public.to_bytes()))
+        );
+
+        assert_eq!(
+            <Spartan as FindAuthor<FarmerId>>::find_author(sp_std::iter::empty()),
+            None
+        );
+    })
+}
+
 #[test]
 fn can_predict_next_epoch_change() {
     new_test_ext().execute_with(|| {
@@ -168,32 +198,272 @@ fn can_update_solution_range_on_era_change() {
     })
 }
 
+#[test]
+fn solution_range_adjustment_is_bounded() {
+    new_test_ext().execute_with(|| {
+        let keypair = Keypair::generate();
+
+        progress_to_block(&keypair, 4);
+        // Change era such that it takes way more slots than expected
+        go_to_block(&keypair, 5, u64::from(Spartan::current_slot()) + 1_000);
+        // Solution range can't increase more than allowed
+        assert_eq!(
+            Spartan::solution_range(),
+            Some(
+                INITIAL_SOLUTION_RANGE * MAX_SOLUTION_RANGE_INCREASE.0
+                    / MAX_SOLUTION_RANGE_INCREASE.1
+            )
+        );
+    });
+
+    // Era can't have fewer slots than blocks in it, so we use large era duration to simulate very
+    // fast block production
+    assert_eq!(
+        next_solution_range(
+            INITIAL_SOLUTION_RANGE,
+            1,
+            1_000,
+            SLOT_PROBABILITY,
+            MAX_SOLUTION_RANGE_INCREASE,
+            MAX_SOLUTION_RANGE_DECREASE,
+        ),
+        INITIAL_SOLUTION_RANGE * MAX_SOLUTION_RANGE_DECREASE.1 / MAX_SOLUTION_RANGE_DECREASE.0
+    );
+}
+
+// Reference implementation of solution range retargeting that uses floating point arithmetic
+fn next_solution_range_f64(
+    previous_solution_range: u64,
+    era_slot_count: u64,
+    era_duration: u32,
+    slot_probability: (u64, u64),
+) -> f64 {
+    let actual_slots_per_block = era_slot_count as f64 / era_duration as f64;
+    let expected_slots_per_block = slot_probability.1 as f64 / slot_probability.0 as f64;
+    let adjustment_factor = actual_slots_per_block / expected_slots_per_block;
+
+    (previous_solution_range as f64 * adjustment_factor).round()
+}
+
+// Fixed point numbers have 18 decimal places, so after multiplication by `u64` solution range and
+// truncation result can be off by at most 20 units, while floating point error is relative.
+fn is_close_to(actual: u64, expected: f64) -> bool {
+    let expected = expected.min(u64::MAX as f64);
+
+    (actual as f64 - expected).abs() <= expected * 1e-15 + 20.0
+}
+
+#[test]
+fn next_solution_range_matches_floating_point_formula() {
+    fn prop(
+        previous_solution_range: u64,
+        era_slot_count: u32,
+        era_duration: u16,
+        slot_probability: (u8, u8),
+    ) -> TestResult {
+        if era_duration == 0 || slot_probability.0 == 0 || slot_probability.1 == 0 {
+            return TestResult::discard();
+        }
+        let slot_probability = (u64::from(slot_probability.0), u64::from(slot_probability.1));
+
+        let actual = next_solution_range(
+            previous_solution_range,
+            u64::from(era_slot_count),
+            u32::from(era_duration),
+            slot_probability,
+            // Bounds are wide enough to never be reached
+            (u64::MAX, 1),
+            (u64::MAX, 1),
+        );
+        let expected = next_solution_range_f64(
+            previous_solution_range,
+            u64::from(era_slot_count),
+            u32::from(era_duration),
+            slot_probability,
+        );
+
+        TestResult::from_bool(is_close_to(actual, expected))
+    }
+
+    QuickCheck::new().quickcheck(prop as fn(_, _, _, _) -> _);
+}
+
+#[test]
+fn next_solution_range_stays_within_bounds() {
+    fn prop(
+        previous_solution_range: u64,
+        era_slot_count: u32,
+        era_duration: u16,
+        slot_probability: (u8, u8),
+        max_adjustment: (u8, u8),
+    ) -> TestResult {
+        if era_duration == 0
+            || slot_probability.0 == 0
+            || slot_probability.1 == 0
+            || max_adjustment.1 == 0
+            || max_adjustment.0 < max_adjustment.1
+        {
+            return TestResult::discard();
+        }
+        let slot_probability = (u64::from(slot_probability.0), u64::from(slot_probability.1));
+        let max_adjustment = (u64::from(max_adjustment.0), u64::from(max_adjustment.1));
+
+        let actual = next_solution_range(
+            previous_solution_range,
+            u64::from(era_slot_count),
+            u32::from(era_duration),
+            slot_probability,
+            max_adjustment,
+            max_adjustment,
+        );
+        let max_factor = max_adjustment.0 as f64 / max_adjustment.1 as f64;
+        let expected = next_solution_range_f64(
+            previous_solution_range,
+            u64::from(era_slot_count),
+            u32::from(era_duration),
+            slot_probability,
+        )
+        .min(previous_solution_range as f64 * max_factor)
+        .max(previous_solution_range as f64 / max_factor);
+
+        TestResult::from_bool(is_close_to(actual, expected))
+    }
+
+    QuickCheck::new().quickcheck(prop as fn(_, _, _, _, _) -> _);
+}
+
+/// Salt of the eon with the given index, derived from the given epoch randomness.
+fn derive_salt(randomness: &sp_consensus_poc::Randomness, eon_index: u64) -> u64 {
+    let mut subject = randomness.to_vec();
+    subject.extend_from_slice(&eon_index.to_le_bytes());
+
+    let hash = sp_io::hashing::blake2_256(&subject);
+    let mut salt = [0u8; 8];
+    salt.copy_from_slice(&hash[..8]);
+
+    u64::from_le_bytes(salt)
+}
+
 #[test]
 fn can_update_salt_on_eon_change() {
     new_test_ext().execute_with(|| {
         let keypair = Keypair::generate();
 
         assert_eq!(<Test as Config>::EonDuration::get(), 5);
-        // Initial salt equals to eon
+        assert_eq!(<Test as Config>::EonNextSaltReveal::get(), 3);
+        // Initial salt is the default one, next salt is not known yet
         assert_eq!(Spartan::salt(), 0);
+        assert_eq!(Spartan::next_salt(), None);
 
         // We produce blocks on every slot
+        progress_to_block(&keypair, 3);
+        // Not yet time to reveal next salt
+        assert_eq!(Spartan::next_salt(), None);
+        progress_to_block(&keypair, 4);
+
+        // Next salt is revealed ahead of time and deposited in the header
+        let next_salt = Spartan::next_salt().unwrap();
+        assert_eq!(next_salt, derive_salt(&Spartan::randomness(), 1));
+        assert_eq!(Spartan::salt(), 0);
+        assert_eq!(
+            System::digest().logs.last(),
+            Some(&DigestItem::Consensus(
+                POC_ENGINE_ID,
+                sp_consensus_poc::ConsensusLog::NextSaltData(
+                    sp_consensus_poc::digests::NextSaltDescriptor { salt: next_salt }
+                )
+                .encode()
+            ))
+        );
+
         progress_to_block(&keypair, 5);
         // Still no salt update
         assert_eq!(Spartan::salt(), 0);
+        assert_eq!(Spartan::next_salt(), Some(next_salt));
         progress_to_block(&keypair, 6);
 
-        // Second eon should have salt updated
-        assert_eq!(Spartan::salt(), 1);
+        // Second eon should have revealed salt, eon change is deposited in the header
+        assert_eq!(Spartan::salt(), next_salt);
+        assert_eq!(Spartan::next_salt(), None);
+        assert_eq!(
+            System::digest().logs.last(),
+            Some(&DigestItem::Consensus(
+                POC_ENGINE_ID,
+                sp_consensus_poc::ConsensusLog::EonSaltData(
+                    sp_consensus_poc::digests::SaltDescriptor { salt: next_salt }
+                )
+                .encode()
+            ))
+        );
 
         // We produce blocks on every slot
+        progress_to_block(&keypair, 9);
+        let next_salt = Spartan::next_salt().unwrap();
+        // Salt is not simply incremented, but derived from randomness
+        assert_ne!(next_salt, Spartan::salt());
+        assert_eq!(next_salt, derive_salt(&Spartan::randomness(), 2));
         progress_to_block(&keypair, 10);
         // Just before eon update, still the same salt as before
-        assert_eq!(Spartan::salt(), 1);
+        assert_ne!(Spartan::salt(), next_salt);
         progress_to_block(&keypair, 11);
 
         // Third eon should have salt updated again
-        assert_eq!(Spartan::salt(), 2);
+        assert_eq!(Spartan::salt(), next_salt);
+    })
+}
+
+#[test]
+fn deposits_solution_range_and_salt_only_when_changed() {
+    new_test_ext().execute_with(|| {
+        let keypair = Keypair::generate();
+
+        let deposited_solution_range_and_salt = || {
+            System::digest()
+                .logs
+                .iter()
+                .filter_map(|log| {
+                    log.consensus_try_to::<sp_consensus_poc::ConsensusLog>(&POC_ENGINE_ID)
+                })
+                .fold((None, None), |(solution_range, salt), log| match log {
+                    sp_consensus_poc::ConsensusLog::SolutionRangeData(descriptor) => {
+                        (Some(descriptor.solution_range), salt)
+                    }
+                    sp_consensus_poc::ConsensusLog::SaltData(descriptor) => {
+                        (solution_range, Some(descriptor.salt))
+                    }
+                    _ => (solution_range, salt),
+                })
+        };
+
+        // Both are deposited in the first block
+        progress_to_block(&keypair, 1);
+        assert_eq!(
+            deposited_solution_range_and_salt(),
+            (Some(INITIAL_SOLUTION_RANGE), Some(0))
+        );
+
+        progress_to_block(&keypair, 2);
+        assert_eq!(deposited_solution_range_and_salt(), (None, None));
+
+        // Solution range changes with the era in block 5 and applies starting with block 6
+        progress_to_block(&keypair, 5);
+        assert_eq!(deposited_solution_range_and_salt(), (None, None));
+        progress_to_block(&keypair, 6);
+        assert_eq!(
+            deposited_solution_range_and_salt(),
+            (Spartan::solution_range(), None)
+        );
+
+        // Salt changes with the eon in block 6 and applies starting with block 7
+        progress_to_block(&keypair, 7);
+        assert_eq!(
+            deposited_solution_range_and_salt(),
+            (None, Some(Spartan::salt()))
+        );
+        assert_ne!(Spartan::salt(), 0);
+
+        progress_to_block(&keypair, 8);
+        assert_eq!(deposited_solution_range_and_salt(), (None, None));
     })
 }
 
@@ -270,6 +540,166 @@ fn only_root_can_enact_config_change() {
     });
 }
 
+#[test]
+fn can_change_durations() {
+    use sp_consensus_poc::digests::NextDurationsDescriptor;
+
+    new_test_ext().execute_with(|| {
+        let keypair = Keypair::generate();
+
+        // We produce blocks on every slot, genesis slot is 1
+        progress_to_block(&keypair, 2);
+
+        assert_ok!(Spartan::plan_durations_change(
+            Origin::root(),
+            NextDurationsDescriptor {
+                epoch_duration: Some(5),
+                era_duration: Some(2),
+                eon_duration: Some(7),
+            },
+        ));
+
+        // Epoch duration change is announced with the next epoch and applies to the one after
+        progress_to_block(&keypair, 4);
+        assert_eq!(Spartan::epoch_index(), 1);
+        assert_eq!(Spartan::current_epoch().duration, 3);
+        assert_eq!(*Spartan::next_epoch().start_slot, 7);
+        assert_eq!(Spartan::next_epoch().duration, 5);
+        assert!(System::digest().logs.contains(&DigestItem::Consensus(
+            POC_ENGINE_ID,
+            sp_consensus_poc::ConsensusLog::NextDurationsData(NextDurationsDescriptor {
+                epoch_duration: Some(5),
+                era_duration: None,
+                eon_duration: None,
+            })
+            .encode()
+        )));
+
+        // Era duration change applies to the era that starts now
+        progress_to_block(&keypair, 5);
+        assert_eq!(Spartan::era_duration(), 2);
+        assert_eq!(EraStartSlot::<Test>::get(), Some(5.into()));
+
+        // Eon duration change applies to the eon that starts now
+        progress_to_block(&keypair, 6);
+        assert_eq!(Spartan::eon_index(), 1);
+        assert_eq!(Spartan::eon_duration(), 7);
+        assert_eq!(*Spartan::current_eon_start(), 6);
+
+        progress_to_block(&keypair, 7);
+        assert_eq!(Spartan::epoch_index(), 2);
+        assert_eq!(*Spartan::current_epoch_start(), 7);
+        assert_eq!(Spartan::current_epoch().duration, 5);
+        assert_eq!(EraStartSlot::<Test>::get(), Some(7.into()));
+
+        progress_to_block(&keypair, 11);
+        assert_eq!(Spartan::epoch_index(), 2);
+        progress_to_block(&keypair, 12);
+        assert_eq!(Spartan::epoch_index(), 3);
+        assert_eq!(Spartan::eon_index(), 1);
+        progress_to_block(&keypair, 13);
+        assert_eq!(Spartan::eon_index(), 2);
+    });
+}
+
+#[test]
+fn durations_change_is_validated() {
+    use sp_consensus_poc::digests::NextDurationsDescriptor;
+    use sp_runtime::DispatchError;
+
+    new_test_ext().execute_with(|| {
+        let res = Spartan::plan_durations_change(Origin::signed(1), Default::default());
+
+        assert_noop!(res, DispatchError::BadOrigin);
+
+        let res = Spartan::plan_durations_change(
+            Origin::root(),
+            NextDurationsDescriptor {
+                epoch_duration: Some(0),
+                ..Default::default()
+            },
+        );
+
+        assert_noop!(res, Error::<Test>::InvalidDurations);
+
+        // Eon must be longer than the lookahead of next salt reveal
+        let res = Spartan::plan_durations_change(
+            Origin::root(),
+            NextDurationsDescriptor {
+                eon_duration: Some(<Test as Config>::EonNextSaltReveal::get()),
+                ..Default::default()
+            },
+        );
+
+        assert_noop!(res, Error::<Test>::InvalidDurations);
+    });
+}
+
+#[test]
+fn can_store_root_blocks() {
+    use sp_runtime::DispatchError;
+
+    let root_block = |segment_index| RootBlock {
+        segment_index,
+        records_root: [segment_index as u8; 32],
+    };
+
+    new_test_ext().execute_with(|| {
+        let res = Spartan::store_root_blocks(Origin::signed(1), vec![root_block(0)]);
+
+        assert_noop!(res, DispatchError::BadOrigin);
+
+        // Root blocks must start from the next expected segment index
+        let res = Spartan::store_root_blocks(Origin::none(), vec![root_block(1)]);
+
+        assert_noop!(res, Error::<Test>::UnexpectedSegmentIndex);
+
+        assert_ok!(Spartan::store_root_blocks(
+            Origin::none(),
+            vec![root_block(0), root_block(1)],
+        ));
+
+        assert_eq!(Spartan::next_archived_segment_index(), 2);
+        assert_eq!(Spartan::records_root(1), Some(root_block(1).records_root));
+        assert!(System::digest().logs.contains(&DigestItem::Consensus(
+            POC_ENGINE_ID,
+            ConsensusLog::RootBlocksData(vec![root_block(0), root_block(1)]).encode(),
+        )));
+
+        // Already stored root blocks are not included into the inherent again
+        let mut inherent_data = InherentData::new();
+        inherent_data
+            .put_data(
+                ROOT_BLOCKS_INHERENT_IDENTIFIER,
+                &vec![root_block(1), root_block(2)],
+            )
+            .unwrap();
+
+        let call = Spartan::create_inherent(&inherent_data).unwrap();
+        assert_eq!(call, Call::store_root_blocks(vec![root_block(2)]));
+        assert_ok!(Spartan::check_inherent(&call, &inherent_data));
+
+        let mismatched_call = Call::store_root_blocks(vec![RootBlock {
+            segment_index: 2,
+            records_root: [0; 32],
+        }]);
+        assert_eq!(
+            Spartan::check_inherent(&mismatched_call, &inherent_data),
+            Err(InherentError::MismatchedRootBlock(2)),
+        );
+
+        // Root blocks that were not archived locally are rejected, including future ones
+        let unknown_call = Call::store_root_blocks(vec![root_block(2), root_block(3)]);
+        assert_eq!(
+            Spartan::check_inherent(&unknown_call, &inherent_data),
+            Err(InherentError::UnknownRootBlock(3)),
+        );
+
+        // Unless there are no local root blocks to check against at all
+        assert_ok!(Spartan::check_inherent(&unknown_call, &InherentData::new()));
+    });
+}
+
 #[test]
 fn can_fetch_current_and_next_epoch_data() {
     new_test_ext().execute_with(|| {
@@ -319,6 +749,33 @@ fn tracks_block_numbers_when_current_and_previous_epoch_started() {
     });
 }
 
+#[test]
+fn randomness_is_annotated_with_epoch_start_blocks() {
+    new_test_ext().execute_with(|| {
+        let keypair = Keypair::generate();
+
+        // epoch 2 started at block 4 and epoch 3 at block 7
+        progress_to_block(&keypair, 8);
+
+        let hash = |randomness: &[u8]| {
+            <Test as frame_system::Config>::Hashing::hash(&[&b"subject"[..], randomness].concat())
+        };
+
+        assert_eq!(
+            RandomnessFromTwoEpochsAgo::<Test>::random(b"subject"),
+            (hash(&Spartan::randomness()), 4),
+        );
+        assert_eq!(
+            RandomnessFromOneEpochAgo::<Test>::random(b"subject"),
+            (hash(&NextRandomness::<Test>::get()), 7),
+        );
+        assert_eq!(
+            CurrentBlockRandomness::<Test>::random(b"subject"),
+            (Some(hash(&Spartan::author_por_randomness().unwrap())), 8),
+        );
+    });
+}
+
 #[test]
 fn report_equivocation_current_session_works() {
     new_test_ext().execute_with(|| {
@@ -345,6 +802,36 @@ fn report_equivocation_current_session_works() {
     });
 }
 
+#[test]
+fn report_equivocation_signed_rewards_reporter() {
+    new_test_ext().execute_with(|| {
+        let keypair = Keypair::generate();
+
+        progress_to_block(&keypair, 1);
+
+        let keypair = Keypair::generate();
+        let farmer_id = FarmerId::from_slice(&keypair.public.to_bytes());
+        let reporter = 1;
+
+        let equivocation_proof = generate_equivocation_proof(&keypair, CurrentSlot::<Test>::get());
+
+        assert_eq!(Balances::free_balance(reporter), 0);
+
+        // report the equivocation as a signed extrinsic
+        assert_ok!(Spartan::report_equivocation(
+            Origin::signed(reporter),
+            equivocation_proof
+        ));
+
+        progress_to_block(&keypair, 2);
+
+        // check that farmer was added to block list and reporter was rewarded
+        assert_eq!(Spartan::is_in_block_list(&farmer_id), true);
+        assert_eq!(Balances::free_balance(reporter), REPORTER_REWARD);
+        assert_eq!(OffencesPoC::farmer_offences(&farmer_id).len(), 1);
+    });
+}
+
 #[test]
 fn report_equivocation_old_session_works() {
     new_test_ext().execute_with(|| {
@@ -374,6 +861,38 @@ fn report_equivocation_old_session_works() {
     })
 }
 
+#[test]
+fn block_list_ban_expires() {
+    new_test_ext().execute_with(|| {
+        let keypair = Keypair::generate();
+
+        progress_to_block(&keypair, 1);
+
+        let offender = Keypair::generate();
+        let farmer_id = FarmerId::from_slice(&offender.public.to_bytes());
+
+        let equivocation_proof = generate_equivocation_proof(&offender, CurrentSlot::<Test>::get());
+        Spartan::report_equivocation_unsigned(Origin::none(), equivocation_proof).unwrap();
+
+        let banned_until = CurrentSlot::<Test>::get() + BanDuration::get();
+        assert_eq!(BlockList::<Test>::get(&farmer_id), Some(banned_until));
+        assert_eq!(Spartan::block_list(), vec![(farmer_id.clone(), banned_until)]);
+
+        // every block has its own slot, so ban is in effect until the block before
+        progress_to_block(&keypair, *banned_until - 1);
+        assert_eq!(Spartan::is_in_block_list(&farmer_id), true);
+
+        progress_to_block(&keypair, *banned_until);
+        assert_eq!(Spartan::is_in_block_list(&farmer_id), false);
+        assert_eq!(Spartan::block_list(), vec![]);
+
+        // expired entry is removed once farmer produces a block again
+        assert!(BlockList::<Test>::contains_key(&farmer_id));
+        progress_to_block(&offender, *banned_until + 1);
+        assert!(!BlockList::<Test>::contains_key(&farmer_id));
+    })
+}
+
 #[test]
 fn report_equivocation_invalid_equivocation_proof() {
     use sp_runtime::traits::Header;
@@ -604,3 +1123,31 @@ fn add_epoch_configurations_migration_works() {
         );
     });
 }
+
+#[test]
+fn migrate_block_list_to_expiring_bans_works() {
+    use frame_support::{storage::unhashed, traits::StorageVersion};
+
+    new_test_ext().execute_with(|| {
+        let keypair = Keypair::generate();
+
+        progress_to_block(&keypair, 3);
+
+        let farmer_id = FarmerId::from_slice(&Keypair::generate().public.to_bytes());
+
+        // entries of storage version 0 were stored without expiry
+        StorageVersion::new(0).put::<Spartan>();
+        unhashed::put(&BlockList::<Test>::hashed_key_for(&farmer_id), &());
+
+        crate::migrations::migrate_block_list_to_expiring_bans::<Test>();
+
+        let banned_until = CurrentSlot::<Test>::get() + BanDuration::get();
+        assert_eq!(BlockList::<Test>::get(&farmer_id), Some(banned_until));
+        assert_eq!(StorageVersion::get::<Spartan>(), 1);
+
+        // running migration again does nothing
+        progress_to_block(&keypair, 4);
+        crate::migrations::migrate_block_list_to_expiring_bans::<Test>();
+        assert_eq!(BlockList::<Test>::get(&farmer_id), Some(banned_until));
+    });
+}
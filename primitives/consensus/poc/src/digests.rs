@@ -17,16 +17,64 @@
 //! Private implementation details of Proof-of-Capacity (PoC) consensus digests.
 
 use super::{FarmerSignature, PoCEpochConfiguration, Slot, POC_ENGINE_ID};
-use codec::{Codec, Decode, Encode};
+use codec::{Codec, Decode, Encode, Input, Output};
+use sp_runtime::traits::{BlakeTwo256, Hash as HashT};
 use sp_runtime::{DigestItem, RuntimeDebug};
 
 use crate::FarmerId;
 use sp_consensus_spartan::Randomness;
 use sp_std::vec::Vec;
 
+/// Proof that the encoded piece belongs to an archived segment of the blockchain history.
+#[derive(Clone, PartialEq, Eq, RuntimeDebug, Encode, Decode)]
+pub struct PieceInclusionProof {
+    /// Index of the piece in the archived history, see [`crate::PIECES_PER_SEGMENT`].
+    pub piece_index: u64,
+    /// The piece that was encoded.
+    pub piece: Vec<u8>,
+    /// Merkle proof of the piece against the records root of its segment.
+    pub merkle_proof: Vec<[u8; 32]>,
+}
+
+/// Part of the solution that is too large for block headers, it is included in the block body
+/// instead for compact solutions, see [`Solution::to_compact`].
+#[derive(Clone, PartialEq, Eq, RuntimeDebug, Encode, Decode)]
+pub struct SolutionData {
+    /// Encoding
+    pub encoding: Vec<u8>,
+    /// Proof of inclusion of the encoded piece, `None` for encodings of the genesis piece
+    pub piece_inclusion_proof: Option<PieceInclusionProof>,
+}
+
+impl SolutionData {
+    /// Commitment to this solution data and the `tag` created from its encoding that compact
+    /// solutions keep in the header.
+    pub fn commitment(&self, tag: &[u8; 8]) -> [u8; 32] {
+        BlakeTwo256::hash_of(&(tag, self)).to_fixed_bytes()
+    }
+}
+
+/// Version of a [`Solution`], which is the first byte of its encoding.
+#[derive(Clone, Copy, PartialEq, Eq, RuntimeDebug, Encode, Decode)]
+pub enum SolutionVersion {
+    /// Encoding of the genesis piece, only accepted until
+    /// [`crate::GENESIS_PIECE_SOLUTIONS_CUTOVER`] segments of the history are archived.
+    #[codec(index = 0)]
+    GenesisPiece,
+    /// Encoding of an archived piece, followed by its [`PieceInclusionProof`].
+    #[codec(index = 1)]
+    ArchivedPiece,
+    /// Compact solution with an empty encoding, followed by a commitment to [`SolutionData`].
+    #[codec(index = 2)]
+    Compact,
+}
+
 // TODO: better documentation here
 /// Solution
-#[derive(Clone, RuntimeDebug, Encode, Decode)]
+///
+/// Solutions are versioned, see [`SolutionVersion`]. Compact solutions never have a piece
+/// inclusion proof, it is part of [`SolutionData`] instead.
+#[derive(Clone, RuntimeDebug)]
 pub struct Solution {
     /// Public key of the farmer that created solution
     pub public_key: FarmerId,
@@ -38,6 +86,11 @@ pub struct Solution {
     pub signature: Vec<u8>,
     /// Tag (hmac of encoding and salt)
     pub tag: [u8; 8],
+    /// Proof of inclusion of the encoded piece, `None` for encodings of the genesis piece
+    pub piece_inclusion_proof: Option<PieceInclusionProof>,
+    /// Commitment to [`SolutionData`] included in the block body, `Some` only for compact
+    /// solutions
+    pub solution_data_commitment: Option<[u8; 32]>,
 }
 
 impl Solution {
@@ -49,10 +102,119 @@ impl Solution {
             encoding: Vec::new(),
             signature: Vec::new(),
             tag: [0u8; 8],
+            piece_inclusion_proof: None,
+            solution_data_commitment: None,
+        }
+    }
+
+    /// Version of this solution.
+    pub fn version(&self) -> SolutionVersion {
+        if self.solution_data_commitment.is_some() {
+            SolutionVersion::Compact
+        } else if self.piece_inclusion_proof.is_some() {
+            SolutionVersion::ArchivedPiece
+        } else {
+            SolutionVersion::GenesisPiece
+        }
+    }
+
+    /// Whether this is a compact solution, see [`Solution::to_compact`].
+    pub fn is_compact(&self) -> bool {
+        self.version() == SolutionVersion::Compact
+    }
+
+    /// Part of the solution that is moved into the block body for compact solutions.
+    pub fn solution_data(&self) -> SolutionData {
+        SolutionData {
+            encoding: self.encoding.clone(),
+            piece_inclusion_proof: self.piece_inclusion_proof.clone(),
+        }
+    }
+
+    /// Compact version of this solution that only keeps a commitment to [`SolutionData`], which
+    /// makes it small enough for block headers.
+    pub fn to_compact(&self) -> Self {
+        Self {
+            public_key: self.public_key.clone(),
+            nonce: self.nonce,
+            encoding: Vec::new(),
+            signature: self.signature.clone(),
+            tag: self.tag,
+            piece_inclusion_proof: None,
+            solution_data_commitment: Some(self.solution_data().commitment(&self.tag)),
+        }
+    }
+
+    /// Full solution from this compact solution and its `solution_data`, `None` if solution data
+    /// doesn't match the commitment.
+    pub fn with_solution_data(self, solution_data: SolutionData) -> Option<Self> {
+        if self.solution_data_commitment != Some(solution_data.commitment(&self.tag)) {
+            return None;
+        }
+
+        Some(Self {
+            encoding: solution_data.encoding,
+            piece_inclusion_proof: solution_data.piece_inclusion_proof,
+            solution_data_commitment: None,
+            ..self
+        })
+    }
+}
+
+impl Encode for Solution {
+    fn encode_to<T: Output + ?Sized>(&self, dest: &mut T) {
+        let version = self.version();
+        version.encode_to(dest);
+        self.public_key.encode_to(dest);
+        self.nonce.encode_to(dest);
+        self.encoding.encode_to(dest);
+        self.signature.encode_to(dest);
+        self.tag.encode_to(dest);
+        match (
+            version,
+            &self.piece_inclusion_proof,
+            &self.solution_data_commitment,
+        ) {
+            (SolutionVersion::ArchivedPiece, Some(piece_inclusion_proof), _) => {
+                piece_inclusion_proof.encode_to(dest);
+            }
+            (SolutionVersion::Compact, _, Some(solution_data_commitment)) => {
+                solution_data_commitment.encode_to(dest);
+            }
+            _ => {}
         }
     }
 }
 
+impl Decode for Solution {
+    fn decode<I: Input>(input: &mut I) -> Result<Self, codec::Error> {
+        let version = SolutionVersion::decode(input)?;
+        let public_key = Decode::decode(input)?;
+        let nonce = Decode::decode(input)?;
+        let encoding: Vec<u8> = Decode::decode(input)?;
+        let signature = Decode::decode(input)?;
+        let tag = Decode::decode(input)?;
+        let (piece_inclusion_proof, solution_data_commitment) = match version {
+            SolutionVersion::GenesisPiece => (None, None),
+            SolutionVersion::ArchivedPiece => (Some(Decode::decode(input)?), None),
+            SolutionVersion::Compact if encoding.is_empty() => (None, Some(Decode::decode(input)?)),
+            SolutionVersion::Compact => {
+                return Err("Compact solution must have an empty encoding".into());
+            }
+        };
+
+        Ok(Self {
+            public_key,
+            nonce,
+            encoding,
+            signature,
+            tag,
+            piece_inclusion_proof,
+            solution_data_commitment,
+        })
+    }
+}
+
 /// A PoC pre-runtime digest. This contains all data required to validate a
 /// block and for the PoC runtime module.
 #[derive(Clone, RuntimeDebug, Encode, Decode)]
@@ -99,6 +261,21 @@ impl From<NextConfigDescriptor> for PoCEpochConfiguration {
     }
 }
 
+/// Durations of epochs, eras and eons that change with the block, `None` for those that don't.
+///
+/// New epoch duration applies to the next epoch announced in the same block using the same rules
+/// as `NextEpochDescriptor`, while new era and eon durations apply to the era and eon that start
+/// with the block.
+#[derive(Decode, Encode, PartialEq, Eq, Clone, Default, RuntimeDebug)]
+pub struct NextDurationsDescriptor {
+    /// Duration of the epoch in slots.
+    pub epoch_duration: Option<u64>,
+    /// Duration of the era in blocks.
+    pub era_duration: Option<u32>,
+    /// Duration of the eon in slots.
+    pub eon_duration: Option<u64>,
+}
+
 /// Information about the solution range for the block.
 #[derive(Decode, Encode, PartialEq, Eq, Clone, RuntimeDebug)]
 pub struct SolutionRangeDescriptor {
@@ -121,8 +298,8 @@ pub struct NextSolutionRangeDescriptor {
     pub solution_range: u64,
 }
 
-/// Salt, if changed. This is broadcast in the each block of the eon, but only applies to the block
-/// after that.
+/// Salt for the next eon. This is broadcast in each block of the eon once the salt is revealed,
+/// but only applies starting with the block after the eon change.
 #[derive(Decode, Encode, PartialEq, Eq, Clone, RuntimeDebug)]
 pub struct NextSaltDescriptor {
     /// Salt used with challenges.
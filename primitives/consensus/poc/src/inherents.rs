@@ -16,13 +16,22 @@
 
 //! Inherents for Proof-of-Capacity (PoC) consensus
 
-use sp_inherents::{Error, InherentData, InherentIdentifier};
-
-use sp_std::result::Result;
+use crate::digests::SolutionData;
+use crate::RootBlock;
+use codec::{Decode, Encode};
+use sp_inherents::{Error, InherentData, InherentIdentifier, IsFatalError};
+use sp_runtime::RuntimeDebug;
+use sp_std::{result::Result, vec::Vec};
 
 /// The PoC inherent identifier.
 pub const INHERENT_IDENTIFIER: InherentIdentifier = *b"poc0slot";
 
+/// The identifier of the inherent with root blocks of archived segments.
+pub const ROOT_BLOCKS_INHERENT_IDENTIFIER: InherentIdentifier = *b"poc0root";
+
+/// The identifier of the inherent with solution data of the block with a compact solution.
+pub const SOLUTION_DATA_INHERENT_IDENTIFIER: InherentIdentifier = *b"poc0sold";
+
 /// The type of the PoC inherent.
 pub type InherentType = sp_consensus_slots::Slot;
 /// Auxiliary trait to extract PoC inherent data.
@@ -43,6 +52,27 @@ impl PoCInherentData for InherentData {
     }
 }
 
+/// The type of the root blocks inherent.
+pub type RootBlocksInherentType = Vec<RootBlock>;
+
+/// The type of the solution data inherent.
+pub type SolutionDataInherentType = SolutionData;
+
+/// Errors that can occur while checking the root blocks inherent.
+#[derive(Encode, Decode, PartialEq, Eq, RuntimeDebug)]
+pub enum InherentError {
+    /// Root block of the segment with this index doesn't match the one archived locally.
+    MismatchedRootBlock(u64),
+    /// Segment with this index was not archived locally.
+    UnknownRootBlock(u64),
+}
+
+impl IsFatalError for InherentError {
+    fn is_fatal_error(&self) -> bool {
+        true
+    }
+}
+
 /// Provides the slot duration inherent data for PoC.
 // TODO: Remove in the future. https://github.com/paritytech/substrate/issues/8029
 #[cfg(feature = "std")]
@@ -100,3 +130,51 @@ impl sp_inherents::InherentDataProvider for InherentDataProvider {
         None
     }
 }
+
+/// Provides root blocks of archived segments that are not yet stored on chain.
+#[cfg(feature = "std")]
+pub struct RootBlocksInherentDataProvider {
+    root_blocks: Option<RootBlocksInherentType>,
+}
+
+#[cfg(feature = "std")]
+impl RootBlocksInherentDataProvider {
+    /// Create new inherent data provider from the given `root_blocks`.
+    pub fn new(root_blocks: RootBlocksInherentType) -> Self {
+        Self {
+            root_blocks: Some(root_blocks),
+        }
+    }
+
+    /// Create inherent data provider for nodes that don't have the blockchain history, such
+    /// nodes neither include root blocks into blocks nor check root blocks included by others.
+    pub fn without_history() -> Self {
+        Self { root_blocks: None }
+    }
+}
+
+#[cfg(feature = "std")]
+#[async_trait::async_trait]
+impl sp_inherents::InherentDataProvider for RootBlocksInherentDataProvider {
+    fn provide_inherent_data(&self, inherent_data: &mut InherentData) -> Result<(), Error> {
+        match &self.root_blocks {
+            Some(root_blocks) => {
+                inherent_data.put_data(ROOT_BLOCKS_INHERENT_IDENTIFIER, root_blocks)
+            }
+            None => Ok(()),
+        }
+    }
+
+    async fn try_handle_error(
+        &self,
+        identifier: &InherentIdentifier,
+        error: &[u8],
+    ) -> Option<Result<(), Error>> {
+        if *identifier != ROOT_BLOCKS_INHERENT_IDENTIFIER {
+            return None;
+        }
+
+        let error = InherentError::decode(&mut &error[..]).ok()?;
+        Some(Err(Error::Application(format!("{:?}", error).into())))
+    }
+}
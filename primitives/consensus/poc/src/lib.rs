@@ -32,8 +32,8 @@ use sp_runtime::{traits::Header, ConsensusEngineId, RuntimeDebug};
 use sp_std::vec::Vec;
 
 use crate::digests::{
-    NextConfigDescriptor, NextEpochDescriptor, NextSaltDescriptor, NextSolutionRangeDescriptor,
-    SaltDescriptor, SolutionRangeDescriptor,
+    NextConfigDescriptor, NextDurationsDescriptor, NextEpochDescriptor, NextSaltDescriptor,
+    NextSolutionRangeDescriptor, SaltDescriptor, SolutionRangeDescriptor,
 };
 
 /// Key type for PoC module.
@@ -46,6 +46,11 @@ mod app {
     app_crypto!(sr25519, KEY_TYPE);
 }
 
+/// A PoC farmer keypair. Necessarily equivalent to the schnorrkel public key used in
+/// the main PoC module. If that ever changes, then this must, too.
+#[cfg(feature = "std")]
+pub type FarmerPair = app::Pair;
+
 /// A PoC farmer signature.
 pub type FarmerSignature = app::Signature;
 
@@ -94,9 +99,18 @@ pub enum ConsensusLog {
     /// The era has changed and the solution range has changed because of that.
     #[codec(index = 5)]
     NextSolutionRangeData(NextSolutionRangeDescriptor),
-    /// The eon has changed and the salt has changed because of that.
+    /// Salt for the next eon was revealed.
     #[codec(index = 6)]
     NextSaltData(NextSaltDescriptor),
+    /// Durations of epochs, eras or eons have changed.
+    #[codec(index = 7)]
+    NextDurationsData(NextDurationsDescriptor),
+    /// Root blocks of archived segments were stored on chain with this block.
+    #[codec(index = 8)]
+    RootBlocksData(Vec<RootBlock>),
+    /// The eon has changed and the salt has changed because of that.
+    #[codec(index = 9)]
+    EonSaltData(SaltDescriptor),
 }
 
 /// Configuration data used by the PoC consensus engine.
@@ -121,6 +135,44 @@ pub struct PoCGenesisConfiguration {
 
     /// The randomness for the genesis epoch.
     pub randomness: Randomness,
+
+    /// Parameters of the Spartan codec used for plotting and solution verification.
+    pub spartan_parameters: SpartanParameters,
+
+    /// Whether blocks are authored with compact solutions in the pre-digest, whose
+    /// [`digests::SolutionData`] is included in the block body with an inherent instead, light
+    /// clients fetch block bodies to verify them. Solution data must be the last argument of the
+    /// inherent call, such that clients can find it in the block body without the runtime.
+    pub compact_pre_digest: bool,
+}
+
+/// Configuration data used by the PoC consensus engine, as returned by version 1 of [`PoCApi`].
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+pub struct PoCGenesisConfigurationV1 {
+    /// The slot duration in milliseconds for PoC.
+    pub slot_duration: u64,
+
+    /// The duration of epochs in slots.
+    pub epoch_length: u64,
+
+    /// A constant value that is used in the threshold calculation formula.
+    pub c: (u64, u64),
+
+    /// The randomness for the genesis epoch.
+    pub randomness: Randomness,
+}
+
+impl From<PoCGenesisConfigurationV1> for PoCGenesisConfiguration {
+    fn from(v1: PoCGenesisConfigurationV1) -> Self {
+        Self {
+            slot_duration: v1.slot_duration,
+            epoch_length: v1.epoch_length,
+            c: v1.c,
+            randomness: v1.randomness,
+            spartan_parameters: SpartanParameters::default(),
+            compact_pre_digest: false,
+        }
+    }
 }
 
 #[cfg(feature = "std")]
@@ -129,7 +181,9 @@ impl sp_consensus::SlotData for PoCGenesisConfiguration {
         std::time::Duration::from_millis(self.slot_duration)
     }
 
-    const SLOT_KEY: &'static [u8] = b"poc_configuration";
+    // Configurations cached under `poc_configuration` by earlier versions lack the Spartan
+    // parameters and compact pre-digest flag and can't be decoded, so they are recomputed.
+    const SLOT_KEY: &'static [u8] = b"poc_configuration_v2";
 }
 
 /// Configuration data used by the PoC consensus engine.
@@ -145,6 +199,57 @@ pub struct PoCEpochConfiguration {
     pub c: (u64, u64),
 }
 
+/// Parameters of the Spartan codec, fixed for the lifetime of the chain.
+///
+/// Piece size is a compile-time parameter of the codec and is not part of these.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct SpartanParameters {
+    /// Seed the genesis piece is derived from.
+    pub genesis_piece_seed: Vec<u8>,
+    /// Number of encoding rounds applied to the genesis piece.
+    pub encode_rounds: u32,
+    /// Signing context of solution signatures.
+    pub signing_context: Vec<u8>,
+}
+
+impl Default for SpartanParameters {
+    fn default() -> Self {
+        Self {
+            genesis_piece_seed: sp_consensus_spartan::GENESIS_PIECE_SEED.as_bytes().to_vec(),
+            encode_rounds: sp_consensus_spartan::ENCODE_ROUNDS as u32,
+            signing_context: sp_consensus_spartan::SIGNING_CONTEXT.to_vec(),
+        }
+    }
+}
+
+/// Number of data records (pieces with history) in an archived segment.
+pub const RECORDS_PER_SEGMENT: usize = 128;
+
+/// Number of erasure-coded parity pieces added to every archived segment.
+pub const PARITY_PIECES_PER_SEGMENT: usize = 128;
+
+/// Number of pieces in an archived segment, records followed by parity pieces. Piece with index
+/// `i` is at position `i % PIECES_PER_SEGMENT` of the segment with index `i / PIECES_PER_SEGMENT`.
+pub const PIECES_PER_SEGMENT: usize = RECORDS_PER_SEGMENT + PARITY_PIECES_PER_SEGMENT;
+
+/// Number of archived segments that need to be stored on chain before solutions with encodings of
+/// the genesis piece stop being accepted. Farmers plot archived pieces as soon as segments are
+/// archived, so by the time the root of the second segment is stored on chain they have pieces of
+/// the first one with roots on chain too.
+pub const GENESIS_PIECE_SOLUTIONS_CUTOVER: u64 = 2;
+
+/// Root block of an archived segment of the blockchain history, commits to the pieces the segment
+/// was split into.
+#[derive(Clone, Copy, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct RootBlock {
+    /// Index of the segment, segments are numbered from 0 in the order of archiving.
+    pub segment_index: u64,
+    /// Merkle root of the segment's pieces, both records and parity pieces.
+    pub records_root: [u8; 32],
+}
+
 /// Verifies the equivocation proof by making sure that: both headers have
 /// different hashes, are targeting the same slot, and have valid signatures by
 /// the same authority.
@@ -247,10 +352,20 @@ pub struct Epoch {
 
 sp_api::decl_runtime_apis! {
     /// API necessary for block authorship with PoC.
+    ///
+    /// Version 2 added Spartan parameters and compact pre-digests to the configuration as well as
+    /// [`PoCApi::block_list`], [`PoCApi::farmer_offences`],
+    /// [`PoCApi::next_archived_segment_index`] and [`PoCApi::records_root`], those must not be
+    /// called on runtimes with version 1.
+    #[api_version(2)]
     pub trait PoCApi {
         /// Return the genesis configuration for PoC. The configuration is only read on genesis.
         fn configuration() -> PoCGenesisConfiguration;
 
+        /// Return the configuration for PoC. Version 1.
+        #[changed_in(2)]
+        fn configuration() -> PoCGenesisConfigurationV1;
+
         /// Current solution range.
         fn solution_range() -> u64;
 
@@ -281,5 +396,17 @@ sp_api::decl_runtime_apis! {
 
         /// Check if `farmer_id` is in block list (due to equivocation)
         fn is_in_block_list(farmer_id: &FarmerId) -> bool;
+
+        /// Farmers in block list (due to equivocation) along with the slot their ban expires at
+        fn block_list() -> Vec<(FarmerId, Slot)>;
+
+        /// Offences reported against `farmer_id`, in the order they were reported.
+        fn farmer_offences(farmer_id: &FarmerId) -> Vec<offence::OffenceRecord>;
+
+        /// Index of the next archived segment whose root block is expected to be stored on chain.
+        fn next_archived_segment_index() -> u64;
+
+        /// Merkle root of pieces of the archived segment with `segment_index`, if stored on chain.
+        fn records_root(segment_index: u64) -> Option<[u8; 32]>;
     }
 }
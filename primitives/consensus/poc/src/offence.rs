@@ -85,9 +85,9 @@ impl sp_runtime::traits::Printable for OffenceError {
 }
 
 /// A trait for decoupling offence reporters from the actual handling of offence reports.
-pub trait ReportOffence<Offender, O: Offence<Offender>> {
+pub trait ReportOffence<Reporter, Offender, O: Offence<Offender>> {
     /// Report an `offence` and reward given `reporters`.
-    fn report_offence(offence: O) -> Result<(), OffenceError>;
+    fn report_offence(reporters: Vec<Reporter>, offence: O) -> Result<(), OffenceError>;
 
     /// Returns true iff all of the given offenders have been previously reported
     /// at the given time slot. This function is useful to prevent the sending of
@@ -95,8 +95,8 @@ pub trait ReportOffence<Offender, O: Offence<Offender>> {
     fn is_known_offence(offenders: &[Offender], time_slot: &O::TimeSlot) -> bool;
 }
 
-impl<Offender, O: Offence<Offender>> ReportOffence<Offender, O> for () {
-    fn report_offence(_offence: O) -> Result<(), OffenceError> {
+impl<Reporter, Offender, O: Offence<Offender>> ReportOffence<Reporter, Offender, O> for () {
+    fn report_offence(_reporters: Vec<Reporter>, _offence: O) -> Result<(), OffenceError> {
         Ok(())
     }
 
@@ -109,23 +109,36 @@ impl<Offender, O: Offence<Offender>> ReportOffence<Offender, O> for () {
 ///
 /// Used to decouple the module that handles offences and
 /// the one that should punish for those offences.
-pub trait OnOffenceHandler<Offender> {
+pub trait OnOffenceHandler<Reporter, Offender> {
     /// A handler for an offence of a particular kind.
     ///
     /// Note that this contains a list of all previous offenders
     /// as well. The implementer should cater for a case, where
     /// the same farmers were reported for the same offence
     /// in the past (see `OffenceCount`).
-    fn on_offence(offenders: &[OffenceDetails<Offender>]);
+    fn on_offence(offenders: &[OffenceDetails<Reporter, Offender>]);
 }
 
-impl<Offender> OnOffenceHandler<Offender> for () {
-    fn on_offence(_offenders: &[OffenceDetails<Offender>]) {}
+impl<Reporter, Offender> OnOffenceHandler<Reporter, Offender> for () {
+    fn on_offence(_offenders: &[OffenceDetails<Reporter, Offender>]) {}
 }
 
 /// A details about an offending authority for a particular kind of offence.
 #[derive(Clone, PartialEq, Eq, Encode, Decode, sp_runtime::RuntimeDebug)]
-pub struct OffenceDetails<Offender> {
+pub struct OffenceDetails<Reporter, Offender> {
     /// The offending authority id
     pub offender: Offender,
+    /// A list of reporters of offences of this authority ID. Possibly empty where there are no
+    /// particular reporters.
+    pub reporters: Vec<Reporter>,
+}
+
+/// A record of an offence committed by a particular farmer, as exposed through the runtime API.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, sp_runtime::RuntimeDebug)]
+pub struct OffenceRecord {
+    /// The kind of the offence.
+    pub kind: Kind,
+    /// SCALE-encoded (kind-specific) time slot at which the offence happened, for equivocations
+    /// this is the slot number.
+    pub time_slot: Vec<u8>,
 }
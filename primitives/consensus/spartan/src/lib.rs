@@ -18,6 +18,8 @@
 //! Primitives for Spartan-based PoR.
 #![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(feature = "std")]
+pub mod merkle;
 #[cfg(feature = "std")]
 pub mod spartan;
 
@@ -27,3 +29,12 @@ pub const RANDOMNESS_LENGTH: usize = 32;
 /// Randomness value.
 pub type Randomness = [u8; RANDOMNESS_LENGTH];
 
+/// Default seed the genesis piece is derived from.
+pub const GENESIS_PIECE_SEED: &str = "spartan";
+
+/// Default number of encoding rounds applied to the genesis piece.
+pub const ENCODE_ROUNDS: usize = 1;
+
+/// Default signing context of solution signatures.
+pub const SIGNING_CONTEXT: &[u8] = b"FARMER";
+
@@ -0,0 +1,126 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Subspace Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Merkle tree over pieces, used to commit to archived segments of the blockchain history.
+
+use ring::digest;
+
+/// Size of the hashes in the Merkle tree.
+pub const HASH_SIZE: usize = 32;
+
+/// Hash of a node in the Merkle tree.
+pub type Hash = [u8; HASH_SIZE];
+
+fn sha256(data: &[&[u8]]) -> Hash {
+    let mut context = digest::Context::new(&digest::SHA256);
+    for data in data {
+        context.update(data);
+    }
+    let mut hash = Hash::default();
+    hash.copy_from_slice(context.finish().as_ref());
+    hash
+}
+
+/// Hash of a leaf of the Merkle tree.
+pub fn hash_leaf(leaf: &[u8]) -> Hash {
+    sha256(&[leaf])
+}
+
+fn hash_nodes(left: &Hash, right: &Hash) -> Hash {
+    sha256(&[left, right])
+}
+
+/// Merkle tree built bottom-up from the hashes of its leaves. Nodes without a sibling are hashed
+/// with themselves.
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+    /// Levels of the tree, from leaf hashes to the root.
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    /// Build Merkle tree from `leaves`, there must be at least one leaf.
+    pub fn from_leaves<'a, I>(leaves: I) -> Self
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        let leaf_hashes: Vec<Hash> = leaves.into_iter().map(hash_leaf).collect();
+        assert!(
+            !leaf_hashes.is_empty(),
+            "Merkle tree must have at least one leaf"
+        );
+
+        let mut levels = vec![leaf_hashes];
+        while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+            let next_level = level
+                .chunks(2)
+                .map(|pair| hash_nodes(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+                .collect();
+            levels.push(next_level);
+        }
+
+        Self { levels }
+    }
+
+    /// Root of the tree.
+    pub fn root(&self) -> Hash {
+        self.levels
+            .last()
+            .expect("At least one level is present; qed")[0]
+    }
+
+    /// Proof of inclusion of the leaf at `position`: hashes of its sibling nodes from the leaf
+    /// level up to, but not including, the root. `None` if there is no such leaf.
+    pub fn proof(&self, position: usize) -> Option<Vec<Hash>> {
+        if position >= self.levels[0].len() {
+            return None;
+        }
+
+        let mut position = position;
+        let proof = self.levels[..self.levels.len() - 1]
+            .iter()
+            .map(|level| {
+                let sibling = level.get(position ^ 1).unwrap_or(&level[position]);
+                position /= 2;
+                *sibling
+            })
+            .collect();
+
+        Some(proof)
+    }
+}
+
+/// Check that `proof` proves inclusion of `leaf` at `position` in the tree with `root`.
+pub fn is_proof_valid(root: &Hash, leaf: &[u8], position: usize, proof: &[Hash]) -> bool {
+    // Position must fit into the depth of the tree implied by the proof
+    if position.checked_shr(proof.len() as u32).unwrap_or(0) != 0 {
+        return false;
+    }
+
+    let mut position = position;
+    let computed_root = proof.iter().fold(hash_leaf(leaf), |node, sibling| {
+        let parent = if position % 2 == 0 {
+            hash_nodes(&node, sibling)
+        } else {
+            hash_nodes(sibling, &node)
+        };
+        position /= 2;
+        parent
+    });
+
+    &computed_root == root
+}
@@ -21,11 +21,10 @@ use ring::{digest, hmac};
 use std::convert::TryInto;
 use std::io::Write;
 
+pub use crate::{ENCODE_ROUNDS, GENESIS_PIECE_SEED, SIGNING_CONTEXT};
+
 pub const PRIME_SIZE_BYTES: usize = 8;
 pub const PIECE_SIZE: usize = 4096;
-pub const GENESIS_PIECE_SEED: &str = "spartan";
-pub const ENCODE_ROUNDS: usize = 1;
-pub const SIGNING_CONTEXT: &[u8] = b"FARMER";
 
 pub type Piece = [u8; PIECE_SIZE];
 pub type Tag = [u8; PRIME_SIZE_BYTES];
@@ -33,20 +32,66 @@ pub type Salt = [u8; 8];
 
 pub struct Spartan {
     instance: spartan_codec::Spartan<PRIME_SIZE_BYTES, PIECE_SIZE>,
+    encode_rounds: usize,
+}
+
+impl Default for Spartan {
+    fn default() -> Self {
+        Self::new(GENESIS_PIECE_SEED.as_bytes(), ENCODE_ROUNDS)
+    }
 }
 
 impl Spartan {
-    pub fn new() -> Self {
+    /// Create codec instance with genesis piece derived from `genesis_piece_seed` and
+    /// `encode_rounds` rounds of encoding.
+    pub fn new(genesis_piece_seed: &[u8], encode_rounds: usize) -> Self {
         Self {
             instance: spartan_codec::Spartan::<PRIME_SIZE_BYTES, PIECE_SIZE>::new(
-                genesis_piece_from_seed(GENESIS_PIECE_SEED),
+                genesis_piece_from_seed(genesis_piece_seed),
             ),
+            encode_rounds,
         }
     }
 
-    pub fn is_encoding_valid(&self, encoding: Piece, public_key: &[u8], nonce: u64) -> bool {
+    /// Encode genesis piece for `public_key` with `nonce`.
+    pub fn encode(&self, public_key: &[u8], nonce: u64) -> Piece {
         self.instance
-            .is_valid(encoding, hash_public_key(public_key), nonce, ENCODE_ROUNDS)
+            .encode(hash_public_key(public_key), nonce, self.encode_rounds)
+    }
+
+    pub fn is_encoding_valid(&self, encoding: Piece, public_key: &[u8], nonce: u64) -> bool {
+        self.instance.is_valid(
+            encoding,
+            hash_public_key(public_key),
+            nonce,
+            self.encode_rounds,
+        )
+    }
+
+    /// Encode arbitrary `piece` for `public_key` with `nonce`, using the same number of rounds as
+    /// for the genesis piece.
+    pub fn encode_piece(&self, piece: Piece, public_key: &[u8], nonce: u64) -> Piece {
+        spartan_codec::Spartan::<PRIME_SIZE_BYTES, PIECE_SIZE>::new(piece).encode(
+            hash_public_key(public_key),
+            nonce,
+            self.encode_rounds,
+        )
+    }
+
+    /// Check that `encoding` is an encoding of `piece` for `public_key` with `nonce`.
+    pub fn is_piece_encoding_valid(
+        &self,
+        encoding: Piece,
+        piece: Piece,
+        public_key: &[u8],
+        nonce: u64,
+    ) -> bool {
+        spartan_codec::Spartan::<PRIME_SIZE_BYTES, PIECE_SIZE>::new(piece).is_valid(
+            encoding,
+            hash_public_key(public_key),
+            nonce,
+            self.encode_rounds,
+        )
     }
 }
 
@@ -55,9 +100,9 @@ pub fn is_commitment_valid(encoding: &Piece, tag: &Tag, salt: &Salt) -> bool {
     &correct_tag == tag
 }
 
-fn genesis_piece_from_seed(seed: &str) -> Piece {
+fn genesis_piece_from_seed(seed: &[u8]) -> Piece {
     let mut piece = [0u8; PIECE_SIZE];
-    let mut input = seed.as_bytes().to_vec();
+    let mut input = seed.to_vec();
     for mut chunk in piece.chunks_mut(digest::SHA256.output_len) {
         input = digest::digest(&digest::SHA256, &input).as_ref().to_vec();
         chunk.write_all(input.as_ref()).unwrap();
@@ -72,7 +117,7 @@ fn hash_public_key(public_key: &[u8]) -> [u8; PRIME_SIZE_BYTES] {
     array
 }
 
-fn create_tag(encoding: &[u8], salt: &[u8]) -> Tag {
+pub fn create_tag(encoding: &[u8], salt: &[u8]) -> Tag {
     let key = hmac::Key::new(hmac::HMAC_SHA256, salt);
     hmac::sign(&key, encoding).as_ref()[0..8]
         .try_into()
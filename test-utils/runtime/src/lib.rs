@@ -604,22 +604,30 @@ impl pallet_babe::Config for Runtime {
 parameter_types! {
 	pub const EraDuration: u32 = 5;
 	pub const EonDuration: u64 = 11;
+	pub const EonNextSaltReveal: u64 = 8;
 	pub const InitialSolutionRange: u64 = u64::MAX;
 	pub const SlotProbability: (u64, u64) = (3, 10);
+	pub const MaxSolutionRangeIncrease: (u64, u64) = (4, 1);
+	pub const MaxSolutionRangeDecrease: (u64, u64) = (4, 1);
+	pub const BanDuration: u64 = 100;
 }
 
 impl pallet_spartan::Config for Runtime {
 	type EpochDuration = EpochDuration;
 	type EraDuration = EraDuration;
 	type EonDuration = EonDuration;
+	type EonNextSaltReveal = EonNextSaltReveal;
 	type InitialSolutionRange = InitialSolutionRange;
 	type SlotProbability = SlotProbability;
+	type MaxSolutionRangeIncrease = MaxSolutionRangeIncrease;
+	type MaxSolutionRangeDecrease = MaxSolutionRangeDecrease;
 	type ExpectedBlockTime = ExpectedBlockTime;
 	type EpochChangeTrigger = pallet_spartan::NormalEpochChange;
 	type EraChangeTrigger = pallet_spartan::NormalEraChange;
 	type EonChangeTrigger = pallet_spartan::NormalEonChange;
 
 	type HandleEquivocation = ();
+	type BanDuration = BanDuration;
 
 	type WeightInfo = ();
 }
@@ -892,6 +900,8 @@ cfg_if! {
 						epoch_length: EpochDuration::get(),
 						c: (3, 10),
 						randomness: <pallet_spartan::Pallet<Runtime>>::randomness(),
+						spartan_parameters: <pallet_spartan::Pallet<Runtime>>::spartan_parameters(),
+						compact_pre_digest: false,
 					}
 				}
 
@@ -929,6 +939,24 @@ cfg_if! {
 				fn is_in_block_list(farmer_id: &sp_consensus_poc::FarmerId) -> bool {
 					<pallet_spartan::Pallet<Runtime>>::is_in_block_list(farmer_id)
 				}
+
+				fn block_list() -> Vec<(sp_consensus_poc::FarmerId, sp_consensus_poc::Slot)> {
+					<pallet_spartan::Pallet<Runtime>>::block_list()
+				}
+
+				fn farmer_offences(
+					_farmer_id: &sp_consensus_poc::FarmerId,
+				) -> Vec<sp_consensus_poc::offence::OffenceRecord> {
+					Vec::new()
+				}
+
+				fn next_archived_segment_index() -> u64 {
+					<pallet_spartan::Pallet<Runtime>>::next_archived_segment_index()
+				}
+
+				fn records_root(segment_index: u64) -> Option<[u8; 32]> {
+					<pallet_spartan::Pallet<Runtime>>::records_root(segment_index)
+				}
 			}
 
 			impl sp_offchain::OffchainWorkerApi<Block> for Runtime {
@@ -1192,6 +1220,8 @@ cfg_if! {
 						epoch_length: EpochDuration::get(),
 						c: (3, 10),
 						randomness: <pallet_spartan::Pallet<Runtime>>::randomness(),
+						spartan_parameters: <pallet_spartan::Pallet<Runtime>>::spartan_parameters(),
+						compact_pre_digest: false,
 					}
 				}
 
@@ -1229,6 +1259,24 @@ cfg_if! {
 				fn is_in_block_list(farmer_id: &sp_consensus_poc::FarmerId) -> bool {
 					<pallet_spartan::Pallet<Runtime>>::is_in_block_list(farmer_id)
 				}
+
+				fn block_list() -> Vec<(sp_consensus_poc::FarmerId, sp_consensus_poc::Slot)> {
+					<pallet_spartan::Pallet<Runtime>>::block_list()
+				}
+
+				fn farmer_offences(
+					_farmer_id: &sp_consensus_poc::FarmerId,
+				) -> Vec<sp_consensus_poc::offence::OffenceRecord> {
+					Vec::new()
+				}
+
+				fn next_archived_segment_index() -> u64 {
+					<pallet_spartan::Pallet<Runtime>>::next_archived_segment_index()
+				}
+
+				fn records_root(segment_index: u64) -> Option<[u8; 32]> {
+					<pallet_spartan::Pallet<Runtime>>::records_root(segment_index)
+				}
 			}
 
 			impl sp_offchain::OffchainWorkerApi<Block> for Runtime {